@@ -109,6 +109,21 @@ fn criterion_benchmark(c: &mut criterion::Criterion) {
             });
         });
 
+        c.bench_function("boxtree insert after snapshot", |b| {
+            b.iter(|| {
+                let _snapshot = tree.snapshot();
+                tree.insert(
+                    &V3c::new(
+                        rng.gen_range(0..tree_size),
+                        rng.gen_range(0..tree_size),
+                        rng.gen_range(0..tree_size),
+                    ),
+                    &Albedo::from(rng.gen_range(0..50000)),
+                )
+                .ok()
+            });
+        });
+
         c.bench_function("boxtree get", |b| {
             b.iter(|| {
                 tree.get(&V3c::new(
@@ -0,0 +1,228 @@
+use crate::{
+    boxtree::{
+        types::{
+            Axis, BoxTreeAccessor, Connectivity, NodeContent, OctreeError, PaletteIndexValues,
+        },
+        BoxTree, BoxTreeEntry, VoxelData,
+    },
+    object_pool::empty_marker,
+    spatial::{math::vector::V3c, Cube},
+};
+use std::{
+    cell::RefCell,
+    ops::{Deref, DerefMut},
+};
+
+impl<T: VoxelData> BoxTree<T> {
+    /// Creates an accessor reading the voxels of the tree, with its cursor at the origin
+    pub fn accessor(&self) -> BoxTreeAccessor<T, &BoxTree<T>> {
+        BoxTreeAccessor::new(self)
+    }
+
+    /// Creates an accessor reading and updating the voxels of the tree, with its cursor at the origin
+    pub fn accessor_mut(&mut self) -> BoxTreeAccessor<T, &mut BoxTree<T>> {
+        BoxTreeAccessor::new(self)
+    }
+}
+
+impl<T: VoxelData, R: Deref<Target = BoxTree<T>>> BoxTreeAccessor<T, R> {
+    fn new(tree: R) -> Self {
+        let root_bounds = Cube::root_bounds(tree.boxtree_size as f32);
+        Self {
+            tree,
+            path: RefCell::new(vec![(BoxTree::<T>::ROOT_NODE_KEY as usize, root_bounds)]),
+            cursor: V3c::unit(0),
+        }
+    }
+
+    /// Provides the palette index values at the given position, continuing from the cached nodes
+    pub(crate) fn get_index(&self, position: &V3c<u32>) -> PaletteIndexValues {
+        let position_ = V3c::from(*position);
+        let mut path = self.path.borrow_mut();
+        if !path[0].1.contains(&position_) {
+            return empty_marker();
+        }
+
+        // Step up to the deepest cached node containing the position, the root node is always kept
+        while !path.last().unwrap().1.contains(&position_) {
+            path.pop();
+        }
+
+        // Step down to the deepest node containing the position
+        loop {
+            let (node_key, bounds) = *path.last().unwrap();
+            if !matches!(self.tree.nodes.get(node_key), NodeContent::Internal(_)) {
+                break;
+            }
+            let sectant = bounds.sectant_for(&position_);
+            let child_key = self.tree.node_children[node_key].child(sectant);
+            if !self.tree.nodes.key_is_valid(child_key) {
+                break;
+            }
+            path.push((child_key, bounds.child_bounds_for(sectant)));
+        }
+
+        let (node_key, bounds) = *path.last().unwrap();
+        self.tree.get_internal(node_key, bounds, position)
+    }
+
+    /// Provides the data at the given position, or empty if the position is outside the tree
+    /// Queries close to the previous one are answered without walking the tree from its root node
+    pub fn get(&self, position: &V3c<u32>) -> BoxTreeEntry<'_, T> {
+        NodeContent::pix_get_ref(
+            &self.get_index(position),
+            &self.tree.voxel_color_palette,
+            &self.tree.voxel_data_palette,
+        )
+    }
+
+    /// Provides the data of the neighbors of the given position, see @Connectivity::neighbor_offsets
+    /// * Returns with None in place of neighbors outside the tree
+    pub fn get_neighbors(
+        &self,
+        position: &V3c<u32>,
+        connectivity: Connectivity,
+    ) -> Vec<Option<BoxTreeEntry<'_, T>>> {
+        connectivity
+            .neighbor_offsets()
+            .map(|offset| {
+                self.offset_position(position, &offset)
+                    .map(|neighbor| self.get(&neighbor))
+            })
+            .collect()
+    }
+
+    /// Provides the given position moved by the given offset, if it is inside the tree
+    fn offset_position(&self, position: &V3c<u32>, offset: &V3c<i32>) -> Option<V3c<u32>> {
+        let size = self.tree.boxtree_size;
+        let moved = V3c::new(
+            position.x.checked_add_signed(offset.x)?,
+            position.y.checked_add_signed(offset.y)?,
+            position.z.checked_add_signed(offset.z)?,
+        );
+        (moved.x < size && moved.y < size && moved.z < size).then_some(moved)
+    }
+
+    /// Checks if the given position is contained within the tree
+    fn check_position(&self, position: &V3c<u32>) -> Result<(), OctreeError> {
+        if self.offset_position(position, &V3c::unit(0)).is_none() {
+            return Err(OctreeError::InvalidPosition {
+                x: position.x,
+                y: position.y,
+                z: position.z,
+            });
+        }
+        Ok(())
+    }
+
+    /// The position the accessor steps from
+    pub fn cursor(&self) -> V3c<u32> {
+        self.cursor
+    }
+
+    /// Provides the data at the position of the cursor
+    pub fn get_at_cursor(&self) -> BoxTreeEntry<'_, T> {
+        self.get(&self.cursor)
+    }
+
+    /// Moves the cursor to the given position
+    /// * `position` - The new position of the cursor, must be contained within the tree
+    pub fn move_to(&mut self, position: &V3c<u32>) -> Result<(), OctreeError> {
+        self.check_position(position)?;
+        self.cursor = *position;
+        Ok(())
+    }
+
+    /// Moves the cursor along the given axis, and provides the data at its new position
+    /// * `axis` - The axis to move the cursor along
+    /// * `delta` - The number of voxels to move the cursor by, negative values move towards the origin
+    /// * Returns with None if the new position would be outside the tree, the cursor is not moved then
+    pub fn step(&mut self, axis: Axis, delta: i32) -> Option<BoxTreeEntry<'_, T>> {
+        let offset = match axis {
+            Axis::X => V3c::new(delta, 0, 0),
+            Axis::Y => V3c::new(0, delta, 0),
+            Axis::Z => V3c::new(0, 0, delta),
+        };
+        self.cursor = self.offset_position(&self.cursor, &offset)?;
+        Some(self.get_at_cursor())
+    }
+}
+
+impl<T: VoxelData, R: DerefMut<Target = BoxTree<T>>> BoxTreeAccessor<T, R> {
+    /// Inserts the given data into the tree at the given position, see @BoxTree::insert
+    /// Voxels inside a brick of the cached nodes are written into it directly.
+    pub fn insert<'a, E: Into<BoxTreeEntry<'a, T>>>(
+        &mut self,
+        position: &V3c<u32>,
+        data: E,
+    ) -> Result<(), OctreeError>
+    where
+        T: 'a,
+    {
+        self.check_position(position)?;
+        let data = data.into();
+
+        // Nothing to do when no operations are requested
+        if data.is_none() {
+            return Ok(());
+        }
+        let target_content = self.tree.add_to_palette(&data)?;
+        self.tree.record_edit(position, 1);
+        self.update_index(position, target_content);
+        Ok(())
+    }
+
+    /// Clears the data at the given position, see @BoxTree::clear
+    pub fn clear(&mut self, position: &V3c<u32>) -> Result<(), OctreeError> {
+        self.check_position(position)?;
+        self.tree.record_edit(position, 1);
+        self.update_index(position, empty_marker());
+        Ok(())
+    }
+
+    /// Overwrites the voxel at the given position with the given palette index values
+    /// The voxel is written into the brick under the cached nodes if the occupancy of the brick is kept,
+    /// otherwise the tree is updated from its root node, and the cached nodes are collected again.
+    fn update_index(&mut self, position: &V3c<u32>, target_content: PaletteIndexValues) {
+        self.get_index(position);
+        let path = self.path.get_mut();
+        let (node_key, node_bounds) = *path.last().unwrap();
+        if !self.tree.update_voxel_in_brick(
+            (node_key, &node_bounds),
+            position,
+            target_content,
+            true,
+        ) {
+            if NodeContent::pix_points_to_empty(
+                &target_content,
+                &self.tree.voxel_color_palette,
+                &self.tree.voxel_data_palette,
+            ) {
+                self.tree.clear_palette_values_at_lod(position, 1);
+            } else {
+                self.tree
+                    .insert_palette_value_at_lod(true, position, 1, target_content);
+            }
+            self.path.get_mut().truncate(1);
+            self.get_index(position);
+            return;
+        }
+
+        // Update the MIPs of the cached nodes, and simplify them as long as they can be simplified
+        let mut simplifyable = self.tree.auto_simplify;
+        let mut simplified_depth = path.len();
+        for (depth, (node_key, node_bounds)) in path.iter().enumerate().rev() {
+            self.tree.update_mip(*node_key, node_bounds, position);
+            if simplifyable {
+                simplifyable = self.tree.simplify(*node_key, false);
+                if simplifyable {
+                    simplified_depth = depth + 1;
+                }
+            }
+        }
+
+        // Children of simplified nodes are removed
+        path.truncate(simplified_depth);
+        self.tree.mark_changed_at_lod(position, 1);
+    }
+}
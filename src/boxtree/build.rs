@@ -0,0 +1,406 @@
+use crate::{
+    boxtree::{
+        types::{BoxTreeEntry, BrickData, MIPMapStrategy, NodeChildren, NodeContent, OctreeError},
+        Albedo, BoxTree, VoxelData, BOX_NODE_CHILDREN_COUNT, BOX_NODE_DIMENSION, MAX_PALETTE_SIZE,
+    },
+    object_pool::empty_marker,
+    spatial::{lut::SECTANT_OFFSET_LUT, math::vector::V3c},
+};
+use std::{
+    collections::HashMap,
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+};
+
+/// A tree built for one sectant of the root node, to be merged into the final tree
+struct SubtreeBuild<T: VoxelData> {
+    sectant: u8,
+    tree: BoxTree<T>,
+
+    /// The order of the first insertion of each color in the palette of @tree, see @BoxTree::insertion_order
+    color_first_seen: Vec<u64>,
+
+    /// The order of the first insertion of each data in the palette of @tree
+    data_first_seen: Vec<u64>,
+}
+
+/// The mapping of the color and data palette indices of a subtree into the merged palettes
+type PaletteMaps = (Vec<u16>, Vec<u16>);
+
+impl<T: VoxelData> BoxTree<T> {
+    /// Creates a boxtree with the voxels provided by the given function for each position
+    /// Subtrees of the root node are built on multiple threads in parallel, then merged together.
+    /// The result equals to inserting every voxel sequentially in x, y, z order: it has the same content,
+    /// structure and palettes, only the keys of the nodes are numbered differently (see @defragment).
+    /// MIPs are disabled, see @MIPMapStrategy::from_fn to build a tree with MIPs
+    /// * `size` - must be `brick_dimension * (4^x)`, see @new
+    /// * `brick_dimension` - must be one of `(2^x)` and smaller than the size of the boxtree, see @new
+    /// * `generator` - provides the voxel for each position, None for empty positions
+    /// * Returns with @OctreeError::PaletteFull if the voxels use more colors or data than the palettes can store
+    pub fn from_fn<'a, F>(
+        size: u32,
+        brick_dimension: u32,
+        generator: F,
+    ) -> Result<Self, OctreeError>
+    where
+        F: Fn(V3c<u32>) -> Option<BoxTreeEntry<'a, T>> + Sync,
+        T: 'a,
+    {
+        Self::check_dimensions(size, brick_dimension)?;
+        let subtree_size = size / BOX_NODE_DIMENSION as u32;
+        if Self::check_dimensions(subtree_size, brick_dimension).is_err() {
+            // The children of the root node are bricks, so there are no subtrees to build separately
+            let mut tree = Self::new(size, brick_dimension)?;
+            tree.insert_generated(&V3c::unit(0), size, &generator, &mut vec![], &mut vec![])?;
+            tree.mark_layout_changed();
+            return Ok(tree);
+        }
+
+        // Build the subtrees of each root sectant in parallel
+        let next_sectant = AtomicUsize::new(0);
+        let worker_count = thread::available_parallelism()
+            .map_or(1, |count| count.get())
+            .min(BOX_NODE_CHILDREN_COUNT);
+        let subtrees = thread::scope(|scope| {
+            let workers = (0..worker_count)
+                .map(|_| {
+                    scope.spawn(|| {
+                        let mut subtrees = Vec::new();
+                        loop {
+                            let sectant = next_sectant.fetch_add(1, Ordering::Relaxed);
+                            if sectant >= BOX_NODE_CHILDREN_COUNT {
+                                return subtrees;
+                            }
+                            subtrees.push(Self::build_subtree(
+                                sectant as u8,
+                                size,
+                                brick_dimension,
+                                &generator,
+                            ));
+                        }
+                    })
+                })
+                .collect::<Vec<_>>();
+            workers
+                .into_iter()
+                .flat_map(|worker| worker.join().expect("Expected subtree builder to finish"))
+                .collect::<Vec<_>>()
+        });
+        let mut subtrees = subtrees
+            .into_iter()
+            .collect::<Option<Vec<_>>>()
+            .ok_or(OctreeError::PaletteFull)?;
+        subtrees.retain(|subtree| {
+            NodeContent::Nothing != *subtree.tree.nodes.get(Self::ROOT_NODE_KEY as usize)
+        });
+        subtrees.sort_by_key(|subtree| subtree.sectant);
+
+        // Merge the palettes in the order the entries were first inserted, then remap the subtrees in parallel
+        let mut tree = Self::new(size, brick_dimension)?;
+        let palette_maps = tree.merge_palettes(&subtrees)?;
+        thread::scope(|scope| {
+            let chunk_size = subtrees.len().div_ceil(worker_count).max(1);
+            for (subtrees, palette_maps) in subtrees
+                .chunks_mut(chunk_size)
+                .zip(palette_maps.chunks(chunk_size))
+            {
+                scope.spawn(move || {
+                    for (subtree, (color_map, data_map)) in subtrees.iter_mut().zip(palette_maps) {
+                        subtree.tree.for_each_palette_index_mut(|index| {
+                            *index =
+                                Self::remap_palette_index(index, Some(color_map), Some(data_map));
+                        });
+                    }
+                });
+            }
+        });
+
+        // Attach the subtrees to the root node
+        if !subtrees.is_empty() {
+            *tree.nodes.get_mut(Self::ROOT_NODE_KEY as usize) = NodeContent::Internal(0);
+            for subtree in subtrees.iter() {
+                let child_key = tree.graft_subtree(&subtree.tree);
+                *tree.node_children[Self::ROOT_NODE_KEY as usize]
+                    .child_mut(subtree.sectant as usize)
+                    .unwrap() = child_key as u32;
+            }
+            let occupied_bits = tree.calculate_node_occupied_bits(Self::ROOT_NODE_KEY as usize);
+            tree.store_occupied_bits(Self::ROOT_NODE_KEY as usize, occupied_bits);
+            if tree.auto_simplify {
+                tree.simplify(Self::ROOT_NODE_KEY as usize, false);
+            }
+        }
+        tree.mark_layout_changed();
+        Ok(tree)
+    }
+
+    /// Creates a boxtree from the given dense array of voxels, building it in parallel, see @from_fn
+    /// The size of the boxtree is the smallest valid size containing the array.
+    /// * `voxels` - The voxels of the array, for position (x,y,z) at `x + y * dimensions.x + z * dimensions.x * dimensions.y`
+    /// * `dimensions` - The number of voxels in the array along each axis
+    /// * `brick_dimension` - must be one of `(2^x)`, see @new
+    pub fn from_dense<'a>(
+        voxels: &[Option<BoxTreeEntry<'a, T>>],
+        dimensions: V3c<u32>,
+        brick_dimension: u32,
+    ) -> Result<Self, OctreeError>
+    where
+        T: 'a,
+    {
+        let voxel_count = (dimensions.x as usize)
+            .checked_mul(dimensions.y as usize)
+            .and_then(|count| count.checked_mul(dimensions.z as usize))
+            .ok_or_else(|| {
+                OctreeError::InvalidStructure("Dense array dimensions are too large".into())
+            })?;
+        if voxels.len() != voxel_count {
+            return Err(OctreeError::InvalidStructure(
+                "Dense array length must match the given dimensions".into(),
+            ));
+        }
+        let max_dimension = dimensions.x.max(dimensions.y).max(dimensions.z);
+        let mut size = brick_dimension
+            .checked_mul(BOX_NODE_DIMENSION as u32)
+            .ok_or(OctreeError::InvalidBrickDimension(brick_dimension))?;
+        while size < max_dimension {
+            size = size
+                .checked_mul(BOX_NODE_DIMENSION as u32)
+                .ok_or(OctreeError::InvalidSize(max_dimension))?;
+        }
+
+        // Indices are below the number of voxels, so they fit into usize
+        let (size_x, size_y) = (dimensions.x as usize, dimensions.y as usize);
+        Self::from_fn(size, brick_dimension, |position| {
+            if position.x < dimensions.x && position.y < dimensions.y && position.z < dimensions.z {
+                voxels[position.x as usize
+                    + position.y as usize * size_x
+                    + position.z as usize * size_x * size_y]
+                    .clone()
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Builds the subtree of the root node under the given sectant as a separate tree
+    /// * Returns with None if the palette of the subtree is full
+    fn build_subtree<'a, F>(
+        sectant: u8,
+        size: u32,
+        brick_dimension: u32,
+        generator: &F,
+    ) -> Option<SubtreeBuild<T>>
+    where
+        F: Fn(V3c<u32>) -> Option<BoxTreeEntry<'a, T>>,
+        T: 'a,
+    {
+        let subtree_size = size / BOX_NODE_DIMENSION as u32;
+        let mut subtree = SubtreeBuild {
+            sectant,
+            tree: Self::new(subtree_size, brick_dimension).ok()?,
+            color_first_seen: vec![],
+            data_first_seen: vec![],
+        };
+        let min_position = V3c::<u32>::from(SECTANT_OFFSET_LUT[sectant as usize] * size as f32);
+        subtree
+            .tree
+            .insert_generated(
+                &min_position,
+                size,
+                generator,
+                &mut subtree.color_first_seen,
+                &mut subtree.data_first_seen,
+            )
+            .ok()?;
+        Some(subtree)
+    }
+
+    /// Inserts the generated voxel for each position of the tree in x, y, z order
+    /// * `min_position` - The global position of the first voxel of the tree
+    /// * `global_size` - The size of the whole tree being built
+    /// * `color_first_seen` - Collects the insertion order of each new color, see @insertion_order
+    /// * `data_first_seen` - Collects the insertion order of each new data
+    fn insert_generated<'a, F>(
+        &mut self,
+        min_position: &V3c<u32>,
+        global_size: u32,
+        generator: &F,
+        color_first_seen: &mut Vec<u64>,
+        data_first_seen: &mut Vec<u64>,
+    ) -> Result<(), OctreeError>
+    where
+        F: Fn(V3c<u32>) -> Option<BoxTreeEntry<'a, T>>,
+        T: 'a,
+    {
+        for x in 0..self.boxtree_size {
+            for y in 0..self.boxtree_size {
+                for z in 0..self.boxtree_size {
+                    let global_position = *min_position + V3c::new(x, y, z);
+                    let Some(entry) = generator(global_position) else {
+                        continue;
+                    };
+                    self.insert(&V3c::new(x, y, z), entry)?;
+                    let order = Self::insertion_order(&global_position, global_size);
+                    color_first_seen.resize(self.voxel_color_palette.len(), order);
+                    data_first_seen.resize(self.voxel_data_palette.len(), order);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The index of the given position when inserting every position of the tree in x, y, z order
+    fn insertion_order(position: &V3c<u32>, size: u32) -> u64 {
+        (position.x as u64 * size as u64 + position.y as u64) * size as u64 + position.z as u64
+    }
+
+    /// Sets the palettes to the entries of the given subtrees, in the order they were first inserted
+    /// * Returns with the color and data palette index mapping of each subtree into the merged palettes
+    fn merge_palettes(
+        &mut self,
+        subtrees: &[SubtreeBuild<T>],
+    ) -> Result<Vec<PaletteMaps>, OctreeError> {
+        let mut palette_maps: Vec<PaletteMaps> = subtrees
+            .iter()
+            .map(|subtree| {
+                (
+                    vec![0; subtree.tree.voxel_color_palette.len()],
+                    vec![0; subtree.tree.voxel_data_palette.len()],
+                )
+            })
+            .collect();
+
+        let mut colors = subtrees
+            .iter()
+            .enumerate()
+            .flat_map(|(subtree_index, subtree)| {
+                subtree
+                    .color_first_seen
+                    .iter()
+                    .enumerate()
+                    .map(move |(color_index, order)| (*order, subtree_index, color_index))
+            })
+            .collect::<Vec<_>>();
+        colors.sort_unstable();
+        let mut color_palette = Vec::<Albedo>::new();
+        let mut color_indices = HashMap::new();
+        for (_, subtree_index, color_index) in colors {
+            let color = subtrees[subtree_index].tree.voxel_color_palette[color_index];
+            let merged_index = *color_indices.entry(color).or_insert_with(|| {
+                color_palette.push(color);
+                color_palette.len() - 1
+            });
+            palette_maps[subtree_index].0[color_index] = merged_index as u16;
+        }
+
+        let mut data = subtrees
+            .iter()
+            .enumerate()
+            .flat_map(|(subtree_index, subtree)| {
+                subtree
+                    .data_first_seen
+                    .iter()
+                    .enumerate()
+                    .map(move |(data_index, order)| (*order, subtree_index, data_index))
+            })
+            .collect::<Vec<_>>();
+        data.sort_unstable();
+        let mut data_palette = Vec::<T>::new();
+        let mut data_indices = HashMap::new();
+        for (_, subtree_index, data_index) in data {
+            let voxel_data = &subtrees[subtree_index].tree.voxel_data_palette[data_index];
+            let merged_index = *data_indices.entry(voxel_data.clone()).or_insert_with(|| {
+                data_palette.push(voxel_data.clone());
+                data_palette.len() - 1
+            });
+            palette_maps[subtree_index].1[data_index] = merged_index as u16;
+        }
+
+        if color_palette.len() > MAX_PALETTE_SIZE || data_palette.len() > MAX_PALETTE_SIZE {
+            return Err(OctreeError::PaletteFull);
+        }
+        self.voxel_color_palette = color_palette.into();
+        self.voxel_data_palette = data_palette.into();
+        self.map_to_color_index_in_palette = color_indices.into();
+        self.map_to_data_index_in_palette = data_indices.into();
+        Ok(palette_maps)
+    }
+
+    /// Copies every node of the given tree into this tree, sharing their bricks
+    /// * Returns with the key of the root node of the copied tree
+    fn graft_subtree(&mut self, subtree: &BoxTree<T>) -> usize {
+        let mut new_keys = vec![empty_marker::<u32>(); subtree.nodes.len()];
+        let mut order = vec![Self::ROOT_NODE_KEY as usize];
+        let mut visit_index = 0;
+        while visit_index < order.len() {
+            let node_key = order[visit_index];
+            visit_index += 1;
+            new_keys[node_key] = self.nodes.push(subtree.nodes.get(node_key).clone()) as u32;
+            if let NodeChildren::Children(children) = &subtree.node_children[node_key] {
+                order.extend(
+                    children
+                        .iter()
+                        .map(|child| *child as usize)
+                        .filter(|child_key| subtree.nodes.key_is_valid(*child_key)),
+                );
+            }
+        }
+
+        self.node_children
+            .resize(self.nodes.len(), NodeChildren::default());
+        self.node_mips.resize(self.nodes.len(), BrickData::Empty);
+        for node_key in order {
+            self.node_children[new_keys[node_key] as usize] = match subtree.node_children[node_key]
+            {
+                NodeChildren::Children(children) => NodeChildren::Children(children.map(|child| {
+                    if subtree.nodes.key_is_valid(child as usize) {
+                        new_keys[child as usize]
+                    } else {
+                        empty_marker()
+                    }
+                })),
+                children => children,
+            };
+        }
+        new_keys[Self::ROOT_NODE_KEY as usize] as usize
+    }
+}
+
+impl MIPMapStrategy {
+    /// Creates a boxtree with the voxels provided by the given function and this MIP strategy, see @BoxTree::from_fn
+    /// In case the strategy is enabled, every MIP is built once bottom-up after the voxels are inserted.
+    pub fn from_fn<'a, T, F>(
+        self,
+        size: u32,
+        brick_dimension: u32,
+        generator: F,
+    ) -> Result<BoxTree<T>, OctreeError>
+    where
+        T: VoxelData + 'a,
+        F: Fn(V3c<u32>) -> Option<BoxTreeEntry<'a, T>> + Sync,
+    {
+        Ok(self.build_mips_of(BoxTree::from_fn(size, brick_dimension, generator)?))
+    }
+
+    /// Creates a boxtree from the given dense array of voxels and this MIP strategy, see @BoxTree::from_dense
+    /// In case the strategy is enabled, every MIP is built once bottom-up after the voxels are inserted.
+    pub fn from_dense<'a, T: VoxelData + 'a>(
+        self,
+        voxels: &[Option<BoxTreeEntry<'a, T>>],
+        dimensions: V3c<u32>,
+        brick_dimension: u32,
+    ) -> Result<BoxTree<T>, OctreeError> {
+        Ok(self.build_mips_of(BoxTree::from_dense(voxels, dimensions, brick_dimension)?))
+    }
+
+    /// Sets this strategy for the given built tree, and calculates its MIPs if the strategy is enabled
+    fn build_mips_of<T: VoxelData>(self, mut tree: BoxTree<T>) -> BoxTree<T> {
+        let enabled = self.enabled;
+        tree.mip_map_strategy = self;
+        if enabled && NodeContent::Nothing != *tree.nodes.get(BoxTree::<T>::ROOT_NODE_KEY as usize)
+        {
+            tree.albedo_mip_map_resampling_strategy().recalculate_mips();
+        }
+        tree
+    }
+}
@@ -0,0 +1,272 @@
+use crate::{
+    boxtree::{
+        types::{
+            BoxTreeChanges, BrickData, ChangeTracker, NodeChildren, NodeContent, PaletteIndexValues,
+        },
+        update::region::{BoxRegion, RegionOverlap, UpdateRegion},
+        BoxTree, VoxelData, BOX_NODE_CHILDREN_COUNT,
+    },
+    spatial::{math::vector::V3c, Cube},
+};
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+/// The number of modified regions kept separately, older regions are merged above it
+const MAX_TRACKED_REGIONS: usize = 256;
+
+impl ChangeTracker {
+    /// Sets the generation of the given key, extending the stored generations if needed
+    fn stamp(generations: &mut Vec<u64>, key: usize, generation: u64) {
+        if generations.len() <= key {
+            generations.resize(key + 1, 0);
+        }
+        generations[key] = generation;
+    }
+
+    /// Stores the signature of the given key, extending the stored signatures if needed
+    /// * Returns with true if the signature differs from the previously stored one
+    fn update_signature(signatures: &mut Vec<u64>, key: usize, signature: u64) -> bool {
+        if signatures.len() <= key {
+            signatures.resize(key + 1, 0);
+        }
+        std::mem::replace(&mut signatures[key], signature) != signature
+    }
+
+    /// Adds the given region to the modified regions, merging the oldest ones above the limit
+    fn add_region(&mut self, min_position: V3c<u32>, max_position: V3c<u32>) {
+        self.regions
+            .push((self.generation, min_position, max_position));
+        if self.regions.len() > MAX_TRACKED_REGIONS {
+            let (_, older_min, older_max) = self.regions.remove(0);
+            let (_, newer_min, newer_max) = &mut self.regions[0];
+            *newer_min = V3c::new(
+                older_min.x.min(newer_min.x),
+                older_min.y.min(newer_min.y),
+                older_min.z.min(newer_min.z),
+            );
+            *newer_max = V3c::new(
+                older_max.x.max(newer_max.x),
+                older_max.y.max(newer_max.y),
+                older_max.z.max(newer_max.z),
+            );
+        }
+    }
+}
+
+impl<T: VoxelData> BoxTree<T> {
+    /// Provides the current generation of the tree, which is incremented by every modification
+    pub fn generation(&self) -> u64 {
+        self.changes.generation
+    }
+
+    /// Collects the nodes, bricks and MIPs modified after the given generation
+    /// Each consumer can keep its own generation, and query the changes since it independently.
+    /// Color palette edits which do not change the stored palette indices are not listed, only the affected MIPs.
+    /// * `generation` - The generation of the tree when the consumer last queried the changes, or 0
+    pub fn changes_since(&self, generation: u64) -> BoxTreeChanges {
+        let tracker = &self.changes;
+        let mut changes = BoxTreeChanges {
+            generation: tracker.generation,
+            ..Default::default()
+        };
+        if generation >= tracker.generation {
+            return changes;
+        }
+
+        changes.layout_changed = generation < tracker.layout_generation;
+        changes.nodes = tracker
+            .node_generations
+            .iter()
+            .enumerate()
+            .filter(|(node_key, node_generation)| {
+                **node_generation > generation && self.nodes.key_is_valid(*node_key)
+            })
+            .map(|(node_key, _)| node_key)
+            .collect();
+        changes.mips = tracker
+            .mip_generations
+            .iter()
+            .enumerate()
+            .filter(|(node_key, mip_generation)| {
+                **mip_generation > generation && self.nodes.key_is_valid(*node_key)
+            })
+            .map(|(node_key, _)| node_key)
+            .collect();
+
+        // Bricks of nodes no longer being leaves are not listed
+        changes.bricks = tracker
+            .brick_generations
+            .iter()
+            .filter(|((node_key, sectant), brick_generation)| {
+                **brick_generation > generation
+                    && self.nodes.key_is_valid(*node_key)
+                    && match self.nodes.get(*node_key) {
+                        NodeContent::Leaf(_) => true,
+                        NodeContent::UniformLeaf(_) => 0 == *sectant,
+                        NodeContent::Nothing | NodeContent::Internal(_) => false,
+                    }
+            })
+            .map(|(brick, _)| *brick)
+            .collect();
+        changes.bricks.sort_unstable();
+        changes.regions = tracker
+            .regions
+            .iter()
+            .filter(|(region_generation, _, _)| *region_generation > generation)
+            .map(|(_, min_position, max_position)| (*min_position, *max_position))
+            .collect();
+        changes
+    }
+
+    /// Records a modification of the voxels inside the given axis aligned box
+    /// Every brick overlapping the box is recorded as modified in a new generation,
+    /// along with the nodes and MIPs changed by the modification, see @stamp_region
+    /// * `min_position` - the first voxel of the box
+    /// * `max_position` - the end of the box (exclusive)
+    pub(crate) fn mark_changed(&mut self, min_position: &V3c<u32>, max_position: &V3c<u32>) {
+        let max_position = V3c::new(
+            max_position.x.min(self.boxtree_size),
+            max_position.y.min(self.boxtree_size),
+            max_position.z.min(self.boxtree_size),
+        );
+        if Self::box_is_empty(min_position, &max_position) {
+            return;
+        }
+        self.changes.generation += 1;
+        self.changes.add_region(*min_position, max_position);
+        self.stamp_region(&BoxRegion {
+            min_position: *min_position,
+            max_position,
+        });
+    }
+
+    /// Records a modification of the voxels updated at the given position and lod size
+    /// Sizes above the brick dimension are rounded up to `brick_dimension * (2^x)`, like the update itself
+    pub(crate) fn mark_changed_at_lod(&mut self, position: &V3c<u32>, size: u32) {
+        let size = if size > self.brick_dim {
+            size.div_ceil(self.brick_dim).next_power_of_two() * self.brick_dim
+        } else {
+            size
+        };
+        self.mark_changed(position, &(*position + V3c::unit(size)));
+    }
+
+    /// Records a modification of the MIPs of the given nodes in a new generation
+    pub(crate) fn mark_changed_mips(&mut self, node_keys: impl IntoIterator<Item = usize>) {
+        self.changes.generation += 1;
+        let generation = self.changes.generation;
+        for node_key in node_keys {
+            ChangeTracker::stamp(&mut self.changes.mip_generations, node_key, generation);
+        }
+    }
+
+    /// Records a modification of the data palette in a new generation
+    /// Stored palette indices do not change, so no nodes or bricks are listed, only the whole tree as a modified region
+    pub(crate) fn mark_changed_data_palette(&mut self) {
+        self.changes.generation += 1;
+        self.changes
+            .add_region(V3c::unit(0), V3c::unit(self.boxtree_size));
+    }
+
+    /// Records that node keys were renumbered, or the size of the tree changed
+    /// Previously recorded modifications are dropped, as every part of the tree is modified.
+    pub(crate) fn mark_layout_changed(&mut self) {
+        let generation = self.changes.generation + 1;
+        self.changes = ChangeTracker {
+            generation,
+            layout_generation: generation,
+            ..Default::default()
+        };
+        let max_position = V3c::unit(self.boxtree_size);
+        self.changes.add_region(V3c::unit(0), max_position);
+        self.stamp_region(&BoxRegion {
+            min_position: V3c::unit(0),
+            max_position,
+        });
+    }
+
+    /// Provides a hash of the given node without the voxels inside its bricks:
+    /// its type, the type of its bricks, its occupancy, its children and its bounds
+    fn node_signature(&self, node_key: usize, node_bounds: &Cube) -> u64 {
+        let hash_brick =
+            |brick: &BrickData<PaletteIndexValues>, hasher: &mut DefaultHasher| match brick {
+                BrickData::Empty => 0u8.hash(hasher),
+                BrickData::Parted(_) => 1u8.hash(hasher),
+                BrickData::Solid(voxel) => (2u8, voxel).hash(hasher),
+            };
+        let mut hasher = DefaultHasher::new();
+        match self.nodes.get(node_key) {
+            NodeContent::Nothing => 0u8.hash(&mut hasher),
+            NodeContent::Internal(_) => 1u8.hash(&mut hasher),
+            NodeContent::Leaf(bricks) => {
+                2u8.hash(&mut hasher);
+                for brick in bricks.iter() {
+                    hash_brick(brick, &mut hasher);
+                }
+            }
+            NodeContent::UniformLeaf(brick) => {
+                3u8.hash(&mut hasher);
+                hash_brick(brick, &mut hasher);
+            }
+        }
+        self.stored_occupied_bits(node_key).hash(&mut hasher);
+        if let NodeChildren::Children(children) = &self.node_children[node_key] {
+            children.hash(&mut hasher);
+        }
+
+        // Keys of deleted nodes might be reused elsewhere
+        node_bounds.min_position.x.to_bits().hash(&mut hasher);
+        node_bounds.min_position.y.to_bits().hash(&mut hasher);
+        node_bounds.min_position.z.to_bits().hash(&mut hasher);
+        node_bounds.size.to_bits().hash(&mut hasher);
+        hasher.finish().max(1)
+    }
+
+    /// Sets the generation of the bricks overlapping the given region to the current one,
+    /// along with the nodes overlapping the region, which were modified since they were last stamped
+    /// MIPs of the nodes overlapping the region are stamped while MIPs are enabled, as edits update them.
+    fn stamp_region(&mut self, region: &BoxRegion) {
+        let generation = self.changes.generation;
+        let mut node_stack = vec![(
+            Self::ROOT_NODE_KEY as usize,
+            Cube::root_bounds(self.boxtree_size as f32),
+        )];
+        while let Some((node_key, node_bounds)) = node_stack.pop() {
+            let signature = self.node_signature(node_key, &node_bounds);
+            if ChangeTracker::update_signature(
+                &mut self.changes.node_signatures,
+                node_key,
+                signature,
+            ) {
+                ChangeTracker::stamp(&mut self.changes.node_generations, node_key, generation);
+            }
+            if self.mip_map_strategy.enabled {
+                ChangeTracker::stamp(&mut self.changes.mip_generations, node_key, generation);
+            }
+            let overlapping_sectants = (0..BOX_NODE_CHILDREN_COUNT as u8).filter(|sectant| {
+                region.overlap(&node_bounds.child_bounds_for(*sectant)) != RegionOverlap::Outside
+            });
+            match (self.nodes.get(node_key), &self.node_children[node_key]) {
+                (NodeContent::Internal(_), NodeChildren::Children(_)) => {
+                    for sectant in overlapping_sectants {
+                        if let Some(child_key) = self.valid_child_for(node_key, sectant) {
+                            node_stack.push((child_key, node_bounds.child_bounds_for(sectant)));
+                        }
+                    }
+                }
+                (NodeContent::Leaf(_), _) => {
+                    for sectant in overlapping_sectants {
+                        self.changes
+                            .brick_generations
+                            .insert((node_key, sectant), generation);
+                    }
+                }
+                (NodeContent::UniformLeaf(_), _) => {
+                    self.changes
+                        .brick_generations
+                        .insert((node_key, 0), generation);
+                }
+                _ => {}
+            }
+        }
+    }
+}
@@ -0,0 +1,479 @@
+use crate::{
+    boxtree::{
+        types::{BrickData, Component, Connectivity, NodeContent, OctreeError, PaletteIndexValues},
+        update::region::BoxRegion,
+        BoxTree, BoxTreeEntry, VoxelData, BOX_NODE_CHILDREN_COUNT,
+    },
+    object_pool::empty_marker,
+    spatial::{
+        math::{flat_projection, matrix_index_for, vector::V3c},
+        Cube,
+    },
+};
+use std::collections::{HashSet, VecDeque};
+
+/// An axis aligned box of voxels sharing the same content inside the tree
+/// Solid bricks, uniform nodes and unoccupied nodes are represented as one block each,
+/// so large uniform areas are processed as a whole instead of voxel by voxel.
+#[derive(Debug, Clone, Copy)]
+struct VoxelBlock {
+    min_position: V3c<u32>,
+    max_position: V3c<u32>,
+    value: PaletteIndexValues,
+}
+
+impl VoxelBlock {
+    fn new(bounds: &Cube, value: PaletteIndexValues) -> Self {
+        let min_position = V3c::<u32>::from(bounds.min_position);
+        Self {
+            min_position,
+            max_position: min_position + V3c::unit(bounds.size as u32),
+            value,
+        }
+    }
+
+    /// Blocks never overlap, so their minimum position identifies them
+    fn key(&self) -> (u32, u32, u32) {
+        (
+            self.min_position.x,
+            self.min_position.y,
+            self.min_position.z,
+        )
+    }
+
+    fn is_empty(&self) -> bool {
+        empty_marker::<PaletteIndexValues>() == self.value
+    }
+
+    /// Provides the part of the block inside the given region
+    /// * Returns with None if the block does not overlap with the region
+    fn clipped(&self, min_position: &V3c<u32>, max_position: &V3c<u32>) -> Option<Self> {
+        let clipped = Self {
+            min_position: V3c::new(
+                self.min_position.x.max(min_position.x),
+                self.min_position.y.max(min_position.y),
+                self.min_position.z.max(min_position.z),
+            ),
+            max_position: V3c::new(
+                self.max_position.x.min(max_position.x),
+                self.max_position.y.min(max_position.y),
+                self.max_position.z.min(max_position.z),
+            ),
+            value: self.value,
+        };
+        if clipped.min_position.x < clipped.max_position.x
+            && clipped.min_position.y < clipped.max_position.y
+            && clipped.min_position.z < clipped.max_position.z
+        {
+            Some(clipped)
+        } else {
+            None
+        }
+    }
+
+    fn voxel_count(&self) -> usize {
+        let size = self.max_position - self.min_position;
+        size.x as usize * size.y as usize * size.z as usize
+    }
+}
+
+impl Connectivity {
+    /// The number of axes along which connected voxels may differ
+    fn max_differing_axes(&self) -> u32 {
+        match self {
+            Connectivity::Face => 1,
+            Connectivity::Edge => 2,
+            Connectivity::Vertex => 3,
+        }
+    }
+
+    /// Provides the offsets of the neighbors of a voxel, ordered by z, then y, then x
+    pub fn neighbor_offsets(&self) -> impl Iterator<Item = V3c<i32>> {
+        let max_differing_axes = self.max_differing_axes() as i32;
+        (-1..=1)
+            .flat_map(|z| (-1..=1).flat_map(move |y| (-1..=1).map(move |x| V3c::new(x, y, z))))
+            .filter(move |offset: &V3c<i32>| {
+                let differing_axes = offset.x.abs() + offset.y.abs() + offset.z.abs();
+                0 < differing_axes && differing_axes <= max_differing_axes
+            })
+    }
+}
+
+impl Component {
+    /// Extends the component with the given block
+    fn add(&mut self, block: &VoxelBlock, anchor: Option<(&V3c<u32>, &V3c<u32>)>) {
+        if 0 == self.voxel_count {
+            self.min_position = block.min_position;
+            self.max_position = block.max_position;
+        } else {
+            self.min_position = V3c::new(
+                self.min_position.x.min(block.min_position.x),
+                self.min_position.y.min(block.min_position.y),
+                self.min_position.z.min(block.min_position.z),
+            );
+            self.max_position = V3c::new(
+                self.max_position.x.max(block.max_position.x),
+                self.max_position.y.max(block.max_position.y),
+                self.max_position.z.max(block.max_position.z),
+            );
+        }
+        self.voxel_count += block.voxel_count();
+        self.touches_anchor |= anchor.is_some_and(|(anchor_min, anchor_max)| {
+            block.clipped(anchor_min, anchor_max).is_some()
+        });
+    }
+}
+
+impl<T: VoxelData> BoxTree<T> {
+    /// Overwrites every voxel connected to the seed with the given entry,
+    /// as long as the voxels on the way match the given predicate.
+    /// Voxels are connected through their faces. Returns with the number of updated voxels.
+    /// * `seed` - The position to start the fill from, must be contained within the tree
+    /// * `predicate` - Tells if the fill can spread into a voxel with the given content
+    /// * `entry` - The data to set in the filled voxels, the voxels are erased if it is empty
+    pub fn flood_fill<'a, P, E>(
+        &mut self,
+        seed: &V3c<u32>,
+        predicate: P,
+        entry: E,
+    ) -> Result<usize, OctreeError>
+    where
+        P: Fn(&BoxTreeEntry<T>) -> bool,
+        E: Into<BoxTreeEntry<'a, T>>,
+        T: 'a,
+    {
+        if !Cube::root_bounds(self.boxtree_size as f32).contains(&V3c::from(*seed)) {
+            return Err(OctreeError::InvalidPosition {
+                x: seed.x,
+                y: seed.y,
+                z: seed.z,
+            });
+        }
+
+        let root_min = V3c::unit(0);
+        let root_max = V3c::unit(self.boxtree_size);
+        let matches = |block: &VoxelBlock| {
+            predicate(&NodeContent::pix_get_ref(
+                &block.value,
+                &self.voxel_color_palette,
+                &self.voxel_data_palette,
+            ))
+        };
+        let seed_block = self.block_at(seed);
+        if !matches(&seed_block) {
+            return Ok(0);
+        }
+
+        let mut filled_blocks = Vec::new();
+        let mut visited = HashSet::from([seed_block.key()]);
+        let mut queue = VecDeque::from([seed_block]);
+        while let Some(block) = queue.pop_front() {
+            self.for_each_neighbor_block(
+                &block,
+                &root_min,
+                &root_max,
+                Connectivity::Face,
+                |neighbor| {
+                    if matches(&neighbor) && visited.insert(neighbor.key()) {
+                        queue.push_back(neighbor);
+                    }
+                },
+            );
+            filled_blocks.push(block);
+        }
+
+        let entry = entry.into();
+        let target_content = if entry.is_none() {
+            empty_marker()
+        } else {
+            self.add_to_palette(&entry)?
+        };
+        let mut filled = Component::default();
+        for block in filled_blocks.iter() {
+            filled.add(block, None);
+        }
+
+        // The whole fill is recorded as a single edit
+        let root_bounds = Cube::root_bounds(self.boxtree_size as f32);
+        self.record_box_update(&filled.min_position, &filled.max_position, |tree| {
+            for block in filled_blocks.iter() {
+                tree.update_region_internal(
+                    Self::ROOT_NODE_KEY as usize,
+                    &root_bounds,
+                    &BoxRegion {
+                        min_position: block.min_position,
+                        max_position: block.max_position,
+                    },
+                    target_content,
+                    false,
+                );
+            }
+        });
+        self.mark_changed(&filled.min_position, &filled.max_position);
+        Ok(filled.voxel_count)
+    }
+
+    /// Collects the groups of connected non-empty voxels inside the given region
+    /// Voxels outside the region are not considered, even if they connect parts of the region.
+    /// * `min_position` - the first voxel of the box, must be contained within the tree
+    /// * `max_position` - the end of the box (exclusive), each component must be at most the size of the tree
+    /// * `connectivity` - The neighbors each voxel is connected to
+    /// * `anchor` - The minimum and (exclusive) maximum position of the area, which components may touch
+    pub fn connected_components(
+        &self,
+        min_position: &V3c<u32>,
+        max_position: &V3c<u32>,
+        connectivity: Connectivity,
+        anchor: Option<(&V3c<u32>, &V3c<u32>)>,
+    ) -> Result<Vec<Component>, OctreeError> {
+        self.check_box_bounds(min_position, max_position)?;
+        let mut components = Vec::new();
+        if Self::box_is_empty(min_position, max_position) {
+            return Ok(components);
+        }
+
+        let mut visited = HashSet::new();
+        let mut queue = VecDeque::new();
+        for seed_block in self.occupied_blocks_in(min_position, max_position) {
+            if !visited.insert(seed_block.key()) {
+                continue;
+            }
+            let mut component = Component::default();
+            queue.push_back(seed_block);
+            while let Some(block) = queue.pop_front() {
+                let block = block
+                    .clipped(min_position, max_position)
+                    .expect("Expected block to overlap with the region");
+                component.add(&block, anchor);
+                self.for_each_neighbor_block(
+                    &block,
+                    min_position,
+                    max_position,
+                    connectivity,
+                    |neighbor| {
+                        if !neighbor.is_empty() && visited.insert(neighbor.key()) {
+                            queue.push_back(neighbor);
+                        }
+                    },
+                );
+            }
+            components.push(component);
+        }
+        Ok(components)
+    }
+
+    /// Provides the largest block stored in the tree containing the given position
+    fn block_at(&self, position: &V3c<u32>) -> VoxelBlock {
+        let position_ = V3c::from(*position);
+        let mut node_key = Self::ROOT_NODE_KEY as usize;
+        let mut bounds = Cube::root_bounds(self.boxtree_size as f32);
+        let block = loop {
+            match self.nodes.get(node_key) {
+                NodeContent::Nothing => break VoxelBlock::new(&bounds, empty_marker()),
+                NodeContent::UniformLeaf(brick) => {
+                    break self.block_in_brick(brick, &bounds, position)
+                }
+                NodeContent::Leaf(bricks) => {
+                    let sectant = bounds.sectant_for(&position_);
+                    break self.block_in_brick(
+                        &bricks[sectant as usize],
+                        &bounds.child_bounds_for(sectant),
+                        position,
+                    );
+                }
+                NodeContent::Internal(occupied_bits) => {
+                    let sectant = bounds.sectant_for(&position_);
+                    let child_bounds = bounds.child_bounds_for(sectant);
+                    match self.valid_child_for(node_key, sectant) {
+                        Some(child_key) if 0 != occupied_bits & (0x01 << sectant) => {
+                            node_key = child_key;
+                            bounds = child_bounds;
+                        }
+                        _ => break VoxelBlock::new(&child_bounds, empty_marker()),
+                    }
+                }
+            }
+        };
+        if block.is_empty()
+            || !NodeContent::pix_points_to_empty(
+                &block.value,
+                &self.voxel_color_palette,
+                &self.voxel_data_palette,
+            )
+        {
+            block
+        } else {
+            VoxelBlock {
+                value: empty_marker(),
+                ..block
+            }
+        }
+    }
+
+    /// Provides the block inside the brick with the given bounds containing the given position
+    fn block_in_brick(
+        &self,
+        brick: &BrickData<PaletteIndexValues>,
+        bounds: &Cube,
+        position: &V3c<u32>,
+    ) -> VoxelBlock {
+        match brick {
+            BrickData::Empty => VoxelBlock::new(bounds, empty_marker()),
+            BrickData::Solid(voxel) => VoxelBlock::new(bounds, *voxel),
+            BrickData::Parted(voxels) => {
+                let cell_size = bounds.size / self.brick_dim as f32;
+                let mat_index = matrix_index_for(bounds, position, self.brick_dim);
+                let cell_bounds = Cube {
+                    min_position: bounds.min_position + V3c::<f32>::from(mat_index) * cell_size,
+                    size: cell_size,
+                };
+                VoxelBlock::new(
+                    &cell_bounds,
+                    voxels[flat_projection(
+                        mat_index.x,
+                        mat_index.y,
+                        mat_index.z,
+                        self.brick_dim as usize,
+                    )],
+                )
+            }
+        }
+    }
+
+    /// Collects the non-empty blocks overlapping with the given region, skipping unoccupied nodes
+    fn occupied_blocks_in(
+        &self,
+        min_position: &V3c<u32>,
+        max_position: &V3c<u32>,
+    ) -> Vec<VoxelBlock> {
+        let mut blocks = Vec::new();
+        let mut collect_brick = |brick: &BrickData<PaletteIndexValues>, bounds: &Cube| {
+            let mut push_block = |block: VoxelBlock| {
+                if block.clipped(min_position, max_position).is_some()
+                    && !NodeContent::pix_points_to_empty(
+                        &block.value,
+                        &self.voxel_color_palette,
+                        &self.voxel_data_palette,
+                    )
+                {
+                    blocks.push(block);
+                }
+            };
+            match brick {
+                BrickData::Empty => {}
+                BrickData::Solid(voxel) => push_block(VoxelBlock::new(bounds, *voxel)),
+                BrickData::Parted(voxels) => {
+                    let cell_size = bounds.size / self.brick_dim as f32;
+                    for (index, voxel) in voxels.iter().enumerate() {
+                        let index = index as u32;
+                        let cell_bounds = Cube {
+                            min_position: bounds.min_position
+                                + V3c::new(
+                                    (index % self.brick_dim) as f32,
+                                    ((index / self.brick_dim) % self.brick_dim) as f32,
+                                    (index / (self.brick_dim * self.brick_dim)) as f32,
+                                ) * cell_size,
+                            size: cell_size,
+                        };
+                        push_block(VoxelBlock::new(&cell_bounds, *voxel));
+                    }
+                }
+            }
+        };
+
+        let overlaps = |bounds: &Cube| {
+            VoxelBlock::new(bounds, empty_marker())
+                .clipped(min_position, max_position)
+                .is_some()
+        };
+        let mut node_stack = vec![(
+            Self::ROOT_NODE_KEY as usize,
+            Cube::root_bounds(self.boxtree_size as f32),
+        )];
+        while let Some((node_key, bounds)) = node_stack.pop() {
+            match self.nodes.get(node_key) {
+                NodeContent::Nothing => {}
+                NodeContent::UniformLeaf(brick) => collect_brick(brick, &bounds),
+                NodeContent::Leaf(bricks) => {
+                    for (sectant, brick) in bricks.iter().enumerate() {
+                        let child_bounds = bounds.child_bounds_for(sectant as u8);
+                        if overlaps(&child_bounds) {
+                            collect_brick(brick, &child_bounds);
+                        }
+                    }
+                }
+                NodeContent::Internal(occupied_bits) => {
+                    for sectant in 0..BOX_NODE_CHILDREN_COUNT as u8 {
+                        let child_bounds = bounds.child_bounds_for(sectant);
+                        if 0 == occupied_bits & (0x01 << sectant) || !overlaps(&child_bounds) {
+                            continue;
+                        }
+                        if let Some(child_key) = self.valid_child_for(node_key, sectant) {
+                            node_stack.push((child_key, child_bounds));
+                        }
+                    }
+                }
+            }
+        }
+        blocks
+    }
+
+    /// Calls the given function with every block inside the region, which is connected to the given block
+    /// Blocks may be visited more than once. Positions along the x axis covered by a neighboring block
+    /// are stepped over, so each block is queried only once per row of voxels next to the given block.
+    /// * `block` - The block to provide the neighbors of, expected to be inside the region
+    fn for_each_neighbor_block(
+        &self,
+        block: &VoxelBlock,
+        min_position: &V3c<u32>,
+        max_position: &V3c<u32>,
+        connectivity: Connectivity,
+        mut visit: impl FnMut(VoxelBlock),
+    ) {
+        let max_differing_axes = connectivity.max_differing_axes();
+        let outside = |value: u32, axis_min: u32, axis_max: u32| {
+            (value < axis_min || value >= axis_max) as u32
+        };
+        let shell_min = V3c::new(
+            block.min_position.x.saturating_sub(1).max(min_position.x),
+            block.min_position.y.saturating_sub(1).max(min_position.y),
+            block.min_position.z.saturating_sub(1).max(min_position.z),
+        );
+        let shell_max = V3c::new(
+            (block.max_position.x + 1).min(max_position.x),
+            (block.max_position.y + 1).min(max_position.y),
+            (block.max_position.z + 1).min(max_position.z),
+        );
+        for z in shell_min.z..shell_max.z {
+            for y in shell_min.y..shell_max.y {
+                let differing_axes = outside(y, block.min_position.y, block.max_position.y)
+                    + outside(z, block.min_position.z, block.max_position.z);
+
+                // Inside the block in both y and z: only the voxels right next to it along x
+                if 0 == differing_axes {
+                    if block.min_position.x > shell_min.x {
+                        visit(self.block_at(&V3c::new(block.min_position.x - 1, y, z)));
+                    }
+                    if block.max_position.x < shell_max.x {
+                        visit(self.block_at(&V3c::new(block.max_position.x, y, z)));
+                    }
+                    continue;
+                }
+
+                let (row_start, row_end) = if differing_axes < max_differing_axes {
+                    (shell_min.x, shell_max.x)
+                } else if differing_axes == max_differing_axes {
+                    (block.min_position.x, block.max_position.x)
+                } else {
+                    continue;
+                };
+                let mut x = row_start;
+                while x < row_end {
+                    let neighbor = self.block_at(&V3c::new(x, y, z));
+                    x = neighbor.max_position.x;
+                    visit(neighbor);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,207 @@
+use crate::{
+    boxtree::{
+        types::{BrickData, DenseRegion, NodeContent, OctreeError, PaletteIndexValues},
+        Albedo, BoxTree, VoxelData, BOX_NODE_CHILDREN_COUNT,
+    },
+    object_pool::empty_marker,
+    spatial::{math::vector::V3c, Cube},
+};
+
+/// Palette index values of a region being collected from the tree
+struct DenseIndices {
+    min_position: V3c<u32>,
+    max_position: V3c<u32>,
+    values: Vec<PaletteIndexValues>,
+}
+
+impl DenseIndices {
+    /// Provides the minimum and (exclusive) maximum position of the part of the given bounds inside the region
+    /// * Returns with None if the bounds do not overlap with the region
+    fn clip(&self, bounds: &Cube) -> Option<(V3c<u32>, V3c<u32>)> {
+        let bounds_min = V3c::<u32>::from(bounds.min_position);
+        let bounds_max = bounds_min + V3c::unit(bounds.size as u32);
+        let min_position = V3c::new(
+            bounds_min.x.max(self.min_position.x),
+            bounds_min.y.max(self.min_position.y),
+            bounds_min.z.max(self.min_position.z),
+        );
+        let max_position = V3c::new(
+            bounds_max.x.min(self.max_position.x),
+            bounds_max.y.min(self.max_position.y),
+            bounds_max.z.min(self.max_position.z),
+        );
+        if min_position.x < max_position.x
+            && min_position.y < max_position.y
+            && min_position.z < max_position.z
+        {
+            Some((min_position, max_position))
+        } else {
+            None
+        }
+    }
+
+    /// Sets every value inside the given bounds to the given value, one row at a time
+    fn fill(&mut self, bounds: &Cube, value: PaletteIndexValues) {
+        let Some((min_position, max_position)) = self.clip(bounds) else {
+            return;
+        };
+        let dimensions = self.max_position - self.min_position;
+        let row_start = (min_position.x - self.min_position.x) as usize;
+        let row_end = (max_position.x - self.min_position.x) as usize;
+        for z in min_position.z..max_position.z {
+            for y in min_position.y..max_position.y {
+                let row = ((z - self.min_position.z) * dimensions.y + (y - self.min_position.y))
+                    as usize
+                    * dimensions.x as usize;
+                self.values[row + row_start..row + row_end].fill(value);
+            }
+        }
+    }
+
+    /// Sets the values inside the given bounds to the content of the given brick
+    fn fill_brick(&mut self, brick: &BrickData<PaletteIndexValues>, bounds: &Cube, brick_dim: u32) {
+        match brick {
+            BrickData::Empty => {}
+            BrickData::Solid(voxel) => self.fill(bounds, *voxel),
+            BrickData::Parted(voxels) => {
+                let cell_size = bounds.size / brick_dim as f32;
+                for (index, voxel) in voxels.iter().enumerate() {
+                    let index = index as u32;
+                    let cell_bounds = Cube {
+                        min_position: bounds.min_position
+                            + V3c::new(
+                                (index % brick_dim) as f32,
+                                ((index / brick_dim) % brick_dim) as f32,
+                                (index / (brick_dim * brick_dim)) as f32,
+                            ) * cell_size,
+                        size: cell_size,
+                    };
+                    self.fill(&cell_bounds, *voxel);
+                }
+            }
+        }
+    }
+}
+
+impl<T: VoxelData> BoxTree<T> {
+    /// Provides the voxels inside the given region as a dense array
+    /// Each voxel is at `x + y * size.x + z * size.x * size.y`, where size is `max_position - min_position`.
+    /// Voxels with only color or only data contain the default value for the missing component.
+    /// * `min_position` - the first voxel of the box, must be contained within the tree
+    /// * `max_position` - the end of the box (exclusive), each component must be at most the size of the tree
+    pub fn to_dense(
+        &self,
+        min_position: &V3c<u32>,
+        max_position: &V3c<u32>,
+    ) -> Result<Vec<Option<(Albedo, T)>>, OctreeError> {
+        Ok(self
+            .dense_indices(min_position, max_position)?
+            .into_iter()
+            .map(|index| {
+                if NodeContent::pix_points_to_empty(
+                    &index,
+                    &self.voxel_color_palette,
+                    &self.voxel_data_palette,
+                ) {
+                    return None;
+                }
+                let entry = NodeContent::pix_get_ref(
+                    &index,
+                    &self.voxel_color_palette,
+                    &self.voxel_data_palette,
+                );
+                Some((
+                    entry.albedo().copied().unwrap_or_default(),
+                    entry.data().cloned().unwrap_or_default(),
+                ))
+            })
+            .collect())
+    }
+
+    /// Provides the palette indices of the voxels inside the given region as a dense array, with the palettes
+    /// Same as @to_dense, but without resolving the palette entries of each voxel
+    pub fn to_dense_indices(
+        &self,
+        min_position: &V3c<u32>,
+        max_position: &V3c<u32>,
+    ) -> Result<DenseRegion<T>, OctreeError> {
+        let voxels = self
+            .dense_indices(min_position, max_position)?
+            .into_iter()
+            .map(|index| {
+                (
+                    NodeContent::pix_color_is_some(&index)
+                        .then(|| NodeContent::pix_color_index(&index) as u16),
+                    NodeContent::pix_data_is_some(&index)
+                        .then(|| NodeContent::pix_data_index(&index) as u16),
+                )
+            })
+            .collect();
+        Ok(DenseRegion {
+            dimensions: V3c::new(
+                max_position.x.saturating_sub(min_position.x),
+                max_position.y.saturating_sub(min_position.y),
+                max_position.z.saturating_sub(min_position.z),
+            ),
+            voxels,
+            color_palette: self.voxel_color_palette.to_vec(),
+            data_palette: self.voxel_data_palette.to_vec(),
+        })
+    }
+
+    /// Collects the palette index values inside the given region in one pass over the overlapping nodes
+    /// Uniform parts of the tree are filled in bulk, empty parts are left as empty markers
+    pub(crate) fn dense_indices(
+        &self,
+        min_position: &V3c<u32>,
+        max_position: &V3c<u32>,
+    ) -> Result<Vec<PaletteIndexValues>, OctreeError> {
+        self.check_box_bounds(min_position, max_position)?;
+        let mut dense = DenseIndices {
+            min_position: *min_position,
+            max_position: *max_position,
+            values: Vec::new(),
+        };
+        if Self::box_is_empty(min_position, max_position) {
+            return Ok(dense.values);
+        }
+        let dimensions = *max_position - *min_position;
+        dense.values = vec![
+            empty_marker::<PaletteIndexValues>();
+            (dimensions.x * dimensions.y * dimensions.z) as usize
+        ];
+
+        let mut node_stack = vec![(
+            Self::ROOT_NODE_KEY as usize,
+            Cube::root_bounds(self.boxtree_size as f32),
+        )];
+        while let Some((node_key, bounds)) = node_stack.pop() {
+            match self.nodes.get(node_key) {
+                NodeContent::Nothing => {}
+                NodeContent::UniformLeaf(brick) => dense.fill_brick(brick, &bounds, self.brick_dim),
+                NodeContent::Leaf(bricks) => {
+                    for (sectant, brick) in bricks.iter().enumerate() {
+                        let child_bounds = bounds.child_bounds_for(sectant as u8);
+                        if dense.clip(&child_bounds).is_some() {
+                            dense.fill_brick(brick, &child_bounds, self.brick_dim);
+                        }
+                    }
+                }
+                NodeContent::Internal(occupied_bits) => {
+                    for sectant in 0..BOX_NODE_CHILDREN_COUNT as u8 {
+                        let child_bounds = bounds.child_bounds_for(sectant);
+                        if 0 == occupied_bits & (0x01 << sectant)
+                            || dense.clip(&child_bounds).is_none()
+                        {
+                            continue;
+                        }
+                        if let Some(child_key) = self.valid_child_for(node_key, sectant) {
+                            node_stack.push((child_key, child_bounds));
+                        }
+                    }
+                }
+            }
+        }
+        Ok(dense.values)
+    }
+}
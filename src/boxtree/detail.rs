@@ -285,6 +285,9 @@ impl<T: crate::boxtree::VoxelData> BoxTree<T> {
                         );
                         self.node_mips
                             .resize(self.node_mips.len().max(self.nodes.len()), BrickData::Empty);
+                        self.node_children[node_new_children[sectant] as usize] =
+                            NodeChildren::NoChildren;
+                        self.node_mips[node_new_children[sectant] as usize] = BrickData::Empty;
                     }
 
                     match brick {
@@ -301,7 +304,8 @@ impl<T: crate::boxtree::VoxelData> BoxTree<T> {
                             // As it is a higher resolution, than the current bitmap, it needs to be bruteforced
                             self.node_children[node_new_children[sectant] as usize] =
                                 NodeChildren::OccupancyBitmap(
-                                    bricks[sectant].calculate_occupied_bits(
+                                    BrickData::calculate_brick_occupied_bits(
+                                        &brick,
                                         self.brick_dim as usize,
                                         &self.voxel_color_palette,
                                         &self.voxel_data_palette,
@@ -331,6 +335,8 @@ impl<T: crate::boxtree::VoxelData> BoxTree<T> {
                             .resize(self.node_mips.len().max(self.nodes.len()), BrickData::Empty);
                         self.node_children[node_new_children[target_sectant] as usize] =
                             NodeChildren::OccupancyBitmap(0);
+                        self.node_mips[node_new_children[target_sectant] as usize] =
+                            BrickData::Empty;
                     }
                     BrickData::Solid(voxel) => {
                         // Push in all solid children for child sectants
@@ -344,13 +350,18 @@ impl<T: crate::boxtree::VoxelData> BoxTree<T> {
                                 self.node_children.len().max(*new_child as usize + 1),
                                 NodeChildren::default(),
                             );
+                            self.node_mips.resize(
+                                self.node_mips.len().max(self.nodes.len()),
+                                BrickData::Empty,
+                            );
                             self.node_children[*new_child as usize] =
                                 NodeChildren::OccupancyBitmap(u64::MAX);
+                            self.node_mips[*new_child as usize] = BrickData::Empty;
                         }
                     }
                     BrickData::Parted(brick) => {
                         // Each brick is mapped to take up one subsection of the current data
-                        let children_bricks = Self::dilute_brick_data(brick, self.brick_dim);
+                        let children_bricks = Self::dilute_brick_data(&brick, self.brick_dim);
                         for (sectant, new_brick) in children_bricks.into_iter().enumerate() {
                             // Push in the new child
                             let child_occupied_bits = BrickData::calculate_brick_occupied_bits(
@@ -359,10 +370,9 @@ impl<T: crate::boxtree::VoxelData> BoxTree<T> {
                                 &self.voxel_color_palette,
                                 &self.voxel_data_palette,
                             );
-                            node_new_children[sectant] = self
-                                .nodes
-                                .push(NodeContent::UniformLeaf(BrickData::Parted(new_brick)))
-                                as u32;
+                            node_new_children[sectant] = self.nodes.push(NodeContent::UniformLeaf(
+                                BrickData::Parted(new_brick.into()),
+                            )) as u32;
 
                             // Potentially Resize node children array to accomodate the new child
                             self.node_children.resize(
@@ -379,6 +389,7 @@ impl<T: crate::boxtree::VoxelData> BoxTree<T> {
                             // Set the occupancy bitmap for the new leaf child node
                             self.node_children[node_new_children[sectant] as usize] =
                                 NodeChildren::OccupancyBitmap(child_occupied_bits);
+                            self.node_mips[node_new_children[sectant] as usize] = BrickData::Empty;
                         }
                     }
                 }
@@ -417,6 +428,7 @@ impl<T: crate::boxtree::VoxelData> BoxTree<T> {
                 self.deallocate_children_of(child); // Recursion should be fine as depth is not expceted to be more, than 32
                 self.nodes.free(child);
                 self.node_children[child] = NodeChildren::NoChildren;
+                self.node_mips[child] = BrickData::Empty;
             }
         }
     }
@@ -445,6 +457,31 @@ impl<T: crate::boxtree::VoxelData> BoxTree<T> {
         }
     }
 
+    /// Calculates the occupied bits of a Node from its children or bricks, instead of the stored values
+    pub(crate) fn calculate_node_occupied_bits(&self, node_key: usize) -> u64 {
+        match self.nodes.get(node_key) {
+            NodeContent::Nothing => 0,
+            NodeContent::Internal(_) => (0..BOX_NODE_CHILDREN_COUNT as u8)
+                .filter(|sectant| {
+                    self.valid_child_for(node_key, *sectant)
+                        .is_some_and(|child_key| 0 != self.stored_occupied_bits(child_key))
+                })
+                .fold(0, |bits, sectant| bits | (0x01 << sectant)),
+            NodeContent::Leaf(bricks) => bricks
+                .iter()
+                .enumerate()
+                .filter(|(_, brick)| {
+                    !brick.contains_nothing(&self.voxel_color_palette, &self.voxel_data_palette)
+                })
+                .fold(0, |bits, (sectant, _)| bits | (0x01 << sectant)),
+            NodeContent::UniformLeaf(brick) => brick.calculate_occupied_bits(
+                self.brick_dim as usize,
+                &self.voxel_color_palette,
+                &self.voxel_data_palette,
+            ),
+        }
+    }
+
     /// Stores the given occupied bits for the given node based on key
     pub(crate) fn store_occupied_bits(&mut self, node_key: usize, new_occupied_bits: u64) {
         match self.nodes.get_mut(node_key) {
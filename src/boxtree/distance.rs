@@ -0,0 +1,630 @@
+use crate::{
+    boxtree::{
+        types::{BrickData, DistanceField, DistanceMetric, NodeContent, OctreeError},
+        BoxTree, VoxelData, BOX_NODE_CHILDREN_COUNT, BOX_NODE_DIMENSION,
+    },
+    spatial::{math::flat_projection, math::vector::V3c, Cube},
+};
+
+/// The squared distance of voxels without any occupied voxel in reach
+const UNREACHED_SQUARED_DISTANCE: f64 = 1e20;
+
+/// Provides the index of the given position inside a box of the given size, ordered by x, then y, then z
+fn box_index(position: V3c<u32>, size: V3c<u32>) -> usize {
+    (position.x + position.y * size.x + position.z * size.x * size.y) as usize
+}
+
+/// Calculates the squared distance to the nearest zero for each element of a line, in place
+/// Lower envelope of parabolas, as described in "Distance Transforms of Sampled Functions" by Felzenszwalb and Huttenlocher.
+/// * `values` - The squared distances of the line, updated to the squared distances including this axis
+/// * `parabolas` - Buffer for the position of each parabola inside the envelope, at least as long as values
+/// * `boundaries` - Buffer for the ranges of the parabolas inside the envelope, longer than values by one
+/// * `source` - Buffer for the squared distances before the transform
+fn squared_distance_transform(
+    values: &mut [f64],
+    parabolas: &mut [usize],
+    boundaries: &mut [f64],
+    source: &mut Vec<f64>,
+) {
+    source.clear();
+    source.extend_from_slice(values);
+    let intersection = |q: usize, p: usize| {
+        ((source[q] + (q * q) as f64) - (source[p] + (p * p) as f64)) / (2 * q - 2 * p) as f64
+    };
+
+    let mut k = 0;
+    parabolas[0] = 0;
+    boundaries[0] = f64::NEG_INFINITY;
+    boundaries[1] = f64::INFINITY;
+    for q in 1..values.len() {
+        let mut s = intersection(q, parabolas[k]);
+        while s <= boundaries[k] {
+            k -= 1;
+            s = intersection(q, parabolas[k]);
+        }
+        k += 1;
+        parabolas[k] = q;
+        boundaries[k] = s;
+        boundaries[k + 1] = f64::INFINITY;
+    }
+
+    k = 0;
+    for (q, value) in values.iter_mut().enumerate() {
+        while boundaries[k + 1] < q as f64 {
+            k += 1;
+        }
+        let offset = q as f64 - parabolas[k] as f64;
+        *value = offset * offset + source[parabolas[k]];
+    }
+}
+
+/// The children of a node with their component along each axis inside each range, indexed by `[axis][min][max]`,
+/// where the range is `min..=max`, so boxes of children can be tested against occupancy bitmaps at once
+const SECTANT_RANGE_MASKS: [[[u64; BOX_NODE_DIMENSION]; BOX_NODE_DIMENSION]; 3] = {
+    let mut masks = [[[0; BOX_NODE_DIMENSION]; BOX_NODE_DIMENSION]; 3];
+    let mut sectant = 0;
+    while sectant < BOX_NODE_CHILDREN_COUNT {
+        let components = [
+            sectant % BOX_NODE_DIMENSION,
+            (sectant / BOX_NODE_DIMENSION) % BOX_NODE_DIMENSION,
+            sectant / (BOX_NODE_DIMENSION * BOX_NODE_DIMENSION),
+        ];
+        let mut axis = 0;
+        while axis < 3 {
+            let mut min = 0;
+            while min <= components[axis] {
+                let mut max = components[axis];
+                while max < BOX_NODE_DIMENSION {
+                    masks[axis][min][max] |= 0x01 << sectant;
+                    max += 1;
+                }
+                min += 1;
+            }
+            axis += 1;
+        }
+        sectant += 1;
+    }
+    masks
+};
+
+/// Provides the cell of a grid inside the given bounds containing the given position
+/// * `dimension` - The number of cells along each axis of the grid
+fn grid_cell(bounds: &Cube, dimension: u32, position: &V3c<f32>) -> V3c<u32> {
+    let cell_size = bounds.size / dimension as f32;
+    let cell = ((*position - bounds.min_position) / cell_size).floor();
+    V3c::<u32>::from(cell.cut_each_component((dimension - 1) as f32))
+}
+
+/// Grows a box of empty cells around the given cell by one cell in each direction, while the new cells are empty
+/// * `dimension` - The number of cells along each axis of the grid
+/// * `new_cells_are_empty` - Tells if the cells of the grown box outside the previous box are empty:
+///   |grown_min, grown_max, box_min, box_max| -> bool
+/// * Returns with the first and last cell of the box along each axis
+fn grow_empty_box(
+    cell: V3c<u32>,
+    dimension: u32,
+    new_cells_are_empty: impl Fn(&V3c<u32>, &V3c<u32>, &V3c<u32>, &V3c<u32>) -> bool,
+) -> (V3c<u32>, V3c<u32>) {
+    let mut box_min = cell;
+    let mut box_max = cell;
+    loop {
+        let grown_min = V3c::new(
+            box_min.x.saturating_sub(1),
+            box_min.y.saturating_sub(1),
+            box_min.z.saturating_sub(1),
+        );
+        let grown_max = V3c::new(
+            (box_max.x + 1).min(dimension - 1),
+            (box_max.y + 1).min(dimension - 1),
+            (box_max.z + 1).min(dimension - 1),
+        );
+        if (grown_min == box_min && grown_max == box_max)
+            || !new_cells_are_empty(&grown_min, &grown_max, &box_min, &box_max)
+        {
+            return (box_min, box_max);
+        }
+        box_min = grown_min;
+        box_max = grown_max;
+    }
+}
+
+/// Provides the largest box of empty cells of a grid around the given empty cell
+/// * `dimension` - The number of cells along each axis of the grid
+/// * `is_occupied` - Tells if the cell at the given flat index is occupied
+/// * Returns with the first and last cell of the box along each axis, or None if the cell is occupied
+fn empty_cells_box(
+    cell: V3c<u32>,
+    dimension: u32,
+    is_occupied: impl Fn(usize) -> bool,
+) -> Option<(V3c<u32>, V3c<u32>)> {
+    let cell_is_empty = |x: u32, y: u32, z: u32| {
+        !is_occupied(flat_projection(
+            x as usize,
+            y as usize,
+            z as usize,
+            dimension as usize,
+        ))
+    };
+    if !cell_is_empty(cell.x, cell.y, cell.z) {
+        return None;
+    }
+
+    // Only the shell of new cells around the previous box is tested
+    Some(grow_empty_box(
+        cell,
+        dimension,
+        |grown_min, grown_max, box_min, box_max| {
+            (grown_min.z..=grown_max.z).all(|z| {
+                (grown_min.y..=grown_max.y).all(|y| {
+                    let crosses_box = (box_min.y..=box_max.y).contains(&y)
+                        && (box_min.z..=box_max.z).contains(&z);
+                    if crosses_box {
+                        // Rows crossing the previous box only have new cells at their ends
+                        [grown_min.x, grown_max.x]
+                            .into_iter()
+                            .filter(|x| !(box_min.x..=box_max.x).contains(x))
+                            .all(|x| cell_is_empty(x, y, z))
+                    } else {
+                        (grown_min.x..=grown_max.x).all(|x| cell_is_empty(x, y, z))
+                    }
+                })
+            })
+        },
+    ))
+}
+
+/// Provides the largest box of empty children of a node around the given empty child
+/// The children inside each grown box are tested at once against the occupancy bitmap of the node.
+/// * `occupied_bits` - The occupancy bitmap of the node
+/// * Returns with the first and last child of the box along each axis, or None if the child is occupied
+pub(crate) fn empty_sectants_box(occupied_bits: u64, sectant: u8) -> Option<(V3c<u32>, V3c<u32>)> {
+    if 0 != occupied_bits & (0x01 << sectant) {
+        return None;
+    }
+    let sectant = sectant as u32;
+    let dimension = BOX_NODE_DIMENSION as u32;
+    let cell = V3c::new(
+        sectant % dimension,
+        (sectant / dimension) % dimension,
+        sectant / (dimension * dimension),
+    );
+    Some(grow_empty_box(
+        cell,
+        dimension,
+        |grown_min, grown_max, _, _| {
+            let grown_box = SECTANT_RANGE_MASKS[0][grown_min.x as usize][grown_max.x as usize]
+                & SECTANT_RANGE_MASKS[1][grown_min.y as usize][grown_max.y as usize]
+                & SECTANT_RANGE_MASKS[2][grown_min.z as usize][grown_max.z as usize];
+            0 == occupied_bits & grown_box
+        },
+    ))
+}
+
+/// Provides the distance from the given position to the border of the given box of cells of a grid inside the given bounds
+/// * `dimension` - The number of cells along each axis of the grid
+/// * `(box_min, box_max)` - The first and last cell of the box along each axis
+fn box_border_distance(
+    bounds: &Cube,
+    dimension: u32,
+    position: &V3c<f32>,
+    (box_min, box_max): (V3c<u32>, V3c<u32>),
+) -> f32 {
+    let cell_size = bounds.size / dimension as f32;
+    let box_min = bounds.min_position + V3c::<f32>::from(box_min) * cell_size;
+    let box_max = bounds.min_position + V3c::<f32>::from(box_max + V3c::unit(1)) * cell_size;
+    (position.x - box_min.x)
+        .min(position.y - box_min.y)
+        .min(position.z - box_min.z)
+        .min(box_max.x - position.x)
+        .min(box_max.y - position.y)
+        .min(box_max.z - position.z)
+        .max(0.)
+}
+
+/// Provides the distance from the given position to the border of the largest empty box around it,
+/// made of the cells of a grid inside the given bounds
+/// * `dimension` - The number of cells along each axis of the grid
+/// * `is_occupied` - Tells if the cell at the given flat index is occupied
+fn empty_cells_distance(
+    bounds: &Cube,
+    dimension: u32,
+    position: &V3c<f32>,
+    is_occupied: impl Fn(usize) -> bool,
+) -> f32 {
+    empty_cells_box(
+        grid_cell(bounds, dimension, position),
+        dimension,
+        is_occupied,
+    )
+    .map_or(0., |empty_box| {
+        box_border_distance(bounds, dimension, position, empty_box)
+    })
+}
+
+impl DistanceField {
+    /// The number of bricks along each axis
+    fn brick_counts(&self) -> V3c<u32> {
+        V3c::new(
+            self.dimensions.x.div_ceil(self.brick_dimension),
+            self.dimensions.y.div_ceil(self.brick_dimension),
+            self.dimensions.z.div_ceil(self.brick_dimension),
+        )
+    }
+
+    /// Provides the distance of the voxel at the given position to the nearest occupied voxel
+    /// * Returns with None if the position is outside the region of the distance field
+    pub fn get(&self, position: &V3c<u32>) -> Option<f32> {
+        let max_position = self.min_position + self.dimensions;
+        if position.x < self.min_position.x
+            || position.y < self.min_position.y
+            || position.z < self.min_position.z
+            || position.x >= max_position.x
+            || position.y >= max_position.y
+            || position.z >= max_position.z
+        {
+            return None;
+        }
+        let offset = *position - self.min_position;
+        let brick_position = offset / self.brick_dimension;
+        match &self.bricks[box_index(brick_position, self.brick_counts())] {
+            None => Some(self.max_distance),
+            Some(distances) => {
+                let voxel = offset - brick_position * self.brick_dimension;
+                Some(
+                    distances[flat_projection(
+                        voxel.x as usize,
+                        voxel.y as usize,
+                        voxel.z as usize,
+                        self.brick_dimension as usize,
+                    )],
+                )
+            }
+        }
+    }
+
+    /// Provides the distance of each voxel inside the region
+    /// The voxel at (x,y,z) relative to the region is at `x + y * dimensions.x + z * dimensions.x * dimensions.y`
+    pub fn to_dense(&self) -> Vec<f32> {
+        let mut distances = Vec::with_capacity(
+            (self.dimensions.x * self.dimensions.y * self.dimensions.z) as usize,
+        );
+        for z in 0..self.dimensions.z {
+            for y in 0..self.dimensions.y {
+                for x in 0..self.dimensions.x {
+                    distances.push(
+                        self.get(&(self.min_position + V3c::new(x, y, z)))
+                            .expect("Expected position to be inside the distance field"),
+                    );
+                }
+            }
+        }
+        distances
+    }
+
+    /// The number of bricks with stored distances
+    pub fn stored_brick_count(&self) -> usize {
+        self.bricks.iter().filter(|brick| brick.is_some()).count()
+    }
+}
+
+impl<T: VoxelData> BoxTree<T> {
+    /// Calculates the distance of each voxel inside the given region to the nearest occupied voxel
+    /// Distances are measured between the centers of the voxels, occupied voxels have a distance of 0.
+    /// Occupied voxels outside the region are considered, so distance fields of neighboring regions match.
+    /// Voxels outside the tree are empty.
+    /// * `min_position` - the first voxel of the box, must be contained within the tree
+    /// * `max_position` - the end of the box (exclusive), each component must be at most the size of the tree
+    /// * `max_distance` - Larger distances are clamped to this value, bricks with only clamped distances are not stored
+    /// * `metric` - The way distances are measured
+    pub fn distance_field(
+        &self,
+        min_position: &V3c<u32>,
+        max_position: &V3c<u32>,
+        max_distance: u32,
+        metric: DistanceMetric,
+    ) -> Result<DistanceField, OctreeError> {
+        self.check_box_bounds(min_position, max_position)?;
+        let dimensions = V3c::new(
+            max_position.x.saturating_sub(min_position.x),
+            max_position.y.saturating_sub(min_position.y),
+            max_position.z.saturating_sub(min_position.z),
+        );
+        let brick_counts = V3c::new(
+            dimensions.x.div_ceil(self.brick_dim),
+            dimensions.y.div_ceil(self.brick_dim),
+            dimensions.z.div_ceil(self.brick_dim),
+        );
+        let mut distance_field = DistanceField {
+            min_position: *min_position,
+            dimensions,
+            brick_dimension: self.brick_dim,
+            max_distance: max_distance as f32,
+            bricks: vec![None; (brick_counts.x * brick_counts.y * brick_counts.z) as usize],
+        };
+        if Self::box_is_empty(min_position, max_position) {
+            return Ok(distance_field);
+        }
+
+        // Only voxels closer than the maximum distance to the region may affect it
+        let outer_min = V3c::new(
+            min_position.x.saturating_sub(max_distance),
+            min_position.y.saturating_sub(max_distance),
+            min_position.z.saturating_sub(max_distance),
+        );
+        let outer_max = V3c::new(
+            max_position
+                .x
+                .saturating_add(max_distance)
+                .min(self.boxtree_size),
+            max_position
+                .y
+                .saturating_add(max_distance)
+                .min(self.boxtree_size),
+            max_position
+                .z
+                .saturating_add(max_distance)
+                .min(self.boxtree_size),
+        );
+        if self.iter_region(&outer_min, &outer_max)?.next().is_none() {
+            return Ok(distance_field);
+        }
+
+        let outer_size = outer_max - outer_min;
+        let distances = match metric {
+            DistanceMetric::Euclidean => self.euclidean_distances(&outer_min, &outer_max)?,
+            DistanceMetric::Chamfer => self.chamfer_distances(&outer_min, &outer_max)?,
+        };
+
+        // Store the distances of the bricks with any voxel closer than the maximum distance
+        let brick_dim = self.brick_dim;
+        for brick_z in 0..brick_counts.z {
+            for brick_y in 0..brick_counts.y {
+                for brick_x in 0..brick_counts.x {
+                    let brick_min = V3c::new(brick_x, brick_y, brick_z) * brick_dim;
+                    let mut brick = vec![max_distance as f32; brick_dim.pow(3) as usize];
+                    let mut brick_is_stored = false;
+                    for z in brick_min.z..(brick_min.z + brick_dim).min(dimensions.z) {
+                        for y in brick_min.y..(brick_min.y + brick_dim).min(dimensions.y) {
+                            for x in brick_min.x..(brick_min.x + brick_dim).min(dimensions.x) {
+                                let outer_offset = *min_position + V3c::new(x, y, z) - outer_min;
+                                let distance = distances[box_index(outer_offset, outer_size)]
+                                    .min(max_distance as f32);
+                                if distance < max_distance as f32 {
+                                    brick_is_stored = true;
+                                }
+                                let voxel = V3c::new(x, y, z) - brick_min;
+                                brick[flat_projection(
+                                    voxel.x as usize,
+                                    voxel.y as usize,
+                                    voxel.z as usize,
+                                    brick_dim as usize,
+                                )] = distance;
+                            }
+                        }
+                    }
+                    if brick_is_stored {
+                        distance_field.bricks
+                            [box_index(V3c::new(brick_x, brick_y, brick_z), brick_counts)] =
+                            Some(brick);
+                    }
+                }
+            }
+        }
+        Ok(distance_field)
+    }
+
+    /// Calculates the exact euclidean distance of each voxel inside the given box to the nearest occupied voxel,
+    /// by transforming the squared distances along each axis one after another
+    fn euclidean_distances(
+        &self,
+        min_position: &V3c<u32>,
+        max_position: &V3c<u32>,
+    ) -> Result<Vec<f32>, OctreeError> {
+        let size = *max_position - *min_position;
+        let size = [size.x as usize, size.y as usize, size.z as usize];
+        let mut squared_distances: Vec<f64> = self
+            .dense_indices(min_position, max_position)?
+            .iter()
+            .map(|index| {
+                if NodeContent::pix_points_to_empty(
+                    index,
+                    &self.voxel_color_palette,
+                    &self.voxel_data_palette,
+                ) {
+                    UNREACHED_SQUARED_DISTANCE
+                } else {
+                    0.
+                }
+            })
+            .collect();
+
+        let longest_side = size[0].max(size[1]).max(size[2]);
+        let mut line = Vec::with_capacity(longest_side);
+        let mut source = Vec::with_capacity(longest_side);
+        let mut parabolas = vec![0; longest_side];
+        let mut boundaries = vec![0.; longest_side + 1];
+        let strides = [1, size[0], size[0] * size[1]];
+        for axis in 0..3 {
+            let (other_a, other_b) = ((axis + 1) % 3, (axis + 2) % 3);
+            for b in 0..size[other_b] {
+                for a in 0..size[other_a] {
+                    let line_start = a * strides[other_a] + b * strides[other_b];
+                    line.clear();
+                    line.extend(
+                        (0..size[axis]).map(|i| squared_distances[line_start + i * strides[axis]]),
+                    );
+                    squared_distance_transform(
+                        &mut line,
+                        &mut parabolas,
+                        &mut boundaries,
+                        &mut source,
+                    );
+                    for (i, value) in line.iter().enumerate() {
+                        squared_distances[line_start + i * strides[axis]] = *value;
+                    }
+                }
+            }
+        }
+        Ok(squared_distances
+            .iter()
+            .map(|squared_distance| squared_distance.sqrt() as f32)
+            .collect())
+    }
+
+    /// Calculates the chamfer distance of each voxel inside the given box to the nearest occupied voxel,
+    /// by propagating the distances forward, then backward through the box
+    fn chamfer_distances(
+        &self,
+        min_position: &V3c<u32>,
+        max_position: &V3c<u32>,
+    ) -> Result<Vec<f32>, OctreeError> {
+        let size = *max_position - *min_position;
+        let size = V3c::<i32>::from(size);
+        let mut distances: Vec<f32> = self
+            .dense_indices(min_position, max_position)?
+            .iter()
+            .map(|index| {
+                if NodeContent::pix_points_to_empty(
+                    index,
+                    &self.voxel_color_palette,
+                    &self.voxel_data_palette,
+                ) {
+                    f32::INFINITY
+                } else {
+                    0.
+                }
+            })
+            .collect();
+
+        // The neighbors before each voxel in scan order, the ones after are in the opposite direction
+        let mut offsets = Vec::new();
+        for z in -1..=1 {
+            for y in -1..=1 {
+                for x in -1..=1 {
+                    if (z, y, x) < (0, 0, 0) {
+                        offsets.push((V3c::new(x, y, z), ((x * x + y * y + z * z) as f32).sqrt()));
+                    }
+                }
+            }
+        }
+        let index_of = |position: V3c<i32>| {
+            (position.x + position.y * size.x + position.z * size.x * size.y) as usize
+        };
+        let inside = |position: V3c<i32>| {
+            position.x >= 0
+                && position.y >= 0
+                && position.z >= 0
+                && position.x < size.x
+                && position.y < size.y
+                && position.z < size.z
+        };
+        for direction in [1, -1] {
+            let scan = |count: i32| -> Vec<i32> {
+                if 1 == direction {
+                    (0..count).collect()
+                } else {
+                    (0..count).rev().collect()
+                }
+            };
+            for z in scan(size.z) {
+                for y in scan(size.y) {
+                    for x in scan(size.x) {
+                        let position = V3c::new(x, y, z);
+                        let mut distance = distances[index_of(position)];
+                        for (offset, step) in &offsets {
+                            let neighbor = position + *offset * direction;
+                            if inside(neighbor) {
+                                distance = distance.min(distances[index_of(neighbor)] + step);
+                            }
+                        }
+                        distances[index_of(position)] = distance;
+                    }
+                }
+            }
+        }
+        Ok(distances)
+    }
+
+    /// Provides a conservative distance from the given point to the nearest occupied voxel
+    /// The distance is measured to the border of the largest empty box around the point, built from the
+    /// empty children of the deepest node containing it, or from the empty voxels of its brick.
+    /// No occupied voxel is closer than the provided distance, so rays can safely step this far through empty space,
+    /// @get_by_ray steps over the empty children of each node the same way.
+    /// * `position` - The point to measure from, in voxel units
+    /// * Returns with 0 if the point is inside an occupied voxel, None if the point is outside the tree
+    pub fn empty_space_distance(&self, position: &V3c<f32>) -> Option<f32> {
+        let mut bounds = Cube::root_bounds(self.boxtree_size as f32);
+        if !bounds.contains(position) {
+            return None;
+        }
+        let brick_is_empty = |brick: &BrickData<_>| match brick {
+            BrickData::Empty => true,
+            BrickData::Solid(voxel) => NodeContent::pix_points_to_empty(
+                voxel,
+                &self.voxel_color_palette,
+                &self.voxel_data_palette,
+            ),
+            BrickData::Parted(_) => false,
+        };
+        let brick_distance = |bounds: &Cube, brick: &BrickData<_>| match brick {
+            BrickData::Parted(voxels) => {
+                empty_cells_distance(bounds, self.brick_dim, position, |index| {
+                    !NodeContent::pix_points_to_empty(
+                        &voxels[index],
+                        &self.voxel_color_palette,
+                        &self.voxel_data_palette,
+                    )
+                })
+            }
+            _ => {
+                if brick_is_empty(brick) {
+                    empty_cells_distance(bounds, 1, position, |_| false)
+                } else {
+                    0.
+                }
+            }
+        };
+
+        let mut node_key = Self::ROOT_NODE_KEY as usize;
+        loop {
+            match self.nodes.get(node_key) {
+                NodeContent::Nothing => {
+                    return Some(empty_cells_distance(&bounds, 1, position, |_| false));
+                }
+                NodeContent::Internal(occupied_bits) => {
+                    let sectant = bounds.sectant_for(position);
+                    let child_key = self
+                        .valid_child_for(node_key, sectant)
+                        .filter(|_| 0 != occupied_bits & (0x01 << sectant));
+                    if let Some(child_key) = child_key {
+                        node_key = child_key;
+                        bounds = bounds.child_bounds_for(sectant);
+                        continue;
+                    }
+                    let dimension = BOX_NODE_DIMENSION as u32;
+                    return Some(
+                        empty_sectants_box(*occupied_bits, sectant).map_or(0., |empty_box| {
+                            box_border_distance(&bounds, dimension, position, empty_box)
+                        }),
+                    );
+                }
+                NodeContent::Leaf(bricks) => {
+                    let sectant = bounds.sectant_for(position);
+                    let brick = &bricks[sectant as usize];
+                    if brick_is_empty(brick) {
+                        return Some(empty_cells_distance(
+                            &bounds,
+                            BOX_NODE_DIMENSION as u32,
+                            position,
+                            |sectant| !brick_is_empty(&bricks[sectant]),
+                        ));
+                    }
+                    return Some(brick_distance(&bounds.child_bounds_for(sectant), brick));
+                }
+                NodeContent::UniformLeaf(brick) => {
+                    return Some(brick_distance(&bounds, brick));
+                }
+            }
+        }
+    }
+}
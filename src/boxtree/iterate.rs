@@ -1,6 +1,11 @@
 use crate::{
     boxtree::{
-        types::NodeContent, Albedo, BoxTree, MIPResamplingMethods, VoxelData, BOX_NODE_DIMENSION,
+        types::{
+            BoxTreeEntry, BoxTreeIter, BrickData, IterationFrame, NodeContent, OctreeError,
+            PaletteIndexValues,
+        },
+        Albedo, BoxTree, MIPResamplingMethods, VoxelData, BOX_NODE_CHILDREN_COUNT,
+        BOX_NODE_DIMENSION,
     },
     spatial::{math::vector::V3c, Cube},
 };
@@ -31,8 +36,25 @@ pub(crate) trait MIPResamplingFunction {
 /// returns with update size
 pub(crate) fn execute_for_relevant_sectants<F: FnMut(V3c<u32>, V3c<u32>, u8, &Cube)>(
     node_bounds: &Cube,
-    position_: &V3c<u32>,
+    position: &V3c<u32>,
     update_size: u32,
+    fun: F,
+) -> V3c<usize> {
+    execute_for_relevant_sectants_in_range(node_bounds, position, &V3c::unit(update_size), fun)
+}
+
+/// Calls the given function for every child position inside the given update range
+/// Same as @execute_for_relevant_sectants, but the update range may differ on each axis
+/// * `node_bounds` - The bounds of the updated node
+/// * `position` - The position of the intended update
+/// * `update_size` - Range of the intended update starting from position on each axis
+/// * `fun` - The function to execute: |position_in_target(global), update_size_in_target, target_child_sectant, &target_bounds| { ... }
+///
+/// returns with update size
+pub(crate) fn execute_for_relevant_sectants_in_range<F: FnMut(V3c<u32>, V3c<u32>, u8, &Cube)>(
+    node_bounds: &Cube,
+    position_: &V3c<u32>,
+    update_size: &V3c<u32>,
     mut fun: F,
 ) -> V3c<usize> {
     if (position_.x as f32 > node_bounds.min_position.x + node_bounds.size)
@@ -49,14 +71,18 @@ pub(crate) fn execute_for_relevant_sectants<F: FnMut(V3c<u32>, V3c<u32>, u8, &Cu
         (position_.y as f32).max(node_bounds.min_position.y),
         (position_.z as f32).max(node_bounds.min_position.z),
     );
-    let update_size = V3c::from(*position_) + V3c::unit(update_size as f32) - position;
+    let update_size = V3c::from(*position_) + V3c::from(*update_size) - position;
     let cell_size = node_bounds.size / BOX_NODE_DIMENSION as f32;
 
-    let mut shifted_position = position;
+    // Iteration starts from the beginning of the first cell touched by the update,
+    // so an unaligned position doesn't skip the last cell overlapping with the update
+    let first_cell_position = node_bounds.min_position
+        + ((position - node_bounds.min_position) / cell_size).floor() * cell_size;
+    let mut shifted_position = first_cell_position;
     while shifted_position.x <= (position.x + update_size.x) {
-        shifted_position.y = position.y;
+        shifted_position.y = first_cell_position.y;
         while shifted_position.y <= (position.y + update_size.y) {
-            shifted_position.z = position.z;
+            shifted_position.z = first_cell_position.z;
             while shifted_position.z <= (position.z + update_size.z) {
                 if !node_bounds.contains(&shifted_position) {
                     shifted_position.z += cell_size;
@@ -162,6 +188,219 @@ impl<T: VoxelData> BoxTree<T>
     }
 }
 
+impl<T: VoxelData> BoxTree<T> {
+    /// Provides an iterator over every voxel containing data inside the tree
+    /// Empty parts of the tree are skipped, voxels are yielded in no particular order
+    pub fn iter(&self) -> BoxTreeIter<'_, T> {
+        BoxTreeIter::new(self, V3c::unit(0), V3c::unit(self.boxtree_size))
+    }
+
+    /// Provides an iterator over every voxel containing data inside the given region
+    /// Only the nodes overlapping with the region are visited
+    /// * `min_position` - the first voxel of the box, must be contained within the tree
+    /// * `max_position` - the end of the box (exclusive), each component must be at most the size of the tree
+    pub fn iter_region(
+        &self,
+        min_position: &V3c<u32>,
+        max_position: &V3c<u32>,
+    ) -> Result<BoxTreeIter<'_, T>, OctreeError> {
+        self.check_box_bounds(min_position, max_position)?;
+        Ok(BoxTreeIter::new(self, *min_position, *max_position))
+    }
+}
+
+impl<'a, T: VoxelData> BoxTreeIter<'a, T> {
+    fn new(tree: &'a BoxTree<T>, min_position: V3c<u32>, max_position: V3c<u32>) -> Self {
+        let mut iter = Self {
+            tree,
+            min_position,
+            max_position,
+            stack: Vec::new(),
+        };
+        let root_bounds = Cube::root_bounds(tree.boxtree_size as f32);
+        if iter.clip(&root_bounds).is_some() {
+            iter.stack.push(IterationFrame::Node {
+                node_key: BoxTree::<T>::ROOT_NODE_KEY as usize,
+                bounds: root_bounds,
+            });
+        }
+        iter
+    }
+
+    /// Provides the minimum and (exclusive) maximum position of the part of the given bounds inside the iterated region
+    /// * Returns with None if the bounds do not overlap with the iterated region
+    fn clip(&self, bounds: &Cube) -> Option<(V3c<u32>, V3c<u32>)> {
+        let bounds_min = V3c::<u32>::from(bounds.min_position);
+        let bounds_max = bounds_min + V3c::unit(bounds.size as u32);
+        let min_position = V3c::new(
+            bounds_min.x.max(self.min_position.x),
+            bounds_min.y.max(self.min_position.y),
+            bounds_min.z.max(self.min_position.z),
+        );
+        let max_position = V3c::new(
+            bounds_max.x.min(self.max_position.x),
+            bounds_max.y.min(self.max_position.y),
+            bounds_max.z.min(self.max_position.z),
+        );
+        if BoxTree::<T>::box_is_empty(&min_position, &max_position) {
+            None
+        } else {
+            Some((min_position, max_position))
+        }
+    }
+
+    /// Provides the frame iterating the given brick inside the given bounds, if there is anything to iterate in it
+    fn brick_frame(
+        &self,
+        brick: &'a BrickData<PaletteIndexValues>,
+        bounds: Cube,
+    ) -> Option<IterationFrame<'a>> {
+        match brick {
+            BrickData::Empty => None,
+            BrickData::Solid(voxel) => self.range_frame(*voxel, &bounds),
+            BrickData::Parted(brick) => {
+                self.clip(&bounds)?;
+                Some(IterationFrame::Brick {
+                    brick,
+                    bounds,
+                    next_index: 0,
+                })
+            }
+        }
+    }
+
+    /// Provides the frame iterating the given content inside the given bounds, if it overlaps with the iterated region
+    fn range_frame(
+        &self,
+        content: PaletteIndexValues,
+        bounds: &Cube,
+    ) -> Option<IterationFrame<'a>> {
+        if NodeContent::pix_points_to_empty(
+            &content,
+            &self.tree.voxel_color_palette,
+            &self.tree.voxel_data_palette,
+        ) {
+            return None;
+        }
+        let (min_position, max_position) = self.clip(bounds)?;
+        Some(IterationFrame::Range {
+            content,
+            min_position,
+            max_position,
+            next_position: min_position,
+        })
+    }
+}
+
+impl<'a, T: VoxelData> Iterator for BoxTreeIter<'a, T> {
+    type Item = (V3c<u32>, BoxTreeEntry<'a, T>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let next_frame = match self.stack.last_mut()? {
+                IterationFrame::Range {
+                    content,
+                    min_position,
+                    max_position,
+                    next_position,
+                } => {
+                    let position = *next_position;
+                    let entry = NodeContent::pix_get_ref(
+                        content,
+                        &self.tree.voxel_color_palette,
+                        &self.tree.voxel_data_palette,
+                    );
+
+                    // Step to the next position inside the range
+                    next_position.x += 1;
+                    if next_position.x == max_position.x {
+                        next_position.x = min_position.x;
+                        next_position.y += 1;
+                        if next_position.y == max_position.y {
+                            next_position.y = min_position.y;
+                            next_position.z += 1;
+                        }
+                    }
+                    if next_position.z == max_position.z {
+                        self.stack.pop();
+                    }
+                    return Some((position, entry));
+                }
+                IterationFrame::Brick {
+                    brick,
+                    bounds,
+                    next_index,
+                } => {
+                    if *next_index == brick.len() {
+                        self.stack.pop();
+                        continue;
+                    }
+                    let brick_dim = self.tree.brick_dim as usize;
+                    let index = *next_index;
+                    *next_index += 1;
+                    let content = brick[index];
+                    let cell_size = bounds.size / brick_dim as f32;
+                    let cell_bounds = Cube {
+                        min_position: bounds.min_position
+                            + V3c::new(
+                                (index % brick_dim) as f32,
+                                ((index / brick_dim) % brick_dim) as f32,
+                                (index / (brick_dim * brick_dim)) as f32,
+                            ) * cell_size,
+                        size: cell_size,
+                    };
+                    self.range_frame(content, &cell_bounds)
+                }
+                IterationFrame::Node { node_key, bounds } => {
+                    let (node_key, bounds) = (*node_key, *bounds);
+                    self.stack.pop();
+                    match self.tree.nodes.get(node_key) {
+                        NodeContent::Nothing => {}
+                        NodeContent::UniformLeaf(brick) => {
+                            if let Some(frame) = self.brick_frame(brick, bounds) {
+                                self.stack.push(frame);
+                            }
+                        }
+                        NodeContent::Leaf(bricks) => {
+                            // Children are pushed in reverse, so they are iterated in the order of their sectants
+                            for sectant in (0..BOX_NODE_CHILDREN_COUNT).rev() {
+                                if let Some(frame) = self.brick_frame(
+                                    &bricks[sectant],
+                                    bounds.child_bounds_for(sectant as u8),
+                                ) {
+                                    self.stack.push(frame);
+                                }
+                            }
+                        }
+                        NodeContent::Internal(occupied_bits) => {
+                            for sectant in (0..BOX_NODE_CHILDREN_COUNT as u8).rev() {
+                                let child_bounds = bounds.child_bounds_for(sectant);
+                                if 0 == occupied_bits & (0x01 << sectant)
+                                    || self.clip(&child_bounds).is_none()
+                                {
+                                    continue;
+                                }
+                                if let Some(child_key) =
+                                    self.tree.valid_child_for(node_key, sectant)
+                                {
+                                    self.stack.push(IterationFrame::Node {
+                                        node_key: child_key,
+                                        bounds: child_bounds,
+                                    });
+                                }
+                            }
+                        }
+                    }
+                    None
+                }
+            };
+            if let Some(frame) = next_frame {
+                self.stack.push(frame);
+            }
+        }
+    }
+}
+
 /// Container to store intermediate values in a higher capacity type ( u8 overflows a lot )
 /// do do do do doo do do do do du doo
 #[derive(Debug, Clone, Eq, Hash, PartialEq)]
@@ -0,0 +1,598 @@
+use crate::{
+    boxtree::{
+        types::{
+            EditJournal, JournalConfig, JournalRecord, JournalTransaction, OctreeError,
+            RecordedContent,
+        },
+        update::{
+            paste::PasteRegion,
+            region::{BoxRegion, RegionOverlap, UpdateRegion},
+        },
+        Albedo, BoxTree, PasteMode, VoxelData, BOX_NODE_DIMENSION,
+    },
+    object_pool::empty_marker,
+    shared::Shared,
+    spatial::{math::vector::V3c, Cube},
+};
+use std::{
+    collections::{HashMap, VecDeque},
+    hash::Hash,
+    mem::size_of,
+};
+
+impl Default for JournalConfig {
+    fn default() -> Self {
+        JournalConfig {
+            memory_limit: 64 * 1024 * 1024,
+            transaction_limit: 256,
+            coalesced_edits: 1,
+        }
+    }
+}
+
+impl<T> EditJournal<T>
+where
+    T: Default + Clone + Eq + Hash,
+{
+    fn new(config: JournalConfig) -> Self {
+        EditJournal {
+            config,
+            undo_stack: VecDeque::new(),
+            redo_stack: Vec::new(),
+            open_transaction: None,
+            memory: 0,
+        }
+    }
+
+    /// Adds the given transaction as the latest undoable one, dropping the oldest transactions above the limits
+    fn push_undo(&mut self, transaction: JournalTransaction<T>) {
+        self.memory += transaction.memory;
+        self.undo_stack.push_back(transaction);
+        self.enforce_limits();
+    }
+
+    /// Removes the latest undoable transaction
+    fn pop_undo(&mut self) -> Option<JournalTransaction<T>> {
+        let transaction = self.undo_stack.pop_back()?;
+        self.memory -= transaction.memory;
+        Some(transaction)
+    }
+
+    /// Drops the oldest transactions above the limits, the latest transaction is always kept
+    fn enforce_limits(&mut self) {
+        while 1 < self.undo_stack.len()
+            && (self.undo_stack.len() > self.config.transaction_limit
+                || self.memory > self.config.memory_limit)
+        {
+            let Some(transaction) = self.undo_stack.pop_front() else {
+                break;
+            };
+            self.memory -= transaction.memory;
+        }
+    }
+
+    /// Closes the transaction collecting the edits, keeping it only if it contains any edit
+    fn end_open_transaction(&mut self) {
+        if let Some(mut transaction) = self.open_transaction.take()
+            && !transaction.records.is_empty()
+        {
+            transaction.sealed = true;
+            self.push_undo(transaction);
+        }
+    }
+
+    fn clear(&mut self) {
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+        self.open_transaction = None;
+        self.memory = 0;
+    }
+}
+
+impl<T: VoxelData> BoxTree<T> {
+    /// Starts recording the edits of the tree, so they can be undone
+    /// Every voxel edit is recorded, including palette edits changing the colors or data of voxels.
+    /// Palette edits are recorded as the previous palettes, re-quantization of the color palette
+    /// is undone to the first color merged into each quantized color.
+    /// If the journal is already enabled, only its configuration is updated
+    pub fn enable_journal(&mut self, config: JournalConfig) {
+        match &mut self.journal {
+            Some(journal) => {
+                journal.config = config;
+                journal.enforce_limits();
+            }
+            None => self.journal = Some(Box::new(EditJournal::new(config))),
+        }
+    }
+
+    /// Stops recording edits, dropping every undoable and redoable transaction
+    pub fn disable_journal(&mut self) {
+        self.journal = None;
+    }
+
+    /// Drops every undoable and redoable transaction, keeping the journal enabled
+    pub fn clear_journal(&mut self) {
+        if let Some(journal) = &mut self.journal {
+            journal.clear();
+        }
+    }
+
+    /// Starts a transaction: edits until @end_transaction are undone and redone together
+    /// An already open transaction is ended first
+    pub fn begin_transaction(&mut self) {
+        if let Some(journal) = &mut self.journal {
+            journal.end_open_transaction();
+            journal.open_transaction = Some(JournalTransaction::default());
+        }
+    }
+
+    /// Ends the transaction started by @begin_transaction
+    pub fn end_transaction(&mut self) {
+        if let Some(journal) = &mut self.journal {
+            journal.end_open_transaction();
+        }
+    }
+
+    /// Merges the latest given number of undoable transactions into one
+    pub fn coalesce_transactions(&mut self, count: usize) {
+        let Some(journal) = &mut self.journal else {
+            return;
+        };
+        journal.end_open_transaction();
+        let mut merged = JournalTransaction {
+            sealed: true,
+            ..Default::default()
+        };
+        let count = count.min(journal.undo_stack.len());
+        for transaction in journal.undo_stack.drain(journal.undo_stack.len() - count..) {
+            merged.records.extend(transaction.records);
+            merged.recorded_areas.extend(transaction.recorded_areas);
+            merged.edit_count += transaction.edit_count;
+            merged.memory += transaction.memory;
+        }
+        if !merged.records.is_empty() {
+            journal.undo_stack.push_back(merged);
+        }
+    }
+
+    /// True if there is a recorded transaction to undo
+    pub fn can_undo(&self) -> bool {
+        self.journal.as_ref().is_some_and(|journal| {
+            !journal.undo_stack.is_empty()
+                || journal
+                    .open_transaction
+                    .as_ref()
+                    .is_some_and(|transaction| !transaction.records.is_empty())
+        })
+    }
+
+    /// True if there is an undone transaction to redo
+    pub fn can_redo(&self) -> bool {
+        self.journal
+            .as_ref()
+            .is_some_and(|journal| !journal.redo_stack.is_empty())
+    }
+
+    /// Restores the tree to its state before the latest transaction, ending the open transaction first
+    /// * Returns with true if a transaction was undone, or error if the palette can not hold the restored colors or data.
+    ///   In case of an error the recorded transactions are dropped
+    pub fn undo(&mut self) -> Result<bool, OctreeError> {
+        let Some(mut journal) = self.journal.take() else {
+            return Ok(false);
+        };
+        journal.end_open_transaction();
+        let result = match journal.pop_undo() {
+            Some(transaction) => self.apply_journal_transaction(&transaction).map(|redo| {
+                journal.redo_stack.push(redo);
+                true
+            }),
+            None => Ok(false),
+        };
+        if result.is_err() {
+            journal.clear();
+        }
+        self.journal = Some(journal);
+        result
+    }
+
+    /// Applies the latest undone transaction again
+    /// * Returns with true if a transaction was redone, or error if the palette can not hold the restored colors or data.
+    ///   In case of an error the recorded transactions are dropped
+    pub fn redo(&mut self) -> Result<bool, OctreeError> {
+        let Some(mut journal) = self.journal.take() else {
+            return Ok(false);
+        };
+        let result = match journal.redo_stack.pop() {
+            Some(transaction) => self.apply_journal_transaction(&transaction).map(|undo| {
+                journal.push_undo(undo);
+                true
+            }),
+            None => Ok(false),
+        };
+        if result.is_err() {
+            journal.clear();
+        }
+        self.journal = Some(journal);
+        result
+    }
+
+    /// Records the contents of the area about to be edited at the given position and size
+    pub(crate) fn record_edit(&mut self, position: &V3c<u32>, size: u32) {
+        if self.journal.is_some() {
+            let area = self.journal_area(position, size);
+            self.record_areas([area]);
+        }
+    }
+
+    /// Records the contents of the given axis aligned box about to be edited
+    /// Nodes inside the box are recorded as a whole, nodes on its border are split up to leaf node sized areas
+    /// * `min_position` - the first voxel of the box
+    /// * `max_position` - the end of the box (exclusive)
+    pub(crate) fn record_box_edit(&mut self, min_position: &V3c<u32>, max_position: &V3c<u32>) {
+        if self.journal.is_none() || Self::box_is_empty(min_position, max_position) {
+            return;
+        }
+
+        let region = BoxRegion {
+            min_position: *min_position,
+            max_position: *max_position,
+        };
+        let leaf_size = (self.brick_dim * BOX_NODE_DIMENSION as u32) as f32;
+        let mut areas = vec![];
+        let mut node_stack = vec![Cube::root_bounds(self.boxtree_size as f32)];
+        while let Some(node_bounds) = node_stack.pop() {
+            match region.overlap(&node_bounds) {
+                RegionOverlap::Outside => {}
+                RegionOverlap::Partial if node_bounds.size > leaf_size => {
+                    region.execute_for_relevant_sectants(&node_bounds, |child_sectant| {
+                        node_stack.push(node_bounds.child_bounds_for(child_sectant));
+                    });
+                }
+                RegionOverlap::Inside | RegionOverlap::Partial => {
+                    areas.push((V3c::from(node_bounds.min_position), node_bounds.size as u32));
+                }
+            }
+        }
+        self.record_areas(areas);
+    }
+
+    /// Records the contents of the whole tree about to be edited
+    pub(crate) fn record_tree_edit(&mut self) {
+        self.record_box_edit(&V3c::unit(0), &V3c::unit(self.boxtree_size));
+    }
+
+    /// Applies the given edit recorded as a single edit of the given box,
+    /// the edits done by the function itself are not recorded separately
+    pub(crate) fn record_box_update<R, F: FnOnce(&mut Self) -> R>(
+        &mut self,
+        min_position: &V3c<u32>,
+        max_position: &V3c<u32>,
+        fun: F,
+    ) -> R {
+        self.record_box_edit(min_position, max_position);
+        let journal = self.journal.take();
+        let result = fun(self);
+        self.journal = journal;
+        result
+    }
+
+    /// Provides the position and size of the node aligned area containing every voxel of an edit
+    /// at the given position and size
+    pub(crate) fn journal_area(&self, position: &V3c<u32>, size: u32) -> (V3c<u32>, u32) {
+        let mut area_size = self.brick_dim * BOX_NODE_DIMENSION as u32;
+        while area_size < size && area_size < self.boxtree_size {
+            area_size *= BOX_NODE_DIMENSION as u32;
+        }
+        (
+            V3c::new(
+                position.x - position.x % area_size,
+                position.y - position.y % area_size,
+                position.z - position.z % area_size,
+            ),
+            area_size,
+        )
+    }
+
+    /// Records the contents of the given areas as a single edit
+    /// * `areas` - The position and size of each area, as provided by @journal_area
+    pub(crate) fn record_areas(&mut self, areas: impl IntoIterator<Item = (V3c<u32>, u32)>) {
+        // The areas are aligned to nodes, so every voxel changed by the edit is inside them
+        self.record_into_transaction(true, |tree, transaction| {
+            for (area_position, area_size) in areas {
+                if transaction.recorded_areas.insert((
+                    area_position.x,
+                    area_position.y,
+                    area_position.z,
+                    area_size,
+                )) {
+                    let record = tree.journal_record(&area_position, area_size);
+                    transaction.memory += record.memory;
+                    transaction.records.push(record);
+                }
+            }
+        });
+    }
+
+    /// Records the given palettes of the tree from before a palette edit, after the edit is done
+    /// * `color_palette` - The color palette before the edit
+    /// * `data_palette` - The data palette before the edit
+    /// * `color_map` - The new index for each color index, in case the edit rewrote the stored color indices
+    /// * `data_map` - The new index for each data index, in case the edit rewrote the stored data indices
+    /// * `is_edit` - false if the palettes are edited as part of the edit recorded next
+    pub(crate) fn record_palette_edit(
+        &mut self,
+        color_palette: Shared<Vec<Albedo>>,
+        data_palette: Shared<Vec<T>>,
+        color_map: Option<&[u16]>,
+        data_map: Option<&[u16]>,
+        is_edit: bool,
+    ) {
+        if self.journal.is_none() {
+            return;
+        }
+        let record = Self::palette_record(
+            color_palette,
+            data_palette,
+            color_map.map(|map| Self::inverse_palette_map(map, self.voxel_color_palette.len())),
+            data_map.map(|map| Self::inverse_palette_map(map, self.voxel_data_palette.len())),
+        );
+        self.record_into_transaction(is_edit, |_, transaction| {
+            transaction.memory += record.memory;
+            transaction.records.push(record);
+        });
+    }
+
+    /// Adds records to the transaction collecting the edits
+    /// Edits outside explicit transactions are merged into the latest implicit transaction, if it has space
+    /// * `is_edit` - false if the records are part of the edit recorded next
+    /// * `fun` - The function adding the records: |tree, transaction| { ... }
+    fn record_into_transaction<F: FnOnce(&Self, &mut JournalTransaction<T>)>(
+        &mut self,
+        is_edit: bool,
+        fun: F,
+    ) {
+        let Some(mut journal) = self.journal.take() else {
+            return;
+        };
+        journal.redo_stack.clear();
+
+        let explicit = journal.open_transaction.is_some();
+        let mut transaction = match journal.open_transaction.take() {
+            Some(transaction) => transaction,
+            None => match journal.undo_stack.back() {
+                Some(latest)
+                    if !latest.sealed && latest.edit_count < journal.config.coalesced_edits =>
+                {
+                    journal.pop_undo().unwrap()
+                }
+                _ => JournalTransaction::default(),
+            },
+        };
+        if is_edit {
+            transaction.edit_count += 1;
+        }
+        fun(self, &mut transaction);
+
+        if explicit {
+            journal.open_transaction = Some(transaction);
+        } else {
+            journal.push_undo(transaction);
+        }
+        self.journal = Some(journal);
+    }
+
+    /// Provides the index of the first entry of the previous palette for each entry of the new palette
+    /// * `map` - The new index for each index of the previous palette
+    /// * `len` - The size of the new palette
+    fn inverse_palette_map(map: &[u16], len: usize) -> Vec<u16> {
+        let mut inverse = vec![0; len];
+        for (previous_index, new_index) in map.iter().enumerate().rev() {
+            if let Some(entry) = inverse.get_mut(*new_index as usize) {
+                *entry = previous_index as u16;
+            }
+        }
+        inverse
+    }
+
+    /// Creates a record restoring the given palettes
+    fn palette_record(
+        color_palette: Shared<Vec<Albedo>>,
+        data_palette: Shared<Vec<T>>,
+        color_map: Option<Vec<u16>>,
+        data_map: Option<Vec<u16>>,
+    ) -> JournalRecord<T> {
+        let memory = size_of::<JournalRecord<T>>()
+            + color_palette.capacity() * size_of::<Albedo>()
+            + data_palette.capacity() * size_of::<T>()
+            + (color_map.as_ref().map_or(0, Vec::len) + data_map.as_ref().map_or(0, Vec::len))
+                * size_of::<u16>();
+        JournalRecord {
+            content: RecordedContent::Palettes {
+                color_palette,
+                data_palette,
+                color_map,
+                data_map,
+            },
+            memory,
+        }
+    }
+
+    /// Copies the voxels of the given area, so they can be restored later
+    fn journal_record(&self, position: &V3c<u32>, size: u32) -> JournalRecord<T> {
+        let mut voxels = BoxTree::new(size, self.brick_dim)
+            .expect("Expected journal area to be a valid tree size");
+        voxels
+            .paste_region(
+                self,
+                &PasteRegion {
+                    source_min: *position,
+                    source_max: *position + V3c::unit(size),
+                    target_min: V3c::unit(0),
+                },
+                PasteMode::SkipEmptySource,
+            )
+            .expect("Expected an empty palette to hold the colors of the area");
+        voxels.nodes.shrink_to_fit();
+        voxels.node_children.shrink_to_fit();
+        voxels.node_mips.shrink_to_fit();
+        let memory = voxels.stats().memory;
+        JournalRecord {
+            content: RecordedContent::Area {
+                position: *position,
+                voxels: Box::new(voxels),
+            },
+            memory: size_of::<JournalRecord<T>>()
+                + memory.nodes
+                + memory.node_children
+                + memory.bricks
+                + memory.mips
+                + memory.palettes,
+        }
+    }
+
+    /// Restores the areas of the given transaction in reverse order of recording
+    /// * Returns with the transaction restoring the areas to their state before the call
+    fn apply_journal_transaction(
+        &mut self,
+        transaction: &JournalTransaction<T>,
+    ) -> Result<JournalTransaction<T>, OctreeError> {
+        let mut reverse = JournalTransaction {
+            recorded_areas: transaction.recorded_areas.clone(),
+            edit_count: transaction.edit_count,
+            sealed: true,
+            ..Default::default()
+        };
+        for record in transaction.records.iter().rev() {
+            let current = match &record.content {
+                RecordedContent::Area { position, voxels } => {
+                    let size = voxels.boxtree_size;
+                    let current = self.journal_record(position, size);
+                    self.paste_region(
+                        voxels,
+                        &PasteRegion {
+                            source_min: V3c::unit(0),
+                            source_max: V3c::unit(size),
+                            target_min: *position,
+                        },
+                        PasteMode::Overwrite,
+                    )?;
+                    current
+                }
+                RecordedContent::Palettes {
+                    color_palette,
+                    data_palette,
+                    color_map,
+                    data_map,
+                } => self.restore_palettes(
+                    color_palette,
+                    data_palette,
+                    color_map.as_deref(),
+                    data_map.as_deref(),
+                ),
+            };
+            reverse.memory += current.memory;
+            reverse.records.push(current);
+        }
+        Ok(reverse)
+    }
+
+    /// Restores the given palettes, rewriting the stored palette indices by the given maps
+    /// Entries added after the recorded palette edit are kept at the end of the restored palettes,
+    /// unless the edit rewrote the stored indices: every later edit is undone at that point,
+    /// so they are only referenced by MIPs, which use the closest restored color instead.
+    /// * `color_map` - The index inside the given color palette for each color index of the recorded edit
+    /// * `data_map` - The index inside the given data palette for each data index of the recorded edit
+    /// * Returns with the record restoring the palettes to their state before the call
+    fn restore_palettes(
+        &mut self,
+        color_palette: &Shared<Vec<Albedo>>,
+        data_palette: &Shared<Vec<T>>,
+        color_map: Option<&[u16]>,
+        data_map: Option<&[u16]>,
+    ) -> JournalRecord<T> {
+        let current_colors = self.voxel_color_palette.clone();
+        let current_data = self.voxel_data_palette.clone();
+        let (restored_colors, color_map) =
+            Self::restored_palette(&current_colors, color_palette, color_map);
+        let (restored_data, data_map) = Self::restored_palette(&current_data, data_palette, data_map);
+        let reverse = Self::palette_record(
+            current_colors.clone(),
+            current_data,
+            color_map
+                .as_ref()
+                .map(|map| Self::inverse_palette_map(map, restored_colors.len())),
+            data_map
+                .as_ref()
+                .map(|map| Self::inverse_palette_map(map, restored_data.len())),
+        );
+
+        // Colors changed in place are only present in the MIPs
+        let changed_colors = restored_colors
+            .iter()
+            .enumerate()
+            .map(|(index, color)| current_colors.get(index) != Some(color))
+            .collect::<Vec<_>>();
+        let data_changed = **self.voxel_data_palette != restored_data;
+        let mut map_to_color_index_in_palette = HashMap::new();
+        for (index, color) in restored_colors.iter().enumerate() {
+            map_to_color_index_in_palette.entry(*color).or_insert(index);
+        }
+        let mut map_to_data_index_in_palette = HashMap::new();
+        for (index, data) in restored_data.iter().enumerate() {
+            map_to_data_index_in_palette
+                .entry(data.clone())
+                .or_insert(index);
+        }
+        self.voxel_color_palette = restored_colors.into();
+        self.voxel_data_palette = restored_data.into();
+        self.map_to_color_index_in_palette = map_to_color_index_in_palette.into();
+        self.map_to_data_index_in_palette = map_to_data_index_in_palette.into();
+        self.palette_revision += 1;
+
+        let color_map = color_map.map(|mut color_map| {
+            for (index, color) in current_colors.iter().enumerate() {
+                if empty_marker::<u16>() == color_map[index] && !self.voxel_color_palette.is_empty()
+                {
+                    color_map[index] = self.nearest_color_index(color);
+                }
+            }
+            color_map
+        });
+        if color_map.is_some() || data_map.is_some() {
+            self.for_each_palette_index_mut(|index| {
+                *index =
+                    Self::remap_palette_index(index, color_map.as_deref(), data_map.as_deref());
+            });
+            self.mark_changed(&V3c::unit(0), &V3c::unit(self.boxtree_size));
+        } else {
+            if self.mip_map_strategy.is_enabled() && changed_colors.iter().any(|changed| *changed)
+            {
+                self.mark_outdated_mips(Self::ROOT_NODE_KEY as usize, &changed_colors);
+            }
+            if data_changed {
+                self.mark_changed_data_palette();
+            }
+        }
+        reverse
+    }
+
+    /// Provides the recorded palette, extended by the entries added to the current palette after the recorded edit
+    /// when the edit did not rewrite the stored indices
+    /// * `map` - The index inside the recorded palette for each index of the recorded edit, if stored indices were rewritten
+    /// * Returns with the restored palette, and the index inside it for each index of the current palette,
+    ///   if stored indices need to be rewritten; entries added after the edit are mapped to an empty marker
+    fn restored_palette<E: Clone>(
+        current: &[E],
+        recorded: &[E],
+        map: Option<&[u16]>,
+    ) -> (Vec<E>, Option<Vec<u16>>) {
+        let mut restored = recorded.to_vec();
+        let Some(map) = map else {
+            restored.extend(current.iter().skip(recorded.len()).cloned());
+            return (restored, None);
+        };
+        let mut current_map = map[..map.len().min(current.len())].to_vec();
+        current_map.resize(current.len(), empty_marker());
+        (restored, Some(current_map))
+    }
+}
@@ -1,11 +1,11 @@
 use crate::spatial::lut::SECTANT_OFFSET_LUT;
 use crate::{
     boxtree::{
-        Albedo, BOX_NODE_DIMENSION, BoxTree, OOB_SECTANT, VoxelData,
+        Albedo, BOX_NODE_CHILDREN_COUNT, BOX_NODE_DIMENSION, BoxTree, OOB_SECTANT, VoxelData,
         iterate::MIPResamplingFunction,
         types::{
             BoxTreeEntry, BrickData, MIPMapStrategy, MIPResamplingMethods, NodeChildren,
-            NodeContent, PaletteIndexValues, StrategyUpdater,
+            NodeContent, PaletteIndexValues, PaletteOverflowStrategy, StrategyUpdater,
         },
     },
     object_pool::empty_marker,
@@ -14,7 +14,7 @@ use crate::{
         math::{flat_projection, matrix_index_for, offset_sectant, vector::V3c},
     },
 };
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 impl<T: VoxelData> BoxTree<T> {
     //####################################################################################
@@ -234,9 +234,13 @@ impl<T: VoxelData> BoxTree<T> {
                             - SECTANT_OFFSET_LUT[child_sectant as usize]
                                 * (self.brick_dim * BOX_NODE_DIMENSION as u32) as f32;
 
-                        let sample = match &self.node_mips
-                            [self.node_children[node_key].child(child_sectant)]
-                        {
+                        // Uniform leaves have no MIP, as their brick is equivalent with it
+                        let child_key = self.node_children[node_key].child(child_sectant);
+                        let child_mip = match self.nodes.get(child_key) {
+                            NodeContent::UniformLeaf(brick) => brick,
+                            _ => &self.node_mips[child_key],
+                        };
+                        let sample = match child_mip {
                             BrickData::Empty => None,
                             BrickData::Solid(voxel) => NodeContent::pix_get_ref(
                                 voxel,
@@ -294,43 +298,57 @@ impl<T: VoxelData> BoxTree<T> {
                     )
                 } else {
                     // Add new color to the color palette
-                    Some(self.add_to_palette(&BoxTreeEntry::Visual(color)))
+                    Some(self.add_mip_color_to_palette(color))
                 }
             } else {
                 // Add new color to the color palette
-                Some(self.add_to_palette(&BoxTreeEntry::Visual(color)))
+                Some(self.add_mip_color_to_palette(color))
             }
         } else {
             None
         };
 
-        if let Some(mip_entry) = mip_entry {
-            // Set MIP entry
-            let pos_in_mip = matrix_index_for(node_bounds, position, self.brick_dim);
-            let flat_pos_in_mip = flat_projection(
-                pos_in_mip.x,
-                pos_in_mip.y,
-                pos_in_mip.z,
-                self.brick_dim as usize,
-            );
-            match &mut self.node_mips[node_key] {
-                BrickData::Empty => {
+        // Set MIP entry, cells without any color below them are cleared
+        let mip_entry = mip_entry.unwrap_or_else(empty_marker);
+        let pos_in_mip = matrix_index_for(node_bounds, position, self.brick_dim);
+        let flat_pos_in_mip = flat_projection(
+            pos_in_mip.x,
+            pos_in_mip.y,
+            pos_in_mip.z,
+            self.brick_dim as usize,
+        );
+        match &mut self.node_mips[node_key] {
+            BrickData::Empty => {
+                if mip_entry != empty_marker::<PaletteIndexValues>() {
                     let mut new_brick_data =
                         vec![empty_marker::<PaletteIndexValues>(); self.brick_dim.pow(3) as usize];
                     new_brick_data[flat_pos_in_mip] = mip_entry;
-                    self.node_mips[node_key] = BrickData::Parted(new_brick_data);
+                    self.node_mips[node_key] = BrickData::Parted(new_brick_data.into());
                 }
-                BrickData::Solid(voxel) => {
+            }
+            BrickData::Solid(voxel) => {
+                if *voxel != mip_entry {
                     let mut new_brick_data = vec![*voxel; self.brick_dim.pow(3) as usize];
                     new_brick_data[flat_pos_in_mip] = mip_entry;
-                    self.node_mips[node_key] = BrickData::Parted(new_brick_data);
-                }
-                BrickData::Parted(brick) => {
-                    brick[flat_pos_in_mip] = mip_entry;
+                    self.node_mips[node_key] = BrickData::Parted(new_brick_data.into());
                 }
             }
+            BrickData::Parted(brick) => {
+                brick[flat_pos_in_mip] = mip_entry;
+            }
         }
     }
+
+    /// Adds the given MIP color to the color palette
+    /// MIPs are approximations, so the closest available color is used when the palette is full
+    fn add_mip_color_to_palette(&mut self, color: &Albedo) -> PaletteIndexValues {
+        self.add_to_palette_keeping(
+            &BoxTreeEntry::Visual(color),
+            PaletteOverflowStrategy::NearestColor,
+            [],
+        )
+        .expect("Expected MIP color to have a place in the palette")
+    }
 }
 
 //####################################################################################
@@ -537,7 +555,8 @@ impl<T: VoxelData> StrategyUpdater<'_, T> {
     //####################################################################################
     /// Recalculates MIPs for the whole content of the boxtree
     pub fn recalculate_mips(&mut self) {
-        self.0.node_mips = vec![BrickData::Empty; self.0.nodes.len()];
+        self.0.node_mips = vec![BrickData::Empty; self.0.nodes.len()].into();
+        self.0.outdated_mips.clear();
 
         // Generating MIPMAPs need to happen while traveling the graph in a DFS manner
         // in order to generate MIPs for the leaf nodes first
@@ -602,6 +621,49 @@ impl<T: VoxelData> StrategyUpdater<'_, T> {
                 }
             }
         }
+        let node_keys = (0..self.0.nodes.len())
+            .filter(|node_key| self.0.nodes.key_is_valid(*node_key))
+            .collect::<Vec<_>>();
+        self.0.mark_changed_mips(node_keys);
+    }
+
+    /// Recalculates the MIPs marked outdated by in-place palette edits, see @BoxTree::map_palette
+    /// Only the marked nodes are resampled, starting from the deepest ones
+    pub fn recalculate_outdated_mips(&mut self) {
+        let outdated_mips = std::mem::take(&mut self.0.outdated_mips);
+        if outdated_mips.contains(&(BoxTree::<T>::ROOT_NODE_KEY as usize)) {
+            self.recalculate_marked_mips(
+                BoxTree::<T>::ROOT_NODE_KEY as usize,
+                &Cube::root_bounds(self.0.boxtree_size as f32),
+                &outdated_mips,
+            );
+        }
+        self.0.mark_changed_mips(outdated_mips);
+    }
+
+    /// Recalculates the MIP of the given node after the MIPs of its marked children
+    fn recalculate_marked_mips(
+        &mut self,
+        node_key: usize,
+        node_bounds: &Cube,
+        marked_nodes: &HashSet<usize>,
+    ) {
+        if matches!(self.0.nodes.get(node_key), NodeContent::Internal(_)) {
+            for sectant in 0..BOX_NODE_CHILDREN_COUNT as u8 {
+                if let Some(child_key) = self
+                    .0
+                    .valid_child_for(node_key, sectant)
+                    .filter(|child_key| marked_nodes.contains(child_key))
+                {
+                    self.recalculate_marked_mips(
+                        child_key,
+                        &node_bounds.child_bounds_for(sectant),
+                        marked_nodes,
+                    );
+                }
+            }
+        }
+        self.recalculate_mip(node_key, node_bounds);
     }
 
     /// Enables or disables mipmap feature for albedo values
@@ -1,7 +1,16 @@
+mod accessor;
+mod build;
+mod changes;
+mod connectivity;
+mod dense;
 mod detail;
+pub(crate) mod distance;
 pub(crate) mod iterate;
+mod journal;
 pub(crate) mod mipmap;
 mod node;
+mod stats;
+mod validate;
 
 /// The inner structure of the container
 pub mod types;
@@ -14,18 +23,27 @@ mod tests;
 
 pub use crate::spatial::math::vector::{V3c, V3cf32};
 pub use types::{
-    Albedo, BoxTree, BoxTreeEntry, MIPMapStrategy, MIPResamplingMethods, StrategyUpdater, VoxelData,
+    Albedo, Axis, BoxTree, BoxTreeAccessor, BoxTreeBatch, BoxTreeChanges, BoxTreeEntry, BoxTreeIter,
+    BoxTreeStats, BrickCounts, Component, Connectivity, DenseRegion, DistanceField, DistanceMetric,
+    JournalConfig, MIPMapStrategy, MIPResamplingMethods, MemoryUsage, NodeCounts,
+    PaletteOverflowStrategy, PaletteUsage, PasteMode, StrategyUpdater, ValidationIssue, VoxelData,
 };
 
 use crate::{
-    boxtree::types::{BrickData, NodeChildren, NodeContent, OctreeError, PaletteIndexValues},
+    boxtree::types::{
+        BrickData, ChangeTracker, NodeChildren, NodeContent, OctreeError, PaletteIndexValues,
+    },
     object_pool::{ObjectPool, empty_marker},
+    shared::Shared,
     spatial::{
         Cube,
         math::{flat_projection, matrix_index_for},
     },
 };
-use std::{collections::HashMap, path::Path};
+use std::{
+    collections::HashSet,
+    path::Path,
+};
 
 #[cfg(feature = "bytecode")]
 use bendy::{decoding::FromBencode, encoding::ToBencode};
@@ -33,7 +51,7 @@ use bendy::{decoding::FromBencode, encoding::ToBencode};
 #[cfg(feature = "bytecode")]
 use std::{
     fs::File,
-    io::{Error, Read, Write},
+    io::{Error, ErrorKind, Read, Write},
 };
 
 //####################################################################################
@@ -124,6 +142,9 @@ pub(crate) const OOB_SECTANT: u8 = 64;
 pub(crate) const BOX_NODE_DIMENSION: usize = 4;
 pub(crate) const BOX_NODE_CHILDREN_COUNT: usize = 64;
 
+/// The number of entries each palette can hold, as the largest index is reserved to mark empty entries
+pub(crate) const MAX_PALETTE_SIZE: usize = u16::MAX as usize;
+
 /// Creates a boxtree with the given parameters, also sets defaults for brick_dimension and user data type if not given!
 #[macro_export]
 macro_rules! make_tree {
@@ -175,18 +196,63 @@ impl<T: VoxelData> BoxTree<T> {
     }
 
     /// loads the data structure from the given file path
+    /// Structurally inconsistent trees are rejected with `ErrorKind::InvalidData`
+    /// MIPs not needed by the tree are dropped, missing ones are recalculated
     #[cfg(feature = "bytecode")]
     pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
         let mut file = File::open(path)?;
         let mut bytes = Vec::new();
         file.read_to_end(&mut bytes)?;
-        Ok(Self::from_bytes(bytes))
+        let mut tree = Self::from_bytes(bytes);
+        tree.drop_unexpected_mips();
+        if let Err(issues) = tree.validate() {
+            let structural_issues = issues
+                .into_iter()
+                .filter(ValidationIssue::is_structural)
+                .collect::<Vec<_>>();
+            if !structural_issues.is_empty() {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("{structural_issues:?}"),
+                ));
+            }
+            tree.albedo_mip_map_resampling_strategy().recalculate_mips();
+        }
+        Ok(tree)
     }
 
     /// creates an boxtree with the given size
     /// * `brick_dimension` - must be one of `(2^x)` and smaller than the size of the boxtree
     /// * `size` - must be `brick_dimension * (4^x)`, e.g: brick_dimension == 2 --> size can be 8,32,128...
     pub fn new(size: u32, brick_dimension: u32) -> Result<Self, OctreeError> {
+        Self::check_dimensions(size, brick_dimension)?;
+        let node_count_estimation = (size / brick_dimension).pow(3);
+        let mut nodes = ObjectPool::with_capacity(node_count_estimation.min(1024) as usize);
+        let root_node_key = nodes.push(NodeContent::Nothing); // The first element is the root Node
+        assert!(root_node_key == 0);
+        Ok(Self {
+            auto_simplify: true,
+            boxtree_size: size,
+            brick_dim: brick_dimension,
+            nodes,
+            node_children: vec![NodeChildren::default()].into(),
+            node_mips: vec![BrickData::Empty].into(),
+            voxel_color_palette: Shared::default(),
+            voxel_data_palette: Shared::default(),
+            map_to_color_index_in_palette: Shared::default(),
+            map_to_data_index_in_palette: Shared::default(),
+            mip_map_strategy: MIPMapStrategy::default(),
+            palette_overflow_strategy: PaletteOverflowStrategy::default(),
+            outdated_mips: HashSet::new(),
+            palette_revision: 0,
+            node_layout_revision: 0,
+            journal: None,
+            changes: ChangeTracker::default(),
+        })
+    }
+
+    /// Checks if a boxtree can be created with the given size and brick dimension, see @new
+    pub(crate) fn check_dimensions(size: u32, brick_dimension: u32) -> Result<(), OctreeError> {
         if 0 == size || (brick_dimension as f32).log(2.0).fract() != 0.0 {
             return Err(OctreeError::InvalidBrickDimension(brick_dimension));
         }
@@ -201,23 +267,7 @@ impl<T: VoxelData> BoxTree<T> {
                 "Octree size must be larger, than BOX_NODE_DIMENSION * brick dimension".into(),
             ));
         }
-        let node_count_estimation = (size / brick_dimension).pow(3);
-        let mut nodes = ObjectPool::with_capacity(node_count_estimation.min(1024) as usize);
-        let root_node_key = nodes.push(NodeContent::Nothing); // The first element is the root Node
-        assert!(root_node_key == 0);
-        Ok(Self {
-            auto_simplify: true,
-            boxtree_size: size,
-            brick_dim: brick_dimension,
-            nodes,
-            node_children: vec![NodeChildren::default()],
-            node_mips: vec![BrickData::Empty],
-            voxel_color_palette: vec![],
-            voxel_data_palette: vec![],
-            map_to_color_index_in_palette: HashMap::new(),
-            map_to_data_index_in_palette: HashMap::new(),
-            mip_map_strategy: MIPMapStrategy::default(),
-        })
+        Ok(())
     }
 
     /// Getter function for the boxtree
@@ -239,6 +289,36 @@ impl<T: VoxelData> BoxTree<T> {
         self.boxtree_size
     }
 
+    /// Creates a read-only copy of the current state of the boxtree in constant time
+    /// The nodes, bricks and palettes are shared with the snapshot, and later edits of either
+    /// the tree or the snapshot only copy the bricks and node pages they modify.
+    /// The snapshot does not carry the edit journal, and it has no recorded changes before its creation.
+    pub fn snapshot(&self) -> Self {
+        Self {
+            brick_dim: self.brick_dim,
+            boxtree_size: self.boxtree_size,
+            nodes: self.nodes.clone(),
+            node_children: self.node_children.clone(),
+            node_mips: self.node_mips.clone(),
+            voxel_color_palette: self.voxel_color_palette.clone(),
+            voxel_data_palette: self.voxel_data_palette.clone(),
+            map_to_color_index_in_palette: self.map_to_color_index_in_palette.clone(),
+            map_to_data_index_in_palette: self.map_to_data_index_in_palette.clone(),
+            auto_simplify: self.auto_simplify,
+            mip_map_strategy: self.mip_map_strategy.clone(),
+            palette_overflow_strategy: self.palette_overflow_strategy,
+            outdated_mips: self.outdated_mips.clone(),
+            palette_revision: self.palette_revision,
+            node_layout_revision: self.node_layout_revision,
+            journal: None,
+            changes: ChangeTracker {
+                generation: self.changes.generation,
+                layout_generation: self.changes.generation,
+                ..Default::default()
+            },
+        }
+    }
+
     /// Object to set the MIP map strategy for each MIP level inside the boxtree
     pub fn albedo_mip_map_resampling_strategy(&mut self) -> StrategyUpdater<'_, T> {
         StrategyUpdater(self)
@@ -246,7 +326,7 @@ impl<T: VoxelData> BoxTree<T> {
 
     /// Internal Getter function for the boxtree, to be able to call get from within the tree itself
     /// * Returns immutable reference to the data of the given node at the given position, if there is any
-    fn get_internal(
+    pub(crate) fn get_internal(
         &self,
         current_node_key: usize,
         mut current_bounds: Cube,
@@ -69,10 +69,10 @@ impl NodeConnection {
 
     /// Erases content, if any
     pub(crate) fn clear(&mut self, child_index: usize) {
-        debug_assert!(child_index < 8);
+        debug_assert!(child_index < BOX_NODE_CHILDREN_COUNT);
         if let NodeChildren::Children(c) = self {
             c[child_index] = empty_marker();
-            if 8 == c.iter().filter(|e| **e == empty_marker::<u32>()).count() {
+            if BOX_NODE_CHILDREN_COUNT == c.iter().filter(|e| **e == empty_marker::<u32>()).count() {
                 *self = NodeChildren::NoChildren;
             }
         }
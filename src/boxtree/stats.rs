@@ -0,0 +1,111 @@
+use crate::boxtree::{
+    types::{
+        BoxTreeStats, BrickCounts, BrickData, NodeConnection, NodeContent, NodeCounts, NodeData,
+        PaletteIndexValues, PaletteUsage,
+    },
+    Albedo, BoxTree, VoxelData, BOX_NODE_CHILDREN_COUNT,
+};
+use std::mem::size_of;
+
+impl<T: VoxelData> BoxTree<T> {
+    /// Collects structural and memory statistics of the tree
+    /// Only nodes reachable from the root are counted, and palette entries are used
+    /// if they are referenced by the voxels or MIPs of these nodes
+    pub fn stats(&self) -> BoxTreeStats {
+        let mut stats = BoxTreeStats {
+            free_node_slots: self.nodes.free_count(),
+            ..Default::default()
+        };
+        let mut used_colors = vec![false; self.voxel_color_palette.len()];
+        let mut used_data = vec![false; self.voxel_data_palette.len()];
+
+        // Counts the brick and marks the palette entries it uses, returns with the heap bytes of its voxels
+        let mut count_brick = |brick: &BrickData<PaletteIndexValues>, counts: &mut BrickCounts| {
+            let mut mark_used = |voxel: &PaletteIndexValues| {
+                if NodeContent::pix_color_is_some(voxel) {
+                    used_colors[NodeContent::pix_color_index(voxel)] = true;
+                }
+                if NodeContent::pix_data_is_some(voxel) {
+                    used_data[NodeContent::pix_data_index(voxel)] = true;
+                }
+            };
+            match brick {
+                BrickData::Empty => {
+                    counts.empty += 1;
+                    0
+                }
+                BrickData::Solid(voxel) => {
+                    counts.solid += 1;
+                    mark_used(voxel);
+                    0
+                }
+                BrickData::Parted(voxels) => {
+                    counts.parted += 1;
+                    voxels.iter().for_each(mark_used);
+                    voxels.capacity() * size_of::<PaletteIndexValues>()
+                }
+            }
+        };
+
+        let mut node_stack = vec![(Self::ROOT_NODE_KEY as usize, 0)];
+        while let Some((node_key, depth)) = node_stack.pop() {
+            if stats.nodes_per_depth.len() <= depth {
+                stats.nodes_per_depth.push(NodeCounts::default());
+            }
+            let content = self.nodes.get(node_key);
+            Self::count_node(&mut stats.nodes, content);
+            Self::count_node(&mut stats.nodes_per_depth[depth], content);
+            match content {
+                NodeContent::Nothing => {}
+                NodeContent::Internal(_) => {
+                    for sectant in 0..BOX_NODE_CHILDREN_COUNT as u8 {
+                        if let Some(child_key) = self.valid_child_for(node_key, sectant) {
+                            node_stack.push((child_key, depth + 1));
+                        }
+                    }
+                }
+                NodeContent::Leaf(bricks) => {
+                    for brick in bricks.iter() {
+                        stats.memory.bricks += count_brick(brick, &mut stats.bricks);
+                    }
+                }
+                NodeContent::UniformLeaf(brick) => {
+                    stats.memory.bricks += count_brick(brick, &mut stats.bricks);
+                }
+            }
+            if let Some(mip) = self.node_mips.get(node_key) {
+                stats.memory.mips += count_brick(mip, &mut stats.mips);
+            }
+        }
+
+        let count_usage = |used: &[bool]| {
+            let used_count = used.iter().filter(|used| **used).count();
+            PaletteUsage {
+                used: used_count,
+                unused: used.len() - used_count,
+            }
+        };
+        stats.color_palette = count_usage(&used_colors);
+        stats.data_palette = count_usage(&used_data);
+
+        stats.memory.nodes = self.nodes.heap_size();
+        stats.memory.node_children = self.node_children.capacity() * size_of::<NodeConnection>();
+        stats.memory.mips += self.node_mips.capacity() * size_of::<BrickData<PaletteIndexValues>>();
+        stats.memory.palettes = self.voxel_color_palette.capacity() * size_of::<Albedo>()
+            + self.voxel_data_palette.capacity() * size_of::<T>()
+            + self.map_to_color_index_in_palette.capacity()
+                * (size_of::<Albedo>() + size_of::<usize>())
+            + self.map_to_data_index_in_palette.capacity() * (size_of::<T>() + size_of::<usize>());
+        stats
+    }
+
+    /// Adds the given node content to the matching counter
+    fn count_node(counts: &mut NodeCounts, content: &NodeData) {
+        match content {
+            NodeContent::Nothing => counts.empty += 1,
+            NodeContent::Internal(_) => counts.internal += 1,
+            NodeContent::Leaf(_) => counts.leaf += 1,
+            NodeContent::UniformLeaf(_) => counts.uniform_leaf += 1,
+        }
+    }
+}
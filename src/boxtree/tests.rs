@@ -1,7 +1,11 @@
 mod iterate_tests {
     use crate::{
-        boxtree::{iterate::execute_for_relevant_sectants, BOX_NODE_DIMENSION},
+        boxtree::{
+            iterate::{execute_for_relevant_sectants, execute_for_relevant_sectants_in_range},
+            Albedo, BoxTree, BoxTreeEntry, BOX_NODE_DIMENSION,
+        },
         spatial::{math::vector::V3c, Cube},
+        voxel_data,
     };
 
     #[test]
@@ -405,6 +409,174 @@ mod iterate_tests {
             (BOX_NODE_DIMENSION - 1) * BOX_NODE_DIMENSION * BOX_NODE_DIMENSION
         );
     }
+
+    #[test]
+    fn test_sectant_execution_unaligned_position_covers_every_overlapping_sectant() {
+        let confines = Cube::root_bounds(16.);
+        let execute_position = V3c::unit(3);
+        let update_size = 6; // covers 3..9, overlapping cells starting at 0, 4 and 8
+        let mut visited_sectants: Vec<u8> = vec![];
+        let mut visited_volume = 0;
+        execute_for_relevant_sectants(
+            &confines,
+            &execute_position,
+            update_size,
+            |position_in_target, update_size_in_target, target_child_sectant, &target_bounds| {
+                assert!(target_bounds.contains(&V3c::from(position_in_target)));
+                assert!(target_bounds.contains(&V3c::from(
+                    position_in_target + update_size_in_target - V3c::unit(1)
+                )));
+                visited_sectants.push(target_child_sectant);
+                visited_volume +=
+                    update_size_in_target.x * update_size_in_target.y * update_size_in_target.z;
+            },
+        );
+        assert_eq!(visited_sectants.len(), 3_usize.pow(3));
+        assert_eq!(visited_volume, update_size.pow(3));
+    }
+
+    #[test]
+    fn test_sectant_execution_in_range_with_different_sizes_per_axis() {
+        let confines = Cube::root_bounds(16.);
+        let mut visited_sectants: Vec<u8> = vec![];
+        let mut visited_volume = 0;
+        execute_for_relevant_sectants_in_range(
+            &confines,
+            &V3c::new(1, 4, 0),
+            &V3c::new(14, 1, 5),
+            |position_in_target, update_size_in_target, target_child_sectant, &target_bounds| {
+                assert!(target_bounds.contains(&V3c::from(
+                    position_in_target + update_size_in_target - V3c::unit(1)
+                )));
+                visited_sectants.push(target_child_sectant);
+                visited_volume +=
+                    update_size_in_target.x * update_size_in_target.y * update_size_in_target.z;
+            },
+        );
+        assert_eq!(visited_sectants.len(), 4 * 2);
+        assert_eq!(visited_volume, 14 * 5);
+    }
+
+    /// Checks that the iterator yields every voxel with data inside the given region exactly once
+    fn check_iterated_voxels<'a>(
+        tree: &BoxTree,
+        iterated: impl Iterator<Item = (V3c<u32>, BoxTreeEntry<'a, u32>)>,
+        min_position: V3c<u32>,
+        max_position: V3c<u32>,
+    ) {
+        let mut visited = vec![false; tree.get_size().pow(3) as usize];
+        for (position, entry) in iterated {
+            assert!(
+                min_position.x <= position.x
+                    && min_position.y <= position.y
+                    && min_position.z <= position.z
+                    && position.x < max_position.x
+                    && position.y < max_position.y
+                    && position.z < max_position.z,
+                "Iterated position {:?} out of region",
+                position
+            );
+            let index = (position.x
+                + position.y * tree.get_size()
+                + position.z * tree.get_size() * tree.get_size()) as usize;
+            assert!(!visited[index], "Position {:?} visited twice", position);
+            visited[index] = true;
+            assert!(
+                entry == tree.get(&position),
+                "Hit mismatch at {:?}",
+                position
+            );
+        }
+        for x in min_position.x..max_position.x {
+            for y in min_position.y..max_position.y {
+                for z in min_position.z..max_position.z {
+                    let index =
+                        (x + y * tree.get_size() + z * tree.get_size() * tree.get_size()) as usize;
+                    assert_eq!(
+                        visited[index],
+                        tree.get(&V3c::new(x, y, z)) != BoxTreeEntry::Empty,
+                        "Iteration mismatch at {:?}",
+                        (x, y, z)
+                    );
+                }
+            }
+        }
+    }
+
+    /// A tree for iteration: a box covering whole uniform nodes and parts of others,
+    /// a box cutting through bricks, holes in both, and data only voxels at the tree bounds
+    fn mixed_content_tree() -> BoxTree {
+        let red: Albedo = 0xFF0000FF.into();
+        let green: Albedo = 0x00FF00FF.into();
+        let mut tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+        tree.insert_box(&V3c::new(8, 0, 4), &V3c::new(24, 8, 20), &red)
+            .expect("insert_box to work");
+        tree.insert_box(&V3c::new(1, 9, 3), &V3c::new(7, 12, 6), (&green, &3))
+            .expect("insert_box to work");
+        tree.clear(&V3c::new(12, 3, 12)).expect("boxtree clear");
+        tree.clear(&V3c::new(12, 7, 6)).expect("boxtree clear");
+        tree.clear(&V3c::new(4, 10, 4)).expect("boxtree clear");
+        for i in (0..32).step_by(3) {
+            tree.insert(&V3c::new(31, 31, i), voxel_data!(&i))
+                .expect("boxtree insert");
+            tree.insert(&V3c::new(0, i, 31), voxel_data!(&(i + 1)))
+                .expect("boxtree insert");
+        }
+        tree
+    }
+
+    #[test]
+    fn test_iter_visits_every_voxel_with_data() {
+        let tree = mixed_content_tree();
+        check_iterated_voxels(&tree, tree.iter(), V3c::unit(0), V3c::unit(32));
+    }
+
+    #[test]
+    fn test_iter_expands_uniform_tree() {
+        let red: Albedo = 0xFF0000FF.into();
+        let mut tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+        assert_eq!(tree.iter().count(), 0);
+
+        tree.insert_box(&V3c::new(0, 0, 0), &V3c::new(32, 32, 32), &red)
+            .expect("insert_box to work");
+        assert_eq!(tree.iter().count(), 32 * 32 * 32);
+        assert!(tree.iter().all(|(_, entry)| entry == (&red).into()));
+    }
+
+    #[test]
+    fn test_iter_region() {
+        let tree = mixed_content_tree();
+        let min_position = V3c::new(2, 7, 4);
+        let max_position = V3c::new(19, 25, 8);
+        check_iterated_voxels(
+            &tree,
+            tree.iter_region(&min_position, &max_position)
+                .expect("iter_region to work"),
+            min_position,
+            max_position,
+        );
+        let min_position = V3c::new(20, 1, 0);
+        let max_position = V3c::new(32, 32, 32);
+        check_iterated_voxels(
+            &tree,
+            tree.iter_region(&min_position, &max_position)
+                .expect("iter_region to work"),
+            min_position,
+            max_position,
+        );
+        assert_eq!(
+            tree.iter_region(&V3c::new(5, 5, 5), &V3c::new(4, 9, 9))
+                .expect("iter_region to work")
+                .count(),
+            0
+        );
+        assert!(tree
+            .iter_region(&V3c::new(0, 0, 0), &V3c::new(33, 1, 1))
+            .is_err());
+        assert!(tree
+            .iter_region(&V3c::new(32, 0, 0), &V3c::new(32, 1, 1))
+            .is_err());
+    }
 }
 
 mod mipmap_tests {
@@ -882,4 +1054,1841 @@ mod mipmap_tests {
                 .unwrap()
         );
     }
+    #[test]
+    fn test_mip_samples_uniform_leaf_children() {
+        let red: Albedo = 0xFF0000FF.into();
+        let mut tree: BoxTree = BoxTree::new(128, 2).ok().unwrap();
+        tree.albedo_mip_map_resampling_strategy()
+            .switch_albedo_mip_maps(true);
+
+        // The box is simplified into a uniform leaf, which has no MIP of its own
+        tree.insert_box(&V3c::new(32, 32, 32), &V3c::new(64, 64, 64), &red)
+            .expect("boxtree insert");
+        assert_eq!(
+            Some(&red),
+            tree.albedo_mip_map_resampling_strategy()
+                .sample_root_mip(OOB_SECTANT, &V3c::new(0, 0, 0))
+                .albedo()
+        );
+        assert!(tree
+            .albedo_mip_map_resampling_strategy()
+            .sample_root_mip(OOB_SECTANT, &V3c::new(1, 1, 1))
+            .albedo()
+            .is_none());
+    }
+
+    #[test]
+    fn test_uniform_leaf_overwrite_drops_previous_mip() {
+        let red: Albedo = 0xFF0000FF.into();
+        let green: Albedo = 0x00FF00FF.into();
+        let mut tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+        tree.albedo_mip_map_resampling_strategy()
+            .switch_albedo_mip_maps(true);
+        tree.insert(&V3c::new(0, 0, 0), &red)
+            .expect("boxtree insert");
+        assert_eq!(
+            Some(&red),
+            tree.albedo_mip_map_resampling_strategy()
+                .sample_root_mip(0, &V3c::new(0, 0, 0))
+                .albedo()
+        );
+
+        // The whole child is overwritten, so its MIP of the previous content is dropped
+        tree.insert_at_lod(&V3c::new(0, 0, 0), 8, &green)
+            .expect("boxtree insert");
+        assert!(tree
+            .albedo_mip_map_resampling_strategy()
+            .sample_root_mip(0, &V3c::new(0, 0, 0))
+            .albedo()
+            .is_none());
+        assert_eq!(
+            Some(&green),
+            tree.albedo_mip_map_resampling_strategy()
+                .sample_root_mip(OOB_SECTANT, &V3c::new(0, 0, 0))
+                .albedo()
+        );
+    }
+}
+
+mod stats_tests {
+    use crate::boxtree::{Albedo, BoxTree, BrickCounts, V3c};
+
+    #[test]
+    fn test_stats_of_empty_tree() {
+        let tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+        let stats = tree.stats();
+        assert_eq!(stats.nodes.empty, 1);
+        assert_eq!(stats.nodes_per_depth.len(), 1);
+        assert_eq!(stats.bricks, BrickCounts::default());
+        assert_eq!(stats.color_palette.used + stats.color_palette.unused, 0);
+        assert_eq!(stats.free_node_slots, 0);
+    }
+
+    #[test]
+    fn test_stats_counts_nodes_bricks_and_palette() {
+        let red: Albedo = 0xFF0000FF.into();
+        let green: Albedo = 0x00FF00FF.into();
+        let mut tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+        tree.auto_simplify = false;
+        tree.insert(&V3c::new(0, 0, 0), (&red, &3))
+            .expect("boxtree insert");
+        tree.insert(&V3c::new(31, 31, 31), &green)
+            .expect("boxtree insert");
+
+        let stats = tree.stats();
+        assert_eq!(stats.nodes.internal, 1);
+        assert_eq!(stats.nodes.leaf, 2);
+        assert_eq!(stats.nodes_per_depth.len(), 2);
+        assert_eq!(stats.nodes_per_depth[0].internal, 1);
+        assert_eq!(stats.nodes_per_depth[1].leaf, 2);
+        assert_eq!(stats.bricks.parted, 2);
+        assert_eq!(stats.bricks.empty, 2 * 63);
+        assert_eq!(stats.color_palette.used, 2);
+        assert_eq!(stats.data_palette.used, 1);
+        assert!(0 < stats.memory.nodes);
+        assert!(0 < stats.memory.bricks);
+        assert!(0 < stats.memory.palettes);
+
+        // Overwritten colors are no longer used, cleared nodes leave free slots in the pool
+        tree.insert(&V3c::new(0, 0, 0), &green)
+            .expect("boxtree insert");
+        tree.clear(&V3c::new(31, 31, 31)).expect("boxtree clear");
+        tree.clear(&V3c::new(0, 0, 0)).expect("boxtree clear");
+        let stats = tree.stats();
+        assert_eq!(stats.color_palette.used, 0);
+        assert!(0 < stats.color_palette.unused);
+        assert!(0 < stats.free_node_slots);
+    }
+}
+
+mod validate_tests {
+    use crate::boxtree::{
+        types::{BrickData, NodeChildren, NodeContent},
+        Albedo, BoxTree, V3c, ValidationIssue,
+    };
+
+    /// A tree with internal nodes, leaf nodes, uniform leaf nodes and MIPs
+    /// A tree with one of each kind of node and brick validate checks, and MIPs for all of them:
+    /// a uniform leaf node, and a leaf node with empty, solid and parted bricks
+    fn valid_tree() -> BoxTree {
+        let red: Albedo = 0xFF0000FF.into();
+        let green: Albedo = 0x00FF00FF.into();
+        let mut tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+        tree.albedo_mip_map_resampling_strategy()
+            .switch_albedo_mip_maps(true);
+        tree.insert_box(&V3c::new(8, 8, 8), &V3c::new(16, 16, 16), &red)
+            .expect("insert_box to work");
+        tree.insert_box(&V3c::new(20, 0, 20), &V3c::new(22, 2, 22), &green)
+            .expect("insert_box to work");
+        tree.insert(&V3c::new(21, 3, 20), (&green, &5))
+            .expect("boxtree insert");
+        tree
+    }
+
+    /// Provides the key of a valid leaf node inside the tree
+    fn leaf_key(tree: &BoxTree) -> usize {
+        (0..tree.nodes.len())
+            .find(|key| {
+                tree.nodes.key_is_valid(*key)
+                    && matches!(tree.nodes.get(*key), NodeContent::Leaf(_))
+            })
+            .expect("Expected tree to contain a leaf node")
+    }
+
+    #[test]
+    fn test_validate_accepts_consistent_trees() {
+        assert!(BoxTree::<u32>::new(32, 2).ok().unwrap().validate().is_ok());
+        let mut tree = valid_tree();
+        assert!((0..tree.nodes.len()).any(|key| tree.nodes.key_is_valid(key)
+            && matches!(tree.nodes.get(key), NodeContent::UniformLeaf(_))));
+        assert_eq!(tree.validate(), Ok(()));
+        tree.clear(&V3c::new(21, 3, 20)).expect("boxtree clear");
+        assert_eq!(tree.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_validate_reports_occupancy_mismatch() {
+        let mut tree = valid_tree();
+        let leaf_key = leaf_key(&tree);
+        let NodeChildren::OccupancyBitmap(occupied_bits) = tree.node_children[leaf_key] else {
+            panic!("Expected leaf node to have an occupancy bitmap");
+        };
+        tree.node_children[leaf_key] = NodeChildren::OccupancyBitmap(!occupied_bits);
+        assert!(tree.validate().is_err_and(|issues| issues.contains(
+            &ValidationIssue::OccupancyMismatch {
+                node_key: leaf_key,
+                stored: !occupied_bits,
+                expected: occupied_bits,
+            }
+        )));
+
+        tree.node_children[leaf_key] = NodeChildren::NoChildren;
+        assert!(tree.validate().is_err_and(
+            |issues| issues.contains(&ValidationIssue::ChildrenMismatch { node_key: leaf_key })
+        ));
+    }
+
+    #[test]
+    fn test_validate_reports_invalid_children() {
+        let mut tree = valid_tree();
+        let root_key = BoxTree::<u32>::ROOT_NODE_KEY as usize;
+        let NodeChildren::Children(mut children) = tree.node_children[root_key] else {
+            panic!("Expected root node to have children");
+        };
+        let sectant = children
+            .iter()
+            .position(|key| tree.nodes.key_is_valid(*key as usize))
+            .expect("Expected root node to have a valid child");
+        let child_key = children[sectant] as usize;
+        children[63] = children[sectant];
+        tree.node_children[root_key] = NodeChildren::Children(children);
+        assert!(tree.validate().is_err_and(|issues| issues.contains(
+            &ValidationIssue::SharedNode {
+                node_key: child_key
+            }
+        )));
+
+        children[63] = tree.nodes.len() as u32 + 10;
+        tree.node_children[root_key] = NodeChildren::Children(children);
+        assert!(tree.validate().is_err_and(|issues| issues.contains(
+            &ValidationIssue::InvalidChild {
+                node_key: root_key,
+                sectant: 63,
+                child_key: tree.nodes.len() + 10,
+            }
+        )));
+    }
+
+    #[test]
+    fn test_validate_reports_invalid_bricks() {
+        let mut tree = valid_tree();
+        let leaf_key = leaf_key(&tree);
+        let color_count = tree.voxel_color_palette.len();
+        let NodeContent::Leaf(bricks) = tree.nodes.get_mut(leaf_key) else {
+            unreachable!();
+        };
+        let brick = bricks
+            .iter_mut()
+            .find(|brick| matches!(brick, BrickData::Parted(_)))
+            .expect("Expected leaf node to contain a parted brick");
+        let BrickData::Parted(voxels) = brick else {
+            unreachable!();
+        };
+        voxels[0] = NodeContent::pix_visual(color_count as u16);
+        let extra_voxel = voxels[1];
+        voxels.push(extra_voxel);
+        let issues = tree.validate().expect_err("Expected tree to be invalid");
+        assert!(issues.contains(&ValidationIssue::ColorIndexOutOfRange {
+            node_key: leaf_key,
+            index: color_count,
+        }));
+        assert!(issues.contains(&ValidationIssue::InvalidBrickSize {
+            node_key: leaf_key,
+            size: 9,
+        }));
+    }
+
+    #[test]
+    fn test_validate_reports_mip_mismatch() {
+        let mut tree = valid_tree();
+        let leaf_key = leaf_key(&tree);
+        tree.node_mips[leaf_key] = BrickData::Empty;
+        assert!(tree.validate().is_err_and(
+            |issues| issues.contains(&ValidationIssue::MipMismatch { node_key: leaf_key })
+        ));
+
+        assert!(tree
+            .validate()
+            .is_err_and(|issues| !issues.iter().any(ValidationIssue::is_structural)));
+
+        // MIPs left over while the feature is disabled are not reported
+        tree.albedo_mip_map_resampling_strategy()
+            .switch_albedo_mip_maps(false);
+        assert_eq!(tree.validate(), Ok(()));
+        let root_key = BoxTree::<u32>::ROOT_NODE_KEY as usize;
+        assert!(!matches!(tree.node_mips[root_key], BrickData::Empty));
+        tree.drop_unexpected_mips();
+        assert!(matches!(tree.node_mips[root_key], BrickData::Empty));
+        assert_eq!(tree.validate(), Ok(()));
+    }
+}
+
+mod journal_tests {
+    use crate::boxtree::{
+        types::{Axis, PaletteOverflowStrategy, PasteMode, RecordedContent},
+        update::shape::Sphere,
+        Albedo, BoxTree, BoxTreeEntry, JournalConfig, V3c, MAX_PALETTE_SIZE,
+    };
+
+    /// Provides the size of each area recorded by the latest undoable transaction, or None for palette records
+    fn recorded_area_sizes(tree: &BoxTree) -> Vec<Option<u32>> {
+        let journal = tree
+            .journal
+            .as_ref()
+            .expect("Expected journal to be enabled");
+        journal
+            .undo_stack
+            .back()
+            .expect("Expected a record")
+            .records
+            .iter()
+            .map(|record| match &record.content {
+                RecordedContent::Area { voxels, .. } => Some(voxels.boxtree_size),
+                RecordedContent::Palettes { .. } => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_undo_and_redo_restore_edits() {
+        let red: Albedo = 0xFF0000FF.into();
+        let green: Albedo = 0x00FF00FF.into();
+        let mut tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+        tree.albedo_mip_map_resampling_strategy()
+            .switch_albedo_mip_maps(true);
+        tree.insert_box(&V3c::new(0, 0, 0), &V3c::new(16, 16, 16), &red)
+            .expect("insert_box to work");
+        tree.enable_journal(JournalConfig::default());
+        assert!(!tree.can_undo());
+
+        tree.insert(&V3c::new(20, 20, 20), (&green, &5))
+            .expect("boxtree insert");
+        tree.clear(&V3c::new(1, 1, 1)).expect("boxtree clear");
+        tree.update(&V3c::new(2, 2, 2), BoxTreeEntry::Informative(&5))
+            .expect("boxtree update");
+        assert!(tree.can_undo());
+
+        assert!(tree.undo().expect("undo to work"));
+        assert!(tree.get(&V3c::new(2, 2, 2)) == (&red).into());
+        assert!(tree.undo().expect("undo to work"));
+        assert!(tree.get(&V3c::new(1, 1, 1)) == (&red).into());
+        assert!(tree.undo().expect("undo to work"));
+        assert!(tree.get(&V3c::new(20, 20, 20)) == BoxTreeEntry::Empty);
+        assert!(!tree.undo().expect("undo to work"));
+        assert!(tree.validate().is_ok());
+
+        assert!(tree.redo().expect("redo to work"));
+        assert!(tree.get(&V3c::new(20, 20, 20)) == (&green, &5).into());
+        assert!(tree.redo().expect("redo to work"));
+        assert!(tree.get(&V3c::new(1, 1, 1)) == BoxTreeEntry::Empty);
+        assert!(tree.redo().expect("redo to work"));
+        assert!(tree.get(&V3c::new(2, 2, 2)) == (&red, &5).into());
+        assert!(!tree.redo().expect("redo to work"));
+        assert!(tree.validate().is_ok());
+    }
+
+    #[test]
+    fn test_undo_restores_edits_at_lod() {
+        let red: Albedo = 0xFF0000FF.into();
+        let mut tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+        tree.albedo_mip_map_resampling_strategy()
+            .switch_albedo_mip_maps(true);
+        tree.enable_journal(JournalConfig::default());
+        tree.insert_at_lod(&V3c::new(0, 0, 0), 8, &red)
+            .expect("boxtree insert");
+        tree.clear_at_lod(&V3c::new(0, 0, 0), 2)
+            .expect("boxtree clear");
+        assert!(tree.get(&V3c::new(1, 1, 1)) == BoxTreeEntry::Empty);
+
+        assert!(tree.undo().expect("undo to work"));
+        assert!(tree.get(&V3c::new(1, 1, 1)) == (&red).into());
+        assert!(tree.get(&V3c::new(7, 7, 7)) == (&red).into());
+        assert!(tree.undo().expect("undo to work"));
+        assert!(tree.get(&V3c::new(7, 7, 7)) == BoxTreeEntry::Empty);
+        assert!(tree.validate().is_ok());
+    }
+
+    #[test]
+    fn test_every_edit_is_recorded() {
+        let red: Albedo = 0xFF0000FF.into();
+        let green: Albedo = 0x00FF00FF.into();
+        let mut tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+        tree.insert_box(&V3c::new(0, 0, 0), &V3c::new(8, 8, 8), &red)
+            .expect("insert_box to work");
+        let mut other: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+        other
+            .insert_box(&V3c::new(0, 0, 0), &V3c::new(4, 4, 4), &green)
+            .expect("insert_box to work");
+        tree.enable_journal(JournalConfig::default());
+
+        tree.insert_box(&V3c::new(20, 20, 20), &V3c::new(24, 24, 24), &green)
+            .expect("insert_box to work");
+        tree.insert_shape(
+            &Sphere {
+                center: V3c::new(26., 26., 26.),
+                radius: 3.,
+            },
+            &green,
+        )
+        .expect("insert_shape to work");
+        tree.paste(&other, &V3c::new(16, 0, 0), PasteMode::Overwrite)
+            .expect("paste to work");
+        tree.mirror_region(&V3c::new(0, 0, 0), &V3c::new(8, 8, 8), Axis::X)
+            .expect("mirror_region to work");
+        tree.subtract(&other).expect("subtract to work");
+        tree.insert_many([(V3c::new(28, 0, 0), &green), (V3c::new(0, 28, 0), &green)])
+            .expect("insert_many to work");
+        tree.replace_color(&red, &green);
+        tree.rotate(Axis::Z, 1);
+        let edits = 8;
+
+        for _ in 0..edits {
+            assert!(tree.undo().expect("undo to work"));
+        }
+        assert!(!tree.can_undo());
+        assert!(tree.get(&V3c::new(1, 1, 1)) == (&red).into());
+        assert!(tree.get(&V3c::new(22, 22, 22)) == BoxTreeEntry::Empty);
+        assert!(tree.get(&V3c::new(26, 26, 26)) == BoxTreeEntry::Empty);
+        assert!(tree.get(&V3c::new(17, 1, 1)) == BoxTreeEntry::Empty);
+        assert!(tree.get(&V3c::new(28, 0, 0)) == BoxTreeEntry::Empty);
+        assert!(tree.validate().is_ok());
+
+        for _ in 0..edits {
+            assert!(tree.redo().expect("redo to work"));
+        }
+        assert!(!tree.can_redo());
+        assert!(tree.get(&V3c::new(26, 5, 5)) == (&green).into());
+        assert!(tree.get(&V3c::new(30, 1, 1)) == BoxTreeEntry::Empty);
+        assert!(tree.get(&V3c::new(31, 28, 0)) == (&green).into());
+        assert!(tree.validate().is_ok());
+    }
+
+    #[test]
+    fn test_box_edits_record_only_the_overlapping_areas() {
+        let red: Albedo = 0xFF0000FF.into();
+        let green: Albedo = 0x00FF00FF.into();
+        let mut tree: BoxTree = BoxTree::new(128, 2).ok().unwrap();
+        for i in 0..128 {
+            tree.insert(&V3c::new(i, (i * 7) % 128, (i * 13) % 128), &red)
+                .expect("boxtree insert");
+        }
+        tree.enable_journal(JournalConfig::default());
+
+        // The box straddles the center of the tree, touching a leaf node in each of the 8 octants
+        tree.insert_box(&V3c::new(63, 63, 63), &V3c::new(65, 65, 65), &green)
+            .expect("insert_box to work");
+        assert_eq!(recorded_area_sizes(&tree), vec![Some(8); 8]);
+
+        // Nodes completely inside the box are recorded as a whole
+        tree.insert_box(&V3c::new(0, 0, 0), &V3c::new(33, 32, 32), &green)
+            .expect("insert_box to work");
+        let area_sizes = recorded_area_sizes(&tree);
+        assert_eq!(area_sizes.len(), 1 + 4 * 4);
+        assert!(area_sizes.contains(&Some(32)));
+
+        assert!(tree.undo().expect("undo to work"));
+        assert!(tree.undo().expect("undo to work"));
+        for i in 0..128 {
+            assert!(tree.get(&V3c::new(i, (i * 7) % 128, (i * 13) % 128)) == (&red).into());
+        }
+        assert!(tree.get(&V3c::new(64, 63, 64)) == BoxTreeEntry::Empty);
+        assert!(tree.get(&V3c::new(1, 2, 3)) == BoxTreeEntry::Empty);
+        assert!(tree.validate().is_ok());
+    }
+
+    #[test]
+    fn test_transactions_are_undone_together() {
+        let red: Albedo = 0xFF0000FF.into();
+        let mut tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+        tree.enable_journal(JournalConfig::default());
+        tree.begin_transaction();
+        tree.insert(&V3c::new(0, 0, 0), &red)
+            .expect("boxtree insert");
+        tree.insert(&V3c::new(31, 31, 31), &red)
+            .expect("boxtree insert");
+        tree.end_transaction();
+        tree.insert(&V3c::new(10, 10, 10), &red)
+            .expect("boxtree insert");
+
+        assert!(tree.undo().expect("undo to work"));
+        assert!(tree.get(&V3c::new(10, 10, 10)) == BoxTreeEntry::Empty);
+        assert!(tree.get(&V3c::new(31, 31, 31)) == (&red).into());
+        assert!(tree.undo().expect("undo to work"));
+        assert!(tree.get(&V3c::new(0, 0, 0)) == BoxTreeEntry::Empty);
+        assert!(tree.get(&V3c::new(31, 31, 31)) == BoxTreeEntry::Empty);
+        assert!(!tree.can_undo());
+
+        // New edits make undone transactions impossible to redo
+        assert!(tree.can_redo());
+        tree.insert(&V3c::new(5, 5, 5), &red)
+            .expect("boxtree insert");
+        assert!(!tree.can_redo());
+    }
+
+    #[test]
+    fn test_coalesced_transactions() {
+        let red: Albedo = 0xFF0000FF.into();
+        let mut tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+        tree.enable_journal(JournalConfig {
+            coalesced_edits: 2,
+            ..Default::default()
+        });
+        for x in 0..4 {
+            tree.insert(&V3c::new(x, 0, 0), &red)
+                .expect("boxtree insert");
+        }
+        tree.coalesce_transactions(2);
+
+        assert!(tree.undo().expect("undo to work"));
+        assert!(tree.get(&V3c::new(0, 0, 0)) == BoxTreeEntry::Empty);
+        assert!(!tree.can_undo());
+        assert!(tree.redo().expect("redo to work"));
+        assert!((0..4).all(|x| tree.get(&V3c::new(x, 0, 0)) == (&red).into()));
+    }
+
+    #[test]
+    fn test_journal_limits() {
+        let red: Albedo = 0xFF0000FF.into();
+        let mut tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+        tree.enable_journal(JournalConfig {
+            transaction_limit: 2,
+            ..Default::default()
+        });
+        for x in 0..4 {
+            tree.insert(&V3c::new(x * 8, 0, 0), &red)
+                .expect("boxtree insert");
+        }
+        assert!(tree.undo().expect("undo to work"));
+        assert!(tree.undo().expect("undo to work"));
+        assert!(!tree.undo().expect("undo to work"));
+        assert!(tree.get(&V3c::new(8, 0, 0)) == (&red).into());
+        assert!(tree.get(&V3c::new(16, 0, 0)) == BoxTreeEntry::Empty);
+
+        // Only the latest transaction is kept above the memory limit
+        tree.enable_journal(JournalConfig {
+            memory_limit: 0,
+            ..Default::default()
+        });
+        tree.insert(&V3c::new(16, 0, 0), &red)
+            .expect("boxtree insert");
+        assert!(tree.can_undo());
+        tree.insert(&V3c::new(24, 0, 0), &red)
+            .expect("boxtree insert");
+        assert!(tree.undo().expect("undo to work"));
+        assert!(!tree.can_undo());
+        assert!(tree.get(&V3c::new(16, 0, 0)) == (&red).into());
+        assert!(tree.get(&V3c::new(24, 0, 0)) == BoxTreeEntry::Empty);
+
+        tree.disable_journal();
+        assert!(!tree.undo().expect("undo to work"));
+    }
+
+    #[test]
+    fn test_palette_edits_record_only_the_palettes() {
+        let red: Albedo = 0xFF0000FF.into();
+        let green: Albedo = 0x00FF00FF.into();
+        let blue: Albedo = 0x0000FFFF.into();
+        let mut tree: BoxTree = BoxTree::new(64, 4).ok().unwrap();
+        for i in 0..64 {
+            tree.insert(&V3c::new(i, (i * 7) % 64, (i * 13) % 64), &red)
+                .expect("boxtree insert");
+        }
+        tree.enable_journal(JournalConfig {
+            memory_limit: 30_000,
+            ..Default::default()
+        });
+        tree.insert(&V3c::new(1, 2, 3), &green)
+            .expect("boxtree insert");
+        tree.insert(&V3c::new(60, 2, 3), &green)
+            .expect("boxtree insert");
+
+        // Palette edits do not copy the voxels, so earlier edits are kept
+        assert_eq!(1, tree.replace_color(&red, &blue));
+        assert_eq!(recorded_area_sizes(&tree), vec![None]);
+        assert_eq!(3, tree.journal.as_ref().unwrap().undo_stack.len());
+        assert!(tree.get(&V3c::new(0, 0, 0)) == (&blue).into());
+
+        assert!(tree.undo().expect("undo to work"));
+        assert!(tree.get(&V3c::new(0, 0, 0)) == (&red).into());
+        assert!(tree.get(&V3c::new(60, 2, 3)) == (&green).into());
+        assert!(tree.undo().expect("undo to work"));
+        assert!(tree.undo().expect("undo to work"));
+        assert!(!tree.can_undo());
+        assert!(tree.get(&V3c::new(1, 2, 3)) == BoxTreeEntry::Empty);
+        assert!(tree.get(&V3c::new(60, 2, 3)) == BoxTreeEntry::Empty);
+
+        for _ in 0..3 {
+            assert!(tree.redo().expect("redo to work"));
+        }
+        assert!(tree.get(&V3c::new(0, 0, 0)) == (&blue).into());
+        assert!(tree.get(&V3c::new(1, 2, 3)) == (&green).into());
+        assert!(tree.validate().is_ok());
+    }
+
+    #[test]
+    fn test_palette_rewrites_are_undone() {
+        let red: Albedo = 0xFF0000FF.into();
+        let green: Albedo = 0x00FF00FF.into();
+        let blue: Albedo = 0x0000FFFF.into();
+        let mut tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+        tree.insert(&V3c::new(0, 0, 0), &green)
+            .expect("boxtree insert");
+        tree.insert_box(&V3c::new(4, 4, 4), &V3c::new(12, 12, 12), &red)
+            .expect("insert_box to work");
+        tree.insert(&V3c::new(20, 20, 20), &blue)
+            .expect("boxtree insert");
+        tree.enable_journal(JournalConfig::default());
+
+        // Compaction moves the remaining colors to the front of the palette
+        tree.clear(&V3c::new(0, 0, 0)).expect("boxtree clear");
+        assert_eq!((1, 0), tree.compact_palette());
+        assert!(tree.undo().expect("undo to work"));
+        assert_eq!(3, tree.voxel_color_palette.len());
+        assert!(tree.get(&V3c::new(4, 4, 4)) == (&red).into());
+        assert!(tree.get(&V3c::new(20, 20, 20)) == (&blue).into());
+        assert!(tree.undo().expect("undo to work"));
+        assert!(tree.get(&V3c::new(0, 0, 0)) == (&green).into());
+        assert!(tree.redo().expect("redo to work"));
+        assert!(tree.redo().expect("redo to work"));
+        assert_eq!(2, tree.voxel_color_palette.len());
+        assert!(tree.get(&V3c::new(4, 4, 4)) == (&red).into());
+        assert!(tree.get(&V3c::new(0, 0, 0)) == BoxTreeEntry::Empty);
+
+        // Re-quantization is undone together with the edit adding the new color
+        tree.palette_overflow_strategy = PaletteOverflowStrategy::Requantize;
+        let mut index = 0;
+        while tree.voxel_color_palette.len() < MAX_PALETTE_SIZE {
+            let color = Albedo {
+                r: (index % 256) as u8,
+                g: (index / 256) as u8,
+                b: 100,
+                a: 255,
+            };
+            index += 1;
+            if !tree.map_to_color_index_in_palette.contains_key(&color) {
+                tree.map_to_color_index_in_palette
+                    .insert(color, tree.voxel_color_palette.len());
+                tree.voxel_color_palette.push(color);
+            }
+        }
+        let new_color: Albedo = 0x123456FF.into();
+        tree.insert(&V3c::new(30, 30, 30), &new_color)
+            .expect("boxtree insert");
+        assert!(tree.voxel_color_palette.len() < MAX_PALETTE_SIZE);
+        assert!(tree.get(&V3c::new(4, 4, 4)) != (&red).into());
+        assert!(tree.undo().expect("undo to work"));
+        assert_eq!(MAX_PALETTE_SIZE, tree.voxel_color_palette.len());
+        assert!(tree.get(&V3c::new(4, 4, 4)) == (&red).into());
+        assert!(tree.get(&V3c::new(20, 20, 20)) == (&blue).into());
+        assert!(tree.get(&V3c::new(30, 30, 30)) == BoxTreeEntry::Empty);
+        assert!(tree.redo().expect("redo to work"));
+        assert!(tree.voxel_color_palette.len() < MAX_PALETTE_SIZE);
+        assert!(tree.get(&V3c::new(30, 30, 30)).albedo().is_some());
+        assert!(tree.validate().is_ok());
+    }
+}
+
+mod changes_tests {
+    use crate::boxtree::{
+        types::{NodeContent, PaletteIndexValues},
+        Albedo, BoxTree, V3c,
+    };
+
+    /// Provides the keys of the valid nodes in the tree with the given content
+    fn node_keys(
+        tree: &BoxTree,
+        is_matching: fn(&NodeContent<PaletteIndexValues>) -> bool,
+    ) -> Vec<usize> {
+        (0..tree.nodes.len())
+            .filter(|key| tree.nodes.key_is_valid(*key) && is_matching(tree.nodes.get(*key)))
+            .collect()
+    }
+
+    #[test]
+    fn test_changes_since_lists_modified_parts() {
+        let red: Albedo = 0xFF0000FF.into();
+        let mut tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+        tree.auto_simplify = false;
+        tree.insert(&V3c::new(31, 31, 31), &red)
+            .expect("boxtree insert");
+        let generation = tree.generation();
+        assert!(tree.changes_since(generation).nodes.is_empty());
+
+        tree.insert(&V3c::new(0, 0, 0), &red)
+            .expect("boxtree insert");
+        let changes = tree.changes_since(generation);
+        assert_eq!(changes.generation, tree.generation());
+        assert!(!changes.layout_changed);
+        assert_eq!(
+            changes.regions,
+            vec![(V3c::new(0, 0, 0), V3c::new(1, 1, 1))]
+        );
+
+        // Only the root getting a new child and the leaf containing the new voxel are modified
+        let leaf_key = tree.node_children[0].child(0);
+        assert_eq!(changes.nodes, vec![0, leaf_key]);
+        assert_eq!(changes.bricks, vec![(leaf_key, 0)]);
+        assert!(changes.mips.is_empty());
+
+        // Ancestors of a modified brick are not listed, unless they are modified themselves
+        let generation = tree.generation();
+        tree.insert(&V3c::new(1, 0, 0), &red)
+            .expect("boxtree insert");
+        let changes = tree.changes_since(generation);
+        assert!(changes.nodes.is_empty());
+        assert_eq!(changes.bricks, vec![(leaf_key, 0)]);
+        tree.insert(&V3c::new(2, 0, 0), &red)
+            .expect("boxtree insert");
+        let changes = tree.changes_since(generation);
+        assert_eq!(changes.nodes, vec![leaf_key]);
+        assert_eq!(changes.bricks, vec![(leaf_key, 0), (leaf_key, 1)]);
+
+        // MIPs along the modified region are listed while MIPs are enabled
+        tree.albedo_mip_map_resampling_strategy()
+            .switch_albedo_mip_maps(true);
+        let generation = tree.generation();
+        tree.insert(&V3c::new(3, 0, 0), &red)
+            .expect("boxtree insert");
+        let changes = tree.changes_since(generation);
+        assert!(changes.nodes.is_empty());
+        assert_eq!(changes.mips, vec![0, leaf_key]);
+    }
+
+    #[test]
+    fn test_consumers_query_changes_independently() {
+        let red: Albedo = 0xFF0000FF.into();
+        let mut tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+        let first_consumer = tree.generation();
+        tree.insert_box(&V3c::new(0, 0, 0), &V3c::new(8, 8, 8), &red)
+            .expect("insert_box to work");
+        let second_consumer = tree.changes_since(first_consumer).generation;
+        tree.clear(&V3c::new(20, 20, 20)).expect("boxtree clear");
+        tree.insert(&V3c::new(20, 20, 20), &red)
+            .expect("boxtree insert");
+
+        assert_eq!(
+            tree.changes_since(first_consumer).regions,
+            vec![
+                (V3c::new(0, 0, 0), V3c::new(8, 8, 8)),
+                (V3c::new(20, 20, 20), V3c::new(21, 21, 21))
+            ]
+        );
+        assert_eq!(
+            tree.changes_since(second_consumer).regions,
+            vec![(V3c::new(20, 20, 20), V3c::new(21, 21, 21))]
+        );
+        let changes = tree.changes_since(tree.generation());
+        assert!(changes.nodes.is_empty() && changes.bricks.is_empty() && changes.mips.is_empty());
+        assert!(changes.regions.is_empty());
+    }
+
+    #[test]
+    fn test_changes_of_mips_and_layout() {
+        let red: Albedo = 0xFF0000FF.into();
+        let green: Albedo = 0x00FF00FF.into();
+        let mut tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+        tree.albedo_mip_map_resampling_strategy()
+            .switch_albedo_mip_maps(true);
+        tree.insert(&V3c::new(0, 0, 0), &red)
+            .expect("boxtree insert");
+        tree.insert(&V3c::new(31, 31, 31), &red)
+            .expect("boxtree insert");
+        tree.clear(&V3c::new(31, 31, 31)).expect("boxtree clear");
+
+        // Palette edits only change the MIPs containing the edited color
+        let generation = tree.generation();
+        tree.replace_color(&red, &green);
+        tree.albedo_mip_map_resampling_strategy()
+            .recalculate_outdated_mips();
+        let changes = tree.changes_since(generation);
+        assert!(changes.nodes.is_empty());
+        assert!(!changes.mips.is_empty());
+
+        // Renumbered nodes are all reported
+        let generation = tree.generation();
+        tree.defragment();
+        let changes = tree.changes_since(generation);
+        assert!(changes.layout_changed);
+        assert_eq!(changes.nodes, node_keys(&tree, |_| true));
+        assert_eq!(
+            changes.bricks.len(),
+            64 * node_keys(&tree, |content| matches!(content, NodeContent::Leaf(_))).len()
+        );
+    }
+}
+
+mod snapshot_tests {
+    use crate::boxtree::{Albedo, BoxTree, V3c};
+    use crate::shared::Shared;
+
+    #[test]
+    fn test_snapshot_keeps_content_after_edits() {
+        let red: Albedo = 0xFF0000FF.into();
+        let green: Albedo = 0x00FF00FF.into();
+        let mut tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+        tree.insert_box(&V3c::new(0, 0, 0), &V3c::new(8, 8, 8), &red)
+            .expect("insert_box to work");
+        tree.insert(&V3c::new(20, 21, 22), &red)
+            .expect("boxtree insert");
+
+        let snapshot = tree.snapshot();
+        assert!(snapshot.node_children.shares_storage(&tree.node_children));
+        assert!(snapshot.node_mips.shares_storage(&tree.node_mips));
+        assert!(Shared::ptr_eq(
+            &snapshot.voxel_color_palette,
+            &tree.voxel_color_palette
+        ));
+
+        tree.insert(&V3c::new(20, 21, 22), &green)
+            .expect("boxtree insert");
+        tree.clear(&V3c::new(1, 1, 1)).expect("boxtree clear");
+        tree.insert(&V3c::new(30, 30, 30), &green)
+            .expect("boxtree insert");
+
+        assert!(*snapshot.get(&V3c::new(20, 21, 22)).albedo().unwrap() == red);
+        assert!(*snapshot.get(&V3c::new(1, 1, 1)).albedo().unwrap() == red);
+        assert!(snapshot.get(&V3c::new(30, 30, 30)).is_none());
+        assert!(*tree.get(&V3c::new(20, 21, 22)).albedo().unwrap() == green);
+        assert!(tree.get(&V3c::new(1, 1, 1)).is_none());
+        assert!(*tree.get(&V3c::new(30, 30, 30)).albedo().unwrap() == green);
+
+        assert_eq!(snapshot.iter().count(), 8 * 8 * 8 + 1);
+        assert_eq!(tree.iter().count(), 8 * 8 * 8 + 1);
+        assert!(snapshot.validate().is_ok());
+    }
+
+    #[test]
+    fn test_edits_copy_only_modified_storage() {
+        let red: Albedo = 0xFF0000FF.into();
+        let green: Albedo = 0x00FF00FF.into();
+        let mut tree: BoxTree = BoxTree::new(128, 2).ok().unwrap();
+        for x in (0..128).step_by(8) {
+            for z in (0..128).step_by(8) {
+                tree.insert(&V3c::new(x, 0, z), &red)
+                    .expect("boxtree insert");
+            }
+        }
+
+        // Overwriting a voxel with a known color only copies the pages of the touched nodes
+        let snapshot = tree.snapshot();
+        tree.insert(&V3c::new(0, 0, 0), &green)
+            .expect("boxtree insert");
+        assert!(Shared::ptr_eq(
+            &snapshot.voxel_data_palette,
+            &tree.voxel_data_palette
+        ));
+        assert!(!snapshot.nodes.shares_storage(&tree.nodes));
+        assert!(snapshot.nodes.shared_page_count(&tree.nodes) > 0);
+        assert!(*snapshot.get(&V3c::new(0, 0, 0)).albedo().unwrap() == red);
+        assert!(*tree.get(&V3c::new(0, 0, 0)).albedo().unwrap() == green);
+
+        // Edits of the snapshot do not change the original tree
+        let mut snapshot = snapshot;
+        snapshot.clear(&V3c::new(8, 0, 8)).expect("boxtree clear");
+        assert!(snapshot.get(&V3c::new(8, 0, 8)).is_none());
+        assert!(*tree.get(&V3c::new(8, 0, 8)).albedo().unwrap() == red);
+    }
+
+    #[cfg(feature = "raytracing")]
+    #[test]
+    fn test_snapshot_get_by_ray() {
+        use crate::spatial::raytracing::Ray;
+        let red: Albedo = 0xFF0000FF.into();
+        let mut tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+        tree.insert(&V3c::new(16, 16, 16), &red)
+            .expect("boxtree insert");
+        let snapshot = tree.snapshot();
+        tree.clear(&V3c::new(16, 16, 16)).expect("boxtree clear");
+
+        let ray = Ray {
+            origin: V3c::new(16.5, 16.5, -10.),
+            direction: V3c::new(0., 0., 1.),
+        };
+        let (entry, _, _) = snapshot.get_by_ray(&ray).expect("Expected ray to hit");
+        assert!(*entry.albedo().unwrap() == red);
+        assert!(tree.get_by_ray(&ray).is_none());
+    }
+}
+
+mod build_tests {
+    use crate::boxtree::{Albedo, BoxTree, BoxTreeEntry, MIPMapStrategy, V3c};
+
+    /// Asserts that both trees have the same content, structure and palettes
+    fn assert_trees_equal(mut tree: BoxTree, mut expected: BoxTree) {
+        assert!(tree.validate().is_ok());
+        assert!(tree.voxel_color_palette == expected.voxel_color_palette);
+        assert!(tree.voxel_data_palette == expected.voxel_data_palette);
+        tree.defragment();
+        expected.defragment();
+        assert_eq!(tree.nodes.len(), expected.nodes.len());
+        for node_key in 0..tree.nodes.len() {
+            assert!(tree.nodes.get(node_key) == expected.nodes.get(node_key));
+        }
+        assert!(tree.node_children == expected.node_children);
+    }
+
+    fn build_sequentially<'a>(
+        size: u32,
+        brick_dimension: u32,
+        generator: impl Fn(V3c<u32>) -> Option<BoxTreeEntry<'a, u32>>,
+    ) -> BoxTree {
+        let mut tree: BoxTree = BoxTree::new(size, brick_dimension).ok().unwrap();
+        for x in 0..size {
+            for y in 0..size {
+                for z in 0..size {
+                    if let Some(entry) = generator(V3c::new(x, y, z)) {
+                        tree.insert(&V3c::new(x, y, z), entry)
+                            .expect("boxtree insert");
+                    }
+                }
+            }
+        }
+        tree
+    }
+
+    #[test]
+    fn test_from_fn_matches_sequential_inserts() {
+        let colors: Vec<Albedo> = (1..=5u32)
+            .map(|i| Albedo::from(0x0000FFFF | (i * 40) << 24 | (i * 20) << 16))
+            .collect();
+        let data: Vec<u32> = vec![7, 11];
+        let generator = |position: V3c<u32>| {
+            if position.x < 8 {
+                // A whole sectant of the root node is uniform
+                Some(BoxTreeEntry::Visual(&colors[0]))
+            } else if (position.x + position.y + position.z).is_multiple_of(5) {
+                Some(BoxTreeEntry::Complex(
+                    &colors[((position.y + position.z) % 5) as usize],
+                    &data[(position.x % 2) as usize],
+                ))
+            } else if position.y > 24 && position.z < 4 {
+                Some(BoxTreeEntry::Informative(&data[1]))
+            } else {
+                None
+            }
+        };
+
+        for brick_dimension in [1, 2] {
+            let size = brick_dimension * 16;
+            let tree = BoxTree::from_fn(size, brick_dimension, generator).expect("from_fn to work");
+            let expected = build_sequentially(size, brick_dimension, generator);
+            for x in 0..size {
+                for y in 0..size {
+                    for z in 0..size {
+                        let position = V3c::new(x, y, z);
+                        assert!(tree.get(&position) == expected.get(&position));
+                    }
+                }
+            }
+            assert_trees_equal(tree, expected);
+        }
+    }
+
+    #[test]
+    fn test_from_fn_simplifies_uniform_tree() {
+        let red: Albedo = 0xFF0000FF.into();
+        let generator = |_| Some(BoxTreeEntry::Visual(&red));
+        let tree = BoxTree::from_fn(32, 2, generator).expect("from_fn to work");
+        assert_trees_equal(tree, build_sequentially(32, 2, generator));
+
+        // Trees without subtrees under the root node are built sequentially
+        let tree = BoxTree::from_fn(8, 2, generator).expect("from_fn to work");
+        assert_trees_equal(tree, build_sequentially(8, 2, generator));
+
+        let empty = BoxTree::<u32>::from_fn(32, 2, |_| None).expect("from_fn to work");
+        assert_trees_equal(empty, BoxTree::new(32, 2).ok().unwrap());
+    }
+
+    #[test]
+    fn test_from_dense() {
+        let red: Albedo = 0xFF0000FF.into();
+        let green: Albedo = 0x00FF00FF.into();
+        let dimensions = V3c::new(10, 3, 7);
+        let voxels: Vec<Option<BoxTreeEntry<u32>>> = (0..10 * 3 * 7)
+            .map(|index| match index % 3 {
+                0 => Some(BoxTreeEntry::Visual(&red)),
+                1 => Some(BoxTreeEntry::Visual(&green)),
+                _ => None,
+            })
+            .collect();
+        let tree = BoxTree::from_dense(&voxels, dimensions, 2).expect("from_dense to work");
+        assert_eq!(tree.get_size(), 32);
+        for x in 0..tree.get_size() {
+            for y in 0..tree.get_size() {
+                for z in 0..tree.get_size() {
+                    let expected = if x < 10 && y < 3 && z < 7 {
+                        voxels[(x + y * 10 + z * 30) as usize].unwrap_or(BoxTreeEntry::Empty)
+                    } else {
+                        BoxTreeEntry::Empty
+                    };
+                    assert!(tree.get(&V3c::new(x, y, z)) == expected);
+                }
+            }
+        }
+
+        assert!(BoxTree::from_dense(&voxels[1..], dimensions, 2).is_err());
+        assert!(BoxTree::from_dense(&voxels, V3c::new(u32::MAX, u32::MAX, 2), 2).is_err());
+    }
+
+    #[test]
+    fn test_from_fn_with_mips() {
+        let red: Albedo = 0xFF0000FF.into();
+        let green: Albedo = 0x00FF00FF.into();
+        let generator = |position: V3c<u32>| {
+            if position.y < 8 {
+                Some(BoxTreeEntry::Visual(&red))
+            } else if (position.x + position.z).is_multiple_of(3) {
+                Some(BoxTreeEntry::Visual(&green))
+            } else {
+                None
+            }
+        };
+        let tree: BoxTree = MIPMapStrategy::default()
+            .set_enabled(true)
+            .from_fn(32, 2, generator)
+            .expect("from_fn to work");
+        assert!(tree.mip_map_strategy.is_enabled());
+        assert!(tree.outdated_mips.is_empty());
+
+        let mut expected: BoxTree = BoxTree::from_fn(32, 2, generator).expect("from_fn to work");
+        expected
+            .albedo_mip_map_resampling_strategy()
+            .switch_albedo_mip_maps(true);
+        for node_key in 0..expected.nodes.len() {
+            if expected.nodes.key_is_valid(node_key) {
+                assert!(tree.node_mips[node_key] == expected.node_mips[node_key]);
+            }
+        }
+
+        let empty = MIPMapStrategy::default()
+            .set_enabled(true)
+            .from_fn::<u32, _>(32, 2, |_| None)
+            .expect("from_fn to work");
+        assert!(empty.mip_map_strategy.is_enabled());
+    }
+}
+
+mod dense_tests {
+    use crate::boxtree::{Albedo, BoxTree, BoxTreeEntry, V3c};
+
+    /// A tree with each kind of entry: a box of complex voxels spanning uniform nodes and
+    /// partial bricks, and a line along each axis so a mixed up index order shows
+    fn sample_tree() -> BoxTree {
+        let red: Albedo = 0xFF0000FF.into();
+        let green: Albedo = 0x00FF00FF.into();
+        let blue: Albedo = 0x0000FFFF.into();
+        let mut tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+        tree.insert_box(&V3c::new(4, 0, 6), &V3c::new(28, 8, 26), (&red, &3))
+            .expect("boxtree insert");
+        for i in 0..32 {
+            tree.insert(&V3c::new(i, 29, 10), &green)
+                .expect("boxtree insert");
+            tree.insert(&V3c::new(3, i, 12), &blue)
+                .expect("boxtree insert");
+            tree.insert(&V3c::new(12, 20, i), BoxTreeEntry::Informative(&(i + 1)))
+                .expect("boxtree insert");
+        }
+        tree
+    }
+
+    #[test]
+    fn test_to_dense_matches_get() {
+        let tree = sample_tree();
+        let min_position = V3c::new(1, 3, 5);
+        let max_position = V3c::new(23, 30, 21);
+        let size = max_position - min_position;
+        let dense = tree
+            .to_dense(&min_position, &max_position)
+            .expect("to_dense to work");
+        assert_eq!(dense.len(), (size.x * size.y * size.z) as usize);
+        for x in min_position.x..max_position.x {
+            for y in min_position.y..max_position.y {
+                for z in min_position.z..max_position.z {
+                    let offset = V3c::new(x, y, z) - min_position;
+                    let index =
+                        (offset.x + offset.y * size.x + offset.z * size.x * size.y) as usize;
+                    let expected = match tree.get(&V3c::new(x, y, z)) {
+                        BoxTreeEntry::Empty => None,
+                        BoxTreeEntry::Visual(albedo) => Some((*albedo, 0)),
+                        BoxTreeEntry::Informative(data) => Some((Albedo::default(), *data)),
+                        BoxTreeEntry::Complex(albedo, data) => Some((*albedo, *data)),
+                    };
+                    assert_eq!(dense[index], expected, "Mismatch at {:?}", (x, y, z));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_to_dense_indices() {
+        let tree = sample_tree();
+        let min_position = V3c::new(0, 0, 0);
+        let max_position = V3c::new(32, 32, 32);
+        let region = tree
+            .to_dense_indices(&min_position, &max_position)
+            .expect("to_dense_indices to work");
+        let dense = tree
+            .to_dense(&min_position, &max_position)
+            .expect("to_dense to work");
+        assert_eq!(region.dimensions, max_position);
+        assert_eq!(region.voxels.len(), dense.len());
+        for (indices, voxel) in region.voxels.iter().zip(dense.iter()) {
+            let albedo = indices.0.map(|index| region.color_palette[index as usize]);
+            let data = indices.1.map(|index| region.data_palette[index as usize]);
+            match voxel {
+                None => assert!(albedo.is_none() && data.is_none()),
+                Some((expected_albedo, expected_data)) => {
+                    assert_eq!(albedo.unwrap_or_default(), *expected_albedo);
+                    assert_eq!(data.unwrap_or_default(), *expected_data);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_to_dense_round_trip() {
+        let tree = sample_tree();
+        let dense = tree
+            .to_dense(&V3c::new(0, 0, 0), &V3c::new(32, 32, 32))
+            .expect("to_dense to work");
+        let entries: Vec<Option<BoxTreeEntry<u32>>> = dense
+            .iter()
+            .map(|voxel| voxel.as_ref().map(|(albedo, data)| (albedo, data).into()))
+            .collect();
+        let rebuilt =
+            BoxTree::from_dense(&entries, V3c::new(32, 32, 32), 2).expect("from_dense to work");
+        for x in 0..32 {
+            for y in 0..32 {
+                for z in 0..32 {
+                    let position = V3c::new(x, y, z);
+                    let expected = dense[(x + y * 32 + z * 32 * 32) as usize];
+                    let actual = match rebuilt.get(&position) {
+                        BoxTreeEntry::Empty => None,
+                        BoxTreeEntry::Visual(albedo) => Some((*albedo, 0)),
+                        BoxTreeEntry::Informative(data) => Some((Albedo::default(), *data)),
+                        BoxTreeEntry::Complex(albedo, data) => Some((*albedo, *data)),
+                    };
+                    assert_eq!(actual, expected);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_to_dense_invalid_bounds() {
+        let tree = sample_tree();
+        assert!(tree
+            .to_dense(&V3c::new(0, 0, 0), &V3c::new(33, 1, 1))
+            .is_err());
+        assert!(tree
+            .to_dense(&V3c::new(32, 0, 0), &V3c::new(32, 1, 1))
+            .is_err());
+        let empty = tree
+            .to_dense_indices(&V3c::new(5, 5, 5), &V3c::new(4, 8, 8))
+            .expect("to_dense_indices to work");
+        assert!(empty.voxels.is_empty());
+        assert_eq!(empty.dimensions, V3c::new(0, 3, 3));
+    }
+}
+
+mod connectivity_tests {
+    use crate::boxtree::{
+        Albedo, BoxTree, BoxTreeEntry, Component, Connectivity, JournalConfig, V3c,
+    };
+    use std::collections::{HashSet, VecDeque};
+
+    /// Voxels reachable from the seed through voxels matching the predicate, queried one by one
+    fn reachable_voxels(
+        tree: &BoxTree,
+        seed: V3c<u32>,
+        connectivity: Connectivity,
+        predicate: impl Fn(&BoxTreeEntry<u32>) -> bool,
+    ) -> HashSet<(u32, u32, u32)> {
+        let max_differing_axes = match connectivity {
+            Connectivity::Face => 1,
+            Connectivity::Edge => 2,
+            Connectivity::Vertex => 3,
+        };
+        let size = tree.get_size() as i32;
+        let mut visited = HashSet::from([(seed.x, seed.y, seed.z)]);
+        let mut queue = VecDeque::from([seed]);
+        while let Some(position) = queue.pop_front() {
+            for dx in -1..=1_i32 {
+                for dy in -1..=1_i32 {
+                    for dz in -1..=1_i32 {
+                        let differing_axes = dx.abs() + dy.abs() + dz.abs();
+                        if 0 == differing_axes || differing_axes > max_differing_axes {
+                            continue;
+                        }
+                        let neighbor = (
+                            position.x as i32 + dx,
+                            position.y as i32 + dy,
+                            position.z as i32 + dz,
+                        );
+                        if neighbor.0 < 0
+                            || neighbor.1 < 0
+                            || neighbor.2 < 0
+                            || neighbor.0 >= size
+                            || neighbor.1 >= size
+                            || neighbor.2 >= size
+                        {
+                            continue;
+                        }
+                        let neighbor =
+                            V3c::new(neighbor.0 as u32, neighbor.1 as u32, neighbor.2 as u32);
+                        if predicate(&tree.get(&neighbor))
+                            && visited.insert((neighbor.x, neighbor.y, neighbor.z))
+                        {
+                            queue.push_back(neighbor);
+                        }
+                    }
+                }
+            }
+        }
+        visited
+    }
+
+    /// A tree with a ground plate, pillars standing on it, a hollow shell enclosing a void
+    /// and chains of voxels touching only by their edges or corners
+    fn structured_tree() -> BoxTree {
+        let red: Albedo = 0xFF0000FF.into();
+        let mut tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+        tree.insert_box(&V3c::new(0, 0, 0), &V3c::new(32, 1, 32), &red)
+            .expect("boxtree insert");
+        for i in 0..4 {
+            tree.insert_box(
+                &V3c::new(2 + i * 4, 1, 2),
+                &V3c::new(3 + i * 4, 10 + i, 3),
+                &red,
+            )
+            .expect("boxtree insert");
+        }
+        tree.insert_box(&V3c::new(18, 4, 18), &V3c::new(30, 16, 30), &red)
+            .expect("boxtree insert");
+        tree.clear_box(&V3c::new(19, 5, 19), &V3c::new(29, 15, 29))
+            .expect("boxtree clear");
+        for i in 0..10 {
+            tree.insert(&V3c::new(4 + i, 12 + i, 8 + i), &red)
+                .expect("boxtree insert");
+            tree.insert(&V3c::new(2 + i, 24, 20 + i), &red)
+                .expect("boxtree insert");
+        }
+        tree
+    }
+
+    #[test]
+    fn test_connected_components_by_connectivity() {
+        let red: Albedo = 0xFF0000FF.into();
+        let mut tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+        tree.insert_box(&V3c::new(0, 0, 0), &V3c::new(16, 4, 16), &red)
+            .expect("boxtree insert");
+        tree.insert_box(&V3c::new(20, 10, 20), &V3c::new(24, 14, 24), &red)
+            .expect("boxtree insert");
+        // Sharing only an edge with the second box
+        tree.insert(&V3c::new(19, 9, 22), &red)
+            .expect("boxtree insert");
+        // Sharing only a corner with the second box
+        tree.insert(&V3c::new(24, 14, 24), &red)
+            .expect("boxtree insert");
+
+        let min_position = V3c::new(0, 0, 0);
+        let max_position = V3c::new(32, 32, 32);
+        let ground = (&V3c::new(0, 0, 0), &V3c::new(32, 1, 32));
+        let counts_for = |connectivity| {
+            let mut counts: Vec<usize> = tree
+                .connected_components(&min_position, &max_position, connectivity, Some(ground))
+                .expect("connected_components to work")
+                .iter()
+                .map(|component| component.voxel_count)
+                .collect();
+            counts.sort();
+            counts
+        };
+        assert_eq!(counts_for(Connectivity::Face), vec![1, 1, 64, 1024]);
+        assert_eq!(counts_for(Connectivity::Edge), vec![1, 65, 1024]);
+        assert_eq!(counts_for(Connectivity::Vertex), vec![66, 1024]);
+
+        let mut components = tree
+            .connected_components(
+                &min_position,
+                &max_position,
+                Connectivity::Vertex,
+                Some(ground),
+            )
+            .expect("connected_components to work");
+        components.sort_by_key(|component| component.voxel_count);
+        assert_eq!(
+            components,
+            vec![
+                Component {
+                    voxel_count: 66,
+                    min_position: V3c::new(19, 9, 20),
+                    max_position: V3c::new(25, 15, 25),
+                    touches_anchor: false,
+                },
+                Component {
+                    voxel_count: 1024,
+                    min_position: V3c::new(0, 0, 0),
+                    max_position: V3c::new(16, 4, 16),
+                    touches_anchor: true,
+                },
+            ]
+        );
+
+        // Only the part of the components inside the region is considered
+        let components = tree
+            .connected_components(
+                &V3c::new(8, 2, 8),
+                &V3c::new(22, 12, 22),
+                Connectivity::Face,
+                None,
+            )
+            .expect("connected_components to work");
+        let mut counts: Vec<usize> = components.iter().map(|c| c.voxel_count).collect();
+        counts.sort();
+        assert_eq!(counts, vec![8, 128]);
+        assert!(components.iter().all(|component| !component.touches_anchor));
+
+        assert!(tree
+            .connected_components(
+                &min_position,
+                &V3c::new(33, 32, 32),
+                Connectivity::Face,
+                None
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn test_connected_components_match_voxel_queries() {
+        let tree = structured_tree();
+        let size = tree.get_size();
+        for connectivity in [Connectivity::Face, Connectivity::Edge, Connectivity::Vertex] {
+            let components = tree
+                .connected_components(
+                    &V3c::new(0, 0, 0),
+                    &V3c::new(size, size, size),
+                    connectivity,
+                    None,
+                )
+                .expect("connected_components to work");
+
+            let mut visited = HashSet::new();
+            let mut expected_counts = Vec::new();
+            for x in 0..size {
+                for y in 0..size {
+                    for z in 0..size {
+                        if visited.contains(&(x, y, z)) || tree.get(&V3c::new(x, y, z)).is_none() {
+                            continue;
+                        }
+                        let component =
+                            reachable_voxels(&tree, V3c::new(x, y, z), connectivity, |entry| {
+                                !entry.is_none()
+                            });
+                        expected_counts.push(component.len());
+                        visited.extend(component);
+                    }
+                }
+            }
+            let mut counts: Vec<usize> = components.iter().map(|c| c.voxel_count).collect();
+            counts.sort();
+            expected_counts.sort();
+            assert_eq!(counts, expected_counts, "Mismatch for {:?}", connectivity);
+        }
+    }
+
+    #[test]
+    fn test_flood_fill() {
+        let red: Albedo = 0xFF0000FF.into();
+        let green: Albedo = 0x00FF00FF.into();
+        let blue: Albedo = 0x0000FFFF.into();
+        let mut tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+        tree.insert_box(&V3c::new(0, 0, 0), &V3c::new(8, 8, 8), &red)
+            .expect("boxtree insert");
+        tree.insert(&V3c::new(3, 3, 3), &green)
+            .expect("boxtree insert");
+
+        let is_red = |entry: &BoxTreeEntry<u32>| *entry == (&red).into();
+        let filled = tree
+            .flood_fill(&V3c::new(0, 0, 0), is_red, &blue)
+            .expect("flood_fill to work");
+        assert_eq!(filled, 511);
+        assert!(tree.get(&V3c::new(7, 7, 7)) == (&blue).into());
+        assert!(tree.get(&V3c::new(3, 3, 3)) == (&green).into());
+        assert!(tree.get(&V3c::new(8, 0, 0)) == BoxTreeEntry::Empty);
+
+        // The seed does not match the predicate
+        assert_eq!(
+            tree.flood_fill(&V3c::new(0, 0, 0), is_red, &green)
+                .expect("flood_fill to work"),
+            0
+        );
+
+        // Empty space around the box
+        let filled = tree
+            .flood_fill(&V3c::new(31, 31, 31), |entry| entry.is_none(), &green)
+            .expect("flood_fill to work");
+        assert_eq!(filled, 32 * 32 * 32 - 512);
+        assert!(tree.get(&V3c::new(8, 0, 0)) == (&green).into());
+
+        // Erasing
+        let filled = tree
+            .flood_fill(
+                &V3c::new(0, 0, 0),
+                |entry| *entry == (&blue).into(),
+                BoxTreeEntry::Empty,
+            )
+            .expect("flood_fill to work");
+        assert_eq!(filled, 511);
+        assert!(tree.get(&V3c::new(0, 0, 0)) == BoxTreeEntry::Empty);
+        assert!(tree.validate().is_ok());
+
+        assert!(tree
+            .flood_fill(&V3c::new(32, 0, 0), |_| true, &red)
+            .is_err());
+    }
+
+    #[test]
+    fn test_flood_fill_is_a_single_edit() {
+        let red: Albedo = 0xFF0000FF.into();
+        let green: Albedo = 0x00FF00FF.into();
+        let mut tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+        // A line of voxels spanning several bricks, each filled as a separate block
+        tree.insert_box(&V3c::new(0, 5, 5), &V3c::new(27, 6, 6), &red)
+            .expect("insert_box to work");
+        tree.enable_journal(JournalConfig::default());
+        let generation = tree.generation();
+
+        let is_red = |entry: &BoxTreeEntry<u32>| *entry == (&red).into();
+        let filled = tree
+            .flood_fill(&V3c::new(0, 5, 5), is_red, &green)
+            .expect("flood_fill to work");
+        assert_eq!(filled, 27);
+        assert_eq!(tree.generation(), generation + 1);
+        assert!((0..27).all(|x| tree.get(&V3c::new(x, 5, 5)) == (&green).into()));
+
+        assert!(tree.undo().expect("undo to work"));
+        assert!(!tree.can_undo());
+        assert!((0..27).all(|x| tree.get(&V3c::new(x, 5, 5)) == (&red).into()));
+        assert!(tree.redo().expect("redo to work"));
+        assert!((0..27).all(|x| tree.get(&V3c::new(x, 5, 5)) == (&green).into()));
+    }
+
+    #[test]
+    fn test_flood_fill_matches_voxel_queries() {
+        let mut tree = structured_tree();
+        let green: Albedo = 0x00FF00FF.into();
+        let seed = (0..32)
+            .map(|x| V3c::new(x, 31, 31))
+            .find(|position| tree.get(position).is_none())
+            .expect("Expected an empty voxel");
+        let expected = reachable_voxels(&tree, seed, Connectivity::Face, |entry| entry.is_none());
+        let filled = tree
+            .flood_fill(&seed, |entry| entry.is_none(), &green)
+            .expect("flood_fill to work");
+        assert_eq!(filled, expected.len());
+        for (x, y, z) in expected {
+            assert!(tree.get(&V3c::new(x, y, z)) == (&green).into());
+        }
+
+        // The void inside the shell is not reached
+        assert!(tree.get(&V3c::new(24, 10, 24)).is_none());
+    }
+}
+
+mod accessor_tests {
+    use crate::boxtree::{Albedo, Axis, BoxTree, BoxTreeEntry, Connectivity, V3c};
+
+    /// A tree where reads crossing a brick or node border see different content: in the lower half
+    /// every brick holds its own data and nodes alternate in color, while the upper half has
+    /// parted bricks along a line crossing the borders and a uniform node at the tree bounds
+    fn border_tree() -> BoxTree {
+        let red: Albedo = 0xFF0000FF.into();
+        let green: Albedo = 0x00FF00FF.into();
+        let blue: Albedo = 0x0000FFFF.into();
+        let mut tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+        for bx in 0..16 {
+            for by in 0..8 {
+                for bz in 0..16 {
+                    if (bx + by + bz) % 3 == 0 {
+                        continue;
+                    }
+                    let color = if (bx / 4 + by / 4 + bz / 4) % 2 == 0 {
+                        &red
+                    } else {
+                        &green
+                    };
+                    let data = bx + by * 16 + bz * 256;
+                    tree.insert_box(
+                        &V3c::new(bx * 2, by * 2, bz * 2),
+                        &V3c::new(bx * 2 + 2, by * 2 + 2, bz * 2 + 2),
+                        (color, &data),
+                    )
+                    .expect("boxtree insert");
+                }
+            }
+        }
+        for i in 0..32 {
+            tree.insert(&V3c::new(i, 16 + i % 3, 31 - i), (&blue, &i))
+                .expect("boxtree insert");
+        }
+        tree.insert_box(&V3c::new(24, 24, 0), &V3c::new(32, 32, 8), &blue)
+            .expect("boxtree insert");
+        tree
+    }
+
+    #[test]
+    fn test_accessor_get_matches_tree() {
+        let tree = border_tree();
+        let accessor = tree.accessor();
+        for x in 0..32 {
+            for y in 0..32 {
+                for z in 0..32 {
+                    let position = V3c::new(x, y, z);
+                    assert!(accessor.get(&position) == tree.get(&position));
+                }
+            }
+        }
+
+        // Scattered queries
+        for i in 0..2000_u32 {
+            let position = V3c::new(i * 17 % 32, i * 29 % 32, i * 11 % 32);
+            assert!(accessor.get(&position) == tree.get(&position));
+        }
+        assert!(accessor.get(&V3c::new(32, 0, 0)) == BoxTreeEntry::Empty);
+    }
+
+    #[test]
+    fn test_accessor_get_neighbors() {
+        let tree = border_tree();
+        let accessor = tree.accessor();
+        for (connectivity, count) in [
+            (Connectivity::Face, 6),
+            (Connectivity::Edge, 18),
+            (Connectivity::Vertex, 26),
+        ] {
+            let offsets: Vec<V3c<i32>> = connectivity.neighbor_offsets().collect();
+            assert_eq!(offsets.len(), count);
+            for position in [V3c::new(5, 6, 7), V3c::new(0, 0, 0), V3c::new(31, 16, 0)] {
+                let neighbors = accessor.get_neighbors(&position, connectivity);
+                assert_eq!(neighbors.len(), count);
+                for (offset, neighbor) in offsets.iter().zip(neighbors.iter()) {
+                    let neighbor_position = V3c::new(
+                        position.x as i32 + offset.x,
+                        position.y as i32 + offset.y,
+                        position.z as i32 + offset.z,
+                    );
+                    if neighbor_position.x < 0
+                        || neighbor_position.y < 0
+                        || neighbor_position.z < 0
+                        || neighbor_position.x >= 32
+                        || neighbor_position.y >= 32
+                        || neighbor_position.z >= 32
+                    {
+                        assert!(neighbor.is_none());
+                    } else {
+                        let neighbor_position = V3c::<u32>::from(neighbor_position);
+                        assert!(*neighbor == Some(tree.get(&neighbor_position)));
+                    }
+                }
+            }
+        }
+        assert_eq!(
+            accessor
+                .get_neighbors(&V3c::new(0, 0, 0), Connectivity::Face)
+                .iter()
+                .filter(|neighbor| neighbor.is_some())
+                .count(),
+            3
+        );
+    }
+
+    #[test]
+    fn test_accessor_cursor_stepping() {
+        let tree = border_tree();
+        let mut accessor = tree.accessor();
+        assert_eq!(accessor.cursor(), V3c::new(0, 0, 0));
+        accessor
+            .move_to(&V3c::new(0, 9, 4))
+            .expect("Expected to move cursor");
+        for x in 1..32 {
+            let entry = accessor.step(Axis::X, 1).expect("Expected to step");
+            assert!(entry == tree.get(&V3c::new(x, 9, 4)));
+        }
+        assert!(accessor.step(Axis::X, 1).is_none());
+        assert_eq!(accessor.cursor(), V3c::new(31, 9, 4));
+
+        assert!(accessor.step(Axis::Z, -5).is_none());
+        assert!(accessor.step(Axis::Z, -4).is_some());
+        assert!(accessor.step(Axis::Y, 3).is_some());
+        assert_eq!(accessor.cursor(), V3c::new(31, 12, 0));
+        assert!(accessor.get_at_cursor() == tree.get(&V3c::new(31, 12, 0)));
+        assert!(accessor.move_to(&V3c::new(0, 32, 0)).is_err());
+        assert_eq!(accessor.cursor(), V3c::new(31, 12, 0));
+    }
+
+    #[test]
+    fn test_accessor_updates() {
+        let red: Albedo = 0xFF0000FF.into();
+        let blue: Albedo = 0x0000FFFF.into();
+        let mut tree = border_tree();
+        let mut accessor = tree.accessor_mut();
+        assert!(accessor.get(&V3c::new(1, 1, 2)) == BoxTreeEntry::Complex(&red, &256));
+        accessor
+            .insert(&V3c::new(1, 1, 2), &blue)
+            .expect("accessor insert");
+        assert!(accessor.get(&V3c::new(1, 1, 2)) == (&blue).into());
+        assert!(accessor.get(&V3c::new(1, 1, 3)) == BoxTreeEntry::Complex(&red, &256));
+        accessor.clear(&V3c::new(1, 1, 3)).expect("accessor clear");
+        assert!(accessor.get(&V3c::new(1, 1, 3)) == BoxTreeEntry::Empty);
+        accessor
+            .insert(&V3c::new(30, 30, 30), &blue)
+            .expect("accessor insert");
+        assert!(accessor.get(&V3c::new(30, 30, 30)) == (&blue).into());
+        assert!(accessor.insert(&V3c::new(32, 0, 0), &blue).is_err());
+
+        assert!(tree.get(&V3c::new(1, 1, 2)) == (&blue).into());
+        assert!(tree.get(&V3c::new(1, 1, 3)) == BoxTreeEntry::Empty);
+        assert!(tree.validate().is_ok());
+    }
+
+    #[test]
+    fn test_accessor_updates_match_tree() {
+        let blue: Albedo = 0x0000FFFF.into();
+        let mut tree = border_tree();
+        tree.albedo_mip_map_resampling_strategy()
+            .switch_albedo_mip_maps(true);
+        let mut accessed_tree = tree.clone();
+
+        // Fill a brick voxel by voxel, so it is simplified, then clear parts of it again
+        let positions = (0..2)
+            .flat_map(|x| {
+                (0..2).flat_map(move |y| (0..2).map(move |z| V3c::new(x + 20, y + 20, z + 20)))
+            })
+            .collect::<Vec<_>>();
+        let mut accessor = accessed_tree.accessor_mut();
+        for position in positions.iter() {
+            tree.insert(position, &blue).expect("boxtree insert");
+            accessor.insert(position, &blue).expect("accessor insert");
+        }
+        for position in positions.iter().step_by(3) {
+            tree.clear(position).expect("boxtree clear");
+            accessor.clear(position).expect("accessor clear");
+            assert!(accessor.get(position) == BoxTreeEntry::Empty);
+        }
+
+        // Updates keep the cached nodes
+        assert!(accessor.path.borrow().len() > 1);
+        for x in 0..32 {
+            for y in 0..32 {
+                for z in 0..32 {
+                    assert!(
+                        tree.get(&V3c::new(x, y, z)) == accessor.get(&V3c::new(x, y, z)),
+                        "Hit mismatch at {:?}",
+                        (x, y, z)
+                    );
+                }
+            }
+        }
+        assert!(accessed_tree.validate().is_ok());
+        assert_eq!(
+            tree.node_mips[BoxTree::<u32>::ROOT_NODE_KEY as usize],
+            accessed_tree.node_mips[BoxTree::<u32>::ROOT_NODE_KEY as usize]
+        );
+    }
+}
+
+mod distance_tests {
+    use crate::{
+        boxtree::{distance::empty_sectants_box, Albedo, BoxTree, DistanceMetric, V3c},
+        spatial::math::flat_projection,
+    };
+
+    fn scattered_tree() -> (BoxTree, Vec<V3c<u32>>) {
+        let red: Albedo = 0xFF0000FF.into();
+        let mut tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+        let mut occupied = Vec::new();
+        for x in 0..32 {
+            for y in 0..32 {
+                for z in 0..32 {
+                    if (x * 7 + y * 13 + z * 5) % 97 == 0 {
+                        tree.insert(&V3c::new(x, y, z), &red)
+                            .expect("boxtree insert");
+                        occupied.push(V3c::new(x, y, z));
+                    }
+                }
+            }
+        }
+        (tree, occupied)
+    }
+
+    fn nearest_distance(occupied: &[V3c<u32>], position: V3c<u32>) -> f32 {
+        occupied
+            .iter()
+            .map(|voxel| (V3c::<f32>::from(*voxel) - V3c::<f32>::from(position)).length())
+            .fold(f32::INFINITY, f32::min)
+    }
+
+    #[test]
+    fn test_distance_field_matches_nearest_voxel() {
+        let (tree, occupied) = scattered_tree();
+        let min_position = V3c::new(3, 5, 7);
+        let max_position = V3c::new(20, 18, 29);
+        let euclidean = tree
+            .distance_field(&min_position, &max_position, 6, DistanceMetric::Euclidean)
+            .expect("distance_field to work");
+        let chamfer = tree
+            .distance_field(&min_position, &max_position, 6, DistanceMetric::Chamfer)
+            .expect("distance_field to work");
+        assert_eq!(euclidean.dimensions, V3c::new(17, 13, 22));
+        for x in min_position.x..max_position.x {
+            for y in min_position.y..max_position.y {
+                for z in min_position.z..max_position.z {
+                    let position = V3c::new(x, y, z);
+                    let expected = nearest_distance(&occupied, position).min(6.);
+                    let distance = euclidean.get(&position).unwrap();
+                    assert!((distance - expected).abs() < 0.001);
+                    if occupied.contains(&position) {
+                        assert_eq!(distance, 0.);
+                    }
+                    let distance = chamfer.get(&position).unwrap();
+                    assert!(distance >= expected - 0.001);
+                    assert!(distance <= expected * 1.13 + 0.001);
+                }
+            }
+        }
+        assert_eq!(euclidean.get(&V3c::new(2, 5, 7)), None);
+        assert_eq!(euclidean.get(&V3c::new(3, 18, 7)), None);
+    }
+
+    #[test]
+    fn test_distance_field_stores_near_bricks() {
+        let red: Albedo = 0xFF0000FF.into();
+        let mut tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+        let empty_field = tree
+            .distance_field(
+                &V3c::new(0, 0, 0),
+                &V3c::new(32, 32, 32),
+                4,
+                DistanceMetric::Euclidean,
+            )
+            .expect("distance_field to work");
+        assert_eq!(empty_field.stored_brick_count(), 0);
+        assert!(empty_field
+            .to_dense()
+            .iter()
+            .all(|distance| *distance == 4.));
+
+        tree.insert(&V3c::new(5, 5, 5), &red)
+            .expect("boxtree insert");
+        let field = tree
+            .distance_field(
+                &V3c::new(0, 0, 0),
+                &V3c::new(32, 32, 32),
+                4,
+                DistanceMetric::Euclidean,
+            )
+            .expect("distance_field to work");
+        assert!(0 < field.stored_brick_count());
+        assert!(field.stored_brick_count() <= 125);
+        assert_eq!(field.get(&V3c::new(5, 5, 5)), Some(0.));
+        assert_eq!(field.get(&V3c::new(5, 8, 5)), Some(3.));
+        assert_eq!(field.get(&V3c::new(20, 20, 20)), Some(4.));
+
+        let dense = field.to_dense();
+        assert_eq!(dense.len(), 32 * 32 * 32);
+        assert_eq!(dense[5 + 7 * 32 + 5 * 32 * 32], 2.);
+
+        // Voxels outside the region are considered
+        let field = tree
+            .distance_field(
+                &V3c::new(7, 5, 5),
+                &V3c::new(12, 6, 6),
+                4,
+                DistanceMetric::Euclidean,
+            )
+            .expect("distance_field to work");
+        assert_eq!(field.to_dense(), vec![2., 3., 4., 4., 4.]);
+
+        assert!(tree
+            .distance_field(
+                &V3c::new(0, 0, 0),
+                &V3c::new(32, 33, 32),
+                4,
+                DistanceMetric::Euclidean,
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn test_empty_space_distance_is_conservative() {
+        let (tree, occupied) = scattered_tree();
+        for x in 0..64 {
+            for y in 0..64 {
+                for z in (0..64).step_by(3) {
+                    let point = V3c::new(x as f32 + 0.25, y as f32 + 0.25, z as f32 + 0.25) / 2.;
+                    let distance = tree
+                        .empty_space_distance(&point)
+                        .expect("Expected point to be inside the tree");
+                    let nearest = occupied
+                        .iter()
+                        .map(|voxel| {
+                            let voxel = V3c::<f32>::from(*voxel);
+                            let outside = V3c::new(
+                                (voxel.x - point.x).max(point.x - voxel.x - 1.).max(0.),
+                                (voxel.y - point.y).max(point.y - voxel.y - 1.).max(0.),
+                                (voxel.z - point.z).max(point.z - voxel.z - 1.).max(0.),
+                            );
+                            outside.length()
+                        })
+                        .fold(f32::INFINITY, f32::min);
+                    assert!(distance <= nearest);
+                }
+            }
+        }
+        assert_eq!(
+            tree.empty_space_distance(&V3c::new(0.5, 0.5, 0.5)),
+            Some(0.)
+        );
+        assert_eq!(tree.empty_space_distance(&V3c::new(32., 1., 1.)), None);
+        assert_eq!(tree.empty_space_distance(&V3c::new(-1., 1., 1.)), None);
+    }
+
+    #[test]
+    fn test_empty_sectants_box() {
+        assert_eq!(empty_sectants_box(0x01, 0), None);
+        assert_eq!(
+            empty_sectants_box(0x01, 63),
+            Some((V3c::new(1, 1, 1), V3c::new(3, 3, 3)))
+        );
+        assert_eq!(
+            empty_sectants_box(0, 21),
+            Some((V3c::new(0, 0, 0), V3c::new(3, 3, 3)))
+        );
+        // The box stops growing at the first occupied child around it
+        let occupied_bits = 0x01 << flat_projection(3, 1, 1, 4);
+        assert_eq!(
+            empty_sectants_box(occupied_bits, flat_projection(2, 1, 1, 4) as u8),
+            Some((V3c::new(2, 1, 1), V3c::new(2, 1, 1)))
+        );
+        assert_eq!(
+            empty_sectants_box(occupied_bits, flat_projection(1, 1, 1, 4) as u8),
+            Some((V3c::new(0, 0, 0), V3c::new(2, 2, 2)))
+        );
+        assert_eq!(
+            empty_sectants_box(occupied_bits, flat_projection(1, 2, 2, 4) as u8),
+            Some((V3c::new(0, 1, 1), V3c::new(2, 3, 3)))
+        );
+    }
+
+    #[test]
+    fn test_empty_space_distance_steps_over_empty_nodes() {
+        let red: Albedo = 0xFF0000FF.into();
+        let mut tree: BoxTree = BoxTree::new(128, 2).ok().unwrap();
+        assert_eq!(
+            tree.empty_space_distance(&V3c::new(20., 30., 40.)),
+            Some(20.)
+        );
+
+        tree.insert(&V3c::new(0, 0, 0), &red)
+            .expect("boxtree insert");
+        let distance = tree
+            .empty_space_distance(&V3c::new(40., 40., 40.))
+            .expect("Expected point to be inside the tree");
+        assert!(distance >= 8.);
+        let distance = tree
+            .empty_space_distance(&V3c::new(2.5, 1.5, 1.5))
+            .expect("Expected point to be inside the tree");
+        assert!(0. < distance && distance <= 1.5);
+    }
 }
@@ -1,5 +1,16 @@
-use crate::{boxtree::BOX_NODE_CHILDREN_COUNT, object_pool::ObjectPool};
-use std::{collections::HashMap, error::Error, hash::Hash};
+use crate::{
+    boxtree::BOX_NODE_CHILDREN_COUNT,
+    object_pool::ObjectPool,
+    shared::{Shared, SharedVec},
+    spatial::{math::vector::V3c, Cube},
+};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet, VecDeque},
+    error::Error,
+    hash::Hash,
+    ops::Deref,
+};
 
 #[cfg(feature = "bytecode")]
 use bendy::{decoding::FromBencode, encoding::ToBencode};
@@ -18,6 +29,58 @@ pub enum OctreeError {
 
     /// Octree query was attempted with an invalid position
     InvalidPosition { x: u32, y: u32, z: u32 },
+
+    /// Octree resize was attempted to a size, which can not contain the stored voxels
+    ContentOutOfBounds(u32),
+
+    /// Octree update was attempted with a new color or data, while the palette is full
+    PaletteFull,
+}
+
+/// A structural problem of a boxtree, found by @BoxTree::validate
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValidationIssue {
+    /// The root node is missing from the node pool
+    MissingRoot,
+
+    /// The node connections or the MIPs are stored for fewer nodes, than the node pool contains
+    StorageSizeMismatch {
+        nodes: usize,
+        node_children: usize,
+        node_mips: usize,
+    },
+
+    /// The stored children of the node do not fit its content
+    ChildrenMismatch { node_key: usize },
+
+    /// The node references a child, which is not valid inside the node pool
+    InvalidChild {
+        node_key: usize,
+        sectant: u8,
+        child_key: usize,
+    },
+
+    /// The node is referenced as a child by more than one node
+    SharedNode { node_key: usize },
+
+    /// The stored occupancy of the node does not match its children or bricks
+    OccupancyMismatch {
+        node_key: usize,
+        stored: u64,
+        expected: u64,
+    },
+
+    /// A brick or MIP of the node has an unexpected number of voxels
+    InvalidBrickSize { node_key: usize, size: usize },
+
+    /// A voxel or MIP of the node references an entry outside of the color palette
+    ColorIndexOutOfRange { node_key: usize, index: usize },
+
+    /// A voxel or MIP of the node references an entry outside of the data palette
+    DataIndexOutOfRange { node_key: usize, index: usize },
+
+    /// The node has no MIP while MIPs are enabled
+    MipMismatch { node_key: usize },
 }
 
 /// An entry for stored voxel data
@@ -46,7 +109,8 @@ where
     Empty,
 
     /// Brick is an NxNxN matrix, size is determined by the parent entity
-    Parted(Vec<T>),
+    /// The matrix is shared between copies of the tree until one of them modifies it
+    Parted(Shared<Vec<T>>),
 
     /// Brick is a single item T, which takes up the entirety of the brick
     Solid(T),
@@ -150,9 +214,419 @@ pub enum MIPResamplingMethods {
     PosterizeBD(f32),
 }
 
+/// Decides how the voxels of a pasted boxtree are combined with the voxels of the target boxtree
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum PasteMode {
+    /// Every voxel inside the pasted area is replaced, empty source voxels clear the target
+    #[default]
+    Overwrite,
+
+    /// Source voxels are only written into target voxels which are empty
+    OnlyIntoEmpty,
+
+    /// Only source voxels containing data are written, overwriting the target
+    SkipEmptySource,
+}
+
+/// Decides what happens when a new color is inserted while the color palette is full
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum PaletteOverflowStrategy {
+    /// The update is rejected with @OctreeError::PaletteFull
+    #[default]
+    Reject,
+
+    /// The closest color already in the palette is used instead of the new color
+    NearestColor,
+
+    /// Colors of the palette are re-quantized to a lower precision, merging similar colors to make space
+    /// Every voxel and MIP is updated to the re-quantized colors, so GPU views of the tree need to be recreated
+    Requantize,
+}
+
+/// The axes of the boxtree coordinate space
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Axis {
+    X,
+    Y,
+    Z,
+}
+
+/// Structural and memory statistics of a boxtree, see @BoxTree::stats
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct BoxTreeStats {
+    /// The number of nodes reachable from the root, for each node content type
+    pub nodes: NodeCounts,
+
+    /// The number of nodes reachable from the root at each depth, the root being at depth 0
+    pub nodes_per_depth: Vec<NodeCounts>,
+
+    /// The number of bricks stored inside leaf nodes, for each brick type
+    pub bricks: BrickCounts,
+
+    /// The number of MIP bricks of the reachable nodes, for each brick type
+    pub mips: BrickCounts,
+
+    /// The usage of the color palette
+    pub color_palette: PaletteUsage,
+
+    /// The usage of the data palette
+    pub data_palette: PaletteUsage,
+
+    /// The number of unused slots inside the node pool, which are reused by later insertions
+    pub free_node_slots: usize,
+
+    /// The estimated heap memory used by each part of the tree
+    pub memory: MemoryUsage,
+}
+
+/// The number of nodes for each node content type
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct NodeCounts {
+    pub empty: usize,
+    pub internal: usize,
+    pub leaf: usize,
+    pub uniform_leaf: usize,
+}
+
+/// The number of bricks for each brick type
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct BrickCounts {
+    pub empty: usize,
+    pub solid: usize,
+    pub parted: usize,
+}
+
+/// The number of palette entries referenced by voxels or MIPs, and the number of entries not referenced
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PaletteUsage {
+    pub used: usize,
+    pub unused: usize,
+}
+
+/// Estimated heap memory usage in bytes, including the reserved but unused capacity of the containers
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryUsage {
+    /// The node pool, including the node contents stored inline
+    pub nodes: usize,
+
+    /// The connections between the nodes
+    pub node_children: usize,
+
+    /// The voxels of parted bricks inside leaf nodes
+    pub bricks: usize,
+
+    /// The MIP bricks, including the voxels of parted MIPs
+    pub mips: usize,
+
+    /// The color and data palettes, including their lookup tables
+    pub palettes: usize,
+}
+
+/// Limits and coalescing of the edit journal, see @BoxTree::enable_journal
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JournalConfig {
+    /// The estimated memory the undoable transactions may use in bytes, the oldest transactions are dropped above it
+    /// The latest transaction is always kept, even if it is larger than the limit
+    pub memory_limit: usize,
+
+    /// The number of transactions which can be undone at most, the latest transaction is always kept
+    pub transaction_limit: usize,
+
+    /// Edits outside of explicit transactions are merged into the previous such transaction,
+    /// until it contains this many edits
+    pub coalesced_edits: usize,
+}
+
+/// A part of the tree before it was edited
+#[derive(Clone)]
+pub(crate) struct JournalRecord<T>
+where
+    T: Default + Clone + Eq + Hash,
+{
+    /// The recorded contents
+    pub(crate) content: RecordedContent<T>,
+
+    /// The estimated memory used by the record in bytes
+    pub(crate) memory: usize,
+}
+
+/// The contents of a part of the tree stored in a @JournalRecord
+#[derive(Clone)]
+pub(crate) enum RecordedContent<T>
+where
+    T: Default + Clone + Eq + Hash,
+{
+    /// The voxels of an area of the tree
+    Area {
+        /// The position of the area inside the tree
+        position: V3c<u32>,
+
+        /// The voxels of the area, the size of the tree is the size of the area
+        voxels: Box<BoxTree<T>>,
+    },
+
+    /// The color and data palettes of the tree
+    Palettes {
+        color_palette: Shared<Vec<Albedo>>,
+        data_palette: Shared<Vec<T>>,
+
+        /// The index inside the recorded color palette for each color index used after the edit,
+        /// in case the edit rewrote the stored color indices
+        color_map: Option<Vec<u16>>,
+
+        /// The index inside the recorded data palette for each data index used after the edit,
+        /// in case the edit rewrote the stored data indices
+        data_map: Option<Vec<u16>>,
+    },
+}
+
+/// Edits undone and redone together
+#[derive(Clone, Default)]
+pub(crate) struct JournalTransaction<T>
+where
+    T: Default + Clone + Eq + Hash,
+{
+    /// The areas to restore, in the order they were recorded
+    pub(crate) records: Vec<JournalRecord<T>>,
+
+    /// The position and size of each recorded area, so the same area is only recorded once
+    pub(crate) recorded_areas: HashSet<(u32, u32, u32, u32)>,
+
+    /// The number of edits in the transaction
+    pub(crate) edit_count: usize,
+
+    /// The estimated memory used by the records of the transaction in bytes
+    pub(crate) memory: usize,
+
+    /// True if no more edits can be merged into the transaction
+    pub(crate) sealed: bool,
+}
+
+/// Records the previous contents of the edited areas of a tree, grouped into transactions
+#[derive(Clone)]
+pub(crate) struct EditJournal<T>
+where
+    T: Default + Clone + Eq + Hash,
+{
+    pub(crate) config: JournalConfig,
+
+    /// Transactions which can be undone, the latest one is at the back
+    pub(crate) undo_stack: VecDeque<JournalTransaction<T>>,
+
+    /// Transactions which can be redone, the latest undone one is at the back
+    pub(crate) redo_stack: Vec<JournalTransaction<T>>,
+
+    /// The transaction started by @BoxTree::begin_transaction, collecting the edits until it ends
+    pub(crate) open_transaction: Option<JournalTransaction<T>>,
+
+    /// The estimated memory used by the undoable transactions in bytes
+    pub(crate) memory: usize,
+}
+
+/// The parts of a tree modified since a given generation, see @BoxTree::changes_since
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BoxTreeChanges {
+    /// The current generation of the tree, to be used for the next query
+    pub generation: u64,
+
+    /// True if node keys were renumbered or the size of the tree changed since the queried generation
+    /// In this case every node, brick and MIP of the tree is listed
+    pub layout_changed: bool,
+
+    /// The keys of the modified nodes, in increasing order
+    pub nodes: Vec<usize>,
+
+    /// The modified bricks by the key of their leaf node and their sectant inside it, in increasing order
+    /// Uniform leaf nodes have their brick at sectant 0
+    pub bricks: Vec<(usize, u8)>,
+
+    /// The keys of the nodes with modified MIPs, in increasing order
+    pub mips: Vec<usize>,
+
+    /// Axis aligned boxes containing every modified voxel, given by their minimum and (exclusive) maximum position
+    pub regions: Vec<(V3c<u32>, V3c<u32>)>,
+}
+
+/// The palette indices of every voxel inside a region of a tree, see @BoxTree::to_dense_indices
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DenseRegion<T> {
+    /// The number of voxels in the region along each axis
+    pub dimensions: V3c<u32>,
+
+    /// The color and data palette indices of each voxel, None for missing components
+    /// The voxel at (x,y,z) relative to the region is at `x + y * dimensions.x + z * dimensions.x * dimensions.y`
+    pub voxels: Vec<(Option<u16>, Option<u16>)>,
+
+    /// The colors referenced by the color indices
+    pub color_palette: Vec<Albedo>,
+
+    /// The data referenced by the data indices
+    pub data_palette: Vec<T>,
+}
+
+/// The neighbors each voxel is connected to, see @BoxTree::connected_components
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Connectivity {
+    /// Voxels sharing a face are connected: 6 neighbors
+    #[default]
+    Face,
+
+    /// Voxels sharing a face or an edge are connected: 18 neighbors
+    Edge,
+
+    /// Voxels sharing a face, an edge or a corner are connected: 26 neighbors
+    Vertex,
+}
+
+/// A group of connected voxels, see @BoxTree::connected_components
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Component {
+    /// The number of voxels in the component
+    pub voxel_count: usize,
+
+    /// The minimum position of the bounding box of the component
+    pub min_position: V3c<u32>,
+
+    /// The (exclusive) maximum position of the bounding box of the component
+    pub max_position: V3c<u32>,
+
+    /// True if any voxel of the component is inside the anchor region
+    pub touches_anchor: bool,
+}
+
+/// The way distances are measured by @BoxTree::distance_field
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DistanceMetric {
+    /// The exact straight line distance
+    #[default]
+    Euclidean,
+
+    /// The length of the shortest path through neighboring voxels, approximating the straight line distance
+    /// Steps to face, edge and corner neighbors are 1, sqrt(2) and sqrt(3) long, so the distance is overestimated
+    /// by at most 13%, but it is faster to calculate.
+    Chamfer,
+}
+
+/// The distance of each voxel inside a region to the nearest occupied voxel, see @BoxTree::distance_field
+/// Distances are stored in bricks, bricks where every voxel is at least the maximum distance away are not stored.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DistanceField {
+    /// The minimum position of the region
+    pub min_position: V3c<u32>,
+
+    /// The number of voxels in the region along each axis
+    pub dimensions: V3c<u32>,
+
+    /// The number of voxels along each axis of a brick, the first brick starts at the minimum position
+    pub brick_dimension: u32,
+
+    /// The distance every larger distance is clamped to
+    pub max_distance: f32,
+
+    /// The distances inside each brick, None if every distance is the maximum distance
+    /// The brick at (x,y,z) is at `x + y * brick_counts.x + z * brick_counts.x * brick_counts.y`,
+    /// and the voxels inside are ordered the same way.
+    pub(crate) bricks: Vec<Option<Vec<f32>>>,
+}
+
+/// The generation of the latest modification of each node, brick and MIP of a tree
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ChangeTracker {
+    /// Incremented by every modification of the tree
+    pub(crate) generation: u64,
+
+    /// The generation in which node keys were last renumbered
+    pub(crate) layout_generation: u64,
+
+    /// The generation of the latest modification of each node, indexed by node key
+    pub(crate) node_generations: Vec<u64>,
+
+    /// The signature of each node when it was last checked for modifications, indexed by node key; 0 if unknown
+    pub(crate) node_signatures: Vec<u64>,
+
+    /// The generation of the latest modification of each MIP, indexed by node key
+    pub(crate) mip_generations: Vec<u64>,
+
+    /// The generation of the latest modification of each brick, by the key of its node and its sectant
+    pub(crate) brick_generations: HashMap<(usize, u8), u64>,
+
+    /// The modified regions with the generation of their latest modification, oldest first
+    pub(crate) regions: Vec<(u64, V3c<u32>, V3c<u32>)>,
+}
+
 /// A helper object for setting Octree MIP map resampling strategy
 pub struct StrategyUpdater<'a, T: Default + Clone + Eq + Hash>(pub(crate) &'a mut BoxTree<T>);
 
+/// A helper object collecting voxel updates to be applied together to a boxtree
+/// Simplification and MIP map updates are done only once for every affected node, when the batch ends
+pub struct BoxTreeBatch<'a, T: Default + Clone + Eq + Hash> {
+    pub(crate) tree: &'a mut BoxTree<T>,
+
+    /// The requested updates in the order of their submission
+    pub(crate) updates: Vec<BatchUpdate>,
+}
+
+/// A single voxel update inside a batch
+pub(crate) struct BatchUpdate {
+    pub(crate) position: V3c<u32>,
+
+    /// The content to update the voxel with; clears the voxel if it points to empty
+    pub(crate) content: PaletteIndexValues,
+
+    /// Decides if the voxel is overwritten, or only the components present in the content are updated
+    pub(crate) overwrite_if_empty: bool,
+}
+
+/// A helper object reading and updating the voxels of a boxtree, see @BoxTree::accessor
+/// The nodes visited by the latest query are cached, so queries near the previous one continue
+/// from the deepest cached node containing the position instead of walking from the root node again.
+/// Updates are possible if the accessor borrows the tree mutably, see @BoxTree::accessor_mut
+pub struct BoxTreeAccessor<T: Default + Clone + Eq + Hash, R: Deref<Target = BoxTree<T>>> {
+    pub(crate) tree: R,
+
+    /// The node keys and bounds from the root node to the node containing the latest queried position
+    pub(crate) path: RefCell<Vec<(usize, Cube)>>,
+
+    /// The position the accessor steps from
+    pub(crate) cursor: V3c<u32>,
+}
+
+/// An iterator over the voxels containing data inside a boxtree, or a region of it
+/// Yields the position of each voxel along with its content
+pub struct BoxTreeIter<'a, T: Default + Clone + Eq + Hash> {
+    pub(crate) tree: &'a BoxTree<T>,
+
+    /// The minimum position of the iterated region
+    pub(crate) min_position: V3c<u32>,
+
+    /// The (exclusive) maximum position of the iterated region
+    pub(crate) max_position: V3c<u32>,
+
+    /// The parts of the tree yet to be iterated
+    pub(crate) stack: Vec<IterationFrame<'a>>,
+}
+
+/// A part of the tree to be iterated
+pub(crate) enum IterationFrame<'a> {
+    /// A node of the tree, which is yet to be expanded
+    Node { node_key: usize, bounds: Cube },
+
+    /// A brick with different voxels, iterated from the given index
+    Brick {
+        brick: &'a [PaletteIndexValues],
+        bounds: Cube,
+        next_index: usize,
+    },
+
+    /// A range of voxels with the same content, iterated from the given position
+    Range {
+        content: PaletteIndexValues,
+        min_position: V3c<u32>,
+        max_position: V3c<u32>,
+        next_position: V3c<u32>,
+    },
+}
+
 /// Configuration object for storing MIP map strategy
 /// Don't forget to @recalculate_mip after you've enabled it, as it is
 /// only updated on boxtree updates otherwise.
@@ -190,26 +664,45 @@ where
     pub(crate) nodes: ObjectPool<NodeData>,
 
     /// Node Connections
-    pub(crate) node_children: Vec<NodeConnection>,
+    pub(crate) node_children: SharedVec<NodeConnection>,
 
     /// Brick data for each node containing a simplified representation, or all empties if the feature is disabled
-    pub(crate) node_mips: Vec<BrickData<PaletteIndexValues>>,
+    pub(crate) node_mips: SharedVec<BrickData<PaletteIndexValues>>,
 
     /// The albedo colors used by the boxtree. Maximum 65535 colors can be used at once
     /// because of a limitation on GPU raytracing, to spare space index values refering the palettes
     /// are stored on 2 Bytes
-    pub(crate) voxel_color_palette: Vec<Albedo>, // referenced by @nodes
-    pub(crate) voxel_data_palette: Vec<T>, // referenced by @nodes
+    pub(crate) voxel_color_palette: Shared<Vec<Albedo>>, // referenced by @nodes
+    pub(crate) voxel_data_palette: Shared<Vec<T>>, // referenced by @nodes
 
     /// Cache variable to help find colors inside the color palette
-    pub(crate) map_to_color_index_in_palette: HashMap<Albedo, usize>,
+    pub(crate) map_to_color_index_in_palette: Shared<HashMap<Albedo, usize>>,
 
     /// Cache variable to help find user data in the palette
-    pub(crate) map_to_data_index_in_palette: HashMap<T, usize>,
+    pub(crate) map_to_data_index_in_palette: Shared<HashMap<T, usize>>,
 
     /// Feature flag to enable/disable simplification attempts during boxtree update operations
     pub auto_simplify: bool,
 
     /// The stored MIP map strategy
     pub(crate) mip_map_strategy: MIPMapStrategy,
+
+    /// Decides how new colors are stored when the color palette is full; Not serialized
+    /// Data palette overflow is always rejected, as data can not be approximated
+    pub palette_overflow_strategy: PaletteOverflowStrategy,
+
+    /// Nodes with MIPs outdated by in-place palette edits, see @StrategyUpdater::recalculate_outdated_mips; Not serialized
+    pub(crate) outdated_mips: HashSet<usize>,
+
+    /// Incremented whenever existing palette entries are changed, so GPU views upload the whole color palette again
+    pub(crate) palette_revision: u64,
+
+    /// Incremented whenever node keys are renumbered, so GPU views upload the whole tree again
+    pub(crate) node_layout_revision: u64,
+
+    /// Records edits so they can be undone, if enabled; Not serialized
+    pub(crate) journal: Option<Box<EditJournal<T>>>,
+
+    /// Tracks which parts of the tree were modified, see @BoxTree::changes_since; Not serialized
+    pub(crate) changes: ChangeTracker,
 }
@@ -0,0 +1,289 @@
+use crate::{
+    boxtree::{
+        types::{BatchUpdate, BoxTreeBatch, BoxTreeEntry, NodeContent, OctreeError},
+        BoxTree, VoxelData,
+    },
+    object_pool::empty_marker,
+    spatial::{math::vector::V3c, Cube},
+};
+use std::collections::{HashMap, HashSet};
+
+impl<T: VoxelData> BoxTree<T> {
+    /// Applies every update submitted inside the given function together
+    /// Updates are grouped by bricks, so consecutive updates of a brick are written into it directly.
+    /// Every affected node is simplified and has its MIP updated
+    /// only once, after the given function returns. Updates submitted before an error are still applied.
+    /// * `fun` - The function submitting the updates into the batch
+    /// * Returns with the result of the given function
+    pub fn batch<R, F: FnOnce(&mut BoxTreeBatch<T>) -> R>(&mut self, fun: F) -> R {
+        let mut batch = BoxTreeBatch {
+            tree: self,
+            updates: Vec::new(),
+        };
+        let result = fun(&mut batch);
+        batch.apply();
+        result
+    }
+
+    /// Inserts every given entry into its position in a single batch, see @batch
+    /// Entries before an invalid position are still inserted
+    /// * `entries` - The positions, and the data to insert into them
+    pub fn insert_many<'a, E, I>(&mut self, entries: I) -> Result<(), OctreeError>
+    where
+        T: 'a,
+        E: Into<BoxTreeEntry<'a, T>>,
+        I: IntoIterator<Item = (V3c<u32>, E)>,
+    {
+        self.batch(|batch| {
+            for (position, data) in entries {
+                batch.insert(&position, data)?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Applies a single update of a batch, without recording it into the journal
+    /// Updates inside the brick of the previous update are written directly into it,
+    /// when the occupancy of the brick stays the same.
+    /// * `cached_leaf` - The deepest node reached by the previous update, updated to the one reached by this update
+    fn apply_batch_update(
+        &mut self,
+        update: &BatchUpdate,
+        cached_leaf: &mut Option<(usize, Cube)>,
+    ) {
+        let position = V3c::<f32>::from(update.position);
+        if cached_leaf
+            .filter(|(node_key, node_bounds)| {
+                self.nodes.key_is_valid(*node_key) && node_bounds.contains(&position)
+            })
+            .is_some_and(|(node_key, node_bounds)| {
+                self.update_voxel_in_brick(
+                    (node_key, &node_bounds),
+                    &update.position,
+                    update.content,
+                    update.overwrite_if_empty,
+                )
+            })
+        {
+            self.mark_changed_at_lod(&update.position, 1);
+            return;
+        }
+
+        if NodeContent::pix_points_to_empty(
+            &update.content,
+            &self.voxel_color_palette,
+            &self.voxel_data_palette,
+        ) {
+            self.clear_palette_values_at_lod(&update.position, 1);
+        } else {
+            self.insert_palette_value_at_lod(
+                update.overwrite_if_empty,
+                &update.position,
+                1,
+                update.content,
+            );
+        }
+        *cached_leaf = self.node_path_to(&update.position).last().copied();
+    }
+
+    /// Provides the keys and bounds of the nodes from the root to the deepest node containing the given position
+    fn node_path_to(&self, position: &V3c<u32>) -> Vec<(usize, Cube)> {
+        let position = V3c::<f32>::from(*position);
+        let mut path = vec![(
+            Self::ROOT_NODE_KEY as usize,
+            Cube::root_bounds(self.boxtree_size as f32),
+        )];
+        loop {
+            let (node_key, node_bounds) = *path.last().unwrap();
+            if !matches!(self.nodes.get(node_key), NodeContent::Internal(_)) {
+                break;
+            }
+            let sectant = node_bounds.sectant_for(&position);
+            let Some(child_key) = self.valid_child_for(node_key, sectant) else {
+                break;
+            };
+            path.push((child_key, node_bounds.child_bounds_for(sectant)));
+        }
+        path
+    }
+}
+
+impl<T: VoxelData> BoxTreeBatch<'_, T> {
+    /// Submits the given data to be inserted into the given voxel position
+    /// If there is already available data it overwrites it, except if all components are empty
+    /// * `position` - the position to insert the data into, must be contained within the tree
+    pub fn insert<'a, E: Into<BoxTreeEntry<'a, T>>>(
+        &mut self,
+        position: &V3c<u32>,
+        data: E,
+    ) -> Result<(), OctreeError>
+    where
+        T: 'a,
+    {
+        self.submit(true, position, data.into())
+    }
+
+    /// Submits the given data to update the given voxel position with
+    /// Already available data is untouched, if it is not specified in the entry
+    /// * `position` - the position to update, must be contained within the tree
+    pub fn update<'a, E: Into<BoxTreeEntry<'a, T>>>(
+        &mut self,
+        position: &V3c<u32>,
+        data: E,
+    ) -> Result<(), OctreeError>
+    where
+        T: 'a,
+    {
+        self.submit(false, position, data.into())
+    }
+
+    /// Submits the voxel at the given position to be cleared
+    /// * `position` - the position to clear, must be contained within the tree
+    pub fn clear(&mut self, position: &V3c<u32>) -> Result<(), OctreeError> {
+        self.check_position(position)?;
+        self.updates.push(BatchUpdate {
+            position: *position,
+            content: empty_marker(),
+            overwrite_if_empty: true,
+        });
+        Ok(())
+    }
+
+    /// The number of updates submitted so far
+    pub fn len(&self) -> usize {
+        self.updates.len()
+    }
+
+    /// Tells if no updates were submitted yet
+    pub fn is_empty(&self) -> bool {
+        self.updates.is_empty()
+    }
+
+    fn check_position(&self, position: &V3c<u32>) -> Result<(), OctreeError> {
+        if !Cube::root_bounds(self.tree.boxtree_size as f32).contains(&V3c::from(*position)) {
+            return Err(OctreeError::InvalidPosition {
+                x: position.x,
+                y: position.y,
+                z: position.z,
+            });
+        }
+        Ok(())
+    }
+
+    fn submit(
+        &mut self,
+        overwrite_if_empty: bool,
+        position: &V3c<u32>,
+        data: BoxTreeEntry<T>,
+    ) -> Result<(), OctreeError> {
+        self.check_position(position)?;
+
+        // Nothing to do when no operations are requested
+        if data.is_none() {
+            return Ok(());
+        }
+
+        // Submitted values are kept valid in case the palette is re-quantized
+        let content = self.tree.add_to_palette_keeping(
+            &data,
+            self.tree.palette_overflow_strategy,
+            self.updates.iter_mut().map(|update| &mut update.content),
+        )?;
+        self.updates.push(BatchUpdate {
+            position: *position,
+            content,
+            overwrite_if_empty,
+        });
+        Ok(())
+    }
+
+    /// Applies the submitted updates to the tree, then simplifies and updates the MIPs of the affected nodes
+    pub(crate) fn apply(&mut self) {
+        let mut updates = std::mem::take(&mut self.updates);
+        if updates.is_empty() {
+            return;
+        }
+
+        // Group updates by bricks; Sorting is stable, so the order of updates inside a brick is kept
+        let tree = &mut *self.tree;
+        let brick_dim = tree.brick_dim;
+        let brick_of = |position: &V3c<u32>| {
+            (
+                position.x / brick_dim,
+                position.y / brick_dim,
+                position.z / brick_dim,
+            )
+        };
+        updates.sort_by_key(|update| brick_of(&update.position));
+
+        // The whole batch is recorded as a single edit
+        if tree.journal.is_some() {
+            let areas = updates
+                .iter()
+                .map(|update| tree.journal_area(&update.position, 1))
+                .collect::<Vec<_>>();
+            tree.record_areas(areas);
+        }
+
+        // Simplification and MIP updates are deferred until every update is applied
+        let auto_simplify = tree.auto_simplify;
+        let mips_enabled = tree.mip_map_strategy.enabled;
+        tree.auto_simplify = false;
+        tree.mip_map_strategy.enabled = false;
+        let mut cached_leaf = None;
+        for update in updates.iter() {
+            tree.apply_batch_update(update, &mut cached_leaf);
+        }
+        tree.auto_simplify = auto_simplify;
+        tree.mip_map_strategy.enabled = mips_enabled;
+
+        if !auto_simplify && !mips_enabled {
+            return;
+        }
+
+        // Collect every affected node, along with the MIP cells affected inside them
+        let mut affected_nodes = HashMap::new();
+        for update in updates.iter() {
+            for (depth, (node_key, node_bounds)) in
+                tree.node_path_to(&update.position).into_iter().enumerate()
+            {
+                let (_, _, affected_cells) = affected_nodes
+                    .entry(node_key)
+                    .or_insert_with(|| (depth, node_bounds, HashSet::new()));
+                if mips_enabled {
+                    let cell_size = node_bounds.size / brick_dim as f32;
+                    let cell: V3c<u32> = ((V3c::from(update.position) - node_bounds.min_position)
+                        / cell_size)
+                        .floor()
+                        .into();
+                    affected_cells.insert((cell.x, cell.y, cell.z));
+                }
+            }
+        }
+
+        // Update MIPs then simplify each affected node once, children first
+        let mut affected_nodes = affected_nodes.into_iter().collect::<Vec<_>>();
+        affected_nodes.sort_by(|(_, (depth_a, ..)), (_, (depth_b, ..))| depth_b.cmp(depth_a));
+        for (node_key, (_, node_bounds, affected_cells)) in affected_nodes {
+            if !tree.nodes.key_is_valid(node_key) {
+                continue;
+            }
+            if !matches!(tree.nodes.get(node_key), NodeContent::Nothing) {
+                let cell_size = node_bounds.size / brick_dim as f32;
+                for (x, y, z) in affected_cells {
+                    let position: V3c<f32> = node_bounds.min_position
+                        + (V3c::<f32>::from(V3c::new(x, y, z)) * cell_size).round();
+                    tree.update_mip(node_key, &node_bounds, &V3c::from(position));
+                }
+            }
+            if auto_simplify {
+                tree.simplify(node_key, false);
+            }
+        }
+
+        // Simplification and MIP updates modify the affected nodes again
+        for update in updates.iter() {
+            tree.mark_changed_at_lod(&update.position, 1);
+        }
+    }
+}
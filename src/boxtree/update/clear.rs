@@ -49,6 +49,16 @@ impl<T: VoxelData> BoxTree<T>
         if clear_size == 0 {
             return Ok(());
         }
+        self.record_edit(position, clear_size);
+        self.clear_palette_values_at_lod(position, clear_size);
+        Ok(())
+    }
+
+    /// Clears the data at the given position and lod size
+    /// The position is expected to be contained within the tree
+    pub(crate) fn clear_palette_values_at_lod(&mut self, position: &V3c<u32>, clear_size: u32) {
+        let root_bounds = Cube::root_bounds(self.boxtree_size as f32);
+
         // A CPU stack does not consume significant relevant resources, e.g. a 4096*4096*4096 chunk has depth of 12
         let mut node_stack = vec![(Self::ROOT_NODE_KEY, root_bounds)];
         let mut actual_update_size = V3c::unit(0);
@@ -247,7 +257,7 @@ impl<T: VoxelData> BoxTree<T>
 
         if !updated {
             // No need to do post-processing operations if data wasn't updated..
-            return Ok(());
+            return;
         }
 
         // post-processing operations
@@ -259,7 +269,18 @@ impl<T: VoxelData> BoxTree<T>
             {
                 Some(child_key)
             } else {
-                None
+                // The updated leaf node is kept, so its occupancy needs to be refreshed
+                let occupied_bits = self.calculate_node_occupied_bits(child_key as usize);
+                if 0 == occupied_bits {
+                    *self.nodes.get_mut(child_key as usize) = NodeContent::Nothing;
+                    self.node_children[child_key as usize] = NodeChildren::NoChildren;
+                    self.node_mips[child_key as usize] = BrickData::Empty;
+                    Some(child_key)
+                } else {
+                    self.store_occupied_bits(child_key as usize, occupied_bits);
+                    self.update_mip(child_key as usize, &child_bounds, position);
+                    None
+                }
             }
         } else {
             None
@@ -272,6 +293,8 @@ impl<T: VoxelData> BoxTree<T>
                 let child_sectant = node_bounds.sectant_for(&V3c::from(*position));
                 self.node_children[node_key as usize].clear(child_sectant as usize);
                 self.nodes.free(child_key as usize);
+                self.node_children[child_key as usize] = NodeChildren::NoChildren;
+                self.node_mips[child_key as usize] = BrickData::Empty;
                 // Occupancy bitmask is re-evaluated fully in the below blocks
                 removed_node = None;
             };
@@ -354,6 +377,6 @@ impl<T: VoxelData> BoxTree<T>
                 break;
             }
         }
-        Ok(())
+        self.mark_changed_at_lod(position, clear_size);
     }
 }
@@ -0,0 +1,431 @@
+use crate::{
+    boxtree::{
+        types::{BrickData, NodeChildren, NodeContent, OctreeError, PaletteIndexValues, PasteMode},
+        update::region::BoxRegion,
+        BoxTree, VoxelData, BOX_NODE_CHILDREN_COUNT, BOX_NODE_DIMENSION,
+    },
+    object_pool::empty_marker,
+    spatial::{
+        math::{flat_projection, matrix_index_for, vector::V3c},
+        Cube,
+    },
+};
+
+/// A part of a tree deciding which voxels to erase in a CSG operation
+#[derive(Clone)]
+enum MaskPart<'a> {
+    /// The part contains no voxels
+    Empty,
+
+    /// A node with its bounds, which has children or bricks
+    Node(usize, Cube),
+
+    /// A brick covering the given bounds, its voxels might cover multiple voxels of the tree
+    Brick(&'a BrickData<PaletteIndexValues>, Cube),
+}
+
+impl<T: VoxelData> BoxTree<T> {
+    /// Adds every voxel of the other tree to this tree; where both trees contain data, the other tree takes precedence
+    /// Empty parts of the other tree are skipped as a whole
+    /// * `other` - The tree to merge into this tree, must have the same size and brick dimension
+    pub fn union_with(&mut self, other: &BoxTree<T>) -> Result<(), OctreeError> {
+        self.check_compatibility(other)?;
+        self.paste(other, &V3c::unit(0), PasteMode::SkipEmptySource)
+    }
+
+    /// Keeps only the voxels of this tree, which also contain data in the other tree
+    /// Both trees are walked together: parts of this tree under empty or completely filled parts
+    /// of the other tree are cleared or kept as a whole, only voxels of mixed bricks are compared one by one
+    /// * `other` - The tree to intersect with, must have the same size and brick dimension
+    pub fn intersect_with(&mut self, other: &BoxTree<T>) -> Result<(), OctreeError> {
+        self.check_compatibility(other)?;
+        self.clear_where(other, false);
+        Ok(())
+    }
+
+    /// Erases every voxel of this tree, which contains data in the other tree
+    /// Both trees are walked together: parts of this tree under empty or completely filled parts
+    /// of the other tree are kept or cleared as a whole, only voxels of mixed bricks are compared one by one
+    /// * `other` - The tree to subtract, must have the same size and brick dimension
+    pub fn subtract(&mut self, other: &BoxTree<T>) -> Result<(), OctreeError> {
+        self.check_compatibility(other)?;
+        self.clear_where(other, true);
+        Ok(())
+    }
+
+    /// Checks if the other tree has the same size and brick dimension as this tree
+    fn check_compatibility(&self, other: &BoxTree<T>) -> Result<(), OctreeError> {
+        if other.boxtree_size != self.boxtree_size {
+            return Err(OctreeError::InvalidSize(other.boxtree_size));
+        }
+        if other.brick_dim != self.brick_dim {
+            return Err(OctreeError::InvalidBrickDimension(other.brick_dim));
+        }
+        Ok(())
+    }
+
+    /// Erases every voxel of this tree, where the other tree is filled or empty, as requested
+    /// * `other` - The tree deciding which voxels to erase, must have the same size and brick dimension
+    /// * `clear_filled` - If true, voxels are erased where the other tree contains data, otherwise where it is empty
+    fn clear_where(&mut self, other: &BoxTree<T>, clear_filled: bool) {
+        let max_position = V3c::unit(self.boxtree_size);
+        let root_bounds = Cube::root_bounds(self.boxtree_size as f32);
+        let other_part = other.mask_part_of_node(Self::ROOT_NODE_KEY as usize, &root_bounds);
+        let updated = self.record_box_update(&V3c::unit(0), &max_position, |tree| {
+            match other.uniform_fill(&other_part, &root_bounds) {
+                Some(filled) if filled == clear_filled => tree.update_region_internal(
+                    Self::ROOT_NODE_KEY as usize,
+                    &root_bounds,
+                    &BoxRegion {
+                        min_position: V3c::unit(0),
+                        max_position,
+                    },
+                    empty_marker(),
+                    false,
+                ),
+                Some(_) => false,
+                None => tree.clear_node_where(
+                    Self::ROOT_NODE_KEY as usize,
+                    &root_bounds,
+                    other,
+                    &other_part,
+                    clear_filled,
+                ),
+            }
+        });
+        if updated {
+            self.mark_changed(&V3c::unit(0), &max_position);
+        }
+    }
+
+    /// Erases the voxels of the given node, where the other tree is filled or empty, as requested
+    /// The other tree is expected to be neither completely filled nor completely empty inside the node
+    /// * `other_part` - The part of the other tree at the bounds of the node
+    /// * Returns with true if the node was changed by the operation
+    fn clear_node_where(
+        &mut self,
+        node_key: usize,
+        node_bounds: &Cube,
+        other: &BoxTree<T>,
+        other_part: &MaskPart,
+        clear_filled: bool,
+    ) -> bool {
+        if let NodeContent::Nothing = self.nodes.get(node_key) {
+            return false;
+        }
+
+        let mut updated = false;
+        if node_bounds.size > (self.brick_dim * BOX_NODE_DIMENSION as u32) as f32 {
+            match self.nodes.get(node_key) {
+                NodeContent::UniformLeaf(BrickData::Empty) => return false,
+                NodeContent::Leaf(_) | NodeContent::UniformLeaf(_) => {
+                    // The leaf needs to be divided, so its children can be kept or erased separately
+                    self.subdivide_leaf_to_nodes(node_key, 0);
+                    if self
+                        .valid_child_for(node_key, 0)
+                        .is_some_and(|child_key| NodeContent::Nothing == *self.nodes.get(child_key))
+                    {
+                        self.remove_child_node(node_key, 0);
+                    }
+                    updated = true;
+                }
+                NodeContent::Nothing | NodeContent::Internal(_) => {}
+            }
+            for sectant in 0..BOX_NODE_CHILDREN_COUNT as u8 {
+                let Some(child_key) = self.valid_child_for(node_key, sectant) else {
+                    continue;
+                };
+                let child_bounds = node_bounds.child_bounds_for(sectant);
+                let child_part = other.mask_part_of_child(other_part, &child_bounds, sectant);
+                match other.uniform_fill(&child_part, &child_bounds) {
+                    Some(filled) if filled == clear_filled => {
+                        self.remove_child_node(node_key, sectant);
+                        updated = true;
+                    }
+                    Some(_) => {}
+                    None => {
+                        updated |= self.clear_node_where(
+                            child_key,
+                            &child_bounds,
+                            other,
+                            &child_part,
+                            clear_filled,
+                        );
+                        if let NodeContent::Nothing = self.nodes.get(child_key) {
+                            self.remove_child_node(node_key, sectant);
+                        }
+                    }
+                }
+            }
+        } else if node_bounds.size > self.brick_dim as f32 {
+            // Changing the layout of a node with content needs post-processing even without voxel changes
+            updated = !matches!(self.nodes.get(node_key), NodeContent::Leaf(_));
+            self.convert_to_leaf(node_key, node_bounds);
+            for sectant in 0..BOX_NODE_CHILDREN_COUNT as u8 {
+                let child_bounds = node_bounds.child_bounds_for(sectant);
+                let NodeContent::Leaf(bricks) = self.nodes.get(node_key) else {
+                    panic!("Expected node to be a Leaf after conversion");
+                };
+                if let Some(new_brick) = self.brick_cleared_where(
+                    &bricks[sectant as usize],
+                    &child_bounds,
+                    other,
+                    &other.mask_part_of_child(other_part, &child_bounds, sectant),
+                    clear_filled,
+                ) {
+                    let NodeContent::Leaf(bricks) = self.nodes.get_mut(node_key) else {
+                        panic!("Expected node to be a Leaf after conversion");
+                    };
+                    bricks[sectant as usize] = new_brick;
+                    updated = true;
+                }
+            }
+        } else if let Some(new_brick) = self.brick_cleared_where(
+            &self.brick_of_node(node_key, node_bounds),
+            node_bounds,
+            other,
+            other_part,
+            clear_filled,
+        ) {
+            self.deallocate_children_of(node_key);
+            *self.nodes.get_mut(node_key) = NodeContent::UniformLeaf(new_brick);
+            self.node_children[node_key] = NodeChildren::OccupancyBitmap(0);
+            updated = true;
+        }
+
+        if updated {
+            let min_position = V3c::<u32>::from(node_bounds.min_position);
+            self.post_process_region_update(
+                node_key,
+                node_bounds,
+                &BoxRegion {
+                    min_position,
+                    max_position: min_position + V3c::unit(node_bounds.size as u32),
+                },
+            );
+        }
+        updated
+    }
+
+    /// Provides the given brick with its voxels erased, where the other tree is filled or empty, as requested
+    /// * `brick_bounds` - The bounds of the brick, its voxels mapped 1:1 to the voxels of the tree
+    /// * `other_part` - The part of the other tree at the bounds of the brick
+    /// * Returns with the new brick data, or None if there is no change in it
+    fn brick_cleared_where(
+        &self,
+        brick: &BrickData<PaletteIndexValues>,
+        brick_bounds: &Cube,
+        other: &BoxTree<T>,
+        other_part: &MaskPart,
+        clear_filled: bool,
+    ) -> Option<BrickData<PaletteIndexValues>> {
+        if brick.contains_nothing(&self.voxel_color_palette, &self.voxel_data_palette) {
+            return None;
+        }
+        match other.uniform_fill(other_part, brick_bounds) {
+            Some(filled) if filled == clear_filled => return Some(BrickData::Empty),
+            Some(_) => return None,
+            None => {}
+        }
+
+        // Only mixed bricks of the other tree are compared voxel by voxel
+        let mut voxels = match brick {
+            BrickData::Empty => return None,
+            BrickData::Solid(voxel) => vec![*voxel; self.brick_dim.pow(3) as usize],
+            BrickData::Parted(voxels) => voxels.to_vec(),
+        };
+        let brick_position = V3c::<u32>::from(brick_bounds.min_position);
+        for x in 0..self.brick_dim {
+            for y in 0..self.brick_dim {
+                for z in 0..self.brick_dim {
+                    let position = brick_position + V3c::new(x, y, z);
+                    if clear_filled == other.is_filled(&other.mask_voxel(other_part, &position)) {
+                        voxels[flat_projection(
+                            x as usize,
+                            y as usize,
+                            z as usize,
+                            self.brick_dim as usize,
+                        )] = empty_marker();
+                    }
+                }
+            }
+        }
+        let mut new_brick = BrickData::Parted(voxels.into());
+        new_brick.simplify(&self.voxel_color_palette, &self.voxel_data_palette);
+        if *brick == new_brick {
+            None
+        } else {
+            Some(new_brick)
+        }
+    }
+
+    /// Provides the part of this tree representing the given node
+    fn mask_part_of_node<'a>(&'a self, node_key: usize, node_bounds: &Cube) -> MaskPart<'a> {
+        if 0 == self.stored_occupied_bits(node_key) {
+            return MaskPart::Empty;
+        }
+        match self.nodes.get(node_key) {
+            NodeContent::Nothing => MaskPart::Empty,
+            NodeContent::UniformLeaf(brick) => MaskPart::Brick(brick, *node_bounds),
+            NodeContent::Internal(_) | NodeContent::Leaf(_) => {
+                MaskPart::Node(node_key, *node_bounds)
+            }
+        }
+    }
+
+    /// Provides the part of this tree at the given child sectant of the given part
+    /// * `child_bounds` - The bounds of the child sectant
+    fn mask_part_of_child<'a>(
+        &'a self,
+        part: &MaskPart<'a>,
+        child_bounds: &Cube,
+        sectant: u8,
+    ) -> MaskPart<'a> {
+        match part {
+            MaskPart::Empty => MaskPart::Empty,
+            MaskPart::Brick(..) => part.clone(),
+            MaskPart::Node(node_key, _) => match self.nodes.get(*node_key) {
+                NodeContent::Leaf(bricks) => {
+                    MaskPart::Brick(&bricks[sectant as usize], *child_bounds)
+                }
+                _ => match self.valid_child_for(*node_key, sectant) {
+                    Some(child_key) => self.mask_part_of_node(child_key, child_bounds),
+                    None => MaskPart::Empty,
+                },
+            },
+        }
+    }
+
+    /// Tells if the given part of this tree is completely filled or completely empty inside the given bounds
+    /// * Returns with None if the bounds contain both filled and empty voxels, or if it can not be told without visiting them
+    fn uniform_fill(&self, part: &MaskPart, bounds: &Cube) -> Option<bool> {
+        match part {
+            MaskPart::Empty => Some(false),
+            MaskPart::Node(..) => None,
+            MaskPart::Brick(BrickData::Empty, _) => Some(false),
+            MaskPart::Brick(BrickData::Solid(voxel), _) => Some(self.is_filled(voxel)),
+            MaskPart::Brick(BrickData::Parted(voxels), brick_bounds) => {
+                // Each voxel of the brick might cover multiple voxels of the tree
+                let cell_size = brick_bounds.size / self.brick_dim as f32;
+                let first_cell = V3c::<usize>::from(
+                    ((bounds.min_position - brick_bounds.min_position) / cell_size).floor(),
+                );
+                let cell_count = (bounds.size / cell_size).max(1.) as usize;
+                let mut fill = None;
+                for x in first_cell.x..first_cell.x + cell_count {
+                    for y in first_cell.y..first_cell.y + cell_count {
+                        for z in first_cell.z..first_cell.z + cell_count {
+                            let filled = self.is_filled(
+                                &voxels[flat_projection(x, y, z, self.brick_dim as usize)],
+                            );
+                            if fill.is_some_and(|fill| fill != filled) {
+                                return None;
+                            }
+                            fill = Some(filled);
+                        }
+                    }
+                }
+                fill
+            }
+        }
+    }
+
+    /// Provides the voxel of the given part of this tree at the given position
+    fn mask_voxel(&self, part: &MaskPart, position: &V3c<u32>) -> PaletteIndexValues {
+        match part {
+            MaskPart::Empty | MaskPart::Brick(BrickData::Empty, _) => empty_marker(),
+            MaskPart::Brick(BrickData::Solid(voxel), _) => *voxel,
+            MaskPart::Brick(BrickData::Parted(voxels), brick_bounds) => {
+                let mat_index = matrix_index_for(brick_bounds, position, self.brick_dim);
+                voxels[flat_projection(
+                    mat_index.x,
+                    mat_index.y,
+                    mat_index.z,
+                    self.brick_dim as usize,
+                )]
+            }
+            MaskPart::Node(node_key, node_bounds) => {
+                self.get_internal(*node_key, *node_bounds, position)
+            }
+        }
+    }
+
+    /// Tells if the given voxel of this tree contains any data
+    fn is_filled(&self, voxel: &PaletteIndexValues) -> bool {
+        !NodeContent::pix_points_to_empty(
+            voxel,
+            &self.voxel_color_palette,
+            &self.voxel_data_palette,
+        )
+    }
+
+    /// Calls the given function for each part of the given node, which contains the same voxel everywhere
+    /// Empty and uniform nodes are reported as a whole, without visiting their voxels
+    /// * `fun` - The function to call: |bounds, voxel| { ... }, empty parts are reported with an empty voxel
+    pub(crate) fn visit_uniform_areas<F: FnMut(&Cube, PaletteIndexValues)>(
+        &self,
+        node_key: usize,
+        node_bounds: &Cube,
+        fun: &mut F,
+    ) {
+        if 0 == self.stored_occupied_bits(node_key) {
+            fun(node_bounds, empty_marker());
+            return;
+        }
+        match self.nodes.get(node_key) {
+            NodeContent::Nothing => fun(node_bounds, empty_marker()),
+            NodeContent::Internal(_) => {
+                for sectant in 0..BOX_NODE_CHILDREN_COUNT as u8 {
+                    let child_bounds = node_bounds.child_bounds_for(sectant);
+                    match self.valid_child_for(node_key, sectant) {
+                        Some(child_key) => self.visit_uniform_areas(child_key, &child_bounds, fun),
+                        None => fun(&child_bounds, empty_marker()),
+                    }
+                }
+            }
+            NodeContent::Leaf(bricks) => {
+                for (sectant, brick) in bricks.iter().enumerate() {
+                    self.visit_uniform_brick_areas(
+                        brick,
+                        &node_bounds.child_bounds_for(sectant as u8),
+                        fun,
+                    );
+                }
+            }
+            NodeContent::UniformLeaf(brick) => {
+                self.visit_uniform_brick_areas(brick, node_bounds, fun);
+            }
+        }
+    }
+
+    /// Calls the given function for each part of the given brick, which contains the same voxel everywhere
+    /// * `fun` - The function to call: |bounds, voxel| { ... }, empty parts are reported with an empty voxel
+    fn visit_uniform_brick_areas<F: FnMut(&Cube, PaletteIndexValues)>(
+        &self,
+        brick: &BrickData<PaletteIndexValues>,
+        brick_bounds: &Cube,
+        fun: &mut F,
+    ) {
+        match brick {
+            BrickData::Empty => fun(brick_bounds, empty_marker()),
+            BrickData::Solid(voxel) => fun(brick_bounds, *voxel),
+            BrickData::Parted(voxels) => {
+                // Each voxel of the brick might cover multiple voxels of the tree
+                let brick_dim = self.brick_dim as usize;
+                let cell_size = brick_bounds.size / self.brick_dim as f32;
+                for (index, voxel) in voxels.iter().enumerate() {
+                    let cell_bounds = Cube {
+                        min_position: brick_bounds.min_position
+                            + V3c::new(
+                                (index % brick_dim) as f32,
+                                ((index / brick_dim) % brick_dim) as f32,
+                                (index / (brick_dim * brick_dim)) as f32,
+                            ) * cell_size,
+                        size: cell_size,
+                    };
+                    fun(&cell_bounds, *voxel);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,74 @@
+use crate::{
+    boxtree::{
+        types::{BrickData, NodeChildren},
+        BoxTree, VoxelData,
+    },
+    object_pool::{empty_marker, ObjectPool},
+};
+use std::collections::HashSet;
+
+impl<T: VoxelData> BoxTree<T> {
+    /// Renumbers the nodes of the tree in breadth-first order, so siblings are stored next to each other
+    /// Freed node slots are dropped, and the backing storage is shrunk to the number of nodes in the tree.
+    /// Node keys change during the operation, so GPU views upload the whole tree again
+    pub fn defragment(&mut self) {
+        debug_assert!(self.nodes.key_is_valid(Self::ROOT_NODE_KEY as usize));
+
+        // Collect the live nodes in breadth-first order, with their new keys
+        let node_count = self.nodes.len();
+        let mut new_keys = vec![empty_marker::<u32>(); node_count];
+        let mut order = vec![Self::ROOT_NODE_KEY as usize];
+        new_keys[Self::ROOT_NODE_KEY as usize] = 0;
+        let mut visit_index = 0;
+        while visit_index < order.len() {
+            let node_key = order[visit_index];
+            visit_index += 1;
+            if let NodeChildren::Children(children) = &self.node_children[node_key] {
+                for child_key in children.iter().map(|child| *child as usize) {
+                    if self.nodes.key_is_valid(child_key)
+                        && new_keys[child_key] == empty_marker::<u32>()
+                    {
+                        new_keys[child_key] = order.len() as u32;
+                        order.push(child_key);
+                    }
+                }
+            }
+        }
+
+        // Move the nodes into their new place, updating the references to their children
+        let mut nodes = ObjectPool::with_capacity(order.len());
+        let mut node_children = Vec::with_capacity(order.len());
+        let mut node_mips = Vec::with_capacity(order.len());
+        for old_key in order.iter().copied() {
+            nodes.push(self.nodes.pop(old_key).unwrap());
+            let mut children = std::mem::take(&mut self.node_children[old_key]);
+            if let NodeChildren::Children(children) = &mut children {
+                for child in children.iter_mut() {
+                    *child = new_keys
+                        .get(*child as usize)
+                        .copied()
+                        .unwrap_or(empty_marker());
+                }
+            }
+            node_children.push(children);
+            node_mips.push(std::mem::replace(
+                &mut self.node_mips[old_key],
+                BrickData::Empty,
+            ));
+        }
+        nodes.shrink_to_fit();
+
+        self.outdated_mips = self
+            .outdated_mips
+            .iter()
+            .filter_map(|node_key| new_keys.get(*node_key))
+            .filter(|new_key| **new_key != empty_marker::<u32>())
+            .map(|new_key| *new_key as usize)
+            .collect::<HashSet<_>>();
+        self.nodes = nodes;
+        self.node_children = node_children.into();
+        self.node_mips = node_mips.into();
+        self.node_layout_revision += 1;
+        self.mark_layout_changed();
+    }
+}
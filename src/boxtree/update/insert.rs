@@ -1,7 +1,9 @@
 use crate::{
     boxtree::{
         iterate::execute_for_relevant_sectants,
-        types::{BoxTreeEntry, BrickData, NodeChildren, NodeContent, OctreeError},
+        types::{
+            BoxTreeEntry, BrickData, NodeChildren, NodeContent, OctreeError, PaletteIndexValues,
+        },
         BoxTree, VoxelData,
     },
     spatial::{
@@ -103,11 +105,33 @@ impl<T: VoxelData> BoxTree<T>
             return Ok(());
         }
 
+        let target_content = self.add_to_palette(&data)?;
+        self.record_edit(position_u32, insert_size);
+        self.insert_palette_value_at_lod(
+            overwrite_if_empty,
+            position_u32,
+            insert_size,
+            target_content,
+        );
+        Ok(())
+    }
+
+    /// Inserts the given palette value into the boxtree at the given position and lod
+    /// The position is expected to be contained within the tree
+    pub(crate) fn insert_palette_value_at_lod(
+        &mut self,
+        overwrite_if_empty: bool,
+        position_u32: &V3c<u32>,
+        insert_size: u32,
+        target_content: PaletteIndexValues,
+    ) {
+        let root_bounds = Cube::root_bounds(self.boxtree_size as f32);
+        let position = V3c::<f32>::from(*position_u32);
+
         // A CPU stack does not consume significant relevant resources, e.g. a 4096*4096*4096 chunk has depth of 12
         let mut node_stack = vec![(Self::ROOT_NODE_KEY, root_bounds)];
         let mut actual_update_size = V3c::unit(0);
         let mut updated = false;
-        let target_content = self.add_to_palette(&data);
         loop {
             let (current_node_key, current_bounds) = *node_stack.last().unwrap();
             let current_node_key = current_node_key as usize;
@@ -167,6 +191,7 @@ impl<T: VoxelData> BoxTree<T>
                                     NodeContent::UniformLeaf(BrickData::Solid(target_content));
                                 self.node_children[target_child_key] =
                                     NodeChildren::OccupancyBitmap(u64::MAX);
+                                self.node_mips[target_child_key] = BrickData::Empty;
                             } else {
                                 // Push in a new uniform leaf child
                                 let new_child_index = self.nodes.push(NodeContent::UniformLeaf(
@@ -185,6 +210,7 @@ impl<T: VoxelData> BoxTree<T>
                                     .unwrap() = new_child_index;
                                 self.node_children[new_child_index as usize] =
                                     NodeChildren::OccupancyBitmap(u64::MAX);
+                                self.node_mips[new_child_index as usize] = BrickData::Empty;
                             }
                         }
                     },
@@ -300,6 +326,8 @@ impl<T: VoxelData> BoxTree<T>
                         );
                         self.node_mips
                             .resize(self.node_mips.len().max(self.nodes.len()), BrickData::Empty);
+                        self.node_children[new_child_node as usize] = NodeChildren::NoChildren;
+                        self.node_mips[new_child_node as usize] = BrickData::Empty;
                         *self.node_children[current_node_key]
                             .child_mut(target_child_sectant as usize)
                             .unwrap() = new_child_node;
@@ -334,7 +362,7 @@ impl<T: VoxelData> BoxTree<T>
 
         if !updated {
             // No need to do post-processing operations if data wasn't updated..
-            return Ok(());
+            return;
         }
 
         // post-processing operations
@@ -419,6 +447,6 @@ impl<T: VoxelData> BoxTree<T>
                 simplifyable = self.simplify(node_key as usize, false);
             }
         }
-        Ok(())
+        self.mark_changed_at_lod(position_u32, insert_size);
     }
 }
@@ -1,13 +1,26 @@
+pub mod batch;
 pub mod clear;
+pub mod csg;
+pub mod defragment;
 pub mod insert;
+pub mod palette;
+pub mod paste;
+pub mod rebrick;
+pub mod region;
+pub mod resize;
+pub mod shape;
+pub mod transform;
 
 #[cfg(test)]
 mod tests;
 
 use crate::{
     boxtree::{
-        types::{BoxTreeEntry, BrickData, NodeChildren, NodeContent, PaletteIndexValues},
-        Albedo, BoxTree, VoxelData, BOX_NODE_CHILDREN_COUNT, BOX_NODE_DIMENSION,
+        types::{
+            BoxTreeEntry, BrickData, NodeChildren, NodeContent, OctreeError, PaletteIndexValues,
+            PaletteOverflowStrategy,
+        },
+        Albedo, BoxTree, VoxelData, BOX_NODE_CHILDREN_COUNT, BOX_NODE_DIMENSION, MAX_PALETTE_SIZE,
     },
     object_pool::empty_marker,
     spatial::{
@@ -35,89 +48,121 @@ impl<T: VoxelData> BoxTree<T>
     //####################################################################################
     /// Updates the stored palette by adding the new colors and data in the given entry
     /// Since unused colors are not removed from the palette, possible "pollution" is possible,
-    /// where unused colors remain in the palette.
-    /// * Returns with the resulting PaletteIndexValues Entry
-    pub(crate) fn add_to_palette(&mut self, entry: &BoxTreeEntry<T>) -> PaletteIndexValues {
+    /// where unused colors remain in the palette, see @compact_palette.
+    /// When the color palette is full, the configured @PaletteOverflowStrategy decides the stored color
+    /// * Returns with the resulting PaletteIndexValues Entry, or an error if the palettes are full
+    pub(crate) fn add_to_palette(
+        &mut self,
+        entry: &BoxTreeEntry<T>,
+    ) -> Result<PaletteIndexValues, OctreeError> {
+        self.add_to_palette_keeping(entry, self.palette_overflow_strategy, [])
+    }
+
+    /// Same as @add_to_palette, but with the given overflow strategy
+    /// * `kept_values` - Palette index values stored outside the tree, to be kept valid
+    ///   in case the palette is re-quantized
+    pub(crate) fn add_to_palette_keeping<'b, I>(
+        &mut self,
+        entry: &BoxTreeEntry<T>,
+        strategy: PaletteOverflowStrategy,
+        kept_values: I,
+    ) -> Result<PaletteIndexValues, OctreeError>
+    where
+        I: IntoIterator<Item = &'b mut PaletteIndexValues>,
+    {
         match entry {
-            BoxTreeEntry::Empty => empty_marker::<PaletteIndexValues>(),
+            BoxTreeEntry::Empty => Ok(empty_marker::<PaletteIndexValues>()),
             BoxTreeEntry::Visual(albedo) => {
                 if **albedo == Albedo::zero() {
-                    return empty_marker();
+                    return Ok(empty_marker());
                 }
-                let potential_new_albedo_index = self.map_to_color_index_in_palette.keys().len();
-                let albedo_index = if let std::collections::hash_map::Entry::Vacant(e) =
-                    self.map_to_color_index_in_palette.entry(**albedo)
-                {
-                    e.insert(potential_new_albedo_index);
-                    self.voxel_color_palette.push(**albedo);
-                    potential_new_albedo_index
-                } else {
-                    self.map_to_color_index_in_palette[albedo]
-                };
-                debug_assert!(
-                    albedo_index < u16::MAX as usize,
-                    "Albedo color palette overflow!"
-                );
-                NodeContent::pix_visual(albedo_index as u16)
+                let albedo_index = self.color_index_in_palette(albedo, strategy, kept_values)?;
+                Ok(NodeContent::pix_visual(albedo_index))
             }
             BoxTreeEntry::Informative(data) => {
                 if data.is_empty() {
-                    return empty_marker();
+                    return Ok(empty_marker());
                 }
-                let potential_new_data_index = self.map_to_data_index_in_palette.keys().len();
-                let data_index = if let std::collections::hash_map::Entry::Vacant(e) =
-                    self.map_to_data_index_in_palette.entry((*data).clone())
-                {
-                    e.insert(potential_new_data_index);
-                    self.voxel_data_palette.push((*data).clone());
-                    potential_new_data_index
-                } else {
-                    self.map_to_data_index_in_palette[data]
-                };
-                debug_assert!(
-                    data_index < u16::MAX as usize,
-                    "Data color palette overflow!"
-                );
-                NodeContent::pix_informal(data_index as u16)
+                Ok(NodeContent::pix_informal(self.data_index_in_palette(data)?))
             }
             BoxTreeEntry::Complex(albedo, data) => {
                 if **albedo == Albedo::zero() {
-                    return self.add_to_palette(&BoxTreeEntry::Informative(*data));
+                    return self.add_to_palette_keeping(
+                        &BoxTreeEntry::Informative(*data),
+                        strategy,
+                        kept_values,
+                    );
                 } else if data.is_empty() {
-                    return self.add_to_palette(&BoxTreeEntry::Visual(albedo));
+                    return self.add_to_palette_keeping(
+                        &BoxTreeEntry::Visual(albedo),
+                        strategy,
+                        kept_values,
+                    );
                 }
-                let potential_new_albedo_index = self.map_to_color_index_in_palette.keys().len();
-                let albedo_index = if let std::collections::hash_map::Entry::Vacant(e) =
-                    self.map_to_color_index_in_palette.entry(**albedo)
+                // Neither palette is changed if the entry can not be stored in both
+                if !self.map_to_data_index_in_palette.contains_key(*data)
+                    && self.voxel_data_palette.len() >= MAX_PALETTE_SIZE
                 {
-                    e.insert(potential_new_albedo_index);
-                    self.voxel_color_palette.push(**albedo);
-                    potential_new_albedo_index
-                } else {
-                    self.map_to_color_index_in_palette[albedo]
-                };
-                let potential_new_data_index = self.map_to_data_index_in_palette.keys().len();
-                let data_index = if let std::collections::hash_map::Entry::Vacant(e) =
-                    self.map_to_data_index_in_palette.entry((*data).clone())
-                {
-                    e.insert(potential_new_data_index);
-                    self.voxel_data_palette.push((*data).clone());
-                    potential_new_data_index
-                } else {
-                    self.map_to_data_index_in_palette[data]
-                };
-                debug_assert!(
-                    albedo_index < u16::MAX as usize,
-                    "Albedo color palette overflow!"
-                );
-                debug_assert!(
-                    data_index < u16::MAX as usize,
-                    "Data color palette overflow!"
-                );
-                NodeContent::pix_complex(albedo_index as u16, data_index as u16)
+                    return Err(OctreeError::PaletteFull);
+                }
+                let albedo_index = self.color_index_in_palette(albedo, strategy, kept_values)?;
+                let data_index = self.data_index_in_palette(data)?;
+                Ok(NodeContent::pix_complex(albedo_index, data_index))
             }
         }
-        // find color in the palette is present, add if not
+    }
+
+    /// Provides the index of the given color in the palette, adding it if not present
+    /// When the palette is full, the given overflow strategy decides the resulting index
+    fn color_index_in_palette<'b, I>(
+        &mut self,
+        albedo: &Albedo,
+        strategy: PaletteOverflowStrategy,
+        kept_values: I,
+    ) -> Result<u16, OctreeError>
+    where
+        I: IntoIterator<Item = &'b mut PaletteIndexValues>,
+    {
+        if let Some(albedo_index) = self.map_to_color_index_in_palette.get(albedo) {
+            return Ok(*albedo_index as u16);
+        }
+        if self.voxel_color_palette.len() >= MAX_PALETTE_SIZE {
+            match strategy {
+                PaletteOverflowStrategy::Reject => return Err(OctreeError::PaletteFull),
+                PaletteOverflowStrategy::NearestColor => {
+                    return Ok(self.nearest_color_index(albedo));
+                }
+                PaletteOverflowStrategy::Requantize => {
+                    let color_map = self.requantize_color_palette();
+                    for value in kept_values {
+                        *value = Self::remap_palette_index(value, Some(&color_map), None);
+                    }
+                    if let Some(albedo_index) = self.map_to_color_index_in_palette.get(albedo) {
+                        return Ok(*albedo_index as u16);
+                    }
+                }
+            }
+        }
+        let albedo_index = self.voxel_color_palette.len();
+        self.map_to_color_index_in_palette
+            .insert(*albedo, albedo_index);
+        self.voxel_color_palette.push(*albedo);
+        Ok(albedo_index as u16)
+    }
+
+    /// Provides the index of the given data in the palette, adding it if not present
+    fn data_index_in_palette(&mut self, data: &T) -> Result<u16, OctreeError> {
+        if let Some(data_index) = self.map_to_data_index_in_palette.get(data) {
+            return Ok(*data_index as u16);
+        }
+        if self.voxel_data_palette.len() >= MAX_PALETTE_SIZE {
+            return Err(OctreeError::PaletteFull);
+        }
+        let data_index = self.voxel_data_palette.len();
+        self.map_to_data_index_in_palette
+            .insert(data.clone(), data_index);
+        self.voxel_data_palette.push(data.clone());
+        Ok(data_index as u16)
     }
 
     //####################################################################################
@@ -174,7 +219,7 @@ impl<T: VoxelData> BoxTree<T>
                             *size,
                             &target_content,
                         );
-                        bricks[target_child_sectant] = BrickData::Parted(new_brick);
+                        bricks[target_child_sectant] = BrickData::Parted(new_brick.into());
                         true
                     }
                     BrickData::Solid(voxel) => {
@@ -204,7 +249,7 @@ impl<T: VoxelData> BoxTree<T>
                                 *size,
                                 &target_content,
                             );
-                            bricks[target_child_sectant] = BrickData::Parted(new_brick);
+                            bricks[target_child_sectant] = BrickData::Parted(new_brick.into());
                             true
                         } else {
                             // Since the Voxel already equals the data to be set, no need to update anything
@@ -248,7 +293,7 @@ impl<T: VoxelData> BoxTree<T>
 
                             // Add a brick to the target sectant and update with the given data
                             let mut new_brick = vec![
-                                self.add_to_palette(&BoxTreeEntry::Empty);
+                                empty_marker::<PaletteIndexValues>();
                                 self.brick_dim.pow(3) as usize
                             ];
                             Self::update_brick(
@@ -260,7 +305,8 @@ impl<T: VoxelData> BoxTree<T>
                                 *size,
                                 &target_content,
                             );
-                            new_leaf_content[target_child_sectant] = BrickData::Parted(new_brick);
+                            new_leaf_content[target_child_sectant] =
+                                BrickData::Parted(new_brick.into());
                             *self.nodes.get_mut(node_key) = NodeContent::Leaf(new_leaf_content);
                             return true;
                         }
@@ -315,11 +361,13 @@ impl<T: VoxelData> BoxTree<T>
                         {
                             // Data request doesn't align with the voxel data
                             // create a voxel brick and try to update with the given data
-                            *mat = BrickData::Parted(vec![
-                                *voxel;
-                                (self.brick_dim * self.brick_dim * self.brick_dim)
-                                    as usize
-                            ]);
+                            *mat = BrickData::Parted(
+                                vec![
+                                    *voxel;
+                                    (self.brick_dim * self.brick_dim * self.brick_dim) as usize
+                                ]
+                                .into(),
+                            );
 
                             return self.leaf_update(
                                 overwrite_if_empty,
@@ -395,8 +443,7 @@ impl<T: VoxelData> BoxTree<T>
                                 .unwrap();
 
                         // Each brick is mapped to take up one subsection of the current data
-                        let child_bricks =
-                            Self::dilute_brick_data(std::mem::take(brick), self.brick_dim);
+                        let child_bricks = Self::dilute_brick_data(brick, self.brick_dim);
                         let mut updated = false;
                         for (sectant, mut new_brick) in child_bricks.into_iter().enumerate() {
                             // Also update the brick if it is the target
@@ -412,7 +459,7 @@ impl<T: VoxelData> BoxTree<T>
                                 );
                                 updated |= true;
                             }
-                            leaf_data[sectant] = BrickData::Parted(new_brick);
+                            leaf_data[sectant] = BrickData::Parted(new_brick.into());
                         }
 
                         *self.nodes.get_mut(node_key) = NodeContent::Leaf(leaf_data);
@@ -491,7 +538,7 @@ impl<T: VoxelData> BoxTree<T>
     /// Provides an array of bricks, based on the given brick data, with the same size of the original brick,
     /// each voxel mapped as the new bricks were the children of the given brick
     pub(crate) fn dilute_brick_data<B>(
-        brick_data: Vec<B>,
+        brick_data: &[B],
         brick_dim: u32,
     ) -> [Vec<B>; BOX_NODE_CHILDREN_COUNT]
     where
@@ -501,7 +548,7 @@ impl<T: VoxelData> BoxTree<T>
 
         if 1 == brick_dim {
             debug_assert_eq!(brick_data.len(), 1);
-            return vec![brick_data.clone(); BOX_NODE_CHILDREN_COUNT]
+            return vec![brick_data.to_vec(); BOX_NODE_CHILDREN_COUNT]
                 .try_into()
                 .unwrap();
         }
@@ -517,7 +564,7 @@ impl<T: VoxelData> BoxTree<T>
                 .unwrap();
         };
 
-        debug_assert!(brick_data.len() <= BOX_NODE_CHILDREN_COUNT);
+        debug_assert!(brick_data.len() >= BOX_NODE_CHILDREN_COUNT);
         let mut result: [Vec<B>; BOX_NODE_CHILDREN_COUNT] = (0..BOX_NODE_CHILDREN_COUNT)
             .map(|sectant| vec![brick_data[sectant]; brick_dim.pow(3) as usize])
             .collect::<Vec<_>>()
@@ -569,6 +616,63 @@ impl<T: VoxelData> BoxTree<T>
         result
     }
 
+    /// Updates a single voxel inside the parted brick containing it, under the given leaf node
+    /// The brick is only updated if its occupancy is kept, so no node needs to be restructured.
+    /// Does not update MIPs or simplify the node.
+    /// * Returns with true if the brick was updated
+    pub(crate) fn update_voxel_in_brick(
+        &mut self,
+        (node_key, node_bounds): (usize, &Cube),
+        position: &V3c<u32>,
+        target_content: PaletteIndexValues,
+        overwrite_if_empty: bool,
+    ) -> bool {
+        let sectant = node_bounds.sectant_for(&V3c::from(*position));
+        let brick_bounds = node_bounds.child_bounds_for(sectant);
+        if brick_bounds.size != self.brick_dim as f32
+            || 0 == self.stored_occupied_bits(node_key) & (0x01 << sectant)
+        {
+            return false;
+        }
+        let clearing = NodeContent::pix_points_to_empty(
+            &target_content,
+            &self.voxel_color_palette,
+            &self.voxel_data_palette,
+        );
+        let NodeContent::Leaf(bricks) = self.nodes.get_mut(node_key) else {
+            return false;
+        };
+        let BrickData::Parted(brick) = &mut bricks[sectant as usize] else {
+            return false;
+        };
+
+        // Clearing the last voxel of a brick changes the occupancy of the node
+        let index = matrix_index_for(&brick_bounds, position, self.brick_dim);
+        let index = flat_projection(index.x, index.y, index.z, self.brick_dim as usize);
+        if clearing
+            && brick.iter().enumerate().all(|(voxel_index, voxel)| {
+                voxel_index == index
+                    || NodeContent::pix_points_to_empty(
+                        voxel,
+                        &self.voxel_color_palette,
+                        &self.voxel_data_palette,
+                    )
+            })
+        {
+            return false;
+        }
+        Self::update_brick(
+            overwrite_if_empty,
+            brick,
+            &brick_bounds,
+            self.brick_dim,
+            *position,
+            V3c::unit(1),
+            &target_content,
+        );
+        true
+    }
+
     /// Updates the content of the given brick and its occupancy bitmap. Each components of mat_index must be smaller, than the size of the brick.
     /// mat_index + size however need not be in bounds, the function will cut each component to fit inside the brick.
     /// * `brick` - mutable reference of the brick to update
@@ -692,6 +796,7 @@ impl<T: VoxelData> BoxTree<T>
                                 );
                                 *self.nodes.get_mut(node_key) = NodeContent::Nothing;
                                 self.node_children[node_key] = NodeChildren::NoChildren;
+                                self.node_mips[node_key] = BrickData::Empty;
                                 true
                             } else {
                                 debug_assert_eq!(
@@ -800,6 +905,7 @@ impl<T: VoxelData> BoxTree<T>
                         *self.nodes.get_mut(node_key) = NodeContent::UniformLeaf(BrickData::Solid(
                             *uniform_solid_value.unwrap(),
                         ));
+                        self.node_mips[node_key] = BrickData::Empty;
                         return true;
                     }
 
@@ -883,12 +989,13 @@ impl<T: VoxelData> BoxTree<T>
 
                     // bricks can be represented as a uniform parted brick matrix!
                     if is_leaf_uniform {
-                        unified_brick = BrickData::Parted(unified_brick_data);
+                        unified_brick = BrickData::Parted(unified_brick_data.into());
                         simplified = true;
                     }
 
                     if !matches!(unified_brick, BrickData::Empty) {
                         *self.nodes.get_mut(node_key) = NodeContent::UniformLeaf(unified_brick);
+                        self.node_mips[node_key] = BrickData::Empty;
                     }
 
                     simplified
@@ -902,6 +1009,7 @@ impl<T: VoxelData> BoxTree<T>
                         }
 
                         *self.nodes.get_mut(node_key) = NodeContent::Nothing;
+                        self.node_mips[node_key] = BrickData::Empty;
                         return true;
                     }
 
@@ -943,6 +1051,10 @@ impl<T: VoxelData> BoxTree<T>
                     let new_node_children = self.node_children[child_keys[0] as usize];
                     self.deallocate_children_of(node_key);
                     self.node_children[node_key] = new_node_children;
+                    if let NodeContent::UniformLeaf(_) = self.nodes.get(node_key) {
+                        // Uniform leaves have no MIP, as their brick is equivalent with it
+                        self.node_mips[node_key] = BrickData::Empty;
+                    }
 
                     // At this point there's no need to call simplify on the new leaf node
                     // because it's been attempted already on the data it copied from
@@ -0,0 +1,368 @@
+use crate::{
+    boxtree::{
+        types::{BrickData, NodeContent, PaletteIndexValues},
+        Albedo, BoxTree, V3c, VoxelData, BOX_NODE_CHILDREN_COUNT, MAX_PALETTE_SIZE,
+    },
+    object_pool::empty_marker,
+};
+use std::collections::HashMap;
+
+impl<T: VoxelData> BoxTree<T> {
+    /// Removes every color and data entry from the palettes, which is not referenced by any voxel or MIP
+    /// Duplicate entries are merged, and every stored palette reference is rewritten to the compacted palettes.
+    /// GPU views of the tree need to be recreated after compaction, as stored palette indices change.
+    /// * Returns with the number of removed entries from the color and data palettes respectively
+    pub fn compact_palette(&mut self) -> (usize, usize) {
+        // Collect every referenced palette entry
+        let mut used_colors = vec![false; self.voxel_color_palette.len()];
+        let mut used_data = vec![false; self.voxel_data_palette.len()];
+        self.for_each_palette_index(|index| {
+            if NodeContent::pix_color_is_some(index) {
+                used_colors[NodeContent::pix_color_index(index)] = true;
+            }
+            if NodeContent::pix_data_is_some(index) {
+                used_data[NodeContent::pix_data_index(index)] = true;
+            }
+        });
+
+        // Rebuild the palettes from the referenced entries only
+        let mut color_map = vec![empty_marker::<u16>(); self.voxel_color_palette.len()];
+        let mut voxel_color_palette = vec![];
+        let mut map_to_color_index_in_palette = HashMap::new();
+        for (old_index, color) in self.voxel_color_palette.iter().enumerate() {
+            if used_colors[old_index] {
+                let new_index = *map_to_color_index_in_palette
+                    .entry(*color)
+                    .or_insert_with(|| {
+                        voxel_color_palette.push(*color);
+                        voxel_color_palette.len() - 1
+                    });
+                color_map[old_index] = new_index as u16;
+            }
+        }
+
+        let mut data_map = vec![empty_marker::<u16>(); self.voxel_data_palette.len()];
+        let mut voxel_data_palette = vec![];
+        let mut map_to_data_index_in_palette = HashMap::new();
+        for (old_index, data) in self.voxel_data_palette.iter().enumerate() {
+            if used_data[old_index] {
+                let new_index = *map_to_data_index_in_palette
+                    .entry(data.clone())
+                    .or_insert_with(|| {
+                        voxel_data_palette.push(data.clone());
+                        voxel_data_palette.len() - 1
+                    });
+                data_map[old_index] = new_index as u16;
+            }
+        }
+
+        // Rewrite every reference to the new palettes
+        self.for_each_palette_index_mut(|index| {
+            *index = Self::remap_palette_index(index, Some(&color_map), Some(&data_map));
+        });
+
+        let removed_colors = self.voxel_color_palette.len() - voxel_color_palette.len();
+        let removed_data = self.voxel_data_palette.len() - voxel_data_palette.len();
+        let previous_colors =
+            std::mem::replace(&mut self.voxel_color_palette, voxel_color_palette.into());
+        let previous_data =
+            std::mem::replace(&mut self.voxel_data_palette, voxel_data_palette.into());
+        self.map_to_color_index_in_palette = map_to_color_index_in_palette.into();
+        self.map_to_data_index_in_palette = map_to_data_index_in_palette.into();
+        self.palette_revision += 1;
+        self.mark_changed(&V3c::unit(0), &V3c::unit(self.boxtree_size));
+        self.record_palette_edit(
+            previous_colors,
+            previous_data,
+            Some(&color_map),
+            Some(&data_map),
+            true,
+        );
+        (removed_colors, removed_data)
+    }
+
+    /// Replaces every occurrence of the given color inside the color palette, see @map_palette
+    /// * Returns with the number of changed palette entries
+    pub fn replace_color(&mut self, old_color: &Albedo, new_color: &Albedo) -> usize {
+        self.map_palette(|color| {
+            if color == *old_color {
+                *new_color
+            } else {
+                color
+            }
+        })
+    }
+
+    /// Changes each color of the palette in place to the result of the given function,
+    /// without rewriting the voxels referencing them. Changes which would make a visible color
+    /// transparent or a transparent color visible are skipped, as they would change the occupancy of the tree.
+    /// Entries becoming equal are kept, new voxels use the first one of them until they are merged by @compact_palette.
+    /// MIPs containing the changed colors are marked outdated, see @StrategyUpdater::recalculate_outdated_mips
+    /// * `fun` - The function to provide the new color for each color: |color| -> color
+    /// * Returns with the number of changed palette entries
+    pub fn map_palette<F: FnMut(Albedo) -> Albedo>(&mut self, mut fun: F) -> usize {
+        let new_colors = self
+            .voxel_color_palette
+            .iter()
+            .map(|color| {
+                let new_color = fun(*color);
+                (new_color != *color && new_color.is_transparent() == color.is_transparent())
+                    .then_some(new_color)
+            })
+            .collect::<Vec<_>>();
+        let changed_colors = new_colors
+            .iter()
+            .map(|new_color| new_color.is_some())
+            .collect::<Vec<_>>();
+        let changed_count = changed_colors.iter().filter(|changed| **changed).count();
+        if 0 == changed_count {
+            return 0;
+        }
+        self.record_palette_edit(
+            self.voxel_color_palette.clone(),
+            self.voxel_data_palette.clone(),
+            None,
+            None,
+            true,
+        );
+        for (color, new_color) in self.voxel_color_palette.iter_mut().zip(new_colors) {
+            if let Some(new_color) = new_color {
+                *color = new_color;
+            }
+        }
+
+        // New voxels refer to the first occurrence of each color
+        self.map_to_color_index_in_palette.clear();
+        for (index, color) in self.voxel_color_palette.iter().enumerate() {
+            self.map_to_color_index_in_palette
+                .entry(*color)
+                .or_insert(index);
+        }
+
+        if self.mip_map_strategy.is_enabled() {
+            self.mark_outdated_mips(Self::ROOT_NODE_KEY as usize, &changed_colors);
+        }
+        self.palette_revision += 1;
+        changed_count
+    }
+
+    /// Replaces every occurrence of the given data inside the data palette in place,
+    /// without rewriting the voxels referencing them. Replacing empty data with non-empty data,
+    /// or the other way around is skipped, as it would change the occupancy of the tree.
+    /// In case the new data is already present in the palette, the replaced entries are kept
+    /// until they are merged by @compact_palette.
+    /// * Returns with the number of changed palette entries
+    pub fn replace_data(&mut self, old_data: &T, new_data: T) -> usize {
+        if *old_data == new_data || old_data.is_empty() != new_data.is_empty() {
+            return 0;
+        }
+        let changed_count = self
+            .voxel_data_palette
+            .iter()
+            .filter(|data| **data == *old_data)
+            .count();
+        if 0 == changed_count {
+            return 0;
+        }
+        self.record_palette_edit(
+            self.voxel_color_palette.clone(),
+            self.voxel_data_palette.clone(),
+            None,
+            None,
+            true,
+        );
+        for data in self.voxel_data_palette.iter_mut() {
+            if *data == *old_data {
+                *data = new_data.clone();
+            }
+        }
+
+        // New voxels refer to the first occurrence of each data
+        self.map_to_data_index_in_palette.clear();
+        for (index, data) in self.voxel_data_palette.iter().enumerate() {
+            self.map_to_data_index_in_palette
+                .entry(data.clone())
+                .or_insert(index);
+        }
+        self.palette_revision += 1;
+        self.mark_changed_data_palette();
+        changed_count
+    }
+
+    /// Provides the index of the color in the palette closest to the given color
+    /// Expects the palette to contain at least one color
+    pub(crate) fn nearest_color_index(&self, albedo: &Albedo) -> u16 {
+        self.voxel_color_palette
+            .iter()
+            .enumerate()
+            .min_by(|(_, a), (_, b)| {
+                a.distance_from(albedo)
+                    .partial_cmp(&b.distance_from(albedo))
+                    .unwrap()
+            })
+            .map(|(index, _)| index as u16)
+            .expect("Expected color palette to contain colors")
+    }
+
+    /// Reduces the precision of the colors in the palette until at most half of the palette is used
+    /// Colors becoming equal are merged, and every stored reference is rewritten to the new palette
+    /// * Returns with the new index of each previous color index
+    pub(crate) fn requantize_color_palette(&mut self) -> Vec<u16> {
+        let previous_colors = self.voxel_color_palette.clone();
+        let mut color_map = (0..self.voxel_color_palette.len() as u16).collect::<Vec<_>>();
+        let mut voxel_color_palette = self.voxel_color_palette.clone();
+        let mut dropped_bits = 0;
+        while voxel_color_palette.len() > MAX_PALETTE_SIZE / 2 && dropped_bits < 7 {
+            dropped_bits += 1;
+            let quantize = |channel: u8| {
+                ((channel >> dropped_bits) << dropped_bits) | (0x01 << (dropped_bits - 1))
+            };
+            voxel_color_palette.clear();
+            let mut map_to_color_index_in_palette = HashMap::new();
+            for (old_index, color) in self.voxel_color_palette.iter().enumerate() {
+                let quantized_color = Albedo {
+                    r: quantize(color.r),
+                    g: quantize(color.g),
+                    b: quantize(color.b),
+                    a: color.a,
+                };
+                let new_index = *map_to_color_index_in_palette
+                    .entry(quantized_color)
+                    .or_insert_with(|| {
+                        voxel_color_palette.push(quantized_color);
+                        voxel_color_palette.len() - 1
+                    });
+                color_map[old_index] = new_index as u16;
+            }
+        }
+
+        self.for_each_palette_index_mut(|index| {
+            *index = Self::remap_palette_index(index, Some(&color_map), None);
+        });
+        self.map_to_color_index_in_palette = voxel_color_palette
+            .iter()
+            .enumerate()
+            .map(|(index, color)| (*color, index))
+            .collect::<HashMap<_, _>>()
+            .into();
+        self.voxel_color_palette = voxel_color_palette;
+        self.palette_revision += 1;
+        self.mark_changed(&V3c::unit(0), &V3c::unit(self.boxtree_size));
+
+        // Re-quantization is part of the edit adding the new color
+        self.record_palette_edit(
+            previous_colors,
+            self.voxel_data_palette.clone(),
+            Some(&color_map),
+            None,
+            false,
+        );
+        color_map
+    }
+
+    /// Provides the given palette index value with its color and data indices replaced by the given maps
+    /// * `color_map` - The new index for each color index, indexed by the current color index
+    /// * `data_map` - The new index for each data index, indexed by the current data index
+    pub(crate) fn remap_palette_index(
+        index: &PaletteIndexValues,
+        color_map: Option<&[u16]>,
+        data_map: Option<&[u16]>,
+    ) -> PaletteIndexValues {
+        let mut result = *index;
+        if let Some(color_map) = color_map.filter(|_| NodeContent::pix_color_is_some(index)) {
+            result = NodeContent::pix_overwrite_color(
+                result,
+                &(color_map[NodeContent::pix_color_index(index)] as PaletteIndexValues),
+            );
+        }
+        if let Some(data_map) = data_map.filter(|_| NodeContent::pix_data_is_some(index)) {
+            result = NodeContent::pix_overwrite_data(
+                result,
+                &((data_map[NodeContent::pix_data_index(index)] as PaletteIndexValues) << 16),
+            );
+        }
+        result
+    }
+
+    /// Marks the MIPs of the given node and its descendants outdated, in case they contain any of the changed colors
+    /// * `changed_colors` - Tells for each color index if the color has been changed
+    /// * Returns with true if the MIP of the given node has been marked outdated
+    pub(crate) fn mark_outdated_mips(&mut self, node_key: usize, changed_colors: &[bool]) -> bool {
+        let contains_changed_color = |brick: &BrickData<PaletteIndexValues>| {
+            let is_changed = |voxel: &PaletteIndexValues| {
+                NodeContent::pix_color_is_some(voxel)
+                    && changed_colors[NodeContent::pix_color_index(voxel)]
+            };
+            match brick {
+                BrickData::Empty => false,
+                BrickData::Solid(voxel) => is_changed(voxel),
+                BrickData::Parted(voxels) => voxels.iter().any(is_changed),
+            }
+        };
+        let mut outdated = contains_changed_color(&self.node_mips[node_key]);
+        match self.nodes.get(node_key) {
+            NodeContent::Nothing => {}
+            NodeContent::Internal(_) => {
+                for sectant in 0..BOX_NODE_CHILDREN_COUNT as u8 {
+                    if let Some(child_key) = self.valid_child_for(node_key, sectant) {
+                        outdated |= self.mark_outdated_mips(child_key, changed_colors);
+                    }
+                }
+            }
+            NodeContent::Leaf(bricks) => outdated |= bricks.iter().any(contains_changed_color),
+            NodeContent::UniformLeaf(brick) => outdated |= contains_changed_color(brick),
+        }
+        if outdated {
+            self.outdated_mips.insert(node_key);
+        }
+        outdated
+    }
+
+    /// Calls the given function for every palette index value stored in the nodes and MIPs of the tree
+    /// MIPs of unused node slots are skipped, as they are not part of the tree anymore
+    pub(crate) fn for_each_palette_index<F: FnMut(&PaletteIndexValues)>(&self, mut fun: F) {
+        let mut visit_brick = |brick: &BrickData<PaletteIndexValues>| match brick {
+            BrickData::Empty => {}
+            BrickData::Solid(voxel) => fun(voxel),
+            BrickData::Parted(voxels) => voxels.iter().for_each(&mut fun),
+        };
+        for node_key in 0..self.nodes.len() {
+            if !self.nodes.key_is_valid(node_key) {
+                continue;
+            }
+            match self.nodes.get(node_key) {
+                NodeContent::Nothing | NodeContent::Internal(_) => {}
+                NodeContent::Leaf(bricks) => bricks.iter().for_each(&mut visit_brick),
+                NodeContent::UniformLeaf(brick) => visit_brick(brick),
+            }
+            visit_brick(&self.node_mips[node_key]);
+        }
+    }
+
+    /// Calls the given function for every palette index value stored in the nodes and MIPs of the tree, allowing to change them
+    /// MIPs of unused node slots are erased, as they are not part of the tree anymore
+    pub(crate) fn for_each_palette_index_mut<F: FnMut(&mut PaletteIndexValues)>(
+        &mut self,
+        mut fun: F,
+    ) {
+        let mut visit_brick = |brick: &mut BrickData<PaletteIndexValues>| match brick {
+            BrickData::Empty => {}
+            BrickData::Solid(voxel) => fun(voxel),
+            BrickData::Parted(voxels) => voxels.iter_mut().for_each(&mut fun),
+        };
+        for node_key in 0..self.nodes.len() {
+            if !self.nodes.key_is_valid(node_key) {
+                if let Some(mip) = self.node_mips.get_mut(node_key) {
+                    *mip = BrickData::Empty;
+                }
+                continue;
+            }
+            match self.nodes.get_mut(node_key) {
+                NodeContent::Nothing | NodeContent::Internal(_) => {}
+                NodeContent::Leaf(bricks) => bricks.iter_mut().for_each(&mut visit_brick),
+                NodeContent::UniformLeaf(brick) => visit_brick(brick),
+            }
+            visit_brick(&mut self.node_mips[node_key]);
+        }
+    }
+}
@@ -0,0 +1,755 @@
+use crate::{
+    boxtree::{
+        types::{
+            BatchUpdate, BoxTreeEntry, BrickData, NodeChildren, NodeContent, OctreeError,
+            PaletteIndexValues, PasteMode, StrategyUpdater,
+        },
+        update::region::{BoxRegion, UpdateRegion},
+        BoxTree, VoxelData, BOX_NODE_CHILDREN_COUNT, BOX_NODE_DIMENSION,
+    },
+    object_pool::empty_marker,
+    spatial::{math::vector::V3c, Cube},
+};
+use std::collections::{HashMap, HashSet};
+
+/// The area of a source tree to be pasted, and its place inside the target tree
+pub(crate) struct PasteRegion {
+    /// The first voxel of the pasted area inside the source tree
+    pub(crate) source_min: V3c<u32>,
+
+    /// The end of the pasted area (exclusive) inside the source tree
+    pub(crate) source_max: V3c<u32>,
+
+    /// The position of the pasted area inside the target tree
+    pub(crate) target_min: V3c<u32>,
+}
+
+impl PasteRegion {
+    /// Provides the part of the given source bounds inside the pasted area, in source coordinates
+    fn clip(&self, bounds: &Cube) -> Option<(V3c<u32>, V3c<u32>)> {
+        BoxRegion {
+            min_position: self.source_min,
+            max_position: self.source_max,
+        }
+        .clip(bounds)
+        .map(|area| (area.min_position, area.max_position))
+    }
+
+    /// Tells if the given source bounds are completely inside the pasted area
+    fn contains(&self, bounds: &Cube) -> bool {
+        self.clip(bounds)
+            .is_some_and(|(min_position, max_position)| {
+                min_position == V3c::from(bounds.min_position)
+                    && max_position == min_position + V3c::unit(bounds.size as u32)
+            })
+    }
+
+    /// Translates the given source position into the target tree
+    fn to_target(&self, position: &V3c<u32>) -> V3c<u32> {
+        *position - self.source_min + self.target_min
+    }
+}
+
+impl<T: VoxelData> BoxTree<T> {
+    /// Copies the voxels of the given axis aligned box into a new boxtree, starting from its origin
+    /// The new tree has the same brick dimension and MIP map strategy, and the smallest valid size containing the box
+    /// * `min_position` - the first voxel of the box, must be contained within the tree
+    /// * `max_position` - the end of the box (exclusive), each component must be at most the size of the tree
+    pub fn extract_region(
+        &self,
+        min_position: &V3c<u32>,
+        max_position: &V3c<u32>,
+    ) -> Result<BoxTree<T>, OctreeError> {
+        self.check_box_bounds(min_position, max_position)?;
+        let extent = (max_position.x.saturating_sub(min_position.x))
+            .max(max_position.y.saturating_sub(min_position.y))
+            .max(max_position.z.saturating_sub(min_position.z));
+        let mut size = self.brick_dim * BOX_NODE_DIMENSION as u32;
+        while size < extent {
+            size *= BOX_NODE_DIMENSION as u32;
+        }
+
+        let mut result = BoxTree::new(size, self.brick_dim)?;
+        result.auto_simplify = self.auto_simplify;
+        result.mip_map_strategy = self.mip_map_strategy.clone();
+        if Self::box_is_empty(min_position, max_position) {
+            return Ok(result);
+        }
+
+        // The result is empty, so skipping empty voxels doesn't change the result
+        result.paste_region(
+            self,
+            &PasteRegion {
+                source_min: *min_position,
+                source_max: *max_position,
+                target_min: V3c::unit(0),
+            },
+            PasteMode::SkipEmptySource,
+        )?;
+        Ok(result)
+    }
+
+    /// Copies the contents of the given tree into this tree at the given position
+    /// Parts of the source extending beyond the bounds of this tree are ignored.
+    /// When the position is aligned to the nodes of the source, whole nodes and bricks are copied directly.
+    /// * `source` - The tree to copy the voxels from; its whole volume is pasted
+    /// * `position` - The position of the origin of the source tree inside this tree, must be contained within the tree
+    /// * `mode` - Decides how the pasted voxels are combined with the existing ones, see @PasteMode
+    pub fn paste(
+        &mut self,
+        source: &BoxTree<T>,
+        position: &V3c<u32>,
+        mode: PasteMode,
+    ) -> Result<(), OctreeError> {
+        if !Cube::root_bounds(self.boxtree_size as f32).contains(&V3c::from(*position)) {
+            return Err(OctreeError::InvalidPosition {
+                x: position.x,
+                y: position.y,
+                z: position.z,
+            });
+        }
+
+        let source_max = V3c::new(
+            source.boxtree_size.min(self.boxtree_size - position.x),
+            source.boxtree_size.min(self.boxtree_size - position.y),
+            source.boxtree_size.min(self.boxtree_size - position.z),
+        );
+        self.paste_region(
+            source,
+            &PasteRegion {
+                source_min: V3c::unit(0),
+                source_max,
+                target_min: *position,
+            },
+            mode,
+        )
+    }
+
+    /// Copies the given area of the source tree into this tree
+    /// The pasted area is expected to fit inside this tree
+    /// * Returns with error if the palette can not hold the pasted colors or data, before any voxel is pasted
+    pub(crate) fn paste_region(
+        &mut self,
+        source: &BoxTree<T>,
+        region: &PasteRegion,
+        mode: PasteMode,
+    ) -> Result<(), OctreeError> {
+        // Every pasted palette entry is added before the tree is changed
+        let mut source_values = HashSet::new();
+        source.collect_palette_values(
+            BoxTree::<T>::ROOT_NODE_KEY as usize,
+            &Cube::root_bounds(source.boxtree_size as f32),
+            region,
+            &mut source_values,
+        );
+        let mut palette_map = HashMap::new();
+        for source_value in source_values {
+            let target_value = self.add_to_palette_keeping(
+                &NodeContent::pix_get_ref(
+                    &source_value,
+                    &source.voxel_color_palette,
+                    &source.voxel_data_palette,
+                ),
+                self.palette_overflow_strategy,
+                palette_map.values_mut(),
+            )?;
+            palette_map.insert(source_value, target_value);
+        }
+
+        // Areas cleared while pasting are part of the recorded edit
+        let target_max = region.to_target(&region.source_max);
+        self.record_box_update(&region.target_min, &target_max, |tree| {
+            tree.paste_node(
+                source,
+                BoxTree::<T>::ROOT_NODE_KEY as usize,
+                &Cube::root_bounds(source.boxtree_size as f32),
+                region,
+                mode,
+                &palette_map,
+            )
+        });
+        self.mark_changed(&region.target_min, &target_max);
+        Ok(())
+    }
+
+    /// Collects the palette index values stored in the given node, where it overlaps with the pasted area
+    fn collect_palette_values(
+        &self,
+        node_key: usize,
+        node_bounds: &Cube,
+        region: &PasteRegion,
+        values: &mut HashSet<PaletteIndexValues>,
+    ) {
+        if region.clip(node_bounds).is_none() {
+            return;
+        }
+        let mut collect_brick = |brick: &BrickData<PaletteIndexValues>| match brick {
+            BrickData::Empty => {}
+            BrickData::Solid(voxel) => {
+                values.insert(*voxel);
+            }
+            BrickData::Parted(voxels) => values.extend(voxels.iter()),
+        };
+        match self.nodes.get(node_key) {
+            NodeContent::Nothing => {}
+            NodeContent::Internal(_) => {
+                for sectant in 0..BOX_NODE_CHILDREN_COUNT as u8 {
+                    if let Some(child_key) = self.valid_child_for(node_key, sectant) {
+                        self.collect_palette_values(
+                            child_key,
+                            &node_bounds.child_bounds_for(sectant),
+                            region,
+                            values,
+                        );
+                    }
+                }
+            }
+            NodeContent::Leaf(bricks) => {
+                for (sectant, brick) in bricks.iter().enumerate() {
+                    if region
+                        .clip(&node_bounds.child_bounds_for(sectant as u8))
+                        .is_some()
+                    {
+                        collect_brick(brick);
+                    }
+                }
+            }
+            NodeContent::UniformLeaf(brick) => collect_brick(brick),
+        }
+    }
+
+    /// Copies the given node of the source tree into this tree, where it overlaps with the pasted area
+    /// * `palette_map` - Palette index values of the source tree mapped to the palette of this tree
+    fn paste_node(
+        &mut self,
+        source: &BoxTree<T>,
+        source_key: usize,
+        source_bounds: &Cube,
+        region: &PasteRegion,
+        mode: PasteMode,
+        palette_map: &HashMap<PaletteIndexValues, PaletteIndexValues>,
+    ) {
+        let Some((source_min, source_max)) = region.clip(source_bounds) else {
+            return;
+        };
+        if self.can_copy_directly(source, source_bounds, region, mode) {
+            self.copy_node(
+                source,
+                Some(source_key),
+                None,
+                &Cube {
+                    min_position: region.to_target(&source_min).into(),
+                    size: source_bounds.size,
+                },
+                palette_map,
+            );
+            return;
+        }
+
+        match source.nodes.get(source_key) {
+            NodeContent::Nothing => {
+                self.paste_content(&source_min, &source_max, region, mode, empty_marker());
+            }
+            NodeContent::Internal(_) => {
+                for sectant in 0..BOX_NODE_CHILDREN_COUNT as u8 {
+                    let child_bounds = source_bounds.child_bounds_for(sectant);
+                    if let Some(child_key) = source.valid_child_for(source_key, sectant) {
+                        self.paste_node(
+                            source,
+                            child_key,
+                            &child_bounds,
+                            region,
+                            mode,
+                            palette_map,
+                        );
+                    } else if let Some((child_min, child_max)) = region.clip(&child_bounds) {
+                        self.paste_content(&child_min, &child_max, region, mode, empty_marker());
+                    }
+                }
+            }
+            NodeContent::Leaf(bricks) => {
+                for (sectant, brick) in bricks.iter().enumerate() {
+                    self.paste_brick(
+                        source,
+                        brick,
+                        &source_bounds.child_bounds_for(sectant as u8),
+                        region,
+                        mode,
+                        palette_map,
+                    );
+                }
+            }
+            NodeContent::UniformLeaf(brick) => {
+                self.paste_brick(source, brick, source_bounds, region, mode, palette_map);
+            }
+        }
+    }
+
+    /// Copies the given brick of the source tree into this tree, where it overlaps with the pasted area
+    fn paste_brick(
+        &mut self,
+        source: &BoxTree<T>,
+        brick: &BrickData<PaletteIndexValues>,
+        source_bounds: &Cube,
+        region: &PasteRegion,
+        mode: PasteMode,
+        palette_map: &HashMap<PaletteIndexValues, PaletteIndexValues>,
+    ) {
+        let Some((source_min, source_max)) = region.clip(source_bounds) else {
+            return;
+        };
+        if source_bounds.size as u32 == self.brick_dim
+            && self.can_copy_directly(source, source_bounds, region, mode)
+        {
+            self.copy_node(
+                source,
+                None,
+                Some(brick),
+                &Cube {
+                    min_position: region.to_target(&source_min).into(),
+                    size: source_bounds.size,
+                },
+                palette_map,
+            );
+            return;
+        }
+
+        match brick {
+            BrickData::Empty => {
+                self.paste_content(&source_min, &source_max, region, mode, empty_marker());
+            }
+            BrickData::Solid(voxel) => {
+                self.paste_content(
+                    &source_min,
+                    &source_max,
+                    region,
+                    mode,
+                    Self::mapped_value(palette_map, voxel),
+                );
+            }
+            BrickData::Parted(voxels) => {
+                // Each voxel of the brick might cover multiple voxels of the tree
+                let cell_size = source_bounds.size / source.brick_dim as f32;
+                for (index, voxel) in voxels.iter().enumerate() {
+                    let brick_dim = source.brick_dim as usize;
+                    let cell_bounds = Cube {
+                        min_position: source_bounds.min_position
+                            + V3c::new(
+                                (index % brick_dim) as f32,
+                                ((index / brick_dim) % brick_dim) as f32,
+                                (index / (brick_dim * brick_dim)) as f32,
+                            ) * cell_size,
+                        size: cell_size,
+                    };
+                    if let Some((cell_min, cell_max)) = region.clip(&cell_bounds) {
+                        self.paste_content(
+                            &cell_min,
+                            &cell_max,
+                            region,
+                            mode,
+                            Self::mapped_value(palette_map, voxel),
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    /// Tells if the given source bounds can be copied into this tree as a whole
+    fn can_copy_directly(
+        &self,
+        source: &BoxTree<T>,
+        source_bounds: &Cube,
+        region: &PasteRegion,
+        mode: PasteMode,
+    ) -> bool {
+        let size = source_bounds.size as u32;
+        if source.brick_dim != self.brick_dim || !region.contains(source_bounds) {
+            return false;
+        }
+        let target_min = region.to_target(&V3c::from(source_bounds.min_position));
+        if !target_min.x.is_multiple_of(size)
+            || !target_min.y.is_multiple_of(size)
+            || !target_min.z.is_multiple_of(size)
+        {
+            return false;
+        }
+        match mode {
+            PasteMode::Overwrite => true,
+            PasteMode::OnlyIntoEmpty | PasteMode::SkipEmptySource => self
+                .iter_region(&target_min, &(target_min + V3c::unit(size)))
+                .is_ok_and(|mut voxels| voxels.next().is_none()),
+        }
+    }
+
+    /// Pastes the given voxel into every position of the given box
+    /// * `source_min` - The first voxel of the box, in source coordinates
+    /// * `source_max` - The end of the box (exclusive), in source coordinates
+    /// * `voxel` - The voxel to paste, in the palette of this tree
+    fn paste_content(
+        &mut self,
+        source_min: &V3c<u32>,
+        source_max: &V3c<u32>,
+        region: &PasteRegion,
+        mode: PasteMode,
+        voxel: PaletteIndexValues,
+    ) {
+        let target_min = region.to_target(source_min);
+        let target_max = region.to_target(source_max);
+        let pasted_empty = matches!(
+            NodeContent::pix_get_ref(&voxel, &self.voxel_color_palette, &self.voxel_data_palette),
+            BoxTreeEntry::Empty
+        );
+        match (mode, pasted_empty) {
+            (PasteMode::Overwrite, true) => {
+                self.update_region_internal(
+                    Self::ROOT_NODE_KEY as usize,
+                    &Cube::root_bounds(self.boxtree_size as f32),
+                    &BoxRegion {
+                        min_position: target_min,
+                        max_position: target_max,
+                    },
+                    empty_marker(),
+                    false,
+                );
+            }
+            (_, true) => {}
+            (PasteMode::Overwrite | PasteMode::SkipEmptySource, false) => {
+                self.update_region_internal(
+                    Self::ROOT_NODE_KEY as usize,
+                    &Cube::root_bounds(self.boxtree_size as f32),
+                    &BoxRegion {
+                        min_position: target_min,
+                        max_position: target_max,
+                    },
+                    voxel,
+                    false,
+                );
+            }
+            (PasteMode::OnlyIntoEmpty, false) => {
+                let mut empty_areas = vec![];
+                let mut empty_positions = vec![];
+                self.collect_empty_areas(
+                    Self::ROOT_NODE_KEY as usize,
+                    &Cube::root_bounds(self.boxtree_size as f32),
+                    &BoxRegion {
+                        min_position: target_min,
+                        max_position: target_max,
+                    },
+                    &mut empty_areas,
+                    &mut empty_positions,
+                );
+                for area in empty_areas {
+                    self.update_region_internal(
+                        Self::ROOT_NODE_KEY as usize,
+                        &Cube::root_bounds(self.boxtree_size as f32),
+                        &area,
+                        voxel,
+                        false,
+                    );
+                }
+                self.batch(|batch| {
+                    batch
+                        .updates
+                        .extend(empty_positions.into_iter().map(|position| BatchUpdate {
+                            position,
+                            content: voxel,
+                            overwrite_if_empty: true,
+                        }));
+                });
+            }
+        }
+    }
+
+    /// Collects the empty parts of the given node inside the given region
+    /// Empty nodes and bricks are found through their occupancy, only voxels of partly filled bricks are checked one by one
+    /// * `empty_areas` - Boxes inside the region without any data
+    /// * `empty_positions` - Empty voxels of partly filled bricks inside the region
+    fn collect_empty_areas(
+        &self,
+        node_key: usize,
+        node_bounds: &Cube,
+        region: &BoxRegion,
+        empty_areas: &mut Vec<BoxRegion>,
+        empty_positions: &mut Vec<V3c<u32>>,
+    ) {
+        let Some(area) = region.clip(node_bounds) else {
+            return;
+        };
+        let occupied_bits = self.stored_occupied_bits(node_key);
+        if 0 == occupied_bits {
+            empty_areas.push(area);
+            return;
+        }
+        match self.nodes.get(node_key) {
+            NodeContent::Nothing => empty_areas.push(area),
+            NodeContent::Internal(_) => {
+                for sectant in 0..BOX_NODE_CHILDREN_COUNT as u8 {
+                    let child_bounds = node_bounds.child_bounds_for(sectant);
+                    match self.valid_child_for(node_key, sectant) {
+                        Some(child_key) if 0 != occupied_bits & (0x01 << sectant) => {
+                            self.collect_empty_areas(
+                                child_key,
+                                &child_bounds,
+                                region,
+                                empty_areas,
+                                empty_positions,
+                            );
+                        }
+                        _ => empty_areas.extend(region.clip(&child_bounds)),
+                    }
+                }
+            }
+            NodeContent::Leaf(bricks) => {
+                for (sectant, brick) in bricks.iter().enumerate() {
+                    let brick_bounds = node_bounds.child_bounds_for(sectant as u8);
+                    if 0 == occupied_bits & (0x01 << sectant) {
+                        empty_areas.extend(region.clip(&brick_bounds));
+                    } else {
+                        self.collect_empty_brick_areas(
+                            brick,
+                            &brick_bounds,
+                            region,
+                            empty_areas,
+                            empty_positions,
+                        );
+                    }
+                }
+            }
+            NodeContent::UniformLeaf(brick) => {
+                self.collect_empty_brick_areas(
+                    brick,
+                    node_bounds,
+                    region,
+                    empty_areas,
+                    empty_positions,
+                );
+            }
+        }
+    }
+
+    /// Collects the empty parts of the given brick inside the given region, see @collect_empty_areas
+    fn collect_empty_brick_areas(
+        &self,
+        brick: &BrickData<PaletteIndexValues>,
+        brick_bounds: &Cube,
+        region: &BoxRegion,
+        empty_areas: &mut Vec<BoxRegion>,
+        empty_positions: &mut Vec<V3c<u32>>,
+    ) {
+        let is_empty = |voxel: &PaletteIndexValues| {
+            NodeContent::pix_points_to_empty(
+                voxel,
+                &self.voxel_color_palette,
+                &self.voxel_data_palette,
+            )
+        };
+        match brick {
+            BrickData::Empty => empty_areas.extend(region.clip(brick_bounds)),
+            BrickData::Solid(voxel) => {
+                if is_empty(voxel) {
+                    empty_areas.extend(region.clip(brick_bounds));
+                }
+            }
+            BrickData::Parted(voxels) => {
+                // Each voxel of the brick might cover multiple voxels of the tree
+                let brick_dim = self.brick_dim as usize;
+                let cell_size = brick_bounds.size / self.brick_dim as f32;
+                for (index, voxel) in voxels.iter().enumerate() {
+                    if !is_empty(voxel) {
+                        continue;
+                    }
+                    let cell_bounds = Cube {
+                        min_position: brick_bounds.min_position
+                            + V3c::new(
+                                (index % brick_dim) as f32,
+                                ((index / brick_dim) % brick_dim) as f32,
+                                (index / (brick_dim * brick_dim)) as f32,
+                            ) * cell_size,
+                        size: cell_size,
+                    };
+                    if 1. < cell_size {
+                        empty_areas.extend(region.clip(&cell_bounds));
+                    } else if region.contains_voxel(&cell_bounds.min_position.into()) {
+                        empty_positions.push(cell_bounds.min_position.into());
+                    }
+                }
+            }
+        }
+    }
+
+    /// Replaces the node of this tree at the given bounds with a copy of the given source node or brick
+    /// Nodes on the way to the target are created or subdivided as needed
+    /// * `source_key` - The node of the source tree to copy, if any
+    /// * `source_brick` - The brick of the source tree to copy, in case no source node is given
+    /// * `target_bounds` - The bounds of the node to replace inside this tree
+    fn copy_node(
+        &mut self,
+        source: &BoxTree<T>,
+        source_key: Option<usize>,
+        source_brick: Option<&BrickData<PaletteIndexValues>>,
+        target_bounds: &Cube,
+        palette_map: &HashMap<PaletteIndexValues, PaletteIndexValues>,
+    ) {
+        // Find the target node, creating the nodes on its path if needed
+        let mut path = vec![(
+            Self::ROOT_NODE_KEY as usize,
+            Cube::root_bounds(self.boxtree_size as f32),
+            0,
+        )];
+        loop {
+            let (node_key, node_bounds, _) = *path.last().unwrap();
+            if node_bounds.size <= target_bounds.size {
+                break;
+            }
+            let sectant = node_bounds.sectant_for(&target_bounds.min_position);
+            match self.nodes.get(node_key) {
+                NodeContent::Nothing | NodeContent::UniformLeaf(BrickData::Empty) => {
+                    *self.nodes.get_mut(node_key) = NodeContent::Internal(0);
+                    self.node_children[node_key] = NodeChildren::NoChildren;
+                }
+                NodeContent::Leaf(_) | NodeContent::UniformLeaf(_) => {
+                    self.subdivide_leaf_to_nodes(node_key, sectant as usize);
+                }
+                NodeContent::Internal(_) => {}
+            }
+            let child_key = match self.valid_child_for(node_key, sectant) {
+                Some(child_key) => child_key,
+                None => self.push_child_node(
+                    node_key,
+                    sectant,
+                    NodeContent::Nothing,
+                    NodeChildren::NoChildren,
+                ),
+            };
+            path.push((child_key, node_bounds.child_bounds_for(sectant), sectant));
+        }
+
+        // Replace the contents of the target node
+        let (target_key, _, _) = *path.last().unwrap();
+        self.deallocate_children_of(target_key);
+        match (source_key, source_brick) {
+            (Some(source_key), _) => {
+                self.copy_subtree(source, source_key, target_key, target_bounds, palette_map);
+            }
+            (None, Some(brick)) => {
+                let brick = Self::remap_brick(brick, palette_map);
+                self.node_children[target_key] =
+                    NodeChildren::OccupancyBitmap(brick.calculate_occupied_bits(
+                        self.brick_dim as usize,
+                        &self.voxel_color_palette,
+                        &self.voxel_data_palette,
+                    ));
+                *self.nodes.get_mut(target_key) = NodeContent::UniformLeaf(brick);
+                self.node_mips[target_key] = BrickData::Empty;
+            }
+            (None, None) => panic!("Expected either a source node or a source brick to copy"),
+        }
+
+        // Refresh the nodes on the path, removing the ones left empty
+        let region = BoxRegion {
+            min_position: target_bounds.min_position.into(),
+            max_position: V3c::<u32>::from(target_bounds.min_position)
+                + V3c::unit(target_bounds.size as u32),
+        };
+        for level in (1..path.len()).rev() {
+            let (child_key, _, sectant) = path[level];
+            let (node_key, node_bounds, _) = path[level - 1];
+            if 0 == self.stored_occupied_bits(child_key) {
+                self.remove_child_node(node_key, sectant);
+            }
+            self.post_process_region_update(node_key, &node_bounds, &region);
+        }
+    }
+
+    /// Copies the given node of the source tree, alongside its children into the given node of this tree
+    fn copy_subtree(
+        &mut self,
+        source: &BoxTree<T>,
+        source_key: usize,
+        target_key: usize,
+        target_bounds: &Cube,
+        palette_map: &HashMap<PaletteIndexValues, PaletteIndexValues>,
+    ) {
+        let content = match source.nodes.get(source_key) {
+            NodeContent::Nothing => NodeContent::Nothing,
+            NodeContent::Internal(occupied_bits) => NodeContent::Internal(*occupied_bits),
+            NodeContent::Leaf(bricks) => NodeContent::Leaf(
+                bricks
+                    .clone()
+                    .map(|brick| Self::remap_brick(&brick, palette_map)),
+            ),
+            NodeContent::UniformLeaf(brick) => {
+                NodeContent::UniformLeaf(Self::remap_brick(brick, palette_map))
+            }
+        };
+        *self.nodes.get_mut(target_key) = content;
+
+        let children = match &source.node_children[source_key] {
+            NodeChildren::Children(source_children) => {
+                let mut children = [empty_marker(); BOX_NODE_CHILDREN_COUNT];
+                for sectant in 0..BOX_NODE_CHILDREN_COUNT {
+                    if !source.nodes.key_is_valid(source_children[sectant] as usize) {
+                        continue;
+                    }
+                    let child_key = self.nodes.push(NodeContent::Nothing);
+                    self.node_children.resize(
+                        self.node_children.len().max(self.nodes.len()),
+                        NodeChildren::default(),
+                    );
+                    self.node_mips
+                        .resize(self.node_mips.len().max(self.nodes.len()), BrickData::Empty);
+                    self.copy_subtree(
+                        source,
+                        source_children[sectant] as usize,
+                        child_key,
+                        &target_bounds.child_bounds_for(sectant as u8),
+                        palette_map,
+                    );
+                    children[sectant] = child_key as u32;
+                }
+                NodeChildren::Children(children)
+            }
+            NodeChildren::NoChildren => NodeChildren::NoChildren,
+            NodeChildren::OccupancyBitmap(occupied_bits) => {
+                NodeChildren::OccupancyBitmap(*occupied_bits)
+            }
+        };
+        self.node_children[target_key] = children;
+
+        // MIPs are rebuilt with the strategy of this tree, after the children are copied
+        self.node_mips[target_key] = BrickData::Empty;
+        if self.mip_map_strategy.enabled
+            && !matches!(self.nodes.get(target_key), NodeContent::Nothing)
+        {
+            StrategyUpdater(self).recalculate_mip(target_key, target_bounds);
+        }
+    }
+
+    /// Provides the given value of the source tree mapped to the palettes of this tree
+    /// Values missing from the map are pasted as empty
+    fn mapped_value(
+        palette_map: &HashMap<PaletteIndexValues, PaletteIndexValues>,
+        voxel: &PaletteIndexValues,
+    ) -> PaletteIndexValues {
+        palette_map.get(voxel).copied().unwrap_or_else(empty_marker)
+    }
+
+    /// Provides the given brick of the source tree with its values mapped to the palettes of this tree
+    fn remap_brick(
+        brick: &BrickData<PaletteIndexValues>,
+        palette_map: &HashMap<PaletteIndexValues, PaletteIndexValues>,
+    ) -> BrickData<PaletteIndexValues> {
+        match brick {
+            BrickData::Empty => BrickData::Empty,
+            BrickData::Solid(voxel) => BrickData::Solid(Self::mapped_value(palette_map, voxel)),
+            BrickData::Parted(voxels) => BrickData::Parted(
+                voxels
+                    .iter()
+                    .map(|voxel| Self::mapped_value(palette_map, voxel))
+                    .collect::<Vec<_>>()
+                    .into(),
+            ),
+        }
+    }
+}
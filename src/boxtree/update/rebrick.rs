@@ -0,0 +1,57 @@
+use crate::{
+    boxtree::{
+        types::{NodeContent, OctreeError, StrategyUpdater},
+        update::region::BoxRegion,
+        BoxTree, VoxelData,
+    },
+    spatial::{math::vector::V3c, Cube},
+};
+
+impl<T: VoxelData> BoxTree<T> {
+    /// Creates a copy of the tree with the given brick dimension, keeping its contents
+    /// The node and brick layout is rebuilt for the new dimension, the palettes and the MIP strategy are kept.
+    /// * `brick_dimension` - The new brick dimension, must be valid for the size of the tree, see @new
+    /// * Returns with error if the brick dimension is invalid for the size of the tree
+    pub fn with_brick_dimension(&self, brick_dimension: u32) -> Result<BoxTree<T>, OctreeError> {
+        let mut result = BoxTree::new(self.boxtree_size, brick_dimension)?;
+        result.voxel_color_palette = self.voxel_color_palette.clone();
+        result.voxel_data_palette = self.voxel_data_palette.clone();
+        result.map_to_color_index_in_palette = self.map_to_color_index_in_palette.clone();
+        result.map_to_data_index_in_palette = self.map_to_data_index_in_palette.clone();
+        result.palette_overflow_strategy = self.palette_overflow_strategy;
+
+        // As the palettes are the same, stored voxels can be inserted without conversion
+        self.visit_uniform_areas(
+            Self::ROOT_NODE_KEY as usize,
+            &Cube::root_bounds(self.boxtree_size as f32),
+            &mut |bounds, voxel| {
+                if NodeContent::pix_points_to_empty(
+                    &voxel,
+                    &self.voxel_color_palette,
+                    &self.voxel_data_palette,
+                ) {
+                    return;
+                }
+                let min_position = V3c::<u32>::from(bounds.min_position);
+                result.update_region_internal(
+                    Self::ROOT_NODE_KEY as usize,
+                    &Cube::root_bounds(self.boxtree_size as f32),
+                    &BoxRegion {
+                        min_position,
+                        max_position: min_position + V3c::unit(bounds.size as u32),
+                    },
+                    voxel,
+                    false,
+                );
+            },
+        );
+
+        // MIPs are built once the whole content is in place
+        result.auto_simplify = self.auto_simplify;
+        result.mip_map_strategy = self.mip_map_strategy.clone();
+        if result.mip_map_strategy.enabled {
+            StrategyUpdater(&mut result).recalculate_mips();
+        }
+        Ok(result)
+    }
+}
@@ -0,0 +1,745 @@
+use crate::{
+    boxtree::{
+        iterate::execute_for_relevant_sectants_in_range,
+        types::{
+            BoxTreeEntry, BrickData, NodeChildren, NodeContent, OctreeError, PaletteIndexValues,
+            StrategyUpdater,
+        },
+        BoxTree, VoxelData, BOX_NODE_CHILDREN_COUNT, BOX_NODE_DIMENSION,
+    },
+    object_pool::empty_marker,
+    spatial::{
+        math::{flat_projection, vector::V3c},
+        Cube,
+    },
+};
+
+/// Describes how an update region relates to an area inside the tree
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum RegionOverlap {
+    /// The area has no voxels inside the region
+    Outside,
+    /// Some voxels of the area might be inside the region
+    Partial,
+    /// Every voxel of the area is inside the region
+    Inside,
+}
+
+/// A set of voxels to be updated together
+pub(crate) trait UpdateRegion {
+    /// Tells how the given bounds relate to the region.
+    /// It is acceptable to report `Partial` overlap when unsure
+    fn overlap(&self, bounds: &Cube) -> RegionOverlap;
+
+    /// Tells if the voxel at the given position is part of the region
+    fn contains_voxel(&self, position: &V3c<u32>) -> bool;
+
+    /// Calls the given function for every child sectant of the given node which might overlap with the region
+    /// * `node_bounds` - The bounds of the node
+    /// * `fun` - The function to execute: |target_child_sectant| { ... }
+    fn execute_for_relevant_sectants<F: FnMut(u8)>(&self, _node_bounds: &Cube, mut fun: F) {
+        for child_sectant in 0..BOX_NODE_CHILDREN_COUNT as u8 {
+            fun(child_sectant);
+        }
+    }
+}
+
+/// Axis aligned box with exclusive maximum position
+pub(crate) struct BoxRegion {
+    pub(crate) min_position: V3c<u32>,
+    pub(crate) max_position: V3c<u32>,
+}
+
+impl BoxRegion {
+    /// Provides the part of the region inside the given bounds, if any
+    pub(crate) fn clip(&self, bounds: &Cube) -> Option<BoxRegion> {
+        let bounds_min = V3c::<u32>::from(bounds.min_position);
+        let bounds_max = bounds_min + V3c::unit(bounds.size as u32);
+        let min_position = V3c::new(
+            bounds_min.x.max(self.min_position.x),
+            bounds_min.y.max(self.min_position.y),
+            bounds_min.z.max(self.min_position.z),
+        );
+        let max_position = V3c::new(
+            bounds_max.x.min(self.max_position.x),
+            bounds_max.y.min(self.max_position.y),
+            bounds_max.z.min(self.max_position.z),
+        );
+        if BoxTree::<u32>::box_is_empty(&min_position, &max_position) {
+            None
+        } else {
+            Some(BoxRegion {
+                min_position,
+                max_position,
+            })
+        }
+    }
+}
+
+impl UpdateRegion for BoxRegion {
+    fn overlap(&self, bounds: &Cube) -> RegionOverlap {
+        let min_position = V3c::<f32>::from(self.min_position);
+        let max_position = V3c::<f32>::from(self.max_position);
+        let bounds_max = bounds.min_position + V3c::unit(bounds.size);
+        if bounds_max.x <= min_position.x
+            || bounds_max.y <= min_position.y
+            || bounds_max.z <= min_position.z
+            || max_position.x <= bounds.min_position.x
+            || max_position.y <= bounds.min_position.y
+            || max_position.z <= bounds.min_position.z
+        {
+            return RegionOverlap::Outside;
+        }
+        if min_position.x <= bounds.min_position.x
+            && min_position.y <= bounds.min_position.y
+            && min_position.z <= bounds.min_position.z
+            && bounds_max.x <= max_position.x
+            && bounds_max.y <= max_position.y
+            && bounds_max.z <= max_position.z
+        {
+            return RegionOverlap::Inside;
+        }
+        RegionOverlap::Partial
+    }
+
+    fn contains_voxel(&self, position: &V3c<u32>) -> bool {
+        self.min_position.x <= position.x
+            && self.min_position.y <= position.y
+            && self.min_position.z <= position.z
+            && position.x < self.max_position.x
+            && position.y < self.max_position.y
+            && position.z < self.max_position.z
+    }
+
+    fn execute_for_relevant_sectants<F: FnMut(u8)>(&self, node_bounds: &Cube, mut fun: F) {
+        execute_for_relevant_sectants_in_range(
+            node_bounds,
+            &self.min_position,
+            &(self.max_position - self.min_position),
+            |_, _, target_child_sectant, _| fun(target_child_sectant),
+        );
+    }
+}
+
+impl<T: VoxelData> BoxTree<T> {
+    /// Inserts the given data into every voxel of the given axis aligned box
+    /// If there is already available data it overwrites it, except if all components are empty
+    /// Nodes and bricks completely inside the box are overwritten as a whole, instead of voxel by voxel
+    /// * `min_position` - the first voxel of the box, must be contained within the tree
+    /// * `max_position` - the end of the box (exclusive), each component must be at most the size of the tree
+    /// * `data` - The data to insert - cloned if needed
+    pub fn insert_box<'a, E: Into<BoxTreeEntry<'a, T>>>(
+        &mut self,
+        min_position: &V3c<u32>,
+        max_position: &V3c<u32>,
+        data: E,
+    ) -> Result<(), OctreeError>
+    where
+        T: 'a,
+    {
+        let data = data.into();
+        self.check_box_bounds(min_position, max_position)?;
+
+        // Nothing to do when no operations are requested
+        if data.is_none() || Self::box_is_empty(min_position, max_position) {
+            return Ok(());
+        }
+
+        let target_content = self.add_to_palette(&data)?;
+        self.record_box_edit(min_position, max_position);
+        if self.update_region_internal(
+            Self::ROOT_NODE_KEY as usize,
+            &Cube::root_bounds(self.boxtree_size as f32),
+            &BoxRegion {
+                min_position: *min_position,
+                max_position: *max_position,
+            },
+            target_content,
+            false,
+        ) {
+            self.mark_changed(min_position, max_position);
+        }
+        Ok(())
+    }
+
+    /// Erases every voxel inside the given axis aligned box
+    /// Nodes completely inside the box are removed as a whole, instead of voxel by voxel
+    /// * `min_position` - the first voxel of the box, must be contained within the tree
+    /// * `max_position` - the end of the box (exclusive), each component must be at most the size of the tree
+    pub fn clear_box(
+        &mut self,
+        min_position: &V3c<u32>,
+        max_position: &V3c<u32>,
+    ) -> Result<(), OctreeError> {
+        self.check_box_bounds(min_position, max_position)?;
+        if Self::box_is_empty(min_position, max_position) {
+            return Ok(());
+        }
+
+        self.record_box_edit(min_position, max_position);
+        if self.update_region_internal(
+            Self::ROOT_NODE_KEY as usize,
+            &Cube::root_bounds(self.boxtree_size as f32),
+            &BoxRegion {
+                min_position: *min_position,
+                max_position: *max_position,
+            },
+            empty_marker(),
+            false,
+        ) {
+            self.mark_changed(min_position, max_position);
+        }
+        Ok(())
+    }
+
+    /// Checks if the box given by its minimum and (exclusive) maximum position can be updated inside the tree
+    pub(crate) fn check_box_bounds(
+        &self,
+        min_position: &V3c<u32>,
+        max_position: &V3c<u32>,
+    ) -> Result<(), OctreeError> {
+        if !Cube::root_bounds(self.boxtree_size as f32).contains(&V3c::from(*min_position)) {
+            return Err(OctreeError::InvalidPosition {
+                x: min_position.x,
+                y: min_position.y,
+                z: min_position.z,
+            });
+        }
+        if max_position.x > self.boxtree_size
+            || max_position.y > self.boxtree_size
+            || max_position.z > self.boxtree_size
+        {
+            return Err(OctreeError::InvalidPosition {
+                x: max_position.x,
+                y: max_position.y,
+                z: max_position.z,
+            });
+        }
+        Ok(())
+    }
+
+    /// Tells if the box given by its minimum and (exclusive) maximum position contains no voxels
+    pub(crate) fn box_is_empty(min_position: &V3c<u32>, max_position: &V3c<u32>) -> bool {
+        min_position.x >= max_position.x
+            || min_position.y >= max_position.y
+            || min_position.z >= max_position.z
+    }
+
+    /// Updates every voxel inside the given region under the given node with the given content
+    /// Erases data in the region if the content points to empty
+    /// * `node_key` - The node to update
+    /// * `node_bounds` - The bounds of the node to update
+    /// * `region` - The region to update, expected to overlap with the node bounds
+    /// * `target_content` - The content to set inside the region
+    /// * `paint` - If true, only the voxels already containing data are updated,
+    ///   overwriting only the components present in the given content
+    /// * Returns with true if the node was changed by the operation
+    pub(crate) fn update_region_internal<R: UpdateRegion>(
+        &mut self,
+        node_key: usize,
+        node_bounds: &Cube,
+        region: &R,
+        target_content: PaletteIndexValues,
+        paint: bool,
+    ) -> bool {
+        let clearing = NodeContent::pix_points_to_empty(
+            &target_content,
+            &self.voxel_color_palette,
+            &self.voxel_data_palette,
+        );
+        match self.nodes.get(node_key) {
+            NodeContent::Nothing if clearing || paint => return false,
+            NodeContent::UniformLeaf(BrickData::Solid(voxel))
+                if *voxel == self.region_voxel(*voxel, target_content, paint) =>
+            {
+                return false;
+            }
+            _ => {}
+        }
+
+        let updated = if node_bounds.size > (self.brick_dim * BOX_NODE_DIMENSION as u32) as f32 {
+            self.update_region_in_children(node_key, node_bounds, region, target_content, paint)
+        } else if node_bounds.size > self.brick_dim as f32 {
+            self.update_region_in_bricks(node_key, node_bounds, region, target_content, paint)
+        } else {
+            self.update_region_in_brick(node_key, node_bounds, region, target_content, paint)
+        };
+
+        // Nodes left without content are cleaned up too, e.g. new nodes without voxels in the region
+        if updated || 0 == self.stored_occupied_bits(node_key) {
+            self.post_process_region_update(node_key, node_bounds, region);
+        }
+        updated
+    }
+
+    /// Provides the value of a voxel inside an updated region
+    /// * `voxel` - The current value of the voxel
+    /// * `target_content` - The content to set inside the region
+    /// * `paint` - If true, only components present in the target content are updated, and only for non-empty voxels
+    fn region_voxel(
+        &self,
+        voxel: PaletteIndexValues,
+        target_content: PaletteIndexValues,
+        paint: bool,
+    ) -> PaletteIndexValues {
+        if !paint {
+            return target_content;
+        }
+        if NodeContent::pix_points_to_empty(
+            &voxel,
+            &self.voxel_color_palette,
+            &self.voxel_data_palette,
+        ) {
+            return voxel;
+        }
+        let mut result = voxel;
+        if NodeContent::pix_color_is_some(&target_content) {
+            result = NodeContent::pix_overwrite_color(result, &target_content);
+        }
+        if NodeContent::pix_data_is_some(&target_content) {
+            result = NodeContent::pix_overwrite_data(result, &target_content);
+        }
+        result
+    }
+
+    /// Updates the children nodes of the given node inside the given region,
+    /// which is expected to be large enough so its children are not bricks
+    fn update_region_in_children<R: UpdateRegion>(
+        &mut self,
+        node_key: usize,
+        node_bounds: &Cube,
+        region: &R,
+        target_content: PaletteIndexValues,
+        paint: bool,
+    ) -> bool {
+        let clearing = NodeContent::pix_points_to_empty(
+            &target_content,
+            &self.voxel_color_palette,
+            &self.voxel_data_palette,
+        );
+        match self.nodes.get(node_key) {
+            NodeContent::UniformLeaf(BrickData::Empty) => {
+                *self.nodes.get_mut(node_key) = NodeContent::Internal(0);
+                self.node_children[node_key] = NodeChildren::NoChildren;
+            }
+            NodeContent::Leaf(_) | NodeContent::UniformLeaf(_) => {
+                // The leaf needs to be divided into separate nodes,
+                // with its children having the same data as the current node to keep integrity
+                self.subdivide_leaf_to_nodes(node_key, 0);
+                if self
+                    .valid_child_for(node_key, 0)
+                    .is_some_and(|child_key| NodeContent::Nothing == *self.nodes.get(child_key))
+                {
+                    self.remove_child_node(node_key, 0);
+                }
+            }
+            NodeContent::Nothing => {
+                *self.nodes.get_mut(node_key) = NodeContent::Internal(0);
+                self.node_children[node_key] = NodeChildren::NoChildren;
+            }
+            NodeContent::Internal(_) => {}
+        }
+
+        let mut updated = false;
+        region.execute_for_relevant_sectants(node_bounds, |child_sectant| {
+            let child_bounds = node_bounds.child_bounds_for(child_sectant);
+            let overlap = region.overlap(&child_bounds);
+            let child_key = self.node_children[node_key].child(child_sectant);
+            match overlap {
+                RegionOverlap::Outside => return,
+                RegionOverlap::Inside if !paint => {
+                    // Whole child node to be overwritten with data
+                    // Occupied bits are set in post-processing
+                    if clearing {
+                        if self.nodes.key_is_valid(child_key) {
+                            self.remove_child_node(node_key, child_sectant);
+                            updated = true;
+                        }
+                        return;
+                    }
+
+                    if self.nodes.key_is_valid(child_key) {
+                        if matches!(
+                            self.nodes.get(child_key),
+                            NodeContent::UniformLeaf(BrickData::Solid(voxel)) if *voxel == target_content
+                        ) {
+                            return;
+                        }
+                        self.deallocate_children_of(child_key);
+                        *self.nodes.get_mut(child_key) =
+                            NodeContent::UniformLeaf(BrickData::Solid(target_content));
+                        self.node_children[child_key] = NodeChildren::OccupancyBitmap(u64::MAX);
+                        self.node_mips[child_key] = BrickData::Empty;
+                    } else {
+                        self.push_child_node(
+                            node_key,
+                            child_sectant,
+                            NodeContent::UniformLeaf(BrickData::Solid(target_content)),
+                            NodeChildren::OccupancyBitmap(u64::MAX),
+                        );
+                    }
+                    updated = true;
+                    return;
+                }
+                RegionOverlap::Inside | RegionOverlap::Partial => {}
+            }
+
+            // Child node is partially covered by the update
+            let child_key = if self.nodes.key_is_valid(child_key) {
+                child_key
+            } else if clearing || paint {
+                // Nothing to update inside a missing child
+                return;
+            } else {
+                self.push_child_node(
+                    node_key,
+                    child_sectant,
+                    NodeContent::Nothing,
+                    NodeChildren::NoChildren,
+                )
+            };
+
+            updated |= self.update_region_internal(
+                child_key,
+                &child_bounds,
+                region,
+                target_content,
+                paint,
+            );
+
+            if let NodeContent::Nothing = self.nodes.get(child_key) {
+                self.remove_child_node(node_key, child_sectant);
+            }
+        });
+        updated
+    }
+
+    /// Updates the bricks of the given node inside the given region,
+    /// which is expected to be the size where its children are bricks
+    fn update_region_in_bricks<R: UpdateRegion>(
+        &mut self,
+        node_key: usize,
+        node_bounds: &Cube,
+        region: &R,
+        target_content: PaletteIndexValues,
+        paint: bool,
+    ) -> bool {
+        // Changing the layout of a node with content needs post-processing even without voxel changes
+        let mut updated = !matches!(
+            self.nodes.get(node_key),
+            NodeContent::Leaf(_) | NodeContent::Nothing
+        );
+        self.convert_to_leaf(node_key, node_bounds);
+
+        region.execute_for_relevant_sectants(node_bounds, |child_sectant| {
+            let child_bounds = node_bounds.child_bounds_for(child_sectant);
+            let overlap = region.overlap(&child_bounds);
+            if let RegionOverlap::Outside = overlap {
+                return;
+            }
+
+            let NodeContent::Leaf(bricks) = self.nodes.get(node_key) else {
+                panic!("Expected node to be a Leaf after conversion");
+            };
+            if let Some(new_brick) = self.updated_brick(
+                &bricks[child_sectant as usize],
+                &child_bounds,
+                region,
+                overlap,
+                target_content,
+                paint,
+            ) {
+                let NodeContent::Leaf(bricks) = self.nodes.get_mut(node_key) else {
+                    panic!("Expected node to be a Leaf after conversion");
+                };
+                bricks[child_sectant as usize] = new_brick;
+                updated = true;
+            }
+        });
+        updated
+    }
+
+    /// Updates the given node inside the given region,
+    /// which is expected to be the size of a single brick
+    fn update_region_in_brick<R: UpdateRegion>(
+        &mut self,
+        node_key: usize,
+        node_bounds: &Cube,
+        region: &R,
+        target_content: PaletteIndexValues,
+        paint: bool,
+    ) -> bool {
+        let Some(new_content) = self.updated_brick(
+            &self.brick_of_node(node_key, node_bounds),
+            node_bounds,
+            region,
+            region.overlap(node_bounds),
+            target_content,
+            paint,
+        ) else {
+            return false;
+        };
+
+        if matches!(self.nodes.get(node_key), NodeContent::UniformLeaf(brick) if *brick == new_content)
+        {
+            return false;
+        }
+        self.deallocate_children_of(node_key);
+        *self.nodes.get_mut(node_key) = NodeContent::UniformLeaf(new_content);
+        self.node_children[node_key] = NodeChildren::OccupancyBitmap(0);
+        true
+    }
+
+    /// Provides the updated version of the given brick mapped 1:1 to voxels
+    /// * `brick` - The brick to update
+    /// * `brick_bounds` - The bounds of the brick
+    /// * `region` - The region to update
+    /// * `overlap` - How the region overlaps with the bounds of the brick
+    /// * `target_content` - The content to set inside the region
+    /// * `paint` - If true, only the voxels already containing data are updated
+    /// * Returns with the new brick data, or None if there is no change in it
+    fn updated_brick<R: UpdateRegion>(
+        &self,
+        brick: &BrickData<PaletteIndexValues>,
+        brick_bounds: &Cube,
+        region: &R,
+        overlap: RegionOverlap,
+        target_content: PaletteIndexValues,
+        paint: bool,
+    ) -> Option<BrickData<PaletteIndexValues>> {
+        let clearing = NodeContent::pix_points_to_empty(
+            &target_content,
+            &self.voxel_color_palette,
+            &self.voxel_data_palette,
+        );
+        let new_brick = match (brick, overlap) {
+            (_, RegionOverlap::Outside) => return None,
+            (BrickData::Empty, _) if clearing || paint => return None,
+            (BrickData::Solid(voxel), RegionOverlap::Inside) => {
+                if clearing {
+                    BrickData::Empty
+                } else {
+                    BrickData::Solid(self.region_voxel(*voxel, target_content, paint))
+                }
+            }
+            (_, RegionOverlap::Inside) if !paint => {
+                // Whole brick to be overwritten with data
+                if clearing {
+                    BrickData::Empty
+                } else {
+                    BrickData::Solid(target_content)
+                }
+            }
+            _ => {
+                let mut voxels = match brick {
+                    BrickData::Empty => {
+                        vec![empty_marker::<PaletteIndexValues>(); self.brick_dim.pow(3) as usize]
+                    }
+                    BrickData::Solid(voxel) => vec![*voxel; self.brick_dim.pow(3) as usize],
+                    BrickData::Parted(voxels) => voxels.to_vec(),
+                };
+                let brick_position = V3c::<u32>::from(brick_bounds.min_position);
+                for x in 0..self.brick_dim {
+                    for y in 0..self.brick_dim {
+                        for z in 0..self.brick_dim {
+                            if !region.contains_voxel(&(brick_position + V3c::new(x, y, z))) {
+                                continue;
+                            }
+                            let flat_index = flat_projection(
+                                x as usize,
+                                y as usize,
+                                z as usize,
+                                self.brick_dim as usize,
+                            );
+                            voxels[flat_index] =
+                                self.region_voxel(voxels[flat_index], target_content, paint);
+                        }
+                    }
+                }
+                BrickData::Parted(voxels.into())
+            }
+        };
+
+        if *brick == new_brick {
+            None
+        } else {
+            Some(new_brick)
+        }
+    }
+
+    /// Converts the given node into a Leaf without losing any of its content.
+    /// Expects the node to be the size where its children are bricks
+    pub(crate) fn convert_to_leaf(&mut self, node_key: usize, node_bounds: &Cube) {
+        let bricks: [BrickData<PaletteIndexValues>; BOX_NODE_CHILDREN_COUNT] = match self
+            .nodes
+            .get(node_key)
+        {
+            NodeContent::Leaf(_) => return,
+            NodeContent::Nothing => vec![BrickData::Empty; BOX_NODE_CHILDREN_COUNT]
+                .try_into()
+                .unwrap(),
+            NodeContent::UniformLeaf(brick) => match brick {
+                BrickData::Empty => vec![BrickData::Empty; BOX_NODE_CHILDREN_COUNT]
+                    .try_into()
+                    .unwrap(),
+                BrickData::Solid(voxel) => vec![BrickData::Solid(*voxel); BOX_NODE_CHILDREN_COUNT]
+                    .try_into()
+                    .unwrap(),
+                BrickData::Parted(brick) => Self::dilute_brick_data(brick, self.brick_dim)
+                    .map(|child_brick| BrickData::Parted(child_brick.into())),
+            },
+            NodeContent::Internal(_) => (0..BOX_NODE_CHILDREN_COUNT)
+                .map(
+                    |sectant| match self.valid_child_for(node_key, sectant as u8) {
+                        Some(child_key) => self
+                            .brick_of_node(child_key, &node_bounds.child_bounds_for(sectant as u8)),
+                        None => BrickData::Empty,
+                    },
+                )
+                .collect::<Vec<_>>()
+                .try_into()
+                .unwrap(),
+        };
+
+        let occupied_bits = bricks
+            .iter()
+            .enumerate()
+            .filter(|(_, brick)| {
+                !brick.contains_nothing(&self.voxel_color_palette, &self.voxel_data_palette)
+            })
+            .fold(0, |bits, (sectant, _)| bits | (0x01 << sectant));
+        self.deallocate_children_of(node_key);
+        *self.nodes.get_mut(node_key) = NodeContent::Leaf(bricks);
+        self.node_children[node_key] = NodeChildren::OccupancyBitmap(occupied_bits);
+    }
+
+    /// Provides the content of the given node as a brick mapped 1:1 to its voxels,
+    /// expecting the node to be the size of a single brick
+    pub(crate) fn brick_of_node(
+        &self,
+        node_key: usize,
+        node_bounds: &Cube,
+    ) -> BrickData<PaletteIndexValues> {
+        match self.nodes.get(node_key) {
+            NodeContent::Nothing => BrickData::Empty,
+            NodeContent::UniformLeaf(brick) => brick.clone(),
+            NodeContent::Leaf(_) | NodeContent::Internal(_) => {
+                // Contents need to be sampled to be represented in a single brick
+                let voxel_size = (node_bounds.size / self.brick_dim as f32).max(1.) as u32;
+                let mut brick = vec![empty_marker(); self.brick_dim.pow(3) as usize];
+                for x in 0..self.brick_dim {
+                    for y in 0..self.brick_dim {
+                        for z in 0..self.brick_dim {
+                            brick[flat_projection(
+                                x as usize,
+                                y as usize,
+                                z as usize,
+                                self.brick_dim as usize,
+                            )] = self.get_internal(
+                                node_key,
+                                *node_bounds,
+                                &(V3c::from(node_bounds.min_position)
+                                    + V3c::new(x, y, z) * voxel_size),
+                            );
+                        }
+                    }
+                }
+                let mut brick = BrickData::Parted(brick.into());
+                brick.simplify(&self.voxel_color_palette, &self.voxel_data_palette);
+                brick
+            }
+        }
+    }
+
+    /// Inserts a new child node under the given sectant of the given node
+    /// * Returns with the key of the new child
+    pub(crate) fn push_child_node(
+        &mut self,
+        node_key: usize,
+        sectant: u8,
+        content: NodeContent<PaletteIndexValues>,
+        children: NodeChildren<u32>,
+    ) -> usize {
+        let child_key = self.nodes.push(content);
+        self.node_children.resize(
+            self.node_children.len().max(self.nodes.len()),
+            NodeChildren::default(),
+        );
+        self.node_mips
+            .resize(self.node_mips.len().max(self.nodes.len()), BrickData::Empty);
+        self.node_children[child_key] = children;
+        self.node_mips[child_key] = BrickData::Empty;
+        *self.node_children[node_key]
+            .child_mut(sectant as usize)
+            .unwrap() = child_key as u32;
+        child_key
+    }
+
+    /// Erases the child node under the given sectant of the given node, alongside its children
+    pub(crate) fn remove_child_node(&mut self, node_key: usize, sectant: u8) {
+        let child_key = self.node_children[node_key].child(sectant);
+        if self.nodes.key_is_valid(child_key) {
+            self.deallocate_children_of(child_key);
+            self.nodes.free(child_key);
+            self.node_children[child_key] = NodeChildren::NoChildren;
+            self.node_mips[child_key] = BrickData::Empty;
+        }
+        if let NodeChildren::Children(children) = &mut self.node_children[node_key] {
+            children[sectant as usize] = empty_marker();
+        }
+    }
+
+    /// Refreshes occupancy information, simplifies the node if needed,
+    /// and updates the MIP of the given node where it overlaps with the updated region
+    pub(crate) fn post_process_region_update<R: UpdateRegion>(
+        &mut self,
+        node_key: usize,
+        node_bounds: &Cube,
+        region: &R,
+    ) {
+        let occupied_bits = self.calculate_node_occupied_bits(node_key);
+
+        if 0 == occupied_bits {
+            self.deallocate_children_of(node_key);
+            *self.nodes.get_mut(node_key) = NodeContent::Nothing;
+            self.node_children[node_key] = NodeChildren::NoChildren;
+            self.node_mips[node_key] = BrickData::Empty;
+            return;
+        }
+        self.store_occupied_bits(node_key, occupied_bits);
+
+        if self.auto_simplify {
+            self.simplify(node_key, false);
+        }
+
+        // A node without a MIP (e.g. a previously uniform leaf) needs every cell sampled
+        if self.mip_map_strategy.enabled
+            && matches!(self.node_mips[node_key], BrickData::Empty)
+            && !matches!(self.nodes.get(node_key), NodeContent::UniformLeaf(_))
+        {
+            StrategyUpdater(self).recalculate_mip(node_key, node_bounds);
+            return;
+        }
+
+        // Update the MIP cells overlapping with the update
+        if self.mip_map_strategy.enabled {
+            let cell_size = node_bounds.size / self.brick_dim as f32;
+            for x in 0..self.brick_dim {
+                for y in 0..self.brick_dim {
+                    for z in 0..self.brick_dim {
+                        let cell_position: V3c<f32> = node_bounds.min_position
+                            + (V3c::<f32>::new(x as f32, y as f32, z as f32) * cell_size).round();
+                        let cell_bounds = Cube {
+                            min_position: cell_position,
+                            size: cell_size.max(1.),
+                        };
+                        if let RegionOverlap::Outside = region.overlap(&cell_bounds) {
+                            continue;
+                        }
+                        self.update_mip(node_key, node_bounds, &V3c::from(cell_position));
+                    }
+                }
+            }
+        }
+    }
+}
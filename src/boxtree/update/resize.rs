@@ -0,0 +1,211 @@
+use crate::{
+    boxtree::{
+        types::{BrickData, NodeChildren, NodeContent, OctreeError, StrategyUpdater},
+        BoxTree, VoxelData, BOX_NODE_CHILDREN_COUNT,
+    },
+    object_pool::empty_marker,
+    spatial::{math::vector::V3c, Cube},
+};
+
+impl<T: VoxelData> BoxTree<T> {
+    /// Changes the size of the boxtree, keeping its contents
+    /// Growing the tree wraps the current root node into a new, larger root node,
+    /// shrinking the tree crops it to the node under the given anchor. Transactions recorded by the journal are dropped.
+    /// * `new_size` - The new size of the tree, must be `brick_dimension * (4^x)`, see @new
+    /// * `anchor` - The position inside the larger tree, where the smaller tree starts:
+    ///   when growing, the position of the current contents inside the resized tree;
+    ///   when shrinking, the position inside the current tree, which will be the origin of the resized tree.
+    ///   Must be a multiple of the smaller size
+    /// * Returns with error if the parameters are invalid, or the contents do not fit inside the new size
+    pub fn resize(&mut self, new_size: u32, anchor: &V3c<u32>) -> Result<(), OctreeError> {
+        Self::check_dimensions(new_size, self.brick_dim)?;
+        let smaller_size = new_size.min(self.boxtree_size);
+        let larger_size = new_size.max(self.boxtree_size);
+        let exceeds_larger_size = |start: u32| {
+            start
+                .checked_add(smaller_size)
+                .is_none_or(|end| end > larger_size)
+        };
+        if !anchor.x.is_multiple_of(smaller_size)
+            || !anchor.y.is_multiple_of(smaller_size)
+            || !anchor.z.is_multiple_of(smaller_size)
+            || exceeds_larger_size(anchor.x)
+            || exceeds_larger_size(anchor.y)
+            || exceeds_larger_size(anchor.z)
+        {
+            return Err(OctreeError::InvalidPosition {
+                x: anchor.x,
+                y: anchor.y,
+                z: anchor.z,
+            });
+        }
+
+        if new_size < self.boxtree_size {
+            self.shrink(new_size, anchor)?;
+            self.mark_layout_changed();
+        } else if new_size > self.boxtree_size {
+            self.grow(new_size, anchor);
+            self.mark_layout_changed();
+        }
+
+        // Recorded edits refer to positions of the previous size
+        self.clear_journal();
+        Ok(())
+    }
+
+    /// Erases every node from the tree
+    fn reset_root(&mut self) {
+        let root_key = Self::ROOT_NODE_KEY as usize;
+        self.deallocate_children_of(root_key);
+        *self.nodes.get_mut(root_key) = NodeContent::Nothing;
+        self.node_children[root_key] = NodeChildren::NoChildren;
+        self.node_mips[root_key] = BrickData::Empty;
+    }
+
+    /// Wraps the root node into a new, larger root node, placing the current contents at the given position
+    fn grow(&mut self, new_size: u32, anchor: &V3c<u32>) {
+        let root_key = Self::ROOT_NODE_KEY as usize;
+        let old_size = self.boxtree_size;
+        self.boxtree_size = new_size;
+        if 0 == self.stored_occupied_bits(root_key) {
+            self.reset_root();
+            return;
+        }
+
+        // Move the current root to a new node
+        let old_root_content = std::mem::take(self.nodes.get_mut(root_key));
+        let old_root_key = self.nodes.push(old_root_content);
+        self.node_children.resize(
+            self.node_children.len().max(self.nodes.len()),
+            NodeChildren::default(),
+        );
+        self.node_mips
+            .resize(self.node_mips.len().max(self.nodes.len()), BrickData::Empty);
+        self.node_children[old_root_key] = std::mem::take(&mut self.node_children[root_key]);
+        self.node_mips[old_root_key] =
+            std::mem::replace(&mut self.node_mips[root_key], BrickData::Empty);
+
+        // Build the chain of nodes from the new root to the previous root
+        let anchor = V3c::<f32>::from(*anchor);
+        let mut new_nodes = vec![];
+        let mut node_key = root_key;
+        let mut node_bounds = Cube::root_bounds(new_size as f32);
+        loop {
+            let sectant = node_bounds.sectant_for(&anchor);
+            let child_bounds = node_bounds.child_bounds_for(sectant);
+            let child_key = if child_bounds.size as u32 == old_size {
+                old_root_key
+            } else {
+                self.nodes.push(NodeContent::Nothing)
+            };
+            self.node_children.resize(
+                self.node_children.len().max(self.nodes.len()),
+                NodeChildren::default(),
+            );
+            self.node_mips
+                .resize(self.node_mips.len().max(self.nodes.len()), BrickData::Empty);
+
+            let mut children = [empty_marker(); BOX_NODE_CHILDREN_COUNT];
+            children[sectant as usize] = child_key as u32;
+            *self.nodes.get_mut(node_key) = NodeContent::Internal(0x01 << sectant);
+            self.node_children[node_key] = NodeChildren::Children(children);
+            self.node_mips[node_key] = BrickData::Empty;
+            new_nodes.push((node_key, node_bounds));
+
+            if child_key == old_root_key {
+                break;
+            }
+            node_key = child_key;
+            node_bounds = child_bounds;
+        }
+
+        // MIPs of the new nodes are built from the bottom up
+        if self.mip_map_strategy.enabled {
+            for (node_key, node_bounds) in new_nodes.into_iter().rev() {
+                StrategyUpdater(self).recalculate_mip(node_key, &node_bounds);
+            }
+        }
+    }
+
+    /// Crops the tree to the node of the given size at the given position
+    fn shrink(&mut self, new_size: u32, anchor: &V3c<u32>) -> Result<(), OctreeError> {
+        // Every voxel outside the new bounds must be empty
+        let new_min = *anchor;
+        let new_max = *anchor + V3c::unit(new_size);
+        let size = self.boxtree_size;
+        let outside_regions = [
+            (V3c::new(0, 0, 0), V3c::new(new_min.x, size, size)),
+            (V3c::new(new_max.x, 0, 0), V3c::new(size, size, size)),
+            (
+                V3c::new(new_min.x, 0, 0),
+                V3c::new(new_max.x, new_min.y, size),
+            ),
+            (
+                V3c::new(new_min.x, new_max.y, 0),
+                V3c::new(new_max.x, size, size),
+            ),
+            (
+                V3c::new(new_min.x, new_min.y, 0),
+                V3c::new(new_max.x, new_max.y, new_min.z),
+            ),
+            (
+                V3c::new(new_min.x, new_min.y, new_max.z),
+                V3c::new(new_max.x, new_max.y, size),
+            ),
+        ];
+        for (min_position, max_position) in outside_regions.iter() {
+            if !Self::box_is_empty(min_position, max_position)
+                && self
+                    .iter_region(min_position, max_position)?
+                    .next()
+                    .is_some()
+            {
+                return Err(OctreeError::ContentOutOfBounds(new_size));
+            }
+        }
+
+        // Find the node to become the new root, subdividing leaf nodes on the way
+        let root_key = Self::ROOT_NODE_KEY as usize;
+        let anchor = V3c::<f32>::from(*anchor);
+        let mut node_key = root_key;
+        let mut node_bounds = Cube::root_bounds(self.boxtree_size as f32);
+        let mut new_root_key = None;
+        while 0 != self.stored_occupied_bits(node_key) {
+            if node_bounds.size as u32 == new_size {
+                new_root_key = Some(node_key);
+                break;
+            }
+            let sectant = node_bounds.sectant_for(&anchor);
+            if matches!(
+                self.nodes.get(node_key),
+                NodeContent::Leaf(_) | NodeContent::UniformLeaf(_)
+            ) {
+                self.subdivide_leaf_to_nodes(node_key, sectant as usize);
+            }
+            let Some(child_key) = self.valid_child_for(node_key, sectant) else {
+                break;
+            };
+            node_key = child_key;
+            node_bounds = node_bounds.child_bounds_for(sectant);
+        }
+
+        self.boxtree_size = new_size;
+        let Some(new_root_key) = new_root_key else {
+            self.reset_root();
+            return Ok(());
+        };
+        if new_root_key == root_key {
+            return Ok(());
+        }
+
+        // Detach the new root from the tree, erase everything else, and move it into the root
+        let content = std::mem::take(self.nodes.get_mut(new_root_key));
+        let children = std::mem::take(&mut self.node_children[new_root_key]);
+        let mip = std::mem::replace(&mut self.node_mips[new_root_key], BrickData::Empty);
+        self.reset_root();
+        *self.nodes.get_mut(root_key) = content;
+        self.node_children[root_key] = children;
+        self.node_mips[root_key] = mip;
+        Ok(())
+    }
+}
@@ -0,0 +1,345 @@
+use crate::{
+    boxtree::{
+        types::{BoxTreeEntry, OctreeError, PaletteIndexValues},
+        update::region::{RegionOverlap, UpdateRegion},
+        BoxTree, VoxelData,
+    },
+    object_pool::empty_marker,
+    spatial::{math::vector::V3c, Cube},
+};
+
+/// A volume described by a signed distance function, which can be used to edit the contents of the tree
+/// Voxels are considered part of the shape when the distance at their center is not positive
+pub trait Shape {
+    /// Provides the signed distance of the given point from the surface of the shape,
+    /// negative values being inside the shape. The magnitude of the result must not be larger,
+    /// than the actual distance from the surface, so it can be used to skip whole nodes.
+    fn distance(&self, point: &V3c<f32>) -> f32;
+
+    /// Provides the minimum and maximum positions of the axis aligned box containing the whole shape
+    fn bounds(&self) -> (V3c<f32>, V3c<f32>);
+}
+
+/// A sphere with the given center and radius
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sphere {
+    pub center: V3c<f32>,
+    pub radius: f32,
+}
+
+/// A capsule: a cylinder with hemispheres at its ends, given by the centers of the hemispheres
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Capsule {
+    pub start: V3c<f32>,
+    pub end: V3c<f32>,
+    pub radius: f32,
+}
+
+/// A capped cylinder given by the centers of its caps; `start` and `end` must be different
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Cylinder {
+    pub start: V3c<f32>,
+    pub end: V3c<f32>,
+    pub radius: f32,
+}
+
+/// An axis aligned ellipsoid with the given center and radius on each axis
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ellipsoid {
+    pub center: V3c<f32>,
+    pub radii: V3c<f32>,
+}
+
+/// A cone given by the center of its base, its apex and the radius of its base
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Cone {
+    pub base: V3c<f32>,
+    pub apex: V3c<f32>,
+    pub radius: f32,
+}
+
+/// A torus lying in the plane parallel to the XZ plane, around the Y axis
+/// * `major_radius` - The distance of the center of the tube from the center of the torus
+/// * `minor_radius` - The radius of the tube
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Torus {
+    pub center: V3c<f32>,
+    pub major_radius: f32,
+    pub minor_radius: f32,
+}
+
+fn component_min(a: &V3c<f32>, b: &V3c<f32>) -> V3c<f32> {
+    V3c::new(a.x.min(b.x), a.y.min(b.y), a.z.min(b.z))
+}
+
+fn component_max(a: &V3c<f32>, b: &V3c<f32>) -> V3c<f32> {
+    V3c::new(a.x.max(b.x), a.y.max(b.y), a.z.max(b.z))
+}
+
+impl Shape for Sphere {
+    fn distance(&self, point: &V3c<f32>) -> f32 {
+        (*point - self.center).length() - self.radius
+    }
+
+    fn bounds(&self) -> (V3c<f32>, V3c<f32>) {
+        (
+            self.center - V3c::unit(self.radius),
+            self.center + V3c::unit(self.radius),
+        )
+    }
+}
+
+impl Shape for Capsule {
+    fn distance(&self, point: &V3c<f32>) -> f32 {
+        let pa = *point - self.start;
+        let ba = self.end - self.start;
+        let baba = ba.dot(&ba);
+        let h = if 0. < baba {
+            (pa.dot(&ba) / baba).clamp(0., 1.)
+        } else {
+            0.
+        };
+        (pa - ba * h).length() - self.radius
+    }
+
+    fn bounds(&self) -> (V3c<f32>, V3c<f32>) {
+        (
+            component_min(&self.start, &self.end) - V3c::unit(self.radius),
+            component_max(&self.start, &self.end) + V3c::unit(self.radius),
+        )
+    }
+}
+
+impl Shape for Cylinder {
+    fn distance(&self, point: &V3c<f32>) -> f32 {
+        let pa = *point - self.start;
+        let ba = self.end - self.start;
+        let baba = ba.dot(&ba);
+        let paba = pa.dot(&ba);
+        let x = (pa * baba - ba * paba).length() - self.radius * baba;
+        let y = (paba - baba * 0.5).abs() - baba * 0.5;
+        let x2 = x * x;
+        let y2 = y * y * baba;
+        let d = if x.max(y) < 0. {
+            -x2.min(y2)
+        } else {
+            (if 0. < x { x2 } else { 0. }) + (if 0. < y { y2 } else { 0. })
+        };
+        d.signum() * d.abs().sqrt() / baba
+    }
+
+    fn bounds(&self) -> (V3c<f32>, V3c<f32>) {
+        (
+            component_min(&self.start, &self.end) - V3c::unit(self.radius),
+            component_max(&self.start, &self.end) + V3c::unit(self.radius),
+        )
+    }
+}
+
+impl Shape for Ellipsoid {
+    fn distance(&self, point: &V3c<f32>) -> f32 {
+        // The distance is calculated for the unit sphere, scaled back by the smallest radius
+        // which keeps the result below the actual distance from the surface
+        let p = *point - self.center;
+        let scaled = V3c::new(p.x / self.radii.x, p.y / self.radii.y, p.z / self.radii.z);
+        (scaled.length() - 1.) * self.radii.x.min(self.radii.y).min(self.radii.z)
+    }
+
+    fn bounds(&self) -> (V3c<f32>, V3c<f32>) {
+        (self.center - self.radii, self.center + self.radii)
+    }
+}
+
+impl Shape for Cone {
+    fn distance(&self, point: &V3c<f32>) -> f32 {
+        // Capped cone with the radius at the apex being zero
+        let ba = self.apex - self.base;
+        let pa = *point - self.base;
+        let rba = -self.radius;
+        let baba = ba.dot(&ba);
+        let papa = pa.dot(&pa);
+        let paba = pa.dot(&ba) / baba;
+        let x = (papa - paba * paba * baba).max(0.).sqrt();
+        let cax = (x - if paba < 0.5 { self.radius } else { 0. }).max(0.);
+        let cay = (paba - 0.5).abs() - 0.5;
+        let k = rba * rba + baba;
+        let f = ((rba * (x - self.radius) + paba * baba) / k).clamp(0., 1.);
+        let cbx = x - self.radius - f * rba;
+        let cby = paba - f;
+        let sign = if cbx < 0. && cay < 0. { -1. } else { 1. };
+        sign * (cax * cax + cay * cay * baba)
+            .min(cbx * cbx + cby * cby * baba)
+            .sqrt()
+    }
+
+    fn bounds(&self) -> (V3c<f32>, V3c<f32>) {
+        (
+            component_min(&self.base, &self.apex) - V3c::unit(self.radius),
+            component_max(&self.base, &self.apex) + V3c::unit(self.radius),
+        )
+    }
+}
+
+impl Shape for Torus {
+    fn distance(&self, point: &V3c<f32>) -> f32 {
+        let p = *point - self.center;
+        let ring_distance = (p.x * p.x + p.z * p.z).sqrt() - self.major_radius;
+        (ring_distance * ring_distance + p.y * p.y).sqrt() - self.minor_radius
+    }
+
+    fn bounds(&self) -> (V3c<f32>, V3c<f32>) {
+        let extent = V3c::new(
+            self.major_radius + self.minor_radius,
+            self.minor_radius,
+            self.major_radius + self.minor_radius,
+        );
+        (self.center - extent, self.center + extent)
+    }
+}
+
+/// Adapter to update the voxels of a shape inside the tree
+struct ShapeRegion<'a, S: Shape + ?Sized> {
+    shape: &'a S,
+    min_position: V3c<f32>,
+    max_position: V3c<f32>,
+}
+
+impl<'a, S: Shape + ?Sized> ShapeRegion<'a, S> {
+    fn new(shape: &'a S) -> Self {
+        let (min_position, max_position) = shape.bounds();
+        Self {
+            shape,
+            min_position,
+            max_position,
+        }
+    }
+}
+
+impl<S: Shape + ?Sized> UpdateRegion for ShapeRegion<'_, S> {
+    fn overlap(&self, bounds: &Cube) -> RegionOverlap {
+        // Only voxel centers are relevant when evaluating the shape
+        let first_center = bounds.min_position + V3c::unit(0.5);
+        let last_center = bounds.min_position + V3c::unit(bounds.size - 0.5);
+        if last_center.x < self.min_position.x
+            || last_center.y < self.min_position.y
+            || last_center.z < self.min_position.z
+            || self.max_position.x < first_center.x
+            || self.max_position.y < first_center.y
+            || self.max_position.z < first_center.z
+        {
+            return RegionOverlap::Outside;
+        }
+
+        let center_distance = self
+            .shape
+            .distance(&(bounds.min_position + V3c::unit(bounds.size / 2.)));
+        let reach = (last_center - first_center).length() / 2.;
+        if reach < center_distance {
+            RegionOverlap::Outside
+        } else if center_distance <= -reach {
+            RegionOverlap::Inside
+        } else {
+            RegionOverlap::Partial
+        }
+    }
+
+    fn contains_voxel(&self, position: &V3c<u32>) -> bool {
+        self.shape
+            .distance(&(V3c::<f32>::from(*position) + V3c::unit(0.5)))
+            <= 0.
+    }
+}
+
+impl<T: VoxelData> BoxTree<T> {
+    /// Inserts the given data into every voxel inside the given shape
+    /// If there is already available data it overwrites it, except if all components are empty
+    /// Nodes completely inside the shape are overwritten as a whole, only the boundary is evaluated voxel by voxel
+    /// * `shape` - The shape to fill, parts of it outside the tree are ignored
+    /// * `data` - The data to insert - cloned if needed
+    pub fn insert_shape<'a, S: Shape + ?Sized, E: Into<BoxTreeEntry<'a, T>>>(
+        &mut self,
+        shape: &S,
+        data: E,
+    ) -> Result<(), OctreeError>
+    where
+        T: 'a,
+    {
+        let data = data.into();
+        let region = ShapeRegion::new(shape);
+        if data.is_none() || !self.overlaps_tree(&region) {
+            return Ok(());
+        }
+        let target_content = self.add_to_palette(&data)?;
+        self.update_shape(&region, target_content, false);
+        Ok(())
+    }
+
+    /// Erases every voxel inside the given shape
+    /// Nodes completely inside the shape are removed as a whole, only the boundary is evaluated voxel by voxel
+    /// * `shape` - The shape to clear, parts of it outside the tree are ignored
+    pub fn clear_shape<S: Shape + ?Sized>(&mut self, shape: &S) -> Result<(), OctreeError> {
+        let region = ShapeRegion::new(shape);
+        if self.overlaps_tree(&region) {
+            self.update_shape(&region, empty_marker(), false);
+        }
+        Ok(())
+    }
+
+    /// Updates every voxel containing data inside the given shape, empty voxels are left untouched
+    /// Only the components present in the given entry are overwritten
+    /// * `shape` - The shape to paint, parts of it outside the tree are ignored
+    /// * `data` - The data to paint the voxels with
+    pub fn paint_shape<'a, S: Shape + ?Sized, E: Into<BoxTreeEntry<'a, T>>>(
+        &mut self,
+        shape: &S,
+        data: E,
+    ) -> Result<(), OctreeError>
+    where
+        T: 'a,
+    {
+        let data = data.into();
+        let region = ShapeRegion::new(shape);
+        if data.is_none() || !self.overlaps_tree(&region) {
+            return Ok(());
+        }
+        let target_content = self.add_to_palette(&data)?;
+        self.update_shape(&region, target_content, true);
+        Ok(())
+    }
+
+    /// Tells if any voxel of the tree is inside the given shape
+    fn overlaps_tree<S: Shape + ?Sized>(&self, region: &ShapeRegion<S>) -> bool {
+        RegionOverlap::Outside != region.overlap(&Cube::root_bounds(self.boxtree_size as f32))
+    }
+
+    /// Updates the voxels inside the given shape with the given content
+    fn update_shape<S: Shape + ?Sized>(
+        &mut self,
+        region: &ShapeRegion<S>,
+        target_content: PaletteIndexValues,
+        paint: bool,
+    ) {
+        // Voxels are part of the shape based on their centers, so the bounds cover every updated voxel
+        let to_position = |position: f32| position.clamp(0., self.boxtree_size as f32) as u32;
+        let min_position = V3c::new(
+            to_position(region.min_position.x.floor()),
+            to_position(region.min_position.y.floor()),
+            to_position(region.min_position.z.floor()),
+        );
+        let max_position = V3c::new(
+            to_position(region.max_position.x.ceil()),
+            to_position(region.max_position.y.ceil()),
+            to_position(region.max_position.z.ceil()),
+        );
+        self.record_box_edit(&min_position, &max_position);
+        if self.update_region_internal(
+            Self::ROOT_NODE_KEY as usize,
+            &Cube::root_bounds(self.boxtree_size as f32),
+            region,
+            target_content,
+            paint,
+        ) {
+            self.mark_changed(&min_position, &max_position);
+        }
+    }
+}
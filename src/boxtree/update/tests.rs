@@ -1,5 +1,12 @@
 use crate::{
-    boxtree::{Albedo, BoxTree, BoxTreeEntry, BOX_NODE_CHILDREN_COUNT},
+    boxtree::{
+        types::{
+            Axis, BrickData, NodeContent, OctreeError, PaletteOverflowStrategy, PasteMode,
+            StrategyUpdater,
+        },
+        update::shape::{Capsule, Cone, Cylinder, Ellipsoid, Shape, Sphere, Torus},
+        Albedo, BoxTree, BoxTreeEntry, BOX_NODE_CHILDREN_COUNT, MAX_PALETTE_SIZE, OOB_SECTANT,
+    },
     spatial::{lut::SECTANT_OFFSET_LUT, math::vector::V3c},
     voxel_data,
 };
@@ -139,6 +146,45 @@ fn test_simple_insert_and_get_where_dim_is_2() {
     assert!(tree.get(&V3c::new(0, 0, 1)) == (&blue).into());
 }
 
+#[test]
+fn test_uniform_brick_keeps_octants_when_subdivided() {
+    let colors: Vec<Albedo> = (0..8)
+        .map(|octant| {
+            Albedo::default()
+                .with_red(32 * octant as u8 + 1)
+                .with_alpha(255)
+        })
+        .collect();
+    let mut tree: BoxTree = BoxTree::new(8, 2).ok().unwrap();
+    for (octant, color) in colors.iter().enumerate() {
+        let min_position = V3c::new(
+            octant as u32 & 1,
+            (octant as u32 >> 1) & 1,
+            octant as u32 >> 2,
+        ) * 4;
+        tree.insert_box(&min_position, &(min_position + V3c::unit(4)), color)
+            .expect("boxtree insert");
+    }
+    assert!(matches!(
+        tree.nodes.get(BoxTree::<u32>::ROOT_NODE_KEY as usize),
+        NodeContent::UniformLeaf(BrickData::Parted(_))
+    ));
+
+    // Inserting a single voxel dilutes the uniform brick into the bricks of the leaf
+    let white: Albedo = 0xFFFFFFFF.into();
+    tree.insert(&V3c::new(7, 7, 7), &white)
+        .expect("boxtree insert");
+    for (octant, color) in colors.iter().enumerate() {
+        let position = V3c::new(
+            octant as u32 & 1,
+            (octant as u32 >> 1) & 1,
+            octant as u32 >> 2,
+        ) * 4;
+        assert!(tree.get(&position) == color.into());
+    }
+    assert!(tree.get(&V3c::new(7, 7, 7)) == (&white).into());
+}
+
 #[test]
 fn test_insert_at_lod_where_dim_is_1() {
     let red: Albedo = 0xFF0000FF.into();
@@ -1521,6 +1567,59 @@ fn test_clear_at_lod_with_unaligned_position_where_dim_is_4() {
     assert_eq!(hits, (512 - 64));
 }
 
+#[test]
+fn test_update_at_lod_with_unaligned_position_across_bricks_where_dim_is_4() {
+    let albedo: Albedo = 0xFFAAEEFF.into();
+    let mut tree: BoxTree = BoxTree::new(16, 4).ok().unwrap();
+
+    // The inserted box spans 3..5 on x, overlapping two bricks
+    tree.insert_at_lod(&V3c::new(3, 1, 1), 2, &albedo)
+        .ok()
+        .unwrap();
+    for x in 0..8 {
+        for y in 0..4 {
+            for z in 0..4 {
+                let inside = (3..5).contains(&x) && (1..3).contains(&y) && (1..3).contains(&z);
+                let hit = tree.get(&V3c::new(x, y, z));
+                if inside {
+                    assert_eq!(hit, (&albedo).into(), "Missing voxel at {:?}", (x, y, z));
+                } else {
+                    assert_eq!(hit, BoxTreeEntry::Empty, "Extra voxel at {:?}", (x, y, z));
+                }
+            }
+        }
+    }
+
+    // The cleared box spans 6..9 on x and 3..6 on y, overlapping four bricks
+    tree.insert_at_lod(&V3c::new(0, 0, 0), 16, &albedo)
+        .ok()
+        .unwrap();
+    tree.clear_at_lod(&V3c::new(6, 3, 1), 3).ok().unwrap();
+    for x in 0..16 {
+        for y in 0..16 {
+            for z in 0..16 {
+                let inside = (6..9).contains(&x) && (3..6).contains(&y) && (1..4).contains(&z);
+                let hit = tree.get(&V3c::new(x, y, z));
+                if inside {
+                    assert_eq!(
+                        hit,
+                        BoxTreeEntry::Empty,
+                        "Voxel not cleared at {:?}",
+                        (x, y, z)
+                    );
+                } else {
+                    assert_eq!(
+                        hit,
+                        (&albedo).into(),
+                        "Voxel wrongly cleared at {:?}",
+                        (x, y, z)
+                    );
+                }
+            }
+        }
+    }
+}
+
 #[test]
 fn test_clear_at_lod_with_unaligned_size_where_dim_is_1() {
     let albedo: Albedo = 0xFFAAEEFF.into();
@@ -1764,3 +1863,1733 @@ fn test_case_inserting_empty() {
         item
     );
 }
+
+#[test]
+fn test_insert_box_unaligned() {
+    let red: Albedo = 0xFF0000FF.into();
+    let mut tree: BoxTree = BoxTree::new(64, 4).ok().unwrap();
+    let min = V3c::new(3, 5, 7);
+    let max = V3c::new(41, 22, 60);
+    tree.insert_box(&min, &max, &red)
+        .expect("insert_box to work");
+
+    for x in 0..64 {
+        for y in 0..64 {
+            for z in 0..64 {
+                let hit = tree.get(&V3c::new(x, y, z));
+                if (min.x..max.x).contains(&x)
+                    && (min.y..max.y).contains(&y)
+                    && (min.z..max.z).contains(&z)
+                {
+                    assert!(hit == (&red).into(), "Hit mismatch at {:?}", (x, y, z));
+                } else {
+                    assert!(
+                        hit == BoxTreeEntry::Empty,
+                        "Hit mismatch at {:?}",
+                        (x, y, z)
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_insert_box_where_dim_is_1() {
+    let red: Albedo = 0xFF0000FF.into();
+    let green: Albedo = 0x00FF00FF.into();
+    let mut tree: BoxTree = BoxTree::new(16, 1).ok().unwrap();
+    tree.auto_simplify = false;
+    tree.insert(&V3c::new(0, 0, 0), &green).ok().unwrap();
+    tree.insert_box(&V3c::new(1, 0, 2), &V3c::new(15, 3, 9), &red)
+        .expect("insert_box to work");
+
+    for x in 0..16 {
+        for y in 0..16 {
+            for z in 0..16 {
+                let hit = tree.get(&V3c::new(x, y, z));
+                if (1..15).contains(&x) && (0..3).contains(&y) && (2..9).contains(&z) {
+                    assert!(hit == (&red).into(), "Hit mismatch at {:?}", (x, y, z));
+                } else if (x, y, z) == (0, 0, 0) {
+                    assert!(hit == (&green).into(), "Hit mismatch at {:?}", (x, y, z));
+                } else {
+                    assert!(
+                        hit == BoxTreeEntry::Empty,
+                        "Hit mismatch at {:?}",
+                        (x, y, z)
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_insert_box_covering_whole_tree_is_uniform() {
+    let red: Albedo = 0xFF0000FF.into();
+    let mut tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+    tree.insert(&V3c::new(5, 5, 5), &Albedo::from(0x00FF00FF))
+        .ok()
+        .unwrap();
+    tree.insert_box(&V3c::unit(0), &V3c::unit(32), &red)
+        .expect("insert_box to work");
+
+    assert!(matches!(
+        tree.nodes.get(BoxTree::<u32>::ROOT_NODE_KEY as usize),
+        NodeContent::UniformLeaf(BrickData::Solid(_))
+    ));
+    for x in 0..32 {
+        for y in 0..32 {
+            for z in 0..32 {
+                let hit = tree.get(&V3c::new(x, y, z));
+                assert!(hit == (&red).into(), "Hit mismatch at {:?}", (x, y, z));
+            }
+        }
+    }
+}
+
+#[test]
+fn test_clear_box_inside_filled_tree() {
+    let red: Albedo = 0xFF0000FF.into();
+    let mut tree: BoxTree = BoxTree::new(64, 4).ok().unwrap();
+    tree.insert_box(&V3c::unit(0), &V3c::unit(64), &red)
+        .expect("insert_box to work");
+    let min = V3c::new(2, 17, 16);
+    let max = V3c::new(50, 33, 64);
+    tree.clear_box(&min, &max).expect("clear_box to work");
+
+    for x in 0..64 {
+        for y in 0..64 {
+            for z in 0..64 {
+                let hit = tree.get(&V3c::new(x, y, z));
+                if (min.x..max.x).contains(&x)
+                    && (min.y..max.y).contains(&y)
+                    && (min.z..max.z).contains(&z)
+                {
+                    assert!(
+                        hit == BoxTreeEntry::Empty,
+                        "Hit mismatch at {:?}",
+                        (x, y, z)
+                    );
+                } else {
+                    assert!(hit == (&red).into(), "Hit mismatch at {:?}", (x, y, z));
+                }
+            }
+        }
+    }
+
+    // Clearing everything leaves no nodes behind
+    tree.clear_box(&V3c::unit(0), &V3c::unit(64))
+        .expect("clear_box to work");
+    assert!(matches!(
+        tree.nodes.get(BoxTree::<u32>::ROOT_NODE_KEY as usize),
+        NodeContent::Nothing
+    ));
+}
+
+#[test]
+fn test_clear_box_inside_parted_uniform_leaf() {
+    // The filled box simplifies nodes into uniform leaves with parted bricks where dim is 2
+    let red: Albedo = 0xFF0000FF.into();
+    let mut tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+    tree.insert_box(&V3c::new(0, 0, 0), &V3c::new(32, 4, 16), &red)
+        .expect("insert_box to work");
+    let min = V3c::new(2, 1, 0);
+    let max = V3c::new(12, 7, 20);
+    tree.clear_box(&min, &max).expect("clear_box to work");
+
+    for x in 0..32 {
+        for y in 0..32 {
+            for z in 0..32 {
+                let hit = tree.get(&V3c::new(x, y, z));
+                if (min.x..max.x).contains(&x)
+                    && (min.y..max.y).contains(&y)
+                    && (min.z..max.z).contains(&z)
+                    || 4 <= y
+                    || 16 <= z
+                {
+                    assert!(
+                        hit == BoxTreeEntry::Empty,
+                        "Hit mismatch at {:?}",
+                        (x, y, z)
+                    );
+                } else {
+                    assert!(hit == (&red).into(), "Hit mismatch at {:?}", (x, y, z));
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_insert_box_keeps_existing_data_outside() {
+    let red: Albedo = 0xFF0000FF.into();
+    let mut tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+    for x in 0..16 {
+        for y in 0..16 {
+            tree.insert(&V3c::new(x, y, x), &Albedo::from(x + y + 1))
+                .ok()
+                .unwrap();
+        }
+    }
+    tree.insert_box(&V3c::new(5, 0, 0), &V3c::new(11, 32, 7), &red)
+        .expect("insert_box to work");
+
+    for x in 0..32 {
+        for y in 0..32 {
+            for z in 0..32 {
+                let hit = tree.get(&V3c::new(x, y, z));
+                if (5..11).contains(&x) && (0..7).contains(&z) {
+                    assert!(hit == (&red).into(), "Hit mismatch at {:?}", (x, y, z));
+                } else if x == z && x < 16 && y < 16 {
+                    assert!(
+                        hit == (&Albedo::from(x + y + 1)).into(),
+                        "Hit mismatch at {:?}",
+                        (x, y, z)
+                    );
+                } else {
+                    assert!(
+                        hit == BoxTreeEntry::Empty,
+                        "Hit mismatch at {:?}",
+                        (x, y, z)
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_box_out_of_bounds() {
+    let red: Albedo = 0xFF0000FF.into();
+    let mut tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+    assert!(tree
+        .insert_box(&V3c::new(32, 0, 0), &V3c::new(32, 2, 2), &red)
+        .is_err());
+    assert!(tree
+        .insert_box(&V3c::new(0, 0, 0), &V3c::new(33, 2, 2), &red)
+        .is_err());
+    assert!(tree
+        .clear_box(&V3c::new(0, 0, 0), &V3c::new(2, 2, 40))
+        .is_err());
+
+    // Empty boxes are no-ops
+    tree.insert_box(&V3c::new(3, 3, 3), &V3c::new(3, 8, 8), &red)
+        .expect("empty box insert to work");
+    assert!(tree.get(&V3c::new(3, 3, 3)) == BoxTreeEntry::Empty);
+}
+
+#[test]
+fn test_insert_shapes_match_distance_function() {
+    let red: Albedo = 0xFF0000FF.into();
+    let shapes: Vec<Box<dyn Shape>> = vec![
+        Box::new(Sphere {
+            center: V3c::new(30.5, 20.2, 33.),
+            radius: 17.,
+        }),
+        Box::new(Capsule {
+            start: V3c::new(5., 10., 5.),
+            end: V3c::new(50., 40., 30.),
+            radius: 6.5,
+        }),
+        Box::new(Cylinder {
+            start: V3c::new(32., 2., 30.),
+            end: V3c::new(32., 60., 34.),
+            radius: 12.,
+        }),
+        Box::new(Ellipsoid {
+            center: V3c::new(32., 32., 32.),
+            radii: V3c::new(25., 9., 14.),
+        }),
+        Box::new(Cone {
+            base: V3c::new(20., 3., 20.),
+            apex: V3c::new(40., 55., 45.),
+            radius: 15.,
+        }),
+        Box::new(Torus {
+            center: V3c::new(32., 30., 32.),
+            major_radius: 20.,
+            minor_radius: 5.,
+        }),
+    ];
+
+    for shape in shapes.iter() {
+        let mut tree: BoxTree = BoxTree::new(64, 4).ok().unwrap();
+        tree.insert_shape(shape.as_ref(), &red)
+            .expect("insert_shape to work");
+        for x in 0..64 {
+            for y in 0..64 {
+                for z in 0..64 {
+                    let hit = tree.get(&V3c::new(x, y, z));
+                    let voxel_center = V3c::new(x as f32, y as f32, z as f32) + V3c::unit(0.5);
+                    if shape.distance(&voxel_center) <= 0. {
+                        assert!(hit == (&red).into(), "Hit mismatch at {:?}", (x, y, z));
+                    } else {
+                        assert!(
+                            hit == BoxTreeEntry::Empty,
+                            "Hit mismatch at {:?}",
+                            (x, y, z)
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_clear_shape_inside_filled_tree() {
+    let red: Albedo = 0xFF0000FF.into();
+    let mut tree: BoxTree = BoxTree::new(64, 4).ok().unwrap();
+    tree.insert_box(&V3c::unit(0), &V3c::unit(64), &red)
+        .expect("insert_box to work");
+    let sphere = Sphere {
+        center: V3c::new(10., 40., 32.),
+        radius: 28.,
+    };
+    tree.clear_shape(&sphere).expect("clear_shape to work");
+
+    for x in 0..64 {
+        for y in 0..64 {
+            for z in 0..64 {
+                let hit = tree.get(&V3c::new(x, y, z));
+                let voxel_center = V3c::new(x as f32, y as f32, z as f32) + V3c::unit(0.5);
+                if sphere.distance(&voxel_center) <= 0. {
+                    assert!(
+                        hit == BoxTreeEntry::Empty,
+                        "Hit mismatch at {:?}",
+                        (x, y, z)
+                    );
+                } else {
+                    assert!(hit == (&red).into(), "Hit mismatch at {:?}", (x, y, z));
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_paint_shape_only_updates_existing_voxels() {
+    let red: Albedo = 0xFF0000FF.into();
+    let blue: Albedo = 0x0000FFFF.into();
+    let mut tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+    tree.albedo_mip_map_resampling_strategy()
+        .switch_albedo_mip_maps(true);
+    tree.insert_box(&V3c::new(0, 0, 0), &V3c::new(32, 8, 32), (&red, &5))
+        .expect("insert_box to work");
+    let sphere = Sphere {
+        center: V3c::new(16., 8., 16.),
+        radius: 10.,
+    };
+    tree.paint_shape(&sphere, &blue)
+        .expect("paint_shape to work");
+
+    for x in 0..32 {
+        for y in 0..32 {
+            for z in 0..32 {
+                let hit = tree.get(&V3c::new(x, y, z));
+                let voxel_center = V3c::new(x as f32, y as f32, z as f32) + V3c::unit(0.5);
+                if 8 <= y {
+                    assert!(
+                        hit == BoxTreeEntry::Empty,
+                        "Hit mismatch at {:?}",
+                        (x, y, z)
+                    );
+                } else if sphere.distance(&voxel_center) <= 0. {
+                    assert!(hit == (&blue, &5).into(), "Hit mismatch at {:?}", (x, y, z));
+                } else {
+                    assert!(hit == (&red, &5).into(), "Hit mismatch at {:?}", (x, y, z));
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_shape_outside_of_tree_keeps_palette() {
+    let red: Albedo = 0xFF0000FF.into();
+    let mut tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+    let sphere = Sphere {
+        center: V3c::new(64., 16., 16.),
+        radius: 10.,
+    };
+    tree.insert_shape(&sphere, (&red, &5))
+        .expect("insert_shape to work");
+    tree.paint_shape(&sphere, (&red, &5))
+        .expect("paint_shape to work");
+    assert!(tree.voxel_color_palette.is_empty());
+    assert!(tree.voxel_data_palette.is_empty());
+    assert_eq!(tree.iter().count(), 0);
+}
+
+#[test]
+fn test_batch_matches_individual_updates() {
+    let red: Albedo = 0xFF0000FF.into();
+    let green: Albedo = 0x00FF00FF.into();
+    let mut tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+    let mut batched_tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+    tree.albedo_mip_map_resampling_strategy()
+        .switch_albedo_mip_maps(true);
+    batched_tree
+        .albedo_mip_map_resampling_strategy()
+        .switch_albedo_mip_maps(true);
+
+    for x in 0..32 {
+        for y in 0..8 {
+            for z in 0..32 {
+                tree.insert(&V3c::new(x, y, z), &red)
+                    .expect("boxtree insert");
+            }
+        }
+    }
+    for x in 4..12 {
+        tree.clear(&V3c::new(x, 2, 5)).expect("boxtree clear");
+        tree.update(&V3c::new(x, 3, 5), voxel_data!(&5))
+            .expect("boxtree update");
+        tree.insert(&V3c::new(x, 20, 7), &green)
+            .expect("boxtree insert");
+    }
+
+    let submitted = batched_tree.batch(|batch| {
+        for x in 0..32 {
+            for y in 0..8 {
+                for z in 0..32 {
+                    batch
+                        .insert(&V3c::new(x, y, z), &red)
+                        .expect("batch insert");
+                }
+            }
+        }
+        for x in 4..12 {
+            batch.clear(&V3c::new(x, 2, 5)).expect("batch clear");
+            batch
+                .update(&V3c::new(x, 3, 5), voxel_data!(&5))
+                .expect("batch update");
+            batch
+                .insert(&V3c::new(x, 20, 7), &green)
+                .expect("batch insert");
+        }
+        batch.len()
+    });
+    assert_eq!(submitted, 32 * 8 * 32 + 3 * 8);
+
+    for x in 0..32 {
+        for y in 0..32 {
+            for z in 0..32 {
+                assert!(
+                    tree.get(&V3c::new(x, y, z)) == batched_tree.get(&V3c::new(x, y, z)),
+                    "Hit mismatch at {:?}",
+                    (x, y, z)
+                );
+            }
+        }
+    }
+    assert_eq!(
+        tree.node_mips[BoxTree::<u32>::ROOT_NODE_KEY as usize],
+        batched_tree.node_mips[BoxTree::<u32>::ROOT_NODE_KEY as usize]
+    );
+}
+
+#[test]
+fn test_batch_updates_inside_bricks() {
+    let red: Albedo = 0xFF0000FF.into();
+    let green: Albedo = 0x00FF00FF.into();
+    let mut tree: BoxTree = BoxTree::new(64, 4).ok().unwrap();
+    tree.insert_box(&V3c::new(0, 0, 0), &V3c::new(8, 4, 4), &red)
+        .expect("insert_box to work");
+    tree.insert(&V3c::new(9, 0, 0), &red)
+        .expect("boxtree insert");
+
+    // Clear the first brick voxel by voxel, paint the second one, and add voxels next to the single voxel
+    tree.batch(|batch| {
+        for x in 0..4 {
+            for y in 0..4 {
+                for z in 0..4 {
+                    batch.clear(&V3c::new(x, y, z)).expect("batch clear");
+                    batch
+                        .insert(&V3c::new(x + 4, y, z), &green)
+                        .expect("batch insert");
+                }
+            }
+        }
+        batch
+            .insert(&V3c::new(10, 0, 0), &green)
+            .expect("batch insert");
+        batch.clear(&V3c::new(9, 0, 0)).expect("batch clear");
+    });
+
+    for x in 0..12 {
+        for y in 0..4 {
+            for z in 0..4 {
+                let expected = if (4..8).contains(&x) || (10, 0, 0) == (x, y, z) {
+                    (&green).into()
+                } else {
+                    BoxTreeEntry::Empty
+                };
+                assert!(
+                    tree.get(&V3c::new(x, y, z)) == expected,
+                    "Hit mismatch at {:?}",
+                    (x, y, z)
+                );
+            }
+        }
+    }
+    assert!(tree.validate().is_ok());
+}
+
+#[test]
+fn test_batch_simplifies_filled_nodes() {
+    let red: Albedo = 0xFF0000FF.into();
+    let mut tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+    tree.insert_many(
+        (0..32)
+            .flat_map(|x| (0..32).flat_map(move |y| (0..32).map(move |z| V3c::new(x, y, z))))
+            .map(|position| (position, &red)),
+    )
+    .expect("insert_many to work");
+
+    assert!(matches!(
+        tree.nodes.get(BoxTree::<u32>::ROOT_NODE_KEY as usize),
+        NodeContent::UniformLeaf(BrickData::Solid(_))
+    ));
+    for x in 0..32 {
+        for y in 0..32 {
+            for z in 0..32 {
+                assert!(
+                    tree.get(&V3c::new(x, y, z)) == (&red).into(),
+                    "Hit mismatch at {:?}",
+                    (x, y, z)
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn test_batch_invalid_position() {
+    let red: Albedo = 0xFF0000FF.into();
+    let mut tree: BoxTree = BoxTree::new(8, 2).ok().unwrap();
+    let result = tree.insert_many([
+        (V3c::new(1, 1, 1), &red),
+        (V3c::new(8, 1, 1), &red),
+        (V3c::new(2, 2, 2), &red),
+    ]);
+
+    assert!(result.is_err());
+    assert!(tree.get(&V3c::new(1, 1, 1)) == (&red).into());
+    assert!(tree.get(&V3c::new(2, 2, 2)) == BoxTreeEntry::Empty);
+}
+
+#[test]
+fn test_resize_grow_keeps_content() {
+    let red: Albedo = 0xFF0000FF.into();
+    let green: Albedo = 0x00FF00FF.into();
+    let mut tree: BoxTree = BoxTree::new(8, 2).ok().unwrap();
+    let mut reference_tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+    tree.albedo_mip_map_resampling_strategy()
+        .switch_albedo_mip_maps(true);
+    reference_tree
+        .albedo_mip_map_resampling_strategy()
+        .switch_albedo_mip_maps(true);
+    let anchor = V3c::new(8, 16, 0);
+    for i in 0..8 {
+        tree.insert(&V3c::new(i, i, 7 - i), &red)
+            .expect("boxtree insert");
+        reference_tree
+            .insert(&(V3c::new(i, i, 7 - i) + anchor), &red)
+            .expect("boxtree insert");
+    }
+    tree.insert_box(&V3c::new(0, 4, 0), &V3c::new(8, 6, 4), &green)
+        .expect("insert_box to work");
+    reference_tree
+        .insert_box(
+            &(V3c::new(0, 4, 0) + anchor),
+            &(V3c::new(8, 6, 4) + anchor),
+            &green,
+        )
+        .expect("insert_box to work");
+
+    tree.resize(32, &anchor).expect("resize to work");
+    assert_eq!(tree.get_size(), 32);
+    for x in 0..32 {
+        for y in 0..32 {
+            for z in 0..32 {
+                assert!(
+                    tree.get(&V3c::new(x, y, z)) == reference_tree.get(&V3c::new(x, y, z)),
+                    "Hit mismatch at {:?}",
+                    (x, y, z)
+                );
+            }
+        }
+    }
+    assert_eq!(
+        tree.node_mips[BoxTree::<u32>::ROOT_NODE_KEY as usize],
+        reference_tree.node_mips[BoxTree::<u32>::ROOT_NODE_KEY as usize]
+    );
+
+    // The grown tree can be updated outside the previous bounds
+    tree.insert(&V3c::new(31, 0, 31), &red)
+        .expect("boxtree insert");
+    assert!(tree.get(&V3c::new(31, 0, 31)) == (&red).into());
+}
+
+#[test]
+fn test_resize_shrink_crops_to_content() {
+    let red: Albedo = 0xFF0000FF.into();
+    let mut tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+    let anchor = V3c::new(8, 16, 0);
+    tree.insert_box(&anchor, &(anchor + V3c::new(8, 3, 8)), &red)
+        .expect("insert_box to work");
+    tree.insert(&(anchor + V3c::new(7, 7, 7)), voxel_data!(&5))
+        .expect("boxtree insert");
+
+    tree.resize(8, &anchor).expect("resize to work");
+    assert_eq!(tree.get_size(), 8);
+    for x in 0..8 {
+        for y in 0..8 {
+            for z in 0..8 {
+                let expected = if y < 3 {
+                    (&red).into()
+                } else if (7, 7, 7) == (x, y, z) {
+                    voxel_data!(&5)
+                } else {
+                    BoxTreeEntry::Empty
+                };
+                assert!(
+                    tree.get(&V3c::new(x, y, z)) == expected,
+                    "Hit mismatch at {:?}",
+                    (x, y, z)
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn test_resize_shrink_content_out_of_bounds() {
+    let red: Albedo = 0xFF0000FF.into();
+    let mut tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+    tree.insert(&V3c::new(9, 17, 1), &red)
+        .expect("boxtree insert");
+    tree.insert(&V3c::new(16, 17, 1), &red)
+        .expect("boxtree insert");
+
+    assert!(matches!(
+        tree.resize(8, &V3c::new(8, 16, 0)),
+        Err(OctreeError::ContentOutOfBounds(8))
+    ));
+    assert_eq!(tree.get_size(), 32);
+    assert!(tree.get(&V3c::new(9, 17, 1)) == (&red).into());
+    assert!(tree.get(&V3c::new(16, 17, 1)) == (&red).into());
+
+    // Shrinking an empty region leaves an empty tree
+    tree.clear(&V3c::new(9, 17, 1)).expect("boxtree clear");
+    tree.clear(&V3c::new(16, 17, 1)).expect("boxtree clear");
+    tree.resize(8, &V3c::new(24, 24, 24))
+        .expect("resize to work");
+    assert_eq!(tree.iter().count(), 0);
+}
+
+#[test]
+fn test_resize_invalid_parameters() {
+    let mut tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+    assert!(matches!(
+        tree.resize(16, &V3c::new(0, 0, 0)),
+        Err(OctreeError::InvalidSize(16))
+    ));
+    assert!(matches!(
+        tree.resize(8, &V3c::new(4, 0, 0)),
+        Err(OctreeError::InvalidPosition { .. })
+    ));
+    assert!(matches!(
+        tree.resize(128, &V3c::new(128, 0, 0)),
+        Err(OctreeError::InvalidPosition { .. })
+    ));
+    assert!(matches!(
+        tree.resize(8, &V3c::new(0, u32::MAX - 7, 0)),
+        Err(OctreeError::InvalidPosition { .. })
+    ));
+    assert_eq!(tree.get_size(), 32);
+}
+
+/// Checks the result of pasting the source tree into the target tree against a voxel by voxel evaluation
+fn check_paste(target: &BoxTree, source: &BoxTree, position: V3c<u32>, mode: PasteMode) {
+    let mut result = target.clone();
+    result
+        .paste(source, &position, mode)
+        .expect("paste to work");
+
+    let size = target.get_size();
+    for x in 0..size {
+        for y in 0..size {
+            for z in 0..size {
+                let target_position = V3c::new(x, y, z);
+                let previous = target.get(&target_position);
+                let expected = if position.x <= x
+                    && position.y <= y
+                    && position.z <= z
+                    && x < position.x + source.get_size()
+                    && y < position.y + source.get_size()
+                    && z < position.z + source.get_size()
+                {
+                    let pasted = source.get(&(target_position - position));
+                    match mode {
+                        PasteMode::Overwrite => pasted,
+                        PasteMode::OnlyIntoEmpty if BoxTreeEntry::Empty == previous => pasted,
+                        PasteMode::OnlyIntoEmpty => previous,
+                        PasteMode::SkipEmptySource if BoxTreeEntry::Empty == pasted => previous,
+                        PasteMode::SkipEmptySource => pasted,
+                    }
+                } else {
+                    previous
+                };
+                assert!(
+                    result.get(&target_position) == expected,
+                    "Hit mismatch at {:?}",
+                    (x, y, z)
+                );
+            }
+        }
+    }
+}
+
+fn paste_test_trees() -> (BoxTree, BoxTree) {
+    let red: Albedo = 0xFF0000FF.into();
+    let green: Albedo = 0x00FF00FF.into();
+    let blue: Albedo = 0x0000FFFF.into();
+    let mut target: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+    target
+        .albedo_mip_map_resampling_strategy()
+        .switch_albedo_mip_maps(true);
+    target
+        .insert_box(&V3c::new(0, 0, 0), &V3c::new(32, 5, 32), &red)
+        .expect("insert_box to work");
+    target
+        .insert_box(&V3c::new(10, 5, 3), &V3c::new(13, 20, 30), (&blue, &2))
+        .expect("insert_box to work");
+
+    // The source palette is ordered differently to the target palette
+    let mut source: BoxTree = BoxTree::new(8, 2).ok().unwrap();
+    source
+        .insert_box(&V3c::new(0, 0, 0), &V3c::new(8, 2, 8), (&green, &7))
+        .expect("insert_box to work");
+    source
+        .insert_box(&V3c::new(2, 2, 2), &V3c::new(5, 7, 4), &blue)
+        .expect("insert_box to work");
+    source
+        .insert(&V3c::new(7, 7, 7), &red)
+        .expect("boxtree insert");
+    source.clear(&V3c::new(1, 1, 1)).expect("boxtree clear");
+    (target, source)
+}
+
+#[test]
+fn test_paste_aligned() {
+    let (target, source) = paste_test_trees();
+    for mode in [
+        PasteMode::Overwrite,
+        PasteMode::OnlyIntoEmpty,
+        PasteMode::SkipEmptySource,
+    ] {
+        check_paste(&target, &source, V3c::new(8, 0, 8), mode);
+        check_paste(&target, &source, V3c::new(24, 16, 0), mode);
+        check_paste(&target, &source, V3c::new(2, 4, 6), mode);
+    }
+}
+
+#[test]
+fn test_paste_unaligned() {
+    let (target, source) = paste_test_trees();
+    for mode in [
+        PasteMode::Overwrite,
+        PasteMode::OnlyIntoEmpty,
+        PasteMode::SkipEmptySource,
+    ] {
+        check_paste(&target, &source, V3c::new(9, 3, 1), mode);
+        // Parts of the source outside of the target are ignored
+        check_paste(&target, &source, V3c::new(27, 1, 30), mode);
+    }
+}
+
+#[test]
+fn test_paste_only_into_empty_over_partly_filled_bricks() {
+    let (target, _) = paste_test_trees();
+    let green: Albedo = 0x00FF00FF.into();
+    let mut source: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+    source
+        .insert_box(&V3c::new(0, 0, 0), &V3c::new(32, 32, 32), &green)
+        .expect("insert_box to work");
+    source.clear(&V3c::new(5, 5, 5)).expect("boxtree clear");
+
+    // Empty nodes, empty bricks and the empty voxels of partly filled bricks are all filled
+    check_paste(
+        &target,
+        &source,
+        V3c::new(0, 0, 0),
+        PasteMode::OnlyIntoEmpty,
+    );
+    check_paste(
+        &target,
+        &source,
+        V3c::new(1, 3, 5),
+        PasteMode::OnlyIntoEmpty,
+    );
+
+    let mut result = target.clone();
+    result
+        .paste(&source, &V3c::new(1, 3, 5), PasteMode::OnlyIntoEmpty)
+        .expect("paste to work");
+    assert_eq!(result.validate(), Ok(()));
+}
+
+#[test]
+fn test_extract_region() {
+    let (target, _) = paste_test_trees();
+    let min_position = V3c::new(8, 2, 4);
+    let max_position = V3c::new(16, 9, 28);
+    let extracted = target
+        .extract_region(&min_position, &max_position)
+        .expect("extract_region to work");
+    assert_eq!(extracted.get_size(), 32);
+
+    for x in 0..32 {
+        for y in 0..32 {
+            for z in 0..32 {
+                let position = V3c::new(x, y, z);
+                let expected = if x < 8 && y < 7 && z < 24 {
+                    target.get(&(position + min_position))
+                } else {
+                    BoxTreeEntry::Empty
+                };
+                assert!(
+                    extracted.get(&position) == expected,
+                    "Hit mismatch at {:?}",
+                    (x, y, z)
+                );
+            }
+        }
+    }
+
+    // Extracted regions can be pasted back into the same place
+    let mut pasted = target.clone();
+    pasted
+        .clear_box(&min_position, &max_position)
+        .expect("clear_box to work");
+    pasted
+        .paste(&extracted, &min_position, PasteMode::SkipEmptySource)
+        .expect("paste to work");
+    for x in 0..32 {
+        for y in 0..32 {
+            for z in 0..32 {
+                assert!(
+                    pasted.get(&V3c::new(x, y, z)) == target.get(&V3c::new(x, y, z)),
+                    "Hit mismatch at {:?}",
+                    (x, y, z)
+                );
+            }
+        }
+    }
+
+    assert!(target
+        .extract_region(&V3c::new(0, 0, 0), &V3c::new(33, 1, 1))
+        .is_err());
+}
+
+fn csg_test_trees() -> (BoxTree, BoxTree) {
+    let red: Albedo = 0xFF0000FF.into();
+    let green: Albedo = 0x00FF00FF.into();
+    let mut tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+    tree.albedo_mip_map_resampling_strategy()
+        .switch_albedo_mip_maps(true);
+    tree.insert_box(&V3c::new(0, 0, 0), &V3c::new(32, 8, 32), &red)
+        .expect("insert_box to work");
+    tree.insert_shape(
+        &Sphere {
+            center: V3c::new(20., 12., 9.),
+            radius: 7.,
+        },
+        (&red, &3),
+    )
+    .expect("insert_shape to work");
+
+    let mut other: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+    other
+        .insert_box(&V3c::new(4, 2, 0), &V3c::new(12, 30, 32), &green)
+        .expect("insert_box to work");
+    other
+        .insert_shape(
+            &Capsule {
+                start: V3c::new(3., 5., 5.),
+                end: V3c::new(28., 14., 20.),
+                radius: 3.5,
+            },
+            voxel_data!(&9),
+        )
+        .expect("insert_shape to work");
+    (tree, other)
+}
+
+#[test]
+fn test_union_with() {
+    let (tree, other) = csg_test_trees();
+    let mut result = tree.clone();
+    result.union_with(&other).expect("union_with to work");
+    for x in 0..32 {
+        for y in 0..32 {
+            for z in 0..32 {
+                let position = V3c::new(x, y, z);
+                let expected = if BoxTreeEntry::Empty == other.get(&position) {
+                    tree.get(&position)
+                } else {
+                    other.get(&position)
+                };
+                assert!(
+                    result.get(&position) == expected,
+                    "Hit mismatch at {:?}",
+                    (x, y, z)
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn test_intersect_with() {
+    let (tree, other) = csg_test_trees();
+    let mut result = tree.clone();
+    result
+        .intersect_with(&other)
+        .expect("intersect_with to work");
+    for x in 0..32 {
+        for y in 0..32 {
+            for z in 0..32 {
+                let position = V3c::new(x, y, z);
+                let expected = if BoxTreeEntry::Empty == other.get(&position) {
+                    BoxTreeEntry::Empty
+                } else {
+                    tree.get(&position)
+                };
+                assert!(
+                    result.get(&position) == expected,
+                    "Hit mismatch at {:?}",
+                    (x, y, z)
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn test_subtract() {
+    let (tree, other) = csg_test_trees();
+    let mut result = tree.clone();
+    result.subtract(&other).expect("subtract to work");
+    for x in 0..32 {
+        for y in 0..32 {
+            for z in 0..32 {
+                let position = V3c::new(x, y, z);
+                let expected = if BoxTreeEntry::Empty == other.get(&position) {
+                    tree.get(&position)
+                } else {
+                    BoxTreeEntry::Empty
+                };
+                assert!(
+                    result.get(&position) == expected,
+                    "Hit mismatch at {:?}",
+                    (x, y, z)
+                );
+            }
+        }
+    }
+
+    let mut incompatible: BoxTree = BoxTree::new(8, 2).ok().unwrap();
+    assert!(matches!(
+        incompatible.subtract(&tree),
+        Err(OctreeError::InvalidSize(32))
+    ));
+}
+
+#[test]
+fn test_csg_keeps_and_drops_whole_nodes() {
+    let red: Albedo = 0xFF0000FF.into();
+    let green: Albedo = 0x00FF00FF.into();
+    let mut tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+    tree.albedo_mip_map_resampling_strategy()
+        .switch_albedo_mip_maps(true);
+    tree.insert_box(&V3c::new(0, 0, 0), &V3c::new(32, 32, 32), &red)
+        .expect("insert_box to work");
+    tree.insert_box(&V3c::new(0, 0, 0), &V3c::new(32, 1, 32), &green)
+        .expect("insert_box to work");
+
+    // A whole node, a part of a brick and a single voxel of the other tree are filled
+    let mut other: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+    other
+        .insert_box(&V3c::new(8, 8, 8), &V3c::new(16, 16, 16), &green)
+        .expect("insert_box to work");
+    other
+        .insert_box(&V3c::new(0, 0, 0), &V3c::new(2, 1, 2), &green)
+        .expect("insert_box to work");
+    other
+        .insert(&V3c::new(31, 5, 17), &green)
+        .expect("insert to work");
+
+    let mut intersection = tree.clone();
+    intersection
+        .intersect_with(&other)
+        .expect("intersect_with to work");
+    let mut difference = tree.clone();
+    difference.subtract(&other).expect("subtract to work");
+    for x in 0..32 {
+        for y in 0..32 {
+            for z in 0..32 {
+                let position = V3c::new(x, y, z);
+                let in_other = BoxTreeEntry::Empty != other.get(&position);
+                let expected = tree.get(&position);
+                assert!(
+                    intersection.get(&position)
+                        == if in_other {
+                            expected
+                        } else {
+                            BoxTreeEntry::Empty
+                        },
+                    "Intersection mismatch at {:?}",
+                    (x, y, z)
+                );
+                assert!(
+                    difference.get(&position)
+                        == if in_other {
+                            BoxTreeEntry::Empty
+                        } else {
+                            expected
+                        },
+                    "Difference mismatch at {:?}",
+                    (x, y, z)
+                );
+            }
+        }
+    }
+    assert_eq!(intersection.validate(), Ok(()));
+    assert_eq!(difference.validate(), Ok(()));
+
+    // MIPs of the changed nodes are kept up to date
+    let root_key = BoxTree::<u32>::ROOT_NODE_KEY as usize;
+    for result in [&intersection, &difference] {
+        let mut rebuilt = result.clone();
+        StrategyUpdater(&mut rebuilt).recalculate_mips();
+        assert_eq!(result.node_mips[root_key], rebuilt.node_mips[root_key]);
+    }
+}
+
+fn transform_test_tree() -> BoxTree {
+    let red: Albedo = 0xFF0000FF.into();
+    let green: Albedo = 0x00FF00FF.into();
+    let mut tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+    tree.albedo_mip_map_resampling_strategy()
+        .switch_albedo_mip_maps(true);
+    tree.insert_box(&V3c::new(0, 0, 0), &V3c::new(32, 4, 16), &red)
+        .expect("insert_box to work");
+    tree.insert_box(&V3c::new(3, 5, 1), &V3c::new(9, 27, 6), (&green, &4))
+        .expect("insert_box to work");
+    tree.insert_shape(
+        &Sphere {
+            center: V3c::new(21., 14., 23.),
+            radius: 5.,
+        },
+        voxel_data!(&7),
+    )
+    .expect("insert_shape to work");
+    tree.insert(&V3c::new(30, 29, 2), &green)
+        .expect("insert to work");
+    tree
+}
+
+/// Checks if every voxel of the original tree is moved to the given position inside the transformed tree
+fn check_transform(
+    original: &BoxTree,
+    transformed: &BoxTree,
+    transform: impl Fn(V3c<u32>) -> V3c<u32>,
+) {
+    let size = original.get_size();
+    for x in 0..size {
+        for y in 0..size {
+            for z in 0..size {
+                let position = V3c::new(x, y, z);
+                assert!(
+                    transformed.get(&transform(position)) == original.get(&position),
+                    "Hit mismatch at {:?}",
+                    (x, y, z)
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn test_rotate() {
+    let tree = transform_test_tree();
+    let size = tree.get_size();
+    let quarter_turn = |axis: Axis, p: V3c<u32>| match axis {
+        Axis::X => V3c::new(p.x, size - 1 - p.z, p.y),
+        Axis::Y => V3c::new(p.z, p.y, size - 1 - p.x),
+        Axis::Z => V3c::new(size - 1 - p.y, p.x, p.z),
+    };
+    for axis in [Axis::X, Axis::Y, Axis::Z] {
+        for quarter_turns in [1, 2, 3, -1] {
+            let mut rotated = tree.clone();
+            rotated.rotate(axis, quarter_turns);
+            check_transform(&tree, &rotated, |position| {
+                (0..quarter_turns.rem_euclid(4))
+                    .fold(position, |position, _| quarter_turn(axis, position))
+            });
+        }
+    }
+
+    // A full turn restores the tree completely
+    let mut rotated = tree.clone();
+    rotated.rotate(Axis::Y, 4);
+    assert!(rotated.node_mips == tree.node_mips);
+    rotated.rotate(Axis::X, 1);
+    rotated.rotate(Axis::X, -1);
+    assert!(rotated.node_mips == tree.node_mips);
+    check_transform(&tree, &rotated, |position| position);
+}
+
+#[test]
+fn test_mirror() {
+    let tree = transform_test_tree();
+    let size = tree.get_size();
+    for axis in [Axis::X, Axis::Y, Axis::Z] {
+        let mut mirrored = tree.clone();
+        mirrored.mirror(axis);
+        check_transform(&tree, &mirrored, |p| match axis {
+            Axis::X => V3c::new(size - 1 - p.x, p.y, p.z),
+            Axis::Y => V3c::new(p.x, size - 1 - p.y, p.z),
+            Axis::Z => V3c::new(p.x, p.y, size - 1 - p.z),
+        });
+    }
+}
+
+/// Checks if the voxels inside the given box are transformed into the box of the transformed extent, starting from the same position
+/// * `transform` - Maps positions relative to the box into positions relative to the transformed box
+fn check_region_transform(
+    original: &BoxTree,
+    transformed: &BoxTree,
+    min_position: V3c<u32>,
+    max_position: V3c<u32>,
+    new_extent: V3c<u32>,
+    transform: impl Fn(V3c<u32>) -> V3c<u32>,
+) {
+    let inside = |position: &V3c<u32>, min: &V3c<u32>, max: &V3c<u32>| {
+        min.x <= position.x
+            && min.y <= position.y
+            && min.z <= position.z
+            && position.x < max.x
+            && position.y < max.y
+            && position.z < max.z
+    };
+    let new_max_position = min_position + new_extent;
+    let size = original.get_size();
+    for x in 0..size {
+        for y in 0..size {
+            for z in 0..size {
+                let position = V3c::new(x, y, z);
+                if inside(&position, &min_position, &max_position) {
+                    assert!(
+                        transformed.get(&(transform(position - min_position) + min_position))
+                            == original.get(&position),
+                        "Hit mismatch at {:?}",
+                        (x, y, z)
+                    );
+                } else if !inside(&position, &min_position, &new_max_position) {
+                    assert!(
+                        transformed.get(&position) == original.get(&position),
+                        "Hit mismatch at {:?}",
+                        (x, y, z)
+                    );
+                }
+                if inside(&position, &min_position, &max_position)
+                    && !inside(&position, &min_position, &new_max_position)
+                {
+                    assert!(
+                        transformed.get(&position) == BoxTreeEntry::Empty,
+                        "Hit mismatch at {:?}",
+                        (x, y, z)
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_rotate_region() {
+    let tree = transform_test_tree();
+    let min_position = V3c::new(2, 1, 0);
+    let max_position = V3c::new(12, 7, 20);
+    let extent = max_position - min_position;
+
+    let mut rotated = tree.clone();
+    rotated
+        .rotate_region(&min_position, &max_position, Axis::Z, 1)
+        .expect("rotate_region to work");
+    check_region_transform(
+        &tree,
+        &rotated,
+        min_position,
+        max_position,
+        V3c::new(extent.y, extent.x, extent.z),
+        |p| V3c::new(extent.y - 1 - p.y, p.x, p.z),
+    );
+
+    let mut rotated = tree.clone();
+    rotated
+        .rotate_region(&min_position, &max_position, Axis::Y, 2)
+        .expect("rotate_region to work");
+    check_region_transform(&tree, &rotated, min_position, max_position, extent, |p| {
+        V3c::new(extent.x - 1 - p.x, p.y, extent.z - 1 - p.z)
+    });
+
+    // The rotated region must fit into the tree
+    assert!(matches!(
+        rotated.rotate_region(&V3c::new(0, 20, 0), &V3c::new(20, 24, 4), Axis::Z, 1),
+        Err(OctreeError::InvalidPosition { x: 4, y: 40, z: 4 })
+    ));
+}
+
+#[test]
+fn test_mirror_region() {
+    let tree = transform_test_tree();
+    let min_position = V3c::new(1, 3, 17);
+    let max_position = V3c::new(25, 18, 29);
+    let extent = max_position - min_position;
+
+    let mut mirrored = tree.clone();
+    mirrored
+        .mirror_region(&min_position, &max_position, Axis::X)
+        .expect("mirror_region to work");
+    check_region_transform(&tree, &mirrored, min_position, max_position, extent, |p| {
+        V3c::new(extent.x - 1 - p.x, p.y, p.z)
+    });
+}
+
+#[test]
+fn test_compact_palette() {
+    let mut tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+    tree.albedo_mip_map_resampling_strategy()
+        .switch_albedo_mip_maps(true);
+    for x in 0..32 {
+        for z in 0..4 {
+            tree.insert(&V3c::new(x, 3, z), (&Albedo::from(x * 4 + z + 1), &(x % 5)))
+                .expect("insert to work");
+        }
+    }
+
+    // Overwriting most voxels leaves their colors unused in the palette
+    let red: Albedo = 0xFF0000FF.into();
+    tree.insert_box(&V3c::new(4, 0, 0), &V3c::new(32, 8, 8), &red)
+        .expect("insert_box to work");
+    let expected = tree.clone();
+    let color_count = tree.voxel_color_palette.len();
+    let data_count = tree.voxel_data_palette.len();
+
+    let (removed_colors, removed_data) = tree.compact_palette();
+    assert!(0 < removed_colors);
+    assert!(0 < removed_data);
+    assert_eq!(color_count - removed_colors, tree.voxel_color_palette.len());
+    assert_eq!(data_count - removed_data, tree.voxel_data_palette.len());
+    assert_eq!(
+        tree.voxel_color_palette.len(),
+        tree.map_to_color_index_in_palette.len()
+    );
+    assert_eq!(
+        tree.voxel_data_palette.len(),
+        tree.map_to_data_index_in_palette.len()
+    );
+
+    for x in 0..32 {
+        for y in 0..32 {
+            for z in 0..32 {
+                let position = V3c::new(x, y, z);
+                assert!(
+                    tree.get(&position) == expected.get(&position),
+                    "Hit mismatch at {:?}",
+                    (x, y, z)
+                );
+            }
+        }
+    }
+
+    // MIPs reference the same colors after compaction
+    let root_key = BoxTree::<u32>::ROOT_NODE_KEY as usize;
+    let (BrickData::Parted(mip), BrickData::Parted(expected_mip)) =
+        (&tree.node_mips[root_key], &expected.node_mips[root_key])
+    else {
+        panic!("Expected root MIP to be parted");
+    };
+    for (voxel, expected_voxel) in mip.iter().zip(expected_mip.iter()) {
+        assert!(
+            NodeContent::pix_get_ref(voxel, &tree.voxel_color_palette, &tree.voxel_data_palette)
+                == NodeContent::pix_get_ref(
+                    expected_voxel,
+                    &expected.voxel_color_palette,
+                    &expected.voxel_data_palette
+                )
+        );
+    }
+
+    // Palette is already compact
+    assert_eq!((0, 0), tree.compact_palette());
+
+    // New entries can still be added without collisions
+    let green: Albedo = 0x00FF00FF.into();
+    tree.insert(&V3c::new(0, 20, 0), &green)
+        .expect("insert to work");
+    assert!(tree.get(&V3c::new(0, 20, 0)) == (&green).into());
+    assert!(tree.get(&V3c::new(10, 3, 0)) == (&red).into());
+}
+
+/// Fills the color palette of the given tree with unused colors up to its capacity
+fn fill_color_palette(tree: &mut BoxTree) {
+    let mut index = 0;
+    while tree.voxel_color_palette.len() < MAX_PALETTE_SIZE {
+        let color = Albedo {
+            r: (index % 256) as u8,
+            g: (index / 256) as u8,
+            b: 100,
+            a: 255,
+        };
+        index += 1;
+        if !tree.map_to_color_index_in_palette.contains_key(&color) {
+            tree.map_to_color_index_in_palette
+                .insert(color, tree.voxel_color_palette.len());
+            tree.voxel_color_palette.push(color);
+        }
+    }
+}
+
+#[test]
+fn test_palette_full_rejects_new_entries() {
+    let red: Albedo = 0xFF0000FF.into();
+    let green: Albedo = 0x00FF00FF.into();
+    let mut tree: BoxTree = BoxTree::new(8, 2).ok().unwrap();
+    tree.insert(&V3c::new(0, 0, 0), &red)
+        .expect("insert to work");
+    fill_color_palette(&mut tree);
+
+    assert!(matches!(
+        tree.insert(&V3c::new(1, 0, 0), &green),
+        Err(OctreeError::PaletteFull)
+    ));
+    assert!(matches!(
+        tree.insert_box(&V3c::new(0, 0, 0), &V3c::new(8, 8, 8), &green),
+        Err(OctreeError::PaletteFull)
+    ));
+    assert!(tree.get(&V3c::new(1, 0, 0)) == BoxTreeEntry::Empty);
+
+    // Colors already in the palette can still be used
+    tree.insert(&V3c::new(1, 0, 0), &red)
+        .expect("insert to work");
+    assert!(tree.get(&V3c::new(1, 0, 0)) == (&red).into());
+
+    // Rejected entries leave no unreferenced data behind
+    let data_count = tree.voxel_data_palette.len();
+    assert!(matches!(
+        tree.insert(&V3c::new(1, 0, 0), (&green, &5)),
+        Err(OctreeError::PaletteFull)
+    ));
+    assert_eq!(data_count, tree.voxel_data_palette.len());
+
+    // Data palette overflow is always rejected
+    tree.palette_overflow_strategy = PaletteOverflowStrategy::NearestColor;
+    for data in 1..=MAX_PALETTE_SIZE as u32 {
+        tree.map_to_data_index_in_palette
+            .insert(data, tree.voxel_data_palette.len());
+        tree.voxel_data_palette.push(data);
+    }
+    assert!(matches!(
+        tree.insert(
+            &V3c::new(2, 0, 0),
+            voxel_data!(&(MAX_PALETTE_SIZE as u32 + 1))
+        ),
+        Err(OctreeError::PaletteFull)
+    ));
+
+    // Rejected entries leave no unreferenced color behind
+    let mut tree: BoxTree = BoxTree::new(8, 2).ok().unwrap();
+    for data in 1..=MAX_PALETTE_SIZE as u32 {
+        tree.map_to_data_index_in_palette
+            .insert(data, tree.voxel_data_palette.len());
+        tree.voxel_data_palette.push(data);
+    }
+    assert!(matches!(
+        tree.insert(&V3c::new(2, 0, 0), (&green, &(MAX_PALETTE_SIZE as u32 + 1))),
+        Err(OctreeError::PaletteFull)
+    ));
+    assert!(tree.voxel_color_palette.is_empty());
+}
+
+#[test]
+fn test_palette_full_uses_nearest_color() {
+    let red: Albedo = 0xFF0000FF.into();
+    let almost_red: Albedo = 0xFE0101FF.into();
+    let mut tree: BoxTree = BoxTree::new(8, 2).ok().unwrap();
+    tree.palette_overflow_strategy = PaletteOverflowStrategy::NearestColor;
+    tree.insert(&V3c::new(0, 0, 0), &red)
+        .expect("insert to work");
+    fill_color_palette(&mut tree);
+
+    tree.insert(&V3c::new(1, 0, 0), &almost_red)
+        .expect("insert to work");
+    assert!(tree.get(&V3c::new(1, 0, 0)) == (&red).into());
+    assert_eq!(MAX_PALETTE_SIZE, tree.voxel_color_palette.len());
+}
+
+#[test]
+fn test_palette_full_requantizes_colors() {
+    let mut tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+    tree.palette_overflow_strategy = PaletteOverflowStrategy::Requantize;
+    tree.albedo_mip_map_resampling_strategy()
+        .switch_albedo_mip_maps(true);
+    let color_at = |x: u32, y: u32| Albedo {
+        r: (x * 8) as u8,
+        g: (y * 8) as u8,
+        b: 200,
+        a: 255,
+    };
+    for x in 0..32 {
+        for y in 0..32 {
+            tree.insert(&V3c::new(x, y, 0), &color_at(x, y))
+                .expect("insert to work");
+        }
+    }
+    fill_color_palette(&mut tree);
+
+    // Updates submitted before the palette is re-quantized stay valid
+    let new_color: Albedo = 0x123456FF.into();
+    tree.batch(|batch| {
+        batch
+            .insert(&V3c::new(0, 0, 1), &color_at(3, 4))
+            .expect("insert to work");
+        batch
+            .insert(&V3c::new(1, 0, 1), &new_color)
+            .expect("insert to work");
+    });
+    assert!(tree.voxel_color_palette.len() <= MAX_PALETTE_SIZE / 2 + 1);
+    assert_eq!(
+        tree.voxel_color_palette.len(),
+        tree.map_to_color_index_in_palette.len()
+    );
+
+    let is_close = |entry: BoxTreeEntry<u32>, color: &Albedo| {
+        entry
+            .albedo()
+            .is_some_and(|albedo| albedo.distance_from(color) < 32.)
+    };
+    for x in 0..32 {
+        for y in 0..32 {
+            assert!(
+                is_close(tree.get(&V3c::new(x, y, 0)), &color_at(x, y)),
+                "Hit mismatch at {:?}",
+                (x, y, 0)
+            );
+        }
+    }
+    assert!(is_close(tree.get(&V3c::new(0, 0, 1)), &color_at(3, 4)));
+    assert!(is_close(tree.get(&V3c::new(1, 0, 1)), &new_color));
+}
+
+#[test]
+fn test_replace_color() {
+    let red: Albedo = 0xFF0000FF.into();
+    let green: Albedo = 0x00FF00FF.into();
+    let blue: Albedo = 0x0000FFFF.into();
+    let mut tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+    tree.insert_box(&V3c::new(0, 0, 0), &V3c::new(8, 8, 8), (&red, &3))
+        .expect("insert_box to work");
+    tree.insert_box(&V3c::new(8, 0, 0), &V3c::new(16, 8, 8), &blue)
+        .expect("insert_box to work");
+    let expected = tree.clone();
+    let color_count = tree.voxel_color_palette.len();
+
+    // Colors are changed in place, voxels keep their data
+    assert_eq!(1, tree.replace_color(&red, &green));
+    assert_eq!(color_count, tree.voxel_color_palette.len());
+    assert_eq!(0, tree.replace_color(&red, &blue));
+    for x in 0..32 {
+        for y in 0..32 {
+            for z in 0..32 {
+                let position = V3c::new(x, y, z);
+                let expected_entry = expected.get(&position);
+                let entry = tree.get(&position);
+                let expected_albedo = match expected_entry.albedo() {
+                    Some(albedo) if *albedo == red => Some(&green),
+                    albedo => albedo,
+                };
+                assert!(
+                    entry.albedo() == expected_albedo && entry.data() == expected_entry.data(),
+                    "Hit mismatch at {:?}",
+                    (x, y, z)
+                );
+            }
+        }
+    }
+
+    // Visible colors can not become transparent
+    assert_eq!(0, tree.replace_color(&green, &Albedo::default()));
+    assert!(tree.get(&V3c::new(0, 0, 0)).albedo() == Some(&green));
+}
+
+#[test]
+fn test_map_palette_merges_equal_colors() {
+    let red: Albedo = 0xFF0000FF.into();
+    let dark_red: Albedo = 0x800000FF.into();
+    let blue: Albedo = 0x0000FFFF.into();
+    let mut tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+    tree.insert_box(&V3c::new(0, 0, 0), &V3c::new(32, 4, 32), &red)
+        .expect("insert_box to work");
+    tree.insert_box(&V3c::new(0, 4, 0), &V3c::new(32, 8, 32), &dark_red)
+        .expect("insert_box to work");
+    tree.insert_box(&V3c::new(0, 8, 0), &V3c::new(32, 12, 32), &blue)
+        .expect("insert_box to work");
+
+    // Every red tone becomes the same red, without rewriting the voxels
+    let generation = tree.generation();
+    assert_eq!(
+        1,
+        tree.map_palette(|color| if 0 < color.r { red } else { color })
+    );
+    assert_eq!(generation, tree.generation());
+    assert!(tree.voxel_color_palette.len() > tree.map_to_color_index_in_palette.len());
+    for x in 0..32 {
+        for y in 0..32 {
+            for z in 0..32 {
+                let expected = match y {
+                    0..8 => (&red).into(),
+                    8..12 => (&blue).into(),
+                    _ => BoxTreeEntry::Empty,
+                };
+                assert!(
+                    tree.get(&V3c::new(x, y, z)) == expected,
+                    "Hit mismatch at {:?}",
+                    (x, y, z)
+                );
+            }
+        }
+    }
+
+    // Equal entries are kept until the palette is compacted
+    assert_eq!((1, 0), tree.compact_palette());
+    assert_eq!(2, tree.voxel_color_palette.len());
+}
+
+#[test]
+fn test_map_palette_marks_outdated_mips() {
+    let red: Albedo = 0xFF0000FF.into();
+    let green: Albedo = 0x00FF00FF.into();
+    let blue: Albedo = 0x0000FFFF.into();
+    let mut tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+    tree.albedo_mip_map_resampling_strategy()
+        .switch_albedo_mip_maps(true);
+    tree.insert_box(&V3c::new(0, 0, 0), &V3c::new(16, 16, 16), &red)
+        .expect("insert_box to work");
+    tree.insert_box(&V3c::new(16, 16, 16), &V3c::new(32, 32, 32), &blue)
+        .expect("insert_box to work");
+    let revision = tree.palette_revision;
+
+    tree.replace_color(&red, &green);
+    assert!(revision < tree.palette_revision);
+    assert!(tree
+        .outdated_mips
+        .contains(&(BoxTree::<u32>::ROOT_NODE_KEY as usize)));
+    let untouched_child = tree.node_children[BoxTree::<u32>::ROOT_NODE_KEY as usize]
+        .child(BOX_NODE_CHILDREN_COUNT as u8 - 1);
+    assert!(!tree.outdated_mips.contains(&untouched_child));
+
+    tree.albedo_mip_map_resampling_strategy()
+        .recalculate_outdated_mips();
+    assert!(tree.outdated_mips.is_empty());
+    let mut expected = tree.clone();
+    expected
+        .albedo_mip_map_resampling_strategy()
+        .recalculate_mips();
+    for x in 0..2 {
+        for y in 0..2 {
+            for z in 0..2 {
+                let position = V3c::new(x, y, z);
+                assert!(
+                    tree.albedo_mip_map_resampling_strategy()
+                        .sample_root_mip(OOB_SECTANT, &position)
+                        == expected
+                            .albedo_mip_map_resampling_strategy()
+                            .sample_root_mip(OOB_SECTANT, &position),
+                    "MIP mismatch at {:?}",
+                    (x, y, z)
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn test_replace_data() {
+    let red: Albedo = 0xFF0000FF.into();
+    let mut tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+    tree.insert_box(&V3c::new(0, 0, 0), &V3c::new(8, 8, 8), (&red, &1))
+        .expect("insert_box to work");
+    tree.insert_box(&V3c::new(8, 0, 0), &V3c::new(16, 8, 8), voxel_data!(&2))
+        .expect("insert_box to work");
+
+    let revision = tree.palette_revision;
+    let generation = tree.generation();
+    assert_eq!(1, tree.replace_data(&1, 2));
+    assert!(revision < tree.palette_revision);
+    let changes = tree.changes_since(generation);
+    assert!(!changes.regions.is_empty());
+    assert!(changes.nodes.is_empty() && changes.bricks.is_empty());
+
+    let revision = tree.palette_revision;
+    assert_eq!(0, tree.replace_data(&1, 3));
+    assert_eq!(revision, tree.palette_revision);
+    assert!(tree.get(&V3c::new(0, 0, 0)) == (&red, &2).into());
+    assert!(tree.get(&V3c::new(8, 0, 0)) == voxel_data!(&2));
+    assert_eq!(
+        tree.voxel_data_palette.len() - 1,
+        tree.map_to_data_index_in_palette.len()
+    );
+}
+
+#[test]
+fn test_defragment_keeps_content() {
+    let red: Albedo = 0xFF0000FF.into();
+    let mut tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+    tree.albedo_mip_map_resampling_strategy()
+        .switch_albedo_mip_maps(true);
+    for x in 0..32 {
+        for z in 0..32 {
+            tree.insert(&V3c::new(x, (x + z) % 32, z), &Albedo::from(x * 32 + z + 1))
+                .expect("insert to work");
+        }
+    }
+    tree.insert_box(&V3c::new(0, 0, 0), &V3c::new(16, 16, 16), &red)
+        .expect("insert_box to work");
+    tree.clear_box(&V3c::new(0, 16, 0), &V3c::new(32, 32, 16))
+        .expect("clear_box to work");
+    assert!(0 < tree.stats().free_node_slots);
+
+    let original = tree.clone();
+    let layout_revision = tree.node_layout_revision;
+    tree.defragment();
+    assert!(layout_revision < tree.node_layout_revision);
+    assert_eq!(tree.validate(), Ok(()));
+
+    // Every node slot is used, and nothing is lost from the tree
+    let stats = tree.stats();
+    let original_stats = original.stats();
+    assert_eq!(stats.free_node_slots, 0);
+    assert_eq!(tree.nodes.len(), tree.node_children.len());
+    assert_eq!(tree.nodes.len(), tree.node_mips.len());
+    assert_eq!(stats.nodes, original_stats.nodes);
+    assert_eq!(stats.nodes_per_depth, original_stats.nodes_per_depth);
+    assert_eq!(stats.bricks, original_stats.bricks);
+    assert_eq!(stats.mips, original_stats.mips);
+    for x in 0..32 {
+        for y in 0..32 {
+            for z in 0..32 {
+                let position = V3c::new(x, y, z);
+                assert!(
+                    tree.get(&position) == original.get(&position),
+                    "Hit mismatch at {:?}",
+                    (x, y, z)
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn test_defragment_stores_siblings_next_to_each_other() {
+    let mut tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+    for x in (0..32).step_by(8) {
+        tree.insert(&V3c::new(x, x, x), &Albedo::from(x + 1))
+            .expect("insert to work");
+    }
+    tree.clear(&V3c::new(8, 8, 8)).expect("clear to work");
+    tree.insert(&V3c::new(31, 0, 0), &Albedo::from(0xFF))
+        .expect("insert to work");
+    tree.defragment();
+
+    // Children of the root are numbered in the order of their sectants, right after the root
+    let root_key = BoxTree::<u32>::ROOT_NODE_KEY as usize;
+    let children = (0..BOX_NODE_CHILDREN_COUNT as u8)
+        .filter_map(|sectant| tree.valid_child_for(root_key, sectant))
+        .collect::<Vec<_>>();
+    assert!(!children.is_empty());
+    assert_eq!(children, (1..=children.len()).collect::<Vec<_>>());
+    assert_eq!(tree.get(&V3c::new(8, 8, 8)), BoxTreeEntry::Empty);
+    assert!(tree.get(&V3c::new(16, 16, 16)) == (&Albedo::from(17)).into());
+    assert!(tree.get(&V3c::new(31, 0, 0)) == (&Albedo::from(0xFF)).into());
+}
+
+#[test]
+fn test_with_brick_dimension_keeps_content() {
+    let red: Albedo = 0xFF0000FF.into();
+    let mut tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+    tree.albedo_mip_map_resampling_strategy()
+        .switch_albedo_mip_maps(true);
+    tree.insert_box(&V3c::new(0, 0, 0), &V3c::new(16, 16, 16), (&red, &7))
+        .expect("insert_box to work");
+    for x in 0..32 {
+        for z in 0..32 {
+            tree.insert(
+                &V3c::new(x, 20 + (x + z) % 5, z),
+                &Albedo::from(x * 32 + z + 1),
+            )
+            .expect("insert to work");
+        }
+    }
+
+    // Rebuild to a larger brick dimension, then back to the original one
+    let mut rebuilt = tree.clone();
+    for brick_dimension in [8, 2] {
+        rebuilt = rebuilt
+            .with_brick_dimension(brick_dimension)
+            .expect("brick dimension to be valid");
+        assert_eq!(rebuilt.brick_dim, brick_dimension);
+        assert_eq!(rebuilt.get_size(), 32);
+        assert_eq!(rebuilt.voxel_color_palette, tree.voxel_color_palette);
+        assert!(rebuilt.mip_map_strategy.is_enabled());
+        assert!(!matches!(
+            rebuilt.node_mips[BoxTree::<u32>::ROOT_NODE_KEY as usize],
+            BrickData::Empty
+        ));
+        for x in 0..32 {
+            for y in 0..32 {
+                for z in 0..32 {
+                    let position = V3c::new(x, y, z);
+                    assert!(
+                        rebuilt.get(&position) == tree.get(&position),
+                        "Hit mismatch at {:?}",
+                        (x, y, z)
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_with_brick_dimension_invalid_parameters() {
+    let tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+    assert!(matches!(
+        tree.with_brick_dimension(4),
+        Err(OctreeError::InvalidSize(32))
+    ));
+    assert!(matches!(
+        tree.with_brick_dimension(3),
+        Err(OctreeError::InvalidBrickDimension(3))
+    ));
+    assert!(matches!(
+        tree.with_brick_dimension(32),
+        Err(OctreeError::InvalidStructure(_))
+    ));
+}
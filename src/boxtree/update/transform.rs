@@ -0,0 +1,280 @@
+use crate::{
+    boxtree::{
+        types::{Axis, BrickData, NodeChildren, NodeContent, OctreeError, PaletteIndexValues},
+        update::paste::PasteRegion,
+        BoxTree, PasteMode, VoxelData, BOX_NODE_CHILDREN_COUNT, BOX_NODE_DIMENSION,
+    },
+    spatial::math::{flat_projection, vector::V3c},
+};
+
+/// A rotation and/or mirroring of a cubic grid, mapping each position to its transformed place
+/// Each component of the transformed position is taken from the original position:
+/// new[i] = old[axes[i]], or size - 1 - old[axes[i]] if flips[i] is set
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct VoxelTransform {
+    axes: [usize; 3],
+    flips: [bool; 3],
+}
+
+impl VoxelTransform {
+    const IDENTITY: VoxelTransform = VoxelTransform {
+        axes: [0, 1, 2],
+        flips: [false, false, false],
+    };
+
+    /// A single quarter turn around the given axis: X rotates into Y around Z, Y into Z around X, Z into X around Y
+    fn quarter_turn(axis: Axis) -> Self {
+        match axis {
+            Axis::X => VoxelTransform {
+                axes: [0, 2, 1],
+                flips: [false, true, false],
+            },
+            Axis::Y => VoxelTransform {
+                axes: [2, 1, 0],
+                flips: [false, false, true],
+            },
+            Axis::Z => VoxelTransform {
+                axes: [1, 0, 2],
+                flips: [true, false, false],
+            },
+        }
+    }
+
+    /// The given number of quarter turns around the given axis, negative values turn the other way
+    fn rotation(axis: Axis, quarter_turns: i32) -> Self {
+        let turn = Self::quarter_turn(axis);
+        (0..quarter_turns.rem_euclid(4)).fold(Self::IDENTITY, |result, _| result.then(&turn))
+    }
+
+    /// Flips the positions along the given axis
+    fn mirror(axis: Axis) -> Self {
+        let mut result = Self::IDENTITY;
+        result.flips[axis as usize] = true;
+        result
+    }
+
+    /// The transformation applying this transformation first, then the given one
+    fn then(&self, other: &VoxelTransform) -> Self {
+        VoxelTransform {
+            axes: other.axes.map(|axis| self.axes[axis]),
+            flips: [0, 1, 2].map(|i| other.flips[i] ^ self.flips[other.axes[i]]),
+        }
+    }
+
+    /// Provides the extent of a box with the given extent after the transformation
+    fn transform_extent(&self, extent: &V3c<u32>) -> V3c<u32> {
+        let extent = [extent.x, extent.y, extent.z];
+        V3c::new(
+            extent[self.axes[0]],
+            extent[self.axes[1]],
+            extent[self.axes[2]],
+        )
+    }
+
+    /// Provides the flat index of the position inside a grid of the given size,
+    /// which is transformed into the position under the given flat index
+    fn source_index(&self, index: usize, size: usize) -> usize {
+        let target = [index % size, (index / size) % size, index / (size * size)];
+        let mut source = [0; 3];
+        for i in 0..3 {
+            source[self.axes[i]] = if self.flips[i] {
+                size - 1 - target[i]
+            } else {
+                target[i]
+            };
+        }
+        flat_projection(source[0], source[1], source[2], size)
+    }
+
+    /// Moves each element of the given grid into its transformed place
+    fn transform_grid<E: Clone>(&self, items: &[E], size: usize) -> Vec<E> {
+        (0..items.len())
+            .map(|index| items[self.source_index(index, size)].clone())
+            .collect()
+    }
+
+    /// Moves each sectant of a node into its transformed place
+    fn transform_sectants<E: Clone>(
+        &self,
+        items: &[E; BOX_NODE_CHILDREN_COUNT],
+    ) -> [E; BOX_NODE_CHILDREN_COUNT] {
+        std::array::from_fn(|sectant| items[self.source_index(sectant, BOX_NODE_DIMENSION)].clone())
+    }
+
+    /// Moves each bit of a sectant occupancy bitmap into its transformed place
+    fn transform_occupancy(&self, occupied_bits: u64) -> u64 {
+        (0..BOX_NODE_CHILDREN_COUNT).fold(0, |result, sectant| {
+            let source_sectant = self.source_index(sectant, BOX_NODE_DIMENSION);
+            result | (((occupied_bits >> source_sectant) & 0x01) << sectant)
+        })
+    }
+
+    /// Moves each voxel of the given brick into its transformed place
+    fn transform_brick(
+        &self,
+        brick: &BrickData<PaletteIndexValues>,
+        brick_dim: usize,
+    ) -> BrickData<PaletteIndexValues> {
+        match brick {
+            BrickData::Parted(voxels) => {
+                BrickData::Parted(self.transform_grid(voxels, brick_dim).into())
+            }
+            BrickData::Empty | BrickData::Solid(_) => brick.clone(),
+        }
+    }
+}
+
+impl<T: VoxelData> BoxTree<T> {
+    /// Rotates the whole tree around its center by the given number of quarter turns
+    /// Nodes and bricks are rearranged in place, without re-inserting any voxel.
+    /// Positive turns rotate X into Y around Z, Y into Z around X and Z into X around Y
+    /// * `axis` - The axis to rotate around
+    /// * `quarter_turns` - The number of 90 degree turns, negative values rotate in the opposite direction
+    pub fn rotate(&mut self, axis: Axis, quarter_turns: i32) {
+        self.transform(&VoxelTransform::rotation(axis, quarter_turns));
+    }
+
+    /// Mirrors the whole tree through its center along the given axis
+    /// Nodes and bricks are rearranged in place, without re-inserting any voxel.
+    /// * `axis` - The axis along which voxel positions are flipped
+    pub fn mirror(&mut self, axis: Axis) {
+        self.transform(&VoxelTransform::mirror(axis));
+    }
+
+    /// Rotates the voxels of the given axis aligned box by the given number of quarter turns, see @rotate
+    /// The rotated box is placed back starting from the same minimum position, so the box is
+    /// not required to be a cube, but its rotated extent must fit inside the tree.
+    /// * `min_position` - the first voxel of the box, must be contained within the tree
+    /// * `max_position` - the end of the box (exclusive), each component must be at most the size of the tree
+    /// * `axis` - The axis to rotate around
+    /// * `quarter_turns` - The number of 90 degree turns, negative values rotate in the opposite direction
+    pub fn rotate_region(
+        &mut self,
+        min_position: &V3c<u32>,
+        max_position: &V3c<u32>,
+        axis: Axis,
+        quarter_turns: i32,
+    ) -> Result<(), OctreeError> {
+        self.transform_region(
+            min_position,
+            max_position,
+            &VoxelTransform::rotation(axis, quarter_turns),
+        )
+    }
+
+    /// Mirrors the voxels of the given axis aligned box through its center along the given axis
+    /// * `min_position` - the first voxel of the box, must be contained within the tree
+    /// * `max_position` - the end of the box (exclusive), each component must be at most the size of the tree
+    /// * `axis` - The axis along which voxel positions are flipped
+    pub fn mirror_region(
+        &mut self,
+        min_position: &V3c<u32>,
+        max_position: &V3c<u32>,
+        axis: Axis,
+    ) -> Result<(), OctreeError> {
+        self.transform_region(min_position, max_position, &VoxelTransform::mirror(axis))
+    }
+
+    /// Rearranges every node, brick and MIP of the tree by the given transformation
+    fn transform(&mut self, transform: &VoxelTransform) {
+        if *transform == VoxelTransform::IDENTITY {
+            return;
+        }
+        self.record_tree_edit();
+        let brick_dim = self.brick_dim as usize;
+        for node_key in 0..self.nodes.len() {
+            if !self.nodes.key_is_valid(node_key) {
+                continue;
+            }
+            let content = match self.nodes.get(node_key) {
+                NodeContent::Nothing => NodeContent::Nothing,
+                NodeContent::Internal(occupied_bits) => {
+                    NodeContent::Internal(transform.transform_occupancy(*occupied_bits))
+                }
+                NodeContent::Leaf(bricks) => NodeContent::Leaf(
+                    transform
+                        .transform_sectants(bricks)
+                        .map(|brick| transform.transform_brick(&brick, brick_dim)),
+                ),
+                NodeContent::UniformLeaf(brick) => {
+                    NodeContent::UniformLeaf(transform.transform_brick(brick, brick_dim))
+                }
+            };
+            *self.nodes.get_mut(node_key) = content;
+
+            self.node_children[node_key] = match self.node_children[node_key] {
+                NodeChildren::NoChildren => NodeChildren::NoChildren,
+                NodeChildren::Children(children) => {
+                    NodeChildren::Children(transform.transform_sectants(&children))
+                }
+                NodeChildren::OccupancyBitmap(occupied_bits) => {
+                    NodeChildren::OccupancyBitmap(transform.transform_occupancy(occupied_bits))
+                }
+            };
+            self.node_mips[node_key] =
+                transform.transform_brick(&self.node_mips[node_key], brick_dim);
+        }
+        self.mark_changed(&V3c::unit(0), &V3c::unit(self.boxtree_size));
+    }
+
+    /// Transforms the voxels of the given box, placing the result back starting from the minimum position of the box
+    fn transform_region(
+        &mut self,
+        min_position: &V3c<u32>,
+        max_position: &V3c<u32>,
+        transform: &VoxelTransform,
+    ) -> Result<(), OctreeError> {
+        self.check_box_bounds(min_position, max_position)?;
+        if Self::box_is_empty(min_position, max_position) {
+            return Ok(());
+        }
+
+        let extent = *max_position - *min_position;
+        let new_max_position = *min_position + transform.transform_extent(&extent);
+        if new_max_position.x > self.boxtree_size
+            || new_max_position.y > self.boxtree_size
+            || new_max_position.z > self.boxtree_size
+        {
+            return Err(OctreeError::InvalidPosition {
+                x: new_max_position.x,
+                y: new_max_position.y,
+                z: new_max_position.z,
+            });
+        }
+
+        // Transform a copy of the box, then find the place of the box inside the transformed copy
+        let mut region = self.extract_region(min_position, max_position)?;
+        region.transform(transform);
+        let size = region.boxtree_size;
+        let extent = [extent.x, extent.y, extent.z];
+        let (source_min, source_max): (Vec<u32>, Vec<u32>) = (0..3)
+            .map(|i| {
+                let axis_extent = extent[transform.axes[i]];
+                if transform.flips[i] {
+                    (size - axis_extent, size)
+                } else {
+                    (0, axis_extent)
+                }
+            })
+            .unzip();
+
+        // Clearing and pasting the box is recorded as a single edit
+        let edited_max_position = V3c::new(
+            max_position.x.max(new_max_position.x),
+            max_position.y.max(new_max_position.y),
+            max_position.z.max(new_max_position.z),
+        );
+        self.record_box_update(min_position, &edited_max_position, |tree| {
+            tree.clear_box(min_position, max_position)?;
+            tree.paste_region(
+                &region,
+                &PasteRegion {
+                    source_min: V3c::new(source_min[0], source_min[1], source_min[2]),
+                    source_max: V3c::new(source_max[0], source_max[1], source_max[2]),
+                    target_min: *min_position,
+                },
+                PasteMode::Overwrite,
+            )
+        })
+    }
+}
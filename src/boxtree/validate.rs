@@ -0,0 +1,220 @@
+use crate::{
+    boxtree::{
+        types::{BrickData, NodeChildren, NodeContent, PaletteIndexValues, ValidationIssue},
+        BoxTree, VoxelData, BOX_NODE_CHILDREN_COUNT,
+    },
+    object_pool::empty_marker,
+};
+
+impl ValidationIssue {
+    /// Tells if the issue is in the structure of the tree, e.g. its nodes, bricks or palette indices
+    /// MIP issues are not structural, as MIPs can be recalculated from the content of the tree
+    pub fn is_structural(&self) -> bool {
+        !matches!(self, ValidationIssue::MipMismatch { .. })
+    }
+}
+
+impl<T: VoxelData> BoxTree<T> {
+    /// Checks the consistency of the internal structure of the tree, e.g. after loading it from a file
+    /// Only nodes reachable from the root are checked
+    /// * Returns with every issue found, or Ok if the tree is consistent
+    pub fn validate(&self) -> Result<(), Vec<ValidationIssue>> {
+        let root_key = Self::ROOT_NODE_KEY as usize;
+        if !self.nodes.key_is_valid(root_key) {
+            return Err(vec![ValidationIssue::MissingRoot]);
+        }
+        if self.node_children.len() < self.nodes.len() || self.node_mips.len() < self.nodes.len() {
+            return Err(vec![ValidationIssue::StorageSizeMismatch {
+                nodes: self.nodes.len(),
+                node_children: self.node_children.len(),
+                node_mips: self.node_mips.len(),
+            }]);
+        }
+
+        let mut issues = vec![];
+        let mut visited = vec![false; self.nodes.len()];
+        let mut node_stack = vec![root_key];
+        visited[root_key] = true;
+        while let Some(node_key) = node_stack.pop() {
+            self.validate_node(node_key, &mut issues);
+            let NodeChildren::Children(children) = &self.node_children[node_key] else {
+                continue;
+            };
+            if !matches!(self.nodes.get(node_key), NodeContent::Internal(_)) {
+                continue;
+            }
+            for (sectant, child_key) in children.iter().enumerate() {
+                let child_key = *child_key as usize;
+                if child_key == empty_marker::<u32>() as usize {
+                    continue;
+                }
+                if !self.nodes.key_is_valid(child_key) {
+                    issues.push(ValidationIssue::InvalidChild {
+                        node_key,
+                        sectant: sectant as u8,
+                        child_key,
+                    });
+                } else if visited[child_key] {
+                    issues.push(ValidationIssue::SharedNode {
+                        node_key: child_key,
+                    });
+                } else {
+                    visited[child_key] = true;
+                    node_stack.push(child_key);
+                }
+            }
+        }
+
+        if issues.is_empty() {
+            Ok(())
+        } else {
+            Err(issues)
+        }
+    }
+
+    /// Checks the content, children, occupancy and MIP of the given node
+    fn validate_node(&self, node_key: usize, issues: &mut Vec<ValidationIssue>) {
+        // Occupancy can only be calculated if every brick of the node is valid
+        let content = self.nodes.get(node_key);
+        let bricks_valid = match content {
+            NodeContent::Nothing | NodeContent::Internal(_) => true,
+            NodeContent::Leaf(bricks) => {
+                // Every brick is checked, so each issue gets reported
+                0 == bricks
+                    .iter()
+                    .filter(|brick| !self.validate_brick(node_key, brick, issues))
+                    .count()
+            }
+            NodeContent::UniformLeaf(brick) => self.validate_brick(node_key, brick, issues),
+        };
+        let mip_valid = self.validate_brick(node_key, &self.node_mips[node_key], issues);
+
+        let stored_occupied_bits = match (content, &self.node_children[node_key]) {
+            (NodeContent::Nothing, NodeChildren::NoChildren | NodeChildren::OccupancyBitmap(0)) => {
+                Some(0)
+            }
+            (NodeContent::Internal(occupied_bits), NodeChildren::Children(_)) => {
+                Some(*occupied_bits)
+            }
+            (
+                NodeContent::Leaf(_) | NodeContent::UniformLeaf(_),
+                NodeChildren::OccupancyBitmap(occupied_bits),
+            ) => Some(*occupied_bits),
+            _ => None,
+        };
+        let Some(stored_occupied_bits) = stored_occupied_bits else {
+            issues.push(ValidationIssue::ChildrenMismatch { node_key });
+            return;
+        };
+
+        if bricks_valid {
+            let expected = match content {
+                NodeContent::Internal(_) => (0..BOX_NODE_CHILDREN_COUNT as u8)
+                    .filter(|sectant| {
+                        self.valid_child_for(node_key, *sectant)
+                            .is_some_and(|child_key| 0 != self.checked_occupied_bits(child_key))
+                    })
+                    .fold(0, |bits, sectant| bits | (0x01 << sectant)),
+                _ => self.calculate_node_occupied_bits(node_key),
+            };
+            if expected != stored_occupied_bits {
+                issues.push(ValidationIssue::OccupancyMismatch {
+                    node_key,
+                    stored: stored_occupied_bits,
+                    expected,
+                });
+            }
+        }
+
+        // MIPs left over in nodes not needing one are not used, so only missing MIPs are reported
+        let has_mip = !matches!(self.node_mips[node_key], BrickData::Empty);
+        if mip_valid && !has_mip && self.expects_mip(node_key, stored_occupied_bits) {
+            issues.push(ValidationIssue::MipMismatch { node_key });
+        }
+    }
+
+    /// Tells if the given node needs a MIP with the given occupancy
+    fn expects_mip(&self, node_key: usize, occupied_bits: u64) -> bool {
+        // Uniform leaf nodes need no MIP, as their content is equivalent with it
+        self.mip_map_strategy.enabled
+            && 0 != occupied_bits
+            && !matches!(self.nodes.get(node_key), NodeContent::UniformLeaf(_))
+    }
+
+    /// Erases the MIPs of the nodes not needing one, e.g. the ones left over after MIPs were disabled
+    #[cfg(any(test, feature = "bytecode"))]
+    pub(crate) fn drop_unexpected_mips(&mut self) {
+        for node_key in 0..self.nodes.len().min(self.node_mips.len()) {
+            if !self.nodes.key_is_valid(node_key)
+                || !self.expects_mip(node_key, self.checked_occupied_bits(node_key))
+            {
+                self.node_mips[node_key] = BrickData::Empty;
+            }
+        }
+    }
+
+    /// Provides the stored occupied bits of the given node, or 0 if its children do not fit its content
+    fn checked_occupied_bits(&self, node_key: usize) -> u64 {
+        match (self.nodes.get(node_key), &self.node_children[node_key]) {
+            (NodeContent::Internal(occupied_bits), NodeChildren::Children(_)) => *occupied_bits,
+            (
+                NodeContent::Leaf(_) | NodeContent::UniformLeaf(_),
+                NodeChildren::OccupancyBitmap(occupied_bits),
+            ) => *occupied_bits,
+            _ => 0,
+        }
+    }
+
+    /// Checks the size of the given brick and the palette indices inside it
+    /// * Returns with true if no issues were found
+    fn validate_brick(
+        &self,
+        node_key: usize,
+        brick: &BrickData<PaletteIndexValues>,
+        issues: &mut Vec<ValidationIssue>,
+    ) -> bool {
+        let issue_count = issues.len();
+        let voxels = match brick {
+            BrickData::Empty => return true,
+            BrickData::Solid(voxel) => std::slice::from_ref(voxel),
+            BrickData::Parted(voxels) => {
+                if voxels.len() != self.brick_dim.pow(3) as usize {
+                    issues.push(ValidationIssue::InvalidBrickSize {
+                        node_key,
+                        size: voxels.len(),
+                    });
+                }
+                voxels.as_slice()
+            }
+        };
+
+        // Report each invalid index only once for the brick
+        let mut invalid_colors = vec![];
+        let mut invalid_data = vec![];
+        for voxel in voxels {
+            let color_index = NodeContent::pix_color_index(voxel);
+            if NodeContent::pix_color_is_some(voxel)
+                && color_index >= self.voxel_color_palette.len()
+                && !invalid_colors.contains(&color_index)
+            {
+                invalid_colors.push(color_index);
+                issues.push(ValidationIssue::ColorIndexOutOfRange {
+                    node_key,
+                    index: color_index,
+                });
+            }
+            let data_index = NodeContent::pix_data_index(voxel);
+            if NodeContent::pix_data_is_some(voxel)
+                && data_index >= self.voxel_data_palette.len()
+                && !invalid_data.contains(&data_index)
+            {
+                invalid_data.push(data_index);
+                issues.push(ValidationIssue::DataIndexOutOfRange {
+                    node_key,
+                    index: data_index,
+                });
+            }
+        }
+        issue_count == issues.len()
+    }
+}
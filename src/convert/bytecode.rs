@@ -1,16 +1,24 @@
 use crate::{
     boxtree::{
-        types::{BrickData, MIPMapStrategy, MIPResamplingMethods, NodeChildren, NodeContent},
+        types::{
+            BrickData, ChangeTracker, MIPMapStrategy, MIPResamplingMethods, NodeChildren,
+            NodeContent, PaletteOverflowStrategy,
+        },
         Albedo, BoxTree, BOX_NODE_CHILDREN_COUNT,
     },
     object_pool::ObjectPool,
+    shared::SharedVec,
     Version,
 };
 use bendy::{
     decoding::{Error, FromBencode, Object},
     encoding::{Error as BencodeError, SingleItemEncoder, ToBencode},
 };
-use std::{collections::HashMap, fmt::Debug, hash::Hash};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Debug,
+    hash::Hash,
+};
 
 impl ToBencode for Version {
     const MAX_DEPTH: usize = 2;
@@ -223,7 +231,7 @@ where
                     for _ in 0..len {
                         brick_data.push(T::decode_bencode_object(list.next_object()?.unwrap())?);
                     }
-                    Ok(BrickData::Parted(brick_data))
+                    Ok(BrickData::Parted(brick_data.into()))
                 }
             }
             _ => Err(bendy::decoding::Error::unexpected_token(
@@ -653,8 +661,8 @@ where
             e.emit(&self.nodes)?;
             e.emit(&self.node_children)?;
             e.emit(&self.node_mips)?;
-            e.emit(&self.voxel_color_palette)?;
-            e.emit(&self.voxel_data_palette)?;
+            e.emit(&*self.voxel_color_palette)?;
+            e.emit(&*self.voxel_data_palette)?;
             e.emit(&self.mip_map_strategy)?;
             Ok(())
         })
@@ -699,8 +707,8 @@ where
                 }?;
 
                 let nodes = ObjectPool::decode_bencode_object(list.next_object()?.unwrap())?;
-                let node_children = Vec::decode_bencode_object(list.next_object()?.unwrap())?;
-                let node_mips = Vec::decode_bencode_object(list.next_object()?.unwrap())?;
+                let node_children = SharedVec::decode_bencode_object(list.next_object()?.unwrap())?;
+                let node_mips = SharedVec::decode_bencode_object(list.next_object()?.unwrap())?;
 
                 let voxel_color_palette =
                     Vec::<Albedo>::decode_bencode_object(list.next_object()?.unwrap())?;
@@ -726,11 +734,17 @@ where
                     nodes,
                     node_children,
                     node_mips,
-                    voxel_color_palette,
-                    voxel_data_palette,
-                    map_to_color_index_in_palette,
-                    map_to_data_index_in_palette,
+                    voxel_color_palette: voxel_color_palette.into(),
+                    voxel_data_palette: voxel_data_palette.into(),
+                    map_to_color_index_in_palette: map_to_color_index_in_palette.into(),
+                    map_to_data_index_in_palette: map_to_data_index_in_palette.into(),
                     mip_map_strategy,
+                    palette_overflow_strategy: PaletteOverflowStrategy::default(),
+                    outdated_mips: HashSet::new(),
+                    palette_revision: 0,
+                    node_layout_revision: 0,
+                    journal: None,
+                    changes: ChangeTracker::default(),
                 })
             }
             _ => Err(bendy::decoding::Error::unexpected_token("List", "not List")),
@@ -226,6 +226,32 @@ fn test_boxtree_file_io() {
     assert_eq!(hits, (64 - 8));
 }
 
+#[test]
+fn test_boxtree_file_io_after_disabling_mips() {
+    let red: Albedo = 0xFF0000FF.into();
+    let green: Albedo = 0x00FF00FF.into();
+    let mut tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+    tree.albedo_mip_map_resampling_strategy()
+        .switch_albedo_mip_maps(true);
+    tree.insert(&V3c::new(1, 2, 3), &red).ok().unwrap();
+    tree.insert(&V3c::new(20, 20, 20), &green).ok().unwrap();
+    tree.albedo_mip_map_resampling_strategy()
+        .switch_albedo_mip_maps(false);
+
+    // MIPs left over from the disabled strategy do not prevent loading
+    tree.save("test_junk_boxtree_disabled_mips").ok().unwrap();
+    let tree_copy = BoxTree::load("test_junk_boxtree_disabled_mips")
+        .expect("Expected tree to be loaded");
+    assert_eq!(tree_copy.validate(), Ok(()));
+    assert!(!tree_copy.mip_map_strategy.is_enabled());
+    assert!(tree_copy
+        .node_mips
+        .iter()
+        .all(|mip| matches!(mip, BrickData::Empty)));
+    assert!(tree_copy.get(&V3c::new(1, 2, 3)) == (&red).into());
+    assert!(tree_copy.get(&V3c::new(20, 20, 20)) == (&green).into());
+}
+
 #[test]
 fn test_big_boxtree_serialize() {
     const TREE_SIZE: u32 = 256;
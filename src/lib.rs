@@ -1,11 +1,15 @@
 #![doc = include_str!("../README.md")]
 
 mod object_pool;
+mod shared;
 mod spatial;
 
 /// Container for voxel data
 pub mod boxtree;
 
+/// Triangle mesh extraction from voxel data
+pub mod mesh;
+
 /// Serialization/deserialization
 #[cfg(any(
     feature = "bytecode",
@@ -0,0 +1,105 @@
+use crate::mesh::Mesh;
+use std::{
+    fs::File,
+    io::{BufWriter, Error, Write},
+    path::Path,
+};
+
+impl Mesh {
+    /// Writes the mesh in Wavefront OBJ format
+    /// Vertex colors are written after the positions, as red, green and blue values in the range 0..1.
+    pub fn write_obj<W: Write>(&self, writer: &mut W) -> Result<(), Error> {
+        writeln!(
+            writer,
+            "# {} vertices, {} triangles",
+            self.vertex_count(),
+            self.triangle_count()
+        )?;
+        for (position, color) in self.positions.iter().zip(self.colors.iter()) {
+            writeln!(
+                writer,
+                "v {} {} {} {} {} {}",
+                position.x,
+                position.y,
+                position.z,
+                color.r as f32 / 255.,
+                color.g as f32 / 255.,
+                color.b as f32 / 255.
+            )?;
+        }
+        for normal in &self.normals {
+            writeln!(writer, "vn {} {} {}", normal.x, normal.y, normal.z)?;
+        }
+        for triangle in self.indices.chunks_exact(3) {
+            // Indices start from 1, and each vertex has its own normal
+            writeln!(
+                writer,
+                "f {0}//{0} {1}//{1} {2}//{2}",
+                triangle[0] + 1,
+                triangle[1] + 1,
+                triangle[2] + 1
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Writes the mesh in binary little endian PLY format
+    /// Each vertex has its position, normal and color with alpha, each face has its three vertex indices.
+    pub fn write_ply<W: Write>(&self, writer: &mut W) -> Result<(), Error> {
+        write!(
+            writer,
+            "ply\n\
+             format binary_little_endian 1.0\n\
+             element vertex {}\n\
+             property float x\n\
+             property float y\n\
+             property float z\n\
+             property float nx\n\
+             property float ny\n\
+             property float nz\n\
+             property uchar red\n\
+             property uchar green\n\
+             property uchar blue\n\
+             property uchar alpha\n\
+             element face {}\n\
+             property list uchar uint vertex_indices\n\
+             end_header\n",
+            self.vertex_count(),
+            self.triangle_count()
+        )?;
+        for ((position, normal), color) in self
+            .positions
+            .iter()
+            .zip(self.normals.iter())
+            .zip(self.colors.iter())
+        {
+            for value in [
+                position.x, position.y, position.z, normal.x, normal.y, normal.z,
+            ] {
+                writer.write_all(&value.to_le_bytes())?;
+            }
+            writer.write_all(&[color.r, color.g, color.b, color.a])?;
+        }
+        for triangle in self.indices.chunks_exact(3) {
+            writer.write_all(&[3])?;
+            for index in triangle {
+                writer.write_all(&index.to_le_bytes())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Saves the mesh to the given file path in Wavefront OBJ format, see @write_obj
+    pub fn save_obj<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_obj(&mut writer)?;
+        writer.flush()
+    }
+
+    /// Saves the mesh to the given file path in binary PLY format, see @write_ply
+    pub fn save_ply<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_ply(&mut writer)?;
+        writer.flush()
+    }
+}
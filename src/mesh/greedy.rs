@@ -0,0 +1,650 @@
+use crate::{
+    boxtree::{
+        types::{BoxTreeChanges, BrickData, NodeContent, OctreeError, PaletteIndexValues},
+        update::region::BoxRegion,
+        Albedo, BoxTree, VoxelData, BOX_NODE_CHILDREN_COUNT, BOX_NODE_DIMENSION,
+    },
+    mesh::{Mesh, MeshChunk},
+    object_pool::empty_marker,
+    spatial::{math::vector::V3c, Cube},
+};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    ops::Range,
+};
+
+/// Provides the position with the given components along the given axes
+fn position_on_axes<N: Copy + Default>(axes: (usize, usize, usize), values: (N, N, N)) -> V3c<N> {
+    let mut components = [N::default(); 3];
+    components[axes.0] = values.0;
+    components[axes.1] = values.1;
+    components[axes.2] = values.2;
+    V3c::new(components[0], components[1], components[2])
+}
+
+/// Provides the axis the faces are facing along, and the two axes the faces are merged along,
+/// in an order keeping the triangles counter-clockwise
+fn face_axes(axis: usize) -> (usize, usize, usize) {
+    (axis, (axis + 1) % 3, (axis + 2) % 3)
+}
+
+/// The faces on one side of an area, clipped to the meshed region
+struct AreaSide {
+    /// The axis the faces are facing along
+    axis: usize,
+
+    /// True if the faces are facing towards the positive direction of the axis
+    positive: bool,
+
+    /// The layer of voxels the faces belong to, along the axis
+    layer: u32,
+
+    /// The range of the faces along the axes they are merged along, see @face_axes
+    u_range: Range<u32>,
+    v_range: Range<u32>,
+}
+
+impl AreaSide {
+    /// Provides the index of the face at the given position on the side, ordered along u, then v
+    fn index(&self, u: u32, v: u32) -> usize {
+        (u - self.u_range.start + (v - self.v_range.start) * self.u_range.len() as u32) as usize
+    }
+}
+
+/// The visible faces of a region being collected from the tree
+struct VisibleFaces<'a, T: VoxelData> {
+    tree: &'a BoxTree<T>,
+    min_position: [u32; 3],
+    max_position: [u32; 3],
+
+    /// The color index of each visible face, or the empty marker for faces without color,
+    /// for each axis, direction and layer of the region containing any visible face
+    masks: BTreeMap<(usize, bool, u32), Vec<Option<u16>>>,
+}
+
+impl<T: VoxelData> VisibleFaces<'_, T> {
+    /// Tells if the given bounds overlap with the region
+    fn overlaps(&self, bounds: &Cube) -> bool {
+        let bounds_min = V3c::<u32>::from(bounds.min_position);
+        let bounds_min = [bounds_min.x, bounds_min.y, bounds_min.z];
+        (0..3).all(|axis| {
+            bounds_min[axis] < self.max_position[axis]
+                && self.min_position[axis] < bounds_min[axis] + bounds.size as u32
+        })
+    }
+
+    /// Tells if the given voxel contains no data
+    fn is_empty(&self, voxel: &PaletteIndexValues) -> bool {
+        NodeContent::pix_points_to_empty(
+            voxel,
+            &self.tree.voxel_color_palette,
+            &self.tree.voxel_data_palette,
+        )
+    }
+
+    /// Collects the visible faces of every occupied node and brick overlapping with the region
+    /// Uniform nodes and bricks are handled as a whole, only parted bricks are visited voxel by voxel
+    fn collect(&mut self) {
+        let tree = self.tree;
+        let mut node_stack = vec![(
+            BoxTree::<T>::ROOT_NODE_KEY as usize,
+            Cube::root_bounds(tree.boxtree_size as f32),
+        )];
+        while let Some((node_key, bounds)) = node_stack.pop() {
+            match tree.nodes.get(node_key) {
+                NodeContent::Nothing => {}
+                NodeContent::Internal(occupied_bits) => {
+                    for sectant in 0..BOX_NODE_CHILDREN_COUNT as u8 {
+                        let child_bounds = bounds.child_bounds_for(sectant);
+                        if 0 == occupied_bits & (0x01 << sectant) || !self.overlaps(&child_bounds) {
+                            continue;
+                        }
+                        if let Some(child_key) = tree.valid_child_for(node_key, sectant) {
+                            node_stack.push((child_key, child_bounds));
+                        }
+                    }
+                }
+                NodeContent::Leaf(bricks) => {
+                    for (sectant, brick) in bricks.iter().enumerate() {
+                        let child_bounds = bounds.child_bounds_for(sectant as u8);
+                        if self.overlaps(&child_bounds) {
+                            self.collect_brick(brick, &child_bounds);
+                        }
+                    }
+                }
+                NodeContent::UniformLeaf(brick) => self.collect_brick(brick, &bounds),
+            }
+        }
+    }
+
+    /// Collects the visible faces of the given brick
+    /// Faces between the voxels of a parted brick are decided by the brick itself,
+    /// the voxels next to the brick are looked up once for each side of it
+    fn collect_brick(&mut self, brick: &BrickData<PaletteIndexValues>, brick_bounds: &Cube) {
+        let voxels = match brick {
+            BrickData::Empty => return,
+            BrickData::Solid(voxel) => return self.collect_area(brick_bounds, voxel),
+            BrickData::Parted(voxels) => voxels,
+        };
+        let mut brick_sides = vec![];
+        for axis in 0..3 {
+            for positive in [false, true] {
+                brick_sides.push(self.side_of(brick_bounds, axis, positive).map(|side| {
+                    let empty_neighbors = self.empty_neighbors(&side);
+                    (side, empty_neighbors)
+                }));
+            }
+        }
+
+        // Each voxel of the brick might cover multiple voxels of the tree
+        let brick_dim = self.tree.brick_dim as usize;
+        let cell_size = brick_bounds.size / brick_dim as f32;
+        for (index, voxel) in voxels.iter().enumerate() {
+            let cell = [
+                index % brick_dim,
+                (index / brick_dim) % brick_dim,
+                index / (brick_dim * brick_dim),
+            ];
+            let cell_bounds = Cube {
+                min_position: brick_bounds.min_position
+                    + V3c::new(cell[0] as f32, cell[1] as f32, cell[2] as f32) * cell_size,
+                size: cell_size,
+            };
+            if self.is_empty(voxel) || !self.overlaps(&cell_bounds) {
+                continue;
+            }
+            let key = NodeContent::pix_color_index(voxel) as u16;
+            for axis in 0..3 {
+                for positive in [false, true] {
+                    let Some(side) = self.side_of(&cell_bounds, axis, positive) else {
+                        continue;
+                    };
+                    let mut neighbor_cell = cell;
+                    let neighbor_inside = if positive {
+                        neighbor_cell[axis] += 1;
+                        neighbor_cell[axis] < brick_dim
+                    } else if 0 < cell[axis] {
+                        neighbor_cell[axis] -= 1;
+                        true
+                    } else {
+                        false
+                    };
+                    if neighbor_inside {
+                        let neighbor_index = neighbor_cell[0]
+                            + neighbor_cell[1] * brick_dim
+                            + neighbor_cell[2] * brick_dim * brick_dim;
+                        if self.is_empty(&voxels[neighbor_index]) {
+                            self.push_faces(&side, key, |_, _| true);
+                        }
+                    } else if let Some((brick_side, empty_neighbors)) =
+                        &brick_sides[axis * 2 + positive as usize]
+                    {
+                        self.push_faces(&side, key, |u, v| empty_neighbors[brick_side.index(u, v)]);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Collects the visible faces on the sides of the given bounds, filled with the given voxel
+    /// A face is visible if the voxel next to it is empty or outside the tree
+    fn collect_area(&mut self, bounds: &Cube, voxel: &PaletteIndexValues) {
+        if self.is_empty(voxel) {
+            return;
+        }
+        let key = NodeContent::pix_color_index(voxel) as u16;
+        for axis in 0..3 {
+            for positive in [false, true] {
+                if let Some(side) = self.side_of(bounds, axis, positive) {
+                    let empty_neighbors = self.empty_neighbors(&side);
+                    self.push_faces(&side, key, |u, v| empty_neighbors[side.index(u, v)]);
+                }
+            }
+        }
+    }
+
+    /// Provides the given side of the given bounds clipped to the region, if it has any faces inside it
+    fn side_of(&self, bounds: &Cube, axis: usize, positive: bool) -> Option<AreaSide> {
+        let area_min = V3c::<u32>::from(bounds.min_position);
+        let area_min = [area_min.x, area_min.y, area_min.z];
+        let area_max = area_min.map(|component| component + bounds.size as u32);
+        let axes = face_axes(axis);
+        let layer = if positive {
+            area_max[axis] - 1
+        } else {
+            area_min[axis]
+        };
+        let side = AreaSide {
+            axis,
+            positive,
+            layer,
+            u_range: area_min[axes.1].max(self.min_position[axes.1])
+                ..area_max[axes.1].min(self.max_position[axes.1]),
+            v_range: area_min[axes.2].max(self.min_position[axes.2])
+                ..area_max[axes.2].min(self.max_position[axes.2]),
+        };
+        if layer < self.min_position[axis]
+            || layer >= self.max_position[axis]
+            || side.u_range.is_empty()
+            || side.v_range.is_empty()
+        {
+            return None;
+        }
+        Some(side)
+    }
+
+    /// Tells for each face of the given side if the voxel next to it is empty or outside the tree
+    /// The voxels next to the side are collected from the occupancy of the nodes and bricks,
+    /// only parted bricks are visited cell by cell
+    /// * Returns with the flags for each face, ordered as by @AreaSide::index
+    fn empty_neighbors(&self, side: &AreaSide) -> Vec<bool> {
+        let face_count = side.u_range.len() * side.v_range.len();
+        let neighbor_layer = if side.positive {
+            Some(side.layer + 1).filter(|layer| *layer < self.tree.boxtree_size)
+        } else {
+            side.layer.checked_sub(1)
+        };
+        let Some(neighbor_layer) = neighbor_layer else {
+            return vec![true; face_count];
+        };
+        let axes = face_axes(side.axis);
+        let mut empty_neighbors = vec![false; face_count];
+        self.mark_empty_in_slab(
+            BoxTree::<T>::ROOT_NODE_KEY as usize,
+            &Cube::root_bounds(self.tree.boxtree_size as f32),
+            side,
+            &BoxRegion {
+                min_position: position_on_axes(
+                    axes,
+                    (neighbor_layer, side.u_range.start, side.v_range.start),
+                ),
+                max_position: position_on_axes(
+                    axes,
+                    (neighbor_layer + 1, side.u_range.end, side.v_range.end),
+                ),
+            },
+            &mut empty_neighbors,
+        );
+        empty_neighbors
+    }
+
+    /// Marks the faces of the given side, which have an empty voxel of the given node next to them
+    /// * `slab` - The voxels next to the side
+    fn mark_empty_in_slab(
+        &self,
+        node_key: usize,
+        node_bounds: &Cube,
+        side: &AreaSide,
+        slab: &BoxRegion,
+        empty_neighbors: &mut [bool],
+    ) {
+        let tree = self.tree;
+        let Some(area) = slab.clip(node_bounds) else {
+            return;
+        };
+        let occupied_bits = tree.stored_occupied_bits(node_key);
+        if 0 == occupied_bits {
+            return Self::mark_area(side, &area, empty_neighbors);
+        }
+        match tree.nodes.get(node_key) {
+            NodeContent::Nothing => Self::mark_area(side, &area, empty_neighbors),
+            NodeContent::Internal(_) => {
+                for sectant in 0..BOX_NODE_CHILDREN_COUNT as u8 {
+                    let child_bounds = node_bounds.child_bounds_for(sectant);
+                    match tree.valid_child_for(node_key, sectant) {
+                        Some(child_key) if 0 != occupied_bits & (0x01 << sectant) => {
+                            self.mark_empty_in_slab(
+                                child_key,
+                                &child_bounds,
+                                side,
+                                slab,
+                                empty_neighbors,
+                            );
+                        }
+                        _ => {
+                            if let Some(area) = slab.clip(&child_bounds) {
+                                Self::mark_area(side, &area, empty_neighbors);
+                            }
+                        }
+                    }
+                }
+            }
+            NodeContent::Leaf(bricks) => {
+                for (sectant, brick) in bricks.iter().enumerate() {
+                    let brick_bounds = node_bounds.child_bounds_for(sectant as u8);
+                    if 0 == occupied_bits & (0x01 << sectant) {
+                        if let Some(area) = slab.clip(&brick_bounds) {
+                            Self::mark_area(side, &area, empty_neighbors);
+                        }
+                    } else {
+                        self.mark_empty_in_brick(brick, &brick_bounds, side, slab, empty_neighbors);
+                    }
+                }
+            }
+            NodeContent::UniformLeaf(brick) => {
+                self.mark_empty_in_brick(brick, node_bounds, side, slab, empty_neighbors);
+            }
+        }
+    }
+
+    /// Marks the faces of the given side, which have an empty voxel of the given brick next to them
+    fn mark_empty_in_brick(
+        &self,
+        brick: &BrickData<PaletteIndexValues>,
+        brick_bounds: &Cube,
+        side: &AreaSide,
+        slab: &BoxRegion,
+        empty_neighbors: &mut [bool],
+    ) {
+        let Some(area) = slab.clip(brick_bounds) else {
+            return;
+        };
+        match brick {
+            BrickData::Empty => Self::mark_area(side, &area, empty_neighbors),
+            BrickData::Solid(voxel) => {
+                if self.is_empty(voxel) {
+                    Self::mark_area(side, &area, empty_neighbors);
+                }
+            }
+            BrickData::Parted(voxels) => {
+                // Only the cells of the brick overlapping with the slab are visited
+                let brick_dim = self.tree.brick_dim as usize;
+                let cell_size = brick_bounds.size as u32 / self.tree.brick_dim;
+                let brick_min = V3c::<u32>::from(brick_bounds.min_position);
+                let first_cell = (area.min_position - brick_min) / cell_size;
+                let end_cell =
+                    (area.max_position - brick_min - V3c::unit(1)) / cell_size + V3c::unit(1);
+                for z in first_cell.z..end_cell.z {
+                    for y in first_cell.y..end_cell.y {
+                        for x in first_cell.x..end_cell.x {
+                            let voxel = &voxels[x as usize
+                                + y as usize * brick_dim
+                                + z as usize * brick_dim * brick_dim];
+                            if !self.is_empty(voxel) {
+                                continue;
+                            }
+                            let cell_bounds = Cube {
+                                min_position: (brick_min + V3c::new(x, y, z) * cell_size).into(),
+                                size: cell_size as f32,
+                            };
+                            if let Some(area) = slab.clip(&cell_bounds) {
+                                Self::mark_area(side, &area, empty_neighbors);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Marks the faces of the given side next to the given area of the slab
+    fn mark_area(side: &AreaSide, area: &BoxRegion, empty_neighbors: &mut [bool]) {
+        let axes = face_axes(side.axis);
+        let area_min = [
+            area.min_position.x,
+            area.min_position.y,
+            area.min_position.z,
+        ];
+        let area_max = [
+            area.max_position.x,
+            area.max_position.y,
+            area.max_position.z,
+        ];
+        for v in area_min[axes.2]..area_max[axes.2] {
+            for u in area_min[axes.1]..area_max[axes.1] {
+                empty_neighbors[side.index(u, v)] = true;
+            }
+        }
+    }
+
+    /// Adds the faces of the given side, for which the given function tells they are visible
+    /// * `visible` - Tells if the face at the given position is visible: |u, v| { ... }
+    fn push_faces<F: Fn(u32, u32) -> bool>(&mut self, side: &AreaSide, key: u16, visible: F) {
+        let axes = face_axes(side.axis);
+        let width = self.max_position[axes.1] - self.min_position[axes.1];
+        let height = self.max_position[axes.2] - self.min_position[axes.2];
+        for v in side.v_range.clone() {
+            for u in side.u_range.clone() {
+                if !visible(u, v) {
+                    continue;
+                }
+                let mask = self
+                    .masks
+                    .entry((side.axis, side.positive, side.layer))
+                    .or_insert_with(|| vec![None; (width * height) as usize]);
+                mask[(u - self.min_position[axes.1] + (v - self.min_position[axes.2]) * width)
+                    as usize] = Some(key);
+            }
+        }
+    }
+}
+
+impl<T: VoxelData> BoxTree<T> {
+    /// Creates a mesh from the visible faces of the voxels inside the given region
+    /// A face is visible if the voxel next to it is empty or outside the tree, voxels outside the region
+    /// are considered, so meshes of neighboring regions fit together without overlapping faces.
+    /// Faces are collected from the occupied nodes and bricks, uniform ones are not visited voxel by voxel.
+    /// Coplanar faces of the same color are merged into larger quads.
+    /// Voxels without color have the default color in the mesh.
+    /// * `min_position` - the first voxel of the box, must be contained within the tree
+    /// * `max_position` - the end of the box (exclusive), each component must be at most the size of the tree
+    pub fn mesh_region(
+        &self,
+        min_position: &V3c<u32>,
+        max_position: &V3c<u32>,
+    ) -> Result<Mesh, OctreeError> {
+        self.check_box_bounds(min_position, max_position)?;
+        let mut mesh = Mesh::default();
+        if Self::box_is_empty(min_position, max_position) {
+            return Ok(mesh);
+        }
+
+        let min_position = [min_position.x, min_position.y, min_position.z];
+        let max_position = [max_position.x, max_position.y, max_position.z];
+        let mut faces = VisibleFaces {
+            tree: self,
+            min_position,
+            max_position,
+            masks: BTreeMap::new(),
+        };
+        faces.collect();
+
+        for ((axis, positive, layer), mut mask) in faces.masks {
+            let axes = face_axes(axis);
+            let width = max_position[axes.1] - min_position[axes.1];
+            let height = max_position[axes.2] - min_position[axes.2];
+            let mut normal = [0.; 3];
+            normal[axis] = if positive { 1. } else { -1. };
+            let normal = V3c::new(normal[0], normal[1], normal[2]);
+
+            // Merge the faces into rectangles, growing them first along u, then along v
+            let plane = (layer + positive as u32) as f32;
+            for v in 0..height {
+                let mut u = 0;
+                while u < width {
+                    let Some(key) = mask[(u + v * width) as usize] else {
+                        u += 1;
+                        continue;
+                    };
+                    let mut quad_width = 1;
+                    while u + quad_width < width
+                        && mask[(u + quad_width + v * width) as usize] == Some(key)
+                    {
+                        quad_width += 1;
+                    }
+                    let mut quad_height = 1;
+                    while v + quad_height < height
+                        && (u..u + quad_width).all(|quad_u| {
+                            mask[(quad_u + (v + quad_height) * width) as usize] == Some(key)
+                        })
+                    {
+                        quad_height += 1;
+                    }
+                    for quad_v in v..v + quad_height {
+                        for quad_u in u..u + quad_width {
+                            mask[(quad_u + quad_v * width) as usize] = None;
+                        }
+                    }
+
+                    let u_start = (min_position[axes.1] + u) as f32;
+                    let u_end = u_start + quad_width as f32;
+                    let v_start = (min_position[axes.2] + v) as f32;
+                    let v_end = v_start + quad_height as f32;
+                    let mut corners = [
+                        position_on_axes(axes, (plane, u_start, v_start)),
+                        position_on_axes(axes, (plane, u_end, v_start)),
+                        position_on_axes(axes, (plane, u_end, v_end)),
+                        position_on_axes(axes, (plane, u_start, v_end)),
+                    ];
+                    if !positive {
+                        corners.reverse();
+                    }
+                    let color = if empty_marker::<u16>() == key {
+                        Albedo::default()
+                    } else {
+                        self.voxel_color_palette[key as usize]
+                    };
+                    mesh.push_quad(corners, normal, color);
+                    u += quad_width;
+                }
+            }
+        }
+        Ok(mesh)
+    }
+
+    /// Creates a mesh for each non-empty chunk of the tree, see @mesh_region
+    /// Chunks containing no voxels are skipped based on the occupancy of the nodes and bricks.
+    /// * `chunk_size` - The size of the chunks, must be the size of a brick or a node: `brick_dimension * (4^x)`
+    pub fn mesh_chunks(&self, chunk_size: u32) -> Result<Vec<MeshChunk>, OctreeError> {
+        self.check_chunk_size(chunk_size)?;
+        let mut chunks = Vec::new();
+        for min_position in self.occupied_chunks(chunk_size) {
+            let chunk = self.mesh_chunk(min_position, chunk_size)?;
+            if !chunk.mesh.is_empty() {
+                chunks.push(chunk);
+            }
+        }
+        Ok(chunks)
+    }
+
+    /// Creates a mesh for each chunk affected by the given changes, see @changes_since
+    /// Chunks next to the modified voxels are included, as the visibility of their faces may have changed.
+    /// Affected chunks without visible faces are provided with an empty mesh, replacing their previous one.
+    /// If the layout of the tree changed, every non-empty chunk is provided as with @mesh_chunks,
+    /// and previously created chunks are to be dropped.
+    /// * `changes` - The changes of the tree since the chunks were last meshed
+    /// * `chunk_size` - The size of the chunks, must be the size of a brick or a node: `brick_dimension * (4^x)`
+    pub fn mesh_changed_chunks(
+        &self,
+        changes: &BoxTreeChanges,
+        chunk_size: u32,
+    ) -> Result<Vec<MeshChunk>, OctreeError> {
+        if changes.layout_changed {
+            return self.mesh_chunks(chunk_size);
+        }
+        self.check_chunk_size(chunk_size)?;
+        let mut chunk_positions = BTreeSet::new();
+        for (min_position, max_position) in &changes.regions {
+            let max_position = V3c::new(
+                (max_position.x + 1).min(self.boxtree_size),
+                (max_position.y + 1).min(self.boxtree_size),
+                (max_position.z + 1).min(self.boxtree_size),
+            );
+            if Self::box_is_empty(min_position, &max_position) {
+                continue;
+            }
+            let first_chunk = V3c::new(
+                min_position.x.saturating_sub(1) / chunk_size,
+                min_position.y.saturating_sub(1) / chunk_size,
+                min_position.z.saturating_sub(1) / chunk_size,
+            );
+            let last_chunk = (max_position - V3c::unit(1)) / chunk_size;
+            for z in first_chunk.z..=last_chunk.z {
+                for y in first_chunk.y..=last_chunk.y {
+                    for x in first_chunk.x..=last_chunk.x {
+                        chunk_positions.insert((z, y, x));
+                    }
+                }
+            }
+        }
+        chunk_positions
+            .into_iter()
+            .map(|(z, y, x)| self.mesh_chunk(V3c::new(x, y, z) * chunk_size, chunk_size))
+            .collect()
+    }
+
+    fn mesh_chunk(
+        &self,
+        min_position: V3c<u32>,
+        chunk_size: u32,
+    ) -> Result<MeshChunk, OctreeError> {
+        Ok(MeshChunk {
+            min_position,
+            size: chunk_size,
+            mesh: self.mesh_region(&min_position, &(min_position + V3c::unit(chunk_size)))?,
+        })
+    }
+
+    /// Checks if the given chunk size matches the size of a brick or a node inside the tree
+    fn check_chunk_size(&self, chunk_size: u32) -> Result<(), OctreeError> {
+        let mut size = self.brick_dim;
+        while size < chunk_size && size < self.boxtree_size {
+            size *= BOX_NODE_DIMENSION as u32;
+        }
+        if size != chunk_size {
+            return Err(OctreeError::InvalidSize(chunk_size));
+        }
+        Ok(())
+    }
+
+    /// Collects the minimum position of every chunk overlapping with an occupied node or brick
+    fn occupied_chunks(&self, chunk_size: u32) -> Vec<V3c<u32>> {
+        let mut chunks = Vec::new();
+        let mut node_stack = vec![(
+            Self::ROOT_NODE_KEY as usize,
+            Cube::root_bounds(self.boxtree_size as f32),
+        )];
+        while let Some((node_key, bounds)) = node_stack.pop() {
+            let node_min = V3c::<u32>::from(bounds.min_position);
+            let node_size = bounds.size as u32;
+            match self.nodes.get(node_key) {
+                NodeContent::Nothing => {}
+                _ if node_size == chunk_size => chunks.push(node_min),
+                NodeContent::Internal(occupied_bits) => {
+                    for sectant in 0..BOX_NODE_CHILDREN_COUNT as u8 {
+                        if 0 == occupied_bits & (0x01 << sectant) {
+                            continue;
+                        }
+                        if let Some(child_key) = self.valid_child_for(node_key, sectant) {
+                            node_stack.push((child_key, bounds.child_bounds_for(sectant)));
+                        }
+                    }
+                }
+                NodeContent::Leaf(bricks) => {
+                    // Chunks smaller than a leaf node have the size of its bricks
+                    for (sectant, brick) in bricks.iter().enumerate() {
+                        if !matches!(brick, BrickData::Empty) {
+                            chunks.push(V3c::from(
+                                bounds.child_bounds_for(sectant as u8).min_position,
+                            ));
+                        }
+                    }
+                }
+                NodeContent::UniformLeaf(brick) => {
+                    if matches!(brick, BrickData::Empty) {
+                        continue;
+                    }
+                    let chunks_per_side = node_size / chunk_size;
+                    for z in 0..chunks_per_side {
+                        for y in 0..chunks_per_side {
+                            for x in 0..chunks_per_side {
+                                chunks.push(node_min + V3c::new(x, y, z) * chunk_size);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        chunks
+    }
+}
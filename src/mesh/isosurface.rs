@@ -0,0 +1,392 @@
+use crate::{
+    boxtree::{
+        types::{NodeContent, OctreeError},
+        Albedo, BoxTree, BoxTreeEntry, VoxelData, BOX_NODE_DIMENSION,
+    },
+    mesh::Mesh,
+    spatial::math::vector::V3c,
+};
+use std::{
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+};
+
+/// The density and color at each voxel of a box, sampled at the voxel centers
+/// Positions are signed, as samples outside the tree are part of the box, containing the density of empty voxels.
+struct SampleGrid {
+    min_position: [i64; 3],
+    size: [i64; 3],
+    densities: Vec<f32>,
+    colors: Vec<Option<Albedo>>,
+}
+
+impl SampleGrid {
+    fn index(&self, position: [i64; 3]) -> usize {
+        let offset = [
+            position[0] - self.min_position[0],
+            position[1] - self.min_position[1],
+            position[2] - self.min_position[2],
+        ];
+        (offset[0] + offset[1] * self.size[0] + offset[2] * self.size[0] * self.size[1]) as usize
+    }
+
+    fn density(&self, position: [i64; 3]) -> f32 {
+        self.densities[self.index(position)]
+    }
+
+    fn color(&self, position: [i64; 3]) -> Option<Albedo> {
+        self.colors[self.index(position)]
+    }
+}
+
+/// Provides the given position moved by one along the given axes
+fn offset_position(position: [i64; 3], offset: [i64; 3]) -> [i64; 3] {
+    [
+        position[0] + offset[0],
+        position[1] + offset[1],
+        position[2] + offset[2],
+    ]
+}
+
+/// Provides the offset of the given corner of a cell, bits of the corner index select the axes
+fn corner_offset(corner: usize) -> [i64; 3] {
+    [
+        (corner & 1) as i64,
+        ((corner >> 1) & 1) as i64,
+        ((corner >> 2) & 1) as i64,
+    ]
+}
+
+impl<T: VoxelData> BoxTree<T> {
+    /// Creates a smooth mesh on the border of the occupied voxels inside the given region, see @isosurface_with
+    /// Occupied voxels have a density of 1, empty voxels have a density of 0.
+    pub fn isosurface(
+        &self,
+        min_position: &V3c<u32>,
+        max_position: &V3c<u32>,
+    ) -> Result<Mesh, OctreeError> {
+        self.isosurface_with(
+            min_position,
+            max_position,
+            |entry| if entry.is_none() { 0. } else { 1. },
+            0.5,
+        )
+    }
+
+    /// Creates a smooth mesh with surface nets, where the density of the voxels crosses the given iso level
+    /// Densities are sampled at the center of each voxel, voxels with a density above the iso level are inside.
+    /// Vertex colors are the average color of the voxels inside next to them.
+    /// Voxels outside the tree have the density of empty voxels, so the surface is closed on the border of the tree.
+    /// Meshes of regions next to each other fit together without gaps or overlapping triangles.
+    /// The region is processed in parallel, by the size of a leaf node.
+    /// * `min_position` - the first voxel of the box, must be contained within the tree
+    /// * `max_position` - the end of the box (exclusive), each component must be at most the size of the tree
+    /// * `density` - Provides the density of a voxel from its content
+    /// * `iso_level` - The density of the surface
+    pub fn isosurface_with<F>(
+        &self,
+        min_position: &V3c<u32>,
+        max_position: &V3c<u32>,
+        density: F,
+        iso_level: f32,
+    ) -> Result<Mesh, OctreeError>
+    where
+        F: Fn(&BoxTreeEntry<T>) -> f32 + Sync,
+    {
+        self.check_box_bounds(min_position, max_position)?;
+        let mut mesh = Mesh::default();
+        if Self::box_is_empty(min_position, max_position) {
+            return Ok(mesh);
+        }
+
+        // Split the region along the leaf nodes
+        let chunk_size = (self.brick_dim * BOX_NODE_DIMENSION as u32).min(self.boxtree_size);
+        let first_chunk = *min_position / chunk_size;
+        let last_chunk = (*max_position - V3c::unit(1)) / chunk_size;
+        let mut chunks = Vec::new();
+        for z in first_chunk.z..=last_chunk.z {
+            for y in first_chunk.y..=last_chunk.y {
+                for x in first_chunk.x..=last_chunk.x {
+                    let chunk_min = V3c::new(x, y, z) * chunk_size;
+                    let chunk_max = chunk_min + V3c::unit(chunk_size);
+                    chunks.push((
+                        V3c::new(
+                            chunk_min.x.max(min_position.x),
+                            chunk_min.y.max(min_position.y),
+                            chunk_min.z.max(min_position.z),
+                        ),
+                        V3c::new(
+                            chunk_max.x.min(max_position.x),
+                            chunk_max.y.min(max_position.y),
+                            chunk_max.z.min(max_position.z),
+                        ),
+                    ));
+                }
+            }
+        }
+
+        let next_chunk = AtomicUsize::new(0);
+        let worker_count = thread::available_parallelism()
+            .map_or(1, |count| count.get())
+            .min(chunks.len());
+        let mut chunk_meshes = thread::scope(|scope| {
+            let workers = (0..worker_count)
+                .map(|_| {
+                    scope.spawn(|| {
+                        let mut chunk_meshes = Vec::new();
+                        loop {
+                            let chunk_index = next_chunk.fetch_add(1, Ordering::Relaxed);
+                            let Some((chunk_min, chunk_max)) = chunks.get(chunk_index) else {
+                                return chunk_meshes;
+                            };
+                            chunk_meshes.push((
+                                chunk_index,
+                                self.isosurface_chunk(chunk_min, chunk_max, &density, iso_level),
+                            ));
+                        }
+                    })
+                })
+                .collect::<Vec<_>>();
+            workers
+                .into_iter()
+                .flat_map(|worker| worker.join().expect("Expected isosurface worker to finish"))
+                .collect::<Vec<_>>()
+        });
+        chunk_meshes.sort_by_key(|(chunk_index, _)| *chunk_index);
+        for (_, chunk_mesh) in chunk_meshes {
+            mesh.append(&chunk_mesh);
+        }
+        Ok(mesh)
+    }
+
+    /// Creates the surface nets mesh of the given chunk
+    /// The chunk contains the triangles of the sign changes between each of its voxels and the next voxel along
+    /// each axis. On the lower border of the tree the chunk also contains the sign changes from outside the tree.
+    /// The chunk is expected to be inside the tree, as checked by @isosurface_with.
+    fn isosurface_chunk<F>(
+        &self,
+        min_position: &V3c<u32>,
+        max_position: &V3c<u32>,
+        density: &F,
+        iso_level: f32,
+    ) -> Mesh
+    where
+        F: Fn(&BoxTreeEntry<T>) -> f32,
+    {
+        let mut mesh = Mesh::default();
+
+        // The range of the voxels where the sign changes towards the next voxel belong to the chunk
+        let owned_min = [min_position.x, min_position.y, min_position.z].map(|component| {
+            if 0 == component {
+                -1
+            } else {
+                component as i64
+            }
+        });
+        let owned_max = [max_position.x, max_position.y, max_position.z].map(|c| c as i64);
+
+        // Cells next to the owned voxels need the samples one step further in each direction
+        let sample_min = owned_min.map(|component| component - 1);
+        let sample_max = owned_max.map(|component| component + 1);
+        let tree_size = self.boxtree_size as i64;
+        let tree_min = sample_min.map(|component| component.max(0) as u32);
+        let tree_max = sample_max.map(|component| component.min(tree_size) as u32);
+        let tree_min = V3c::new(tree_min[0], tree_min[1], tree_min[2]);
+        let tree_max = V3c::new(tree_max[0], tree_max[1], tree_max[2]);
+
+        // Without any voxels every sample has the same density, so there is no surface
+        if self
+            .iter_region(&tree_min, &tree_max)
+            .expect("Expected chunk samples to be inside the tree")
+            .next()
+            .is_none()
+        {
+            return mesh;
+        }
+
+        let indices = self
+            .dense_indices(&tree_min, &tree_max)
+            .expect("Expected chunk samples to be inside the tree");
+        let tree_box_size = tree_max - tree_min;
+        let empty_density = density(&BoxTreeEntry::Empty);
+        let mut samples = SampleGrid {
+            min_position: sample_min,
+            size: [
+                sample_max[0] - sample_min[0],
+                sample_max[1] - sample_min[1],
+                sample_max[2] - sample_min[2],
+            ],
+            densities: Vec::new(),
+            colors: Vec::new(),
+        };
+        for z in sample_min[2]..sample_max[2] {
+            for y in sample_min[1]..sample_max[1] {
+                for x in sample_min[0]..sample_max[0] {
+                    if x < 0 || y < 0 || z < 0 || x >= tree_size || y >= tree_size || z >= tree_size
+                    {
+                        samples.densities.push(empty_density);
+                        samples.colors.push(None);
+                        continue;
+                    }
+                    let offset = V3c::new(x as u32, y as u32, z as u32) - tree_min;
+                    let index = indices[(offset.x
+                        + offset.y * tree_box_size.x
+                        + offset.z * tree_box_size.x * tree_box_size.y)
+                        as usize];
+                    let entry = NodeContent::pix_get_ref(
+                        &index,
+                        &self.voxel_color_palette,
+                        &self.voxel_data_palette,
+                    );
+                    samples.densities.push(density(&entry));
+                    samples.colors.push(entry.albedo().copied());
+                }
+            }
+        }
+
+        // Place a vertex into every cell between 8 samples where the surface crosses the cell
+        let cell_min = sample_min;
+        let cell_size = [
+            owned_max[0] - cell_min[0],
+            owned_max[1] - cell_min[1],
+            owned_max[2] - cell_min[2],
+        ];
+        let cell_index = |cell: [i64; 3]| {
+            ((cell[0] - cell_min[0])
+                + (cell[1] - cell_min[1]) * cell_size[0]
+                + (cell[2] - cell_min[2]) * cell_size[0] * cell_size[1]) as usize
+        };
+        let mut cell_vertices = vec![None; (cell_size[0] * cell_size[1] * cell_size[2]) as usize];
+        for z in cell_min[2]..owned_max[2] {
+            for y in cell_min[1]..owned_max[1] {
+                for x in cell_min[0]..owned_max[0] {
+                    let cell = [x, y, z];
+                    let densities = [0, 1, 2, 3, 4, 5, 6, 7].map(|corner| {
+                        samples.density(offset_position(cell, corner_offset(corner)))
+                    });
+                    let inside = densities.map(|corner_density| corner_density > iso_level);
+                    if inside.iter().all(|corner| *corner) || inside.iter().all(|corner| !*corner) {
+                        continue;
+                    }
+
+                    // Average the points where the surface crosses the edges of the cell
+                    let mut position_sum = V3c::unit(0.);
+                    let mut crossing_count = 0;
+                    let mut color_sum = [0.; 4];
+                    let mut color_count = 0;
+                    for corner in 0..8 {
+                        for axis_bit in [1, 2, 4] {
+                            let other_corner = corner | axis_bit;
+                            if corner == other_corner || inside[corner] == inside[other_corner] {
+                                continue;
+                            }
+                            let ratio = (iso_level - densities[corner])
+                                / (densities[other_corner] - densities[corner]);
+                            let start = corner_offset(corner).map(|c| c as f32);
+                            let end = corner_offset(other_corner).map(|c| c as f32);
+                            position_sum += V3c::new(
+                                start[0] + (end[0] - start[0]) * ratio,
+                                start[1] + (end[1] - start[1]) * ratio,
+                                start[2] + (end[2] - start[2]) * ratio,
+                            );
+                            crossing_count += 1;
+
+                            let inside_corner = if inside[corner] { corner } else { other_corner };
+                            if let Some(color) =
+                                samples.color(offset_position(cell, corner_offset(inside_corner)))
+                            {
+                                color_sum[0] += color.r as f32;
+                                color_sum[1] += color.g as f32;
+                                color_sum[2] += color.b as f32;
+                                color_sum[3] += color.a as f32;
+                                color_count += 1;
+                            }
+                        }
+                    }
+
+                    // Density increases inwards, so the normal is the opposite of the gradient
+                    let mut gradient = [0.; 3];
+                    for (corner, corner_density) in densities.iter().enumerate() {
+                        let offset = corner_offset(corner);
+                        for axis in 0..3 {
+                            gradient[axis] += if 1 == offset[axis] {
+                                *corner_density
+                            } else {
+                                -*corner_density
+                            };
+                        }
+                    }
+                    let normal = V3c::new(-gradient[0], -gradient[1], -gradient[2]);
+                    let normal = if 0. < normal.length() {
+                        normal.normalized()
+                    } else {
+                        normal
+                    };
+
+                    let color = if 0 < color_count {
+                        let color_count = color_count as f32;
+                        Albedo {
+                            r: (color_sum[0] / color_count).round() as u8,
+                            g: (color_sum[1] / color_count).round() as u8,
+                            b: (color_sum[2] / color_count).round() as u8,
+                            a: (color_sum[3] / color_count).round() as u8,
+                        }
+                    } else {
+                        Albedo::default()
+                    };
+
+                    // Samples are at the center of the voxels
+                    cell_vertices[cell_index(cell)] = Some(mesh.positions.len() as u32);
+                    mesh.positions.push(
+                        V3c::new(x as f32 + 0.5, y as f32 + 0.5, z as f32 + 0.5)
+                            + position_sum / crossing_count as f32,
+                    );
+                    mesh.normals.push(normal);
+                    mesh.colors.push(color);
+                }
+            }
+        }
+
+        // Connect the vertices of the 4 cells around each owned edge crossing the surface
+        for z in owned_min[2]..owned_max[2] {
+            for y in owned_min[1]..owned_max[1] {
+                for x in owned_min[0]..owned_max[0] {
+                    let start = [x, y, z];
+                    let start_inside = samples.density(start) > iso_level;
+                    for axis in 0..3 {
+                        let mut axis_offset = [0; 3];
+                        axis_offset[axis] = 1;
+                        let end_inside =
+                            samples.density(offset_position(start, axis_offset)) > iso_level;
+                        if start_inside == end_inside {
+                            continue;
+                        }
+
+                        // Cells in counter-clockwise order, when viewed along the axis
+                        let mut u_offset = [0; 3];
+                        u_offset[(axis + 1) % 3] = -1;
+                        let mut v_offset = [0; 3];
+                        v_offset[(axis + 2) % 3] = -1;
+                        let mut cells = [
+                            offset_position(offset_position(start, u_offset), v_offset),
+                            offset_position(start, v_offset),
+                            start,
+                            offset_position(start, u_offset),
+                        ]
+                        .map(|cell| {
+                            cell_vertices[cell_index(cell)]
+                                .expect("Expected a vertex in every cell around a crossing edge")
+                        });
+                        if !start_inside {
+                            cells.reverse();
+                        }
+                        mesh.indices.extend_from_slice(&[
+                            cells[0], cells[1], cells[2], cells[0], cells[2], cells[3],
+                        ]);
+                    }
+                }
+            }
+        }
+        mesh
+    }
+}
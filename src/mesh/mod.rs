@@ -0,0 +1,83 @@
+mod export;
+mod greedy;
+mod isosurface;
+
+#[cfg(test)]
+mod tests;
+
+use crate::boxtree::{Albedo, V3c};
+
+/// A triangle mesh with normal and color for each vertex
+/// The positions, normals and colors have one element for each vertex, while every three indices
+/// form a triangle, in counter-clockwise order when viewed from the direction its normal points to.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Mesh {
+    /// The position of each vertex, in voxel units
+    pub positions: Vec<V3c<f32>>,
+
+    /// The normal of each vertex
+    pub normals: Vec<V3c<f32>>,
+
+    /// The color of each vertex
+    pub colors: Vec<Albedo>,
+
+    /// The vertex indices of each triangle
+    pub indices: Vec<u32>,
+}
+
+/// The mesh of the voxels inside one chunk of a tree, see @BoxTree::mesh_chunks
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MeshChunk {
+    /// The minimum position of the chunk
+    pub min_position: V3c<u32>,
+
+    /// The size of the chunk along each axis
+    pub size: u32,
+
+    /// The faces of the voxels inside the chunk
+    pub mesh: Mesh,
+}
+
+impl Mesh {
+    /// The number of vertices inside the mesh
+    pub fn vertex_count(&self) -> usize {
+        self.positions.len()
+    }
+
+    /// The number of triangles inside the mesh
+    pub fn triangle_count(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    /// True if the mesh has no triangles
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+
+    /// Adds the vertices and triangles of the given mesh to this one
+    pub fn append(&mut self, other: &Mesh) {
+        let index_offset = self.positions.len() as u32;
+        self.positions.extend_from_slice(&other.positions);
+        self.normals.extend_from_slice(&other.normals);
+        self.colors.extend_from_slice(&other.colors);
+        self.indices
+            .extend(other.indices.iter().map(|index| index + index_offset));
+    }
+
+    /// Adds a quad of two triangles to the mesh
+    /// * `corners` - The corners of the quad, in counter-clockwise order when viewed from the direction of the normal
+    pub(crate) fn push_quad(&mut self, corners: [V3c<f32>; 4], normal: V3c<f32>, color: Albedo) {
+        let first_index = self.positions.len() as u32;
+        self.positions.extend_from_slice(&corners);
+        self.normals.extend_from_slice(&[normal; 4]);
+        self.colors.extend_from_slice(&[color; 4]);
+        self.indices.extend_from_slice(&[
+            first_index,
+            first_index + 1,
+            first_index + 2,
+            first_index,
+            first_index + 2,
+            first_index + 3,
+        ]);
+    }
+}
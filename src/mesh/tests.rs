@@ -0,0 +1,510 @@
+mod greedy_tests {
+    use crate::{
+        boxtree::{Albedo, BoxTree, BoxTreeEntry, V3c},
+        mesh::Mesh,
+    };
+
+    /// The summed area of the triangles, each checked to face the direction of its normal
+    fn mesh_area(mesh: &Mesh) -> f32 {
+        mesh.indices
+            .chunks_exact(3)
+            .map(|triangle| {
+                let a = mesh.positions[triangle[0] as usize];
+                let b = mesh.positions[triangle[1] as usize];
+                let c = mesh.positions[triangle[2] as usize];
+                let cross = (b - a).cross(c - a);
+                assert!(cross.dot(&mesh.normals[triangle[0] as usize]) > 0.);
+                cross.length() / 2.
+            })
+            .sum()
+    }
+
+    /// The number of voxel faces inside the region not covered by another voxel, queried one by one
+    fn visible_face_count(tree: &BoxTree, min_position: V3c<u32>, max_position: V3c<u32>) -> usize {
+        let size = tree.get_size() as i32;
+        let mut count = 0;
+        for x in min_position.x..max_position.x {
+            for y in min_position.y..max_position.y {
+                for z in min_position.z..max_position.z {
+                    if tree.get(&V3c::new(x, y, z)).is_none() {
+                        continue;
+                    }
+                    for offset in [
+                        V3c::new(1, 0, 0),
+                        V3c::new(-1, 0, 0),
+                        V3c::new(0, 1, 0),
+                        V3c::new(0, -1, 0),
+                        V3c::new(0, 0, 1),
+                        V3c::new(0, 0, -1),
+                    ] {
+                        let neighbor = V3c::new(x as i32, y as i32, z as i32) + offset;
+                        if neighbor.x < 0
+                            || neighbor.y < 0
+                            || neighbor.z < 0
+                            || neighbor.x >= size
+                            || neighbor.y >= size
+                            || neighbor.z >= size
+                            || tree.get(&V3c::<u32>::from(neighbor)).is_none()
+                        {
+                            count += 1;
+                        }
+                    }
+                }
+            }
+        }
+        count
+    }
+
+    /// A tree with faces at brick seams and at the tree bounds: boxes of different colors meeting
+    /// at a brick seam, voxels meeting at a node seam, a bar spanning the tree along its lower edge,
+    /// a uniform node in the far corner and a voxel without albedo
+    fn seam_tree() -> BoxTree {
+        let red: Albedo = 0xFF0000FF.into();
+        let green: Albedo = 0x00FF00FF.into();
+        let blue: Albedo = 0x0000FFFF.into();
+        let mut tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+        tree.insert_box(&V3c::new(1, 1, 3), &V3c::new(7, 6, 11), &red)
+            .expect("boxtree insert");
+        tree.insert_box(&V3c::new(1, 6, 3), &V3c::new(7, 11, 11), &blue)
+            .expect("boxtree insert");
+        tree.insert(&V3c::new(7, 20, 3), &blue)
+            .expect("boxtree insert");
+        tree.insert(&V3c::new(8, 20, 3), &red)
+            .expect("boxtree insert");
+        tree.insert_box(&V3c::new(0, 0, 0), &V3c::new(32, 2, 1), &green)
+            .expect("boxtree insert");
+        tree.insert_box(&V3c::new(24, 24, 24), &V3c::new(32, 32, 32), &green)
+            .expect("boxtree insert");
+        tree.insert(&V3c::new(31, 31, 0), BoxTreeEntry::Informative(&5))
+            .expect("boxtree insert");
+        tree
+    }
+
+    #[test]
+    fn test_mesh_single_voxel() {
+        let red: Albedo = 0xFF0000FF.into();
+        let mut tree: BoxTree = BoxTree::new(8, 2).ok().unwrap();
+        tree.insert(&V3c::new(3, 4, 5), &red)
+            .expect("boxtree insert");
+        let mesh = tree
+            .mesh_region(&V3c::new(0, 0, 0), &V3c::new(8, 8, 8))
+            .expect("mesh_region to work");
+        assert_eq!(mesh.vertex_count(), 24);
+        assert_eq!(mesh.triangle_count(), 12);
+        assert_eq!(mesh.normals.len(), 24);
+        assert!(mesh.colors.iter().all(|color| *color == red));
+        assert!(mesh.positions.iter().all(|position| {
+            (3. ..=4.).contains(&position.x)
+                && (4. ..=5.).contains(&position.y)
+                && (5. ..=6.).contains(&position.z)
+        }));
+        assert_eq!(mesh_area(&mesh), 6.);
+    }
+
+    #[test]
+    fn test_mesh_merges_faces_of_same_color() {
+        let red: Albedo = 0xFF0000FF.into();
+        let blue: Albedo = 0x0000FFFF.into();
+        let mut tree: BoxTree = BoxTree::new(8, 2).ok().unwrap();
+        tree.insert_box(&V3c::new(1, 1, 1), &V3c::new(5, 5, 5), &red)
+            .expect("boxtree insert");
+        let mesh = tree
+            .mesh_region(&V3c::new(0, 0, 0), &V3c::new(8, 8, 8))
+            .expect("mesh_region to work");
+        assert_eq!(mesh.triangle_count(), 12);
+        assert_eq!(mesh_area(&mesh), 96.);
+
+        // Faces of different colors are not merged
+        tree.insert_box(&V3c::new(3, 1, 1), &V3c::new(5, 5, 5), &blue)
+            .expect("boxtree insert");
+        let mesh = tree
+            .mesh_region(&V3c::new(0, 0, 0), &V3c::new(8, 8, 8))
+            .expect("mesh_region to work");
+        assert_eq!(mesh.triangle_count(), 20);
+        assert_eq!(mesh_area(&mesh), 96.);
+        assert_eq!(
+            mesh.colors.iter().filter(|color| **color == blue).count(),
+            20
+        );
+    }
+
+    #[test]
+    fn test_mesh_uniform_nodes() {
+        let red: Albedo = 0xFF0000FF.into();
+        let mut tree: BoxTree = BoxTree::new(128, 2).ok().unwrap();
+        tree.insert_box(&V3c::new(0, 0, 0), &V3c::new(64, 64, 64), &red)
+            .expect("boxtree insert");
+        let mesh = tree
+            .mesh_region(&V3c::new(0, 0, 0), &V3c::new(128, 128, 128))
+            .expect("mesh_region to work");
+        assert_eq!(mesh.triangle_count(), 12);
+        assert_eq!(mesh_area(&mesh), 6. * 64. * 64.);
+
+        // Faces between voxels of the same node are not visible
+        let mesh = tree
+            .mesh_region(&V3c::new(16, 16, 16), &V3c::new(48, 48, 48))
+            .expect("mesh_region to work");
+        assert!(mesh.is_empty());
+        let mesh = tree
+            .mesh_region(&V3c::new(0, 0, 56), &V3c::new(8, 8, 72))
+            .expect("mesh_region to work");
+        assert_eq!(mesh_area(&mesh), 3. * 8. * 8.);
+    }
+
+    #[test]
+    fn test_mesh_region_matches_visible_faces() {
+        let tree = seam_tree();
+        let mesh = tree
+            .mesh_region(&V3c::new(0, 0, 0), &V3c::new(32, 32, 32))
+            .expect("mesh_region to work");
+        assert_eq!(
+            mesh_area(&mesh) as usize,
+            visible_face_count(&tree, V3c::new(0, 0, 0), V3c::new(32, 32, 32))
+        );
+        assert!(mesh.colors.contains(&Albedo::default()));
+
+        // Boxes meeting at a brick seam only show their outer faces
+        let mesh = tree
+            .mesh_region(&V3c::new(0, 1, 2), &V3c::new(8, 12, 12))
+            .expect("mesh_region to work");
+        assert_eq!(mesh_area(&mesh), 2. * (6. * 10. + 6. * 8. + 10. * 8.));
+
+        // Faces at the tree bounds are visible
+        let mesh = tree
+            .mesh_region(&V3c::new(24, 24, 24), &V3c::new(32, 32, 32))
+            .expect("mesh_region to work");
+        assert_eq!(mesh_area(&mesh), 6. * 8. * 8.);
+
+        let min_position = V3c::new(3, 5, 7);
+        let max_position = V3c::new(20, 18, 29);
+        let mesh = tree
+            .mesh_region(&min_position, &max_position)
+            .expect("mesh_region to work");
+        assert_eq!(
+            mesh_area(&mesh) as usize,
+            visible_face_count(&tree, min_position, max_position)
+        );
+        assert!(tree
+            .mesh_region(&V3c::new(0, 0, 0), &V3c::new(33, 32, 32))
+            .is_err());
+    }
+
+    #[test]
+    fn test_mesh_chunks() {
+        let tree = seam_tree();
+        let whole = tree
+            .mesh_region(&V3c::new(0, 0, 0), &V3c::new(32, 32, 32))
+            .expect("mesh_region to work");
+        for chunk_size in [2, 8, 32] {
+            let chunks = tree.mesh_chunks(chunk_size).expect("mesh_chunks to work");
+            let mut combined = Mesh::default();
+            for chunk in &chunks {
+                assert_eq!(chunk.size, chunk_size);
+                assert!(!chunk.mesh.is_empty());
+                let chunk_max = chunk.min_position + V3c::unit(chunk_size);
+                assert!(chunk.mesh.positions.iter().all(|position| {
+                    position.x >= chunk.min_position.x as f32
+                        && position.y >= chunk.min_position.y as f32
+                        && position.z >= chunk.min_position.z as f32
+                        && position.x <= chunk_max.x as f32
+                        && position.y <= chunk_max.y as f32
+                        && position.z <= chunk_max.z as f32
+                }));
+                combined.append(&chunk.mesh);
+            }
+            assert_eq!(mesh_area(&combined), mesh_area(&whole));
+        }
+        assert!(tree.mesh_chunks(4).is_err());
+        assert!(tree.mesh_chunks(1).is_err());
+        assert!(tree.mesh_chunks(128).is_err());
+    }
+
+    #[test]
+    fn test_mesh_changed_chunks() {
+        let red: Albedo = 0xFF0000FF.into();
+        let mut tree = seam_tree();
+        let generation = tree.generation();
+
+        // The edit is on the border of the chunk, so the faces of the neighboring chunk change too
+        tree.insert(&V3c::new(16, 20, 20), &red)
+            .expect("boxtree insert");
+        tree.clear(&V3c::new(0, 0, 0)).expect("boxtree clear");
+        let chunks = tree
+            .mesh_changed_chunks(&tree.changes_since(generation), 8)
+            .expect("mesh_changed_chunks to work");
+        let positions: Vec<V3c<u32>> = chunks.iter().map(|chunk| chunk.min_position).collect();
+        assert!(positions.contains(&V3c::new(8, 16, 16)));
+        assert!(positions.contains(&V3c::new(16, 16, 16)));
+        assert!(positions.contains(&V3c::new(0, 0, 0)));
+        assert!(!positions.contains(&V3c::new(24, 24, 24)));
+        for chunk in chunks {
+            let expected = tree
+                .mesh_region(
+                    &chunk.min_position,
+                    &(chunk.min_position + V3c::unit(chunk.size)),
+                )
+                .expect("mesh_region to work");
+            assert!(chunk.mesh == expected);
+        }
+    }
+
+    #[test]
+    fn test_mesh_export() {
+        let red: Albedo = 0xFF0000FF.into();
+        let mut tree: BoxTree = BoxTree::new(8, 2).ok().unwrap();
+        tree.insert_box(&V3c::new(1, 1, 1), &V3c::new(3, 2, 2), &red)
+            .expect("boxtree insert");
+        let mesh = tree
+            .mesh_region(&V3c::new(0, 0, 0), &V3c::new(8, 8, 8))
+            .expect("mesh_region to work");
+
+        let mut obj = Vec::new();
+        mesh.write_obj(&mut obj).expect("write_obj to work");
+        let obj = String::from_utf8(obj).expect("Expected OBJ to be text");
+        assert_eq!(
+            obj.lines().filter(|line| line.starts_with("v ")).count(),
+            mesh.vertex_count()
+        );
+        assert_eq!(
+            obj.lines().filter(|line| line.starts_with("vn ")).count(),
+            mesh.vertex_count()
+        );
+        assert_eq!(
+            obj.lines().filter(|line| line.starts_with("f ")).count(),
+            mesh.triangle_count()
+        );
+        assert!(obj.contains("v 1 1 1 1 0 0"));
+
+        let mut ply = Vec::new();
+        mesh.write_ply(&mut ply).expect("write_ply to work");
+        let header_end = b"end_header\n";
+        let header_length = ply
+            .windows(header_end.len())
+            .position(|window| window == header_end)
+            .expect("Expected PLY header")
+            + header_end.len();
+        let header = String::from_utf8(ply[..header_length].to_vec()).expect("Expected header");
+        assert!(header.starts_with("ply\nformat binary_little_endian 1.0\n"));
+        assert!(header.contains(&format!("element vertex {}\n", mesh.vertex_count())));
+        assert!(header.contains(&format!("element face {}\n", mesh.triangle_count())));
+        assert_eq!(
+            ply.len(),
+            header_length + mesh.vertex_count() * 28 + mesh.triangle_count() * 13
+        );
+    }
+}
+
+mod isosurface_tests {
+    use crate::{
+        boxtree::{Albedo, BoxTree, BoxTreeEntry, V3c},
+        mesh::Mesh,
+    };
+    use std::collections::HashMap;
+
+    type QuantizedPosition = (i64, i64, i64);
+
+    fn quantize(position: V3c<f32>) -> QuantizedPosition {
+        (
+            (position.x * 1024.).round() as i64,
+            (position.y * 1024.).round() as i64,
+            (position.z * 1024.).round() as i64,
+        )
+    }
+
+    /// Checks that every edge is shared by exactly two triangles, running in opposite directions
+    /// Vertices are compared by position, so duplicated vertices on the border of chunks are merged.
+    fn assert_closed(mesh: &Mesh) {
+        let mut edges: HashMap<(QuantizedPosition, QuantizedPosition), usize> = HashMap::new();
+        for triangle in mesh.indices.chunks_exact(3) {
+            for (start, end) in [(0, 1), (1, 2), (2, 0)] {
+                *edges
+                    .entry((
+                        quantize(mesh.positions[triangle[start] as usize]),
+                        quantize(mesh.positions[triangle[end] as usize]),
+                    ))
+                    .or_default() += 1;
+            }
+        }
+        for ((start, end), count) in &edges {
+            assert_eq!(*count, 1);
+            assert_eq!(edges.get(&(*end, *start)), Some(&1));
+        }
+    }
+
+    /// The triangles of the mesh by their vertex positions, starting from the smallest one
+    fn sorted_triangles(mesh: &Mesh) -> Vec<[QuantizedPosition; 3]> {
+        let mut triangles: Vec<[QuantizedPosition; 3]> = mesh
+            .indices
+            .chunks_exact(3)
+            .map(|triangle| {
+                let mut corners = [0, 1, 2].map(|i| quantize(mesh.positions[triangle[i] as usize]));
+                let first = (0..3).min_by_key(|i| corners[*i]).unwrap();
+                corners.rotate_left(first);
+                corners
+            })
+            .collect();
+        triangles.sort();
+        triangles
+    }
+
+    fn sphere_tree(center: V3c<f32>, radius: f32, color: &Albedo) -> BoxTree {
+        let mut tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+        for x in 0..32 {
+            for y in 0..32 {
+                for z in 0..32 {
+                    let voxel_center = V3c::new(x as f32 + 0.5, y as f32 + 0.5, z as f32 + 0.5);
+                    if (voxel_center - center).length() < radius {
+                        tree.insert(&V3c::new(x, y, z), color)
+                            .expect("boxtree insert");
+                    }
+                }
+            }
+        }
+        tree
+    }
+
+    #[test]
+    fn test_isosurface_sphere() {
+        let red: Albedo = 0xFF0000FF.into();
+        let center = V3c::new(16., 16., 16.);
+        let tree = sphere_tree(center, 6., &red);
+        let mesh = tree
+            .isosurface(&V3c::new(0, 0, 0), &V3c::new(32, 32, 32))
+            .expect("isosurface to work");
+        assert!(!mesh.is_empty());
+        assert_eq!(mesh.normals.len(), mesh.vertex_count());
+        assert!(mesh.colors.iter().all(|color| *color == red));
+        assert_closed(&mesh);
+        for (position, normal) in mesh.positions.iter().zip(mesh.normals.iter()) {
+            let offset = *position - center;
+            assert!((4.5..7.5).contains(&offset.length()));
+            assert!(offset.dot(normal) > 0.);
+        }
+        for triangle in mesh.indices.chunks_exact(3) {
+            let a = mesh.positions[triangle[0] as usize];
+            let b = mesh.positions[triangle[1] as usize];
+            let c = mesh.positions[triangle[2] as usize];
+            assert!((b - a).cross(c - a).dot(&((a + b + c) / 3. - center)) > 0.);
+        }
+
+        let empty_tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+        assert!(empty_tree
+            .isosurface(&V3c::new(0, 0, 0), &V3c::new(32, 32, 32))
+            .expect("isosurface to work")
+            .is_empty());
+        assert!(tree
+            .isosurface(&V3c::new(0, 0, 0), &V3c::new(32, 33, 32))
+            .is_err());
+    }
+
+    #[test]
+    fn test_isosurface_regions_fit_together() {
+        let red: Albedo = 0xFF0000FF.into();
+        let mut tree = sphere_tree(V3c::new(14., 18., 15.), 7., &red);
+        tree.insert_box(&V3c::new(0, 0, 0), &V3c::new(10, 12, 9), &red)
+            .expect("boxtree insert");
+        tree.insert_box(&V3c::new(25, 20, 28), &V3c::new(32, 32, 32), &red)
+            .expect("boxtree insert");
+        let whole = tree
+            .isosurface(&V3c::new(0, 0, 0), &V3c::new(32, 32, 32))
+            .expect("isosurface to work");
+
+        // The surface is closed on the border of the tree
+        assert_closed(&whole);
+
+        let mut combined = Mesh::default();
+        for (min_position, max_position) in [
+            (V3c::new(0, 0, 0), V3c::new(13, 32, 32)),
+            (V3c::new(13, 0, 0), V3c::new(32, 17, 32)),
+            (V3c::new(13, 17, 0), V3c::new(32, 32, 32)),
+        ] {
+            combined.append(
+                &tree
+                    .isosurface(&min_position, &max_position)
+                    .expect("isosurface to work"),
+            );
+        }
+        assert_eq!(combined.triangle_count(), whole.triangle_count());
+        assert_eq!(sorted_triangles(&combined), sorted_triangles(&whole));
+    }
+
+    #[test]
+    fn test_isosurface_colors() {
+        let red: Albedo = 0xFF0000FF.into();
+        let blue: Albedo = 0x0000FFFF.into();
+        let mut tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+        tree.insert_box(&V3c::new(8, 8, 8), &V3c::new(16, 24, 24), &red)
+            .expect("boxtree insert");
+        tree.insert_box(&V3c::new(16, 8, 8), &V3c::new(24, 24, 24), &blue)
+            .expect("boxtree insert");
+        let mesh = tree
+            .isosurface(&V3c::new(0, 0, 0), &V3c::new(32, 32, 32))
+            .expect("isosurface to work");
+        assert_closed(&mesh);
+        for (position, color) in mesh.positions.iter().zip(mesh.colors.iter()) {
+            if position.x < 15. {
+                assert_eq!(*color, red);
+            } else if position.x > 17. {
+                assert_eq!(*color, blue);
+            }
+        }
+
+        // Colors are blended where the two boxes meet
+        assert!(mesh
+            .colors
+            .iter()
+            .any(|color| 0 < color.r && color.r < 255 && 0 < color.b && color.b < 255));
+    }
+
+    #[test]
+    fn test_isosurface_with_density() {
+        let mut tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+        tree.insert_box(
+            &V3c::new(4, 4, 4),
+            &V3c::new(28, 28, 28),
+            BoxTreeEntry::Informative(&1),
+        )
+        .expect("boxtree insert");
+        tree.insert_box(
+            &V3c::new(10, 12, 14),
+            &V3c::new(20, 18, 22),
+            BoxTreeEntry::Informative(&3),
+        )
+        .expect("boxtree insert");
+        let density = |entry: &BoxTreeEntry<u32>| entry.data().map_or(0., |data| *data as f32);
+
+        // Only the inner box is above the iso level
+        let mesh = tree
+            .isosurface_with(&V3c::new(0, 0, 0), &V3c::new(32, 32, 32), density, 2.)
+            .expect("isosurface_with to work");
+        assert!(!mesh.is_empty());
+        assert_closed(&mesh);
+        assert!(mesh.positions.iter().all(|position| {
+            (10. ..=20.).contains(&position.x)
+                && (12. ..=18.).contains(&position.y)
+                && (14. ..=22.).contains(&position.z)
+        }));
+        assert!(mesh.colors.iter().all(|color| *color == Albedo::default()));
+
+        // Both boxes are above the iso level, so the surface is the outer box
+        let mesh = tree
+            .isosurface_with(&V3c::new(0, 0, 0), &V3c::new(32, 32, 32), density, 0.5)
+            .expect("isosurface_with to work");
+        assert_closed(&mesh);
+        assert!(mesh.positions.iter().all(|position| {
+            (4. ..=28.).contains(&position.x)
+                && (4. ..=28.).contains(&position.y)
+                && (4. ..=28.).contains(&position.z)
+        }));
+        assert!(mesh.positions.iter().all(|position| {
+            !((6. ..=26.).contains(&position.x)
+                && (6. ..=26.).contains(&position.y)
+                && (6. ..=26.).contains(&position.z))
+        }));
+
+        assert!(tree
+            .isosurface_with(&V3c::new(0, 0, 0), &V3c::new(32, 32, 32), density, 5.)
+            .expect("isosurface_with to work")
+            .is_empty());
+    }
+}
@@ -1,4 +1,4 @@
-use std::vec::Vec;
+use crate::shared::SharedVec;
 
 /// One item in a datapool with a used flag
 #[derive(Clone)]
@@ -84,8 +84,8 @@ where
 /// It keeps track of different buffers for different levels in the graph, allocating more space initially to lower levels
 #[derive(Default, Clone)]
 pub(crate) struct ObjectPool<T> {
-    buffer: Vec<ReusableItem<T>>, // Pool of objects to be reused
-    first_available: usize,       // the index of the first available item
+    buffer: SharedVec<ReusableItem<T>>, // Pool of objects to be reused, shared between clones
+    first_available: usize,             // the index of the first available item
 }
 
 #[cfg(feature = "bytecode")]
@@ -117,7 +117,7 @@ where
                         "Something else",
                     )),
                 }?;
-                let buffer = Vec::decode_bencode_object(list.next_object()?.unwrap())?;
+                let buffer = SharedVec::decode_bencode_object(list.next_object()?.unwrap())?;
                 Ok(Self {
                     first_available,
                     buffer,
@@ -138,7 +138,7 @@ where
 {
     pub(crate) fn with_capacity(capacity: usize) -> Self {
         ObjectPool {
-            buffer: Vec::with_capacity(capacity),
+            buffer: SharedVec::with_capacity(capacity),
             ..Default::default()
         }
     }
@@ -233,6 +233,33 @@ where
     pub(crate) fn key_is_valid(&self, key: usize) -> bool {
         key < self.buffer.len() && self.buffer[key].reserved
     }
+
+    /// True if both pools refer to the same stored slots, see @SharedVec::shares_storage
+    #[cfg(test)]
+    pub(crate) fn shares_storage(&self, other: &Self) -> bool {
+        self.buffer.shares_storage(&other.buffer)
+    }
+
+    /// The number of slot pages stored by both pools at the same place
+    #[cfg(test)]
+    pub(crate) fn shared_page_count(&self, other: &Self) -> usize {
+        self.buffer.shared_page_count(&other.buffer)
+    }
+
+    /// The number of slots not holding any item, available for reuse
+    pub(crate) fn free_count(&self) -> usize {
+        self.buffer.iter().filter(|item| !item.reserved).count()
+    }
+
+    /// Releases the capacity of the pool not used by any slot
+    pub(crate) fn shrink_to_fit(&mut self) {
+        self.buffer.shrink_to_fit();
+    }
+
+    /// The heap memory reserved by the pool in bytes, not including heap memory owned by the items
+    pub(crate) fn heap_size(&self) -> usize {
+        self.buffer.capacity() * std::mem::size_of::<ReusableItem<T>>()
+    }
 }
 
 #[cfg(test)]
@@ -235,6 +235,71 @@ impl BoxTreeGPUDataHandler {
         None
     }
 
+    /// Writes the children information of the given node into its children, based on the uploaded nodes and bricks
+    fn inject_node_children<T: VoxelData>(
+        &mut self,
+        tree: &BoxTree<T>,
+        node_key: usize,
+        node_index: usize,
+    ) {
+        let parent_first_child_index = node_index * BOX_NODE_CHILDREN_COUNT;
+        match tree.nodes.get(node_key) {
+            NodeContent::Nothing => {}
+            NodeContent::Internal(_) => {
+                for sectant in 0..BOX_NODE_CHILDREN_COUNT {
+                    let child_key = tree.node_children[node_key].child(sectant as u8);
+                    if child_key != empty_marker::<u32>() as usize {
+                        self.render_data.node_children[parent_first_child_index + sectant] = *self
+                            .upload_targets
+                            .node_key_vs_meta_index
+                            .get_by_left(&child_key)
+                            .unwrap_or(&(empty_marker::<u32>() as usize))
+                            as u32;
+                    } else {
+                        self.render_data.node_children[parent_first_child_index + sectant] =
+                            empty_marker::<u32>();
+                    }
+                }
+            }
+            NodeContent::UniformLeaf(brick) => {
+                if let BrickData::Solid(voxel) = brick {
+                    self.render_data.node_children[parent_first_child_index] = 0x80000000 | *voxel;
+                } else if let Some(brick_index) = self
+                    .upload_targets
+                    .brick_ownership
+                    .get_by_right(&BrickOwnedBy::NodeAsChild(node_key as u32, 0))
+                {
+                    self.render_data.node_children[parent_first_child_index] =
+                        0x7FFFFFFF & *brick_index as u32;
+                } else {
+                    self.render_data.node_children[parent_first_child_index] =
+                        empty_marker::<u32>();
+                }
+            }
+            NodeContent::Leaf(bricks) => {
+                for (sectant, brick) in bricks.iter().enumerate().take(BOX_NODE_CHILDREN_COUNT) {
+                    if let BrickData::Solid(voxel) = brick {
+                        self.render_data.node_children[parent_first_child_index + sectant] =
+                            0x80000000 | voxel;
+                    } else {
+                        let node_entry = BrickOwnedBy::NodeAsChild(node_key as u32, sectant as u8);
+                        let brick_ownership = self
+                            .upload_targets
+                            .brick_ownership
+                            .get_by_right(&node_entry);
+                        if let Some(brick_index) = brick_ownership {
+                            self.render_data.node_children[parent_first_child_index + sectant] =
+                                0x7FFFFFFF & *brick_index as u32;
+                        } else {
+                            self.render_data.node_children[parent_first_child_index + sectant] =
+                                empty_marker::<u32>();
+                        }
+                    }
+                }
+            }
+        }
+    }
+
     /// Writes most of the data of the given node to the first available index
     /// Writes: metadata, available child information, occupied bits and parent connections
     /// It will try to collecty MIP information if still available, but will not upload a MIP
@@ -363,55 +428,7 @@ impl BoxTreeGPUDataHandler {
         }
 
         // Add child nodes of new child if any is available
-        let parent_first_child_index = node_index * BOX_NODE_CHILDREN_COUNT;
-        match tree.nodes.get(node_key) {
-            NodeContent::Nothing => {}
-            NodeContent::Internal(_) => {
-                for sectant in 0..BOX_NODE_CHILDREN_COUNT {
-                    let child_key = tree.node_children[node_key].child(sectant as u8);
-                    if child_key != empty_marker::<u32>() as usize {
-                        self.render_data.node_children[parent_first_child_index + sectant] = *self
-                            .upload_targets
-                            .node_key_vs_meta_index
-                            .get_by_left(&child_key)
-                            .unwrap_or(&(empty_marker::<u32>() as usize))
-                            as u32;
-                    } else {
-                        self.render_data.node_children[parent_first_child_index + sectant] =
-                            empty_marker::<u32>();
-                    }
-                }
-            }
-            NodeContent::UniformLeaf(brick) => {
-                if let BrickData::Solid(voxel) = brick {
-                    self.render_data.node_children[parent_first_child_index] = 0x80000000 | *voxel;
-                } else {
-                    self.render_data.node_children[parent_first_child_index] =
-                        empty_marker::<u32>();
-                }
-            }
-            NodeContent::Leaf(bricks) => {
-                for (sectant, brick) in bricks.iter().enumerate().take(BOX_NODE_CHILDREN_COUNT) {
-                    if let BrickData::Solid(voxel) = brick {
-                        self.render_data.node_children[parent_first_child_index + sectant] =
-                            0x80000000 | voxel;
-                    } else {
-                        let node_entry = BrickOwnedBy::NodeAsChild(node_key as u32, sectant as u8);
-                        let brick_ownership = self
-                            .upload_targets
-                            .brick_ownership
-                            .get_by_right(&node_entry);
-                        if let Some(brick_index) = brick_ownership {
-                            self.render_data.node_children[parent_first_child_index + sectant] =
-                                0x7FFFFFFF & *brick_index as u32;
-                        } else {
-                            self.render_data.node_children[parent_first_child_index + sectant] =
-                                empty_marker::<u32>();
-                        }
-                    }
-                }
-            }
-        }
+        self.inject_node_children(tree, node_key, node_index);
 
         // Try to collect node MIP entry
         self.render_data.node_mips[node_index] = match tree.node_mips[node_key] {
@@ -433,6 +450,80 @@ impl BoxTreeGPUDataHandler {
         (node_index, modifications)
     }
 
+    /// Writes the data of the given node again, if it is uploaded: metadata, occupied bits and children information
+    /// Bricks of the node which are no longer parted are released, uploaded children are connected to it again
+    /// * `returns` - The meta index of the node, if it is uploaded
+    pub(crate) fn update_node<T: VoxelData>(
+        &mut self,
+        tree: &BoxTree<T>,
+        node_key: usize,
+    ) -> Option<usize> {
+        let node_index = *self
+            .upload_targets
+            .node_key_vs_meta_index
+            .get_by_left(&node_key)?;
+
+        // Release bricks no longer present in the node
+        for sectant in 0..BOX_NODE_CHILDREN_COUNT {
+            let brick_is_parted = match tree.nodes.get(node_key) {
+                NodeContent::Leaf(bricks) => matches!(bricks[sectant], BrickData::Parted(_)),
+                NodeContent::UniformLeaf(brick) => {
+                    0 == sectant && matches!(brick, BrickData::Parted(_))
+                }
+                NodeContent::Nothing | NodeContent::Internal(_) => false,
+            };
+            if !brick_is_parted {
+                self.upload_targets
+                    .brick_ownership
+                    .remove_by_right(&BrickOwnedBy::NodeAsChild(node_key as u32, sectant as u8));
+            }
+
+            // Disconnect previous children, uploaded children are connected again below
+            let child_index =
+                self.render_data.node_children[node_index * BOX_NODE_CHILDREN_COUNT + sectant];
+            if self
+                .upload_targets
+                .node_index_vs_parent
+                .get(&(child_index as usize))
+                == Some(&(node_index, sectant as u8))
+            {
+                self.upload_targets
+                    .node_index_vs_parent
+                    .remove(&(child_index as usize));
+            }
+        }
+
+        Self::inject_node_properties(
+            &mut self.render_data.node_metadata,
+            node_index,
+            tree,
+            node_key,
+        );
+        let occupied_bits = tree.stored_occupied_bits(node_key);
+        self.render_data.node_ocbits[node_index * 2] = (occupied_bits & 0x00000000FFFFFFFF) as u32;
+        self.render_data.node_ocbits[node_index * 2 + 1] =
+            ((occupied_bits & 0xFFFFFFFF00000000) >> 32) as u32;
+        let child_children_offset = node_index * BOX_NODE_CHILDREN_COUNT;
+        self.render_data.node_children.splice(
+            (child_children_offset)..(child_children_offset + BOX_NODE_CHILDREN_COUNT),
+            vec![empty_marker::<u32>(); BOX_NODE_CHILDREN_COUNT],
+        );
+        self.inject_node_children(tree, node_key, node_index);
+
+        if let NodeContent::Internal(_) = tree.nodes.get(node_key) {
+            for sectant in 0..BOX_NODE_CHILDREN_COUNT {
+                let child_index =
+                    self.render_data.node_children[node_index * BOX_NODE_CHILDREN_COUNT + sectant];
+                if child_index != empty_marker::<u32>() {
+                    self.upload_targets
+                        .node_index_vs_parent
+                        .insert(child_index as usize, (node_index, sectant as u8));
+                }
+            }
+        }
+        Some(node_index)
+    }
+
     //##############################################################################
     //  ███████████  ███████████   █████   █████████  █████   ████
     // ░░███░░░░░███░░███░░░░░███ ░░███   ███░░░░░███░░███   ███░
@@ -721,4 +812,102 @@ impl BoxTreeGPUDataHandler {
             }
         }
     }
+
+    /// Writes the voxels of the given brick again, if it is uploaded
+    /// * `(node_key, sectant)` - The key of the leaf node containing the brick, and the sectant of the brick inside it
+    /// * `returns` - The brick update to write to the GPU, if any
+    pub(crate) fn update_brick<'a, T: VoxelData>(
+        &self,
+        tree: &'a BoxTree<T>,
+        (node_key, sectant): (usize, u8),
+    ) -> CacheUpdatePackage<'a> {
+        let brick = match tree.nodes.get(node_key) {
+            NodeContent::Leaf(bricks) => &bricks[sectant as usize],
+            NodeContent::UniformLeaf(brick) => brick,
+            NodeContent::Nothing | NodeContent::Internal(_) => {
+                return CacheUpdatePackage::default();
+            }
+        };
+        let brick_index = self
+            .upload_targets
+            .brick_ownership
+            .get_by_right(&BrickOwnedBy::NodeAsChild(node_key as u32, sectant));
+        match (brick, brick_index) {
+            (BrickData::Parted(brick), Some(brick_index)) => CacheUpdatePackage {
+                allocation_failed: false,
+                brick_update: Some(BrickUpdate {
+                    brick_index: *brick_index,
+                    data: &brick[..],
+                }),
+                modified_nodes: vec![],
+            },
+            _ => CacheUpdatePackage::default(),
+        }
+    }
+
+    /// Writes the MIP of the given node again, if the node is uploaded
+    /// Parted MIPs not uploaded yet are added to the brick upload queue, others are released if not parted anymore
+    /// * `returns` - The brick update to write to the GPU and the modified nodes, if any
+    pub(crate) fn update_mip<'a, T: VoxelData>(
+        &mut self,
+        tree: &'a BoxTree<T>,
+        node_key: usize,
+    ) -> CacheUpdatePackage<'a> {
+        let Some(node_index) = self
+            .upload_targets
+            .node_key_vs_meta_index
+            .get_by_left(&node_key)
+            .copied()
+        else {
+            return CacheUpdatePackage::default();
+        };
+        let mip_ownership = BrickOwnedBy::NodeAsMIP(node_key as u32);
+        let brick_update = match &tree.node_mips[node_key] {
+            BrickData::Parted(brick) => {
+                match self
+                    .upload_targets
+                    .brick_ownership
+                    .get_by_right(&mip_ownership)
+                {
+                    Some(brick_index) => {
+                        self.render_data.node_mips[node_index] = 0x7FFFFFFF & *brick_index as u32;
+                        Some(BrickUpdate {
+                            brick_index: *brick_index,
+                            data: &brick[..],
+                        })
+                    }
+                    None => {
+                        // The node is already uploaded, so the MIP is queued to be uploaded on its own
+                        self.render_data.node_mips[node_index] = empty_marker();
+                        self.upload_targets
+                            .brick_upload_queue
+                            .push(BrickUploadRequest {
+                                ownership: mip_ownership.clone(),
+                                min_position: V3c::unit(0.), // min_position not used for MIPs
+                            });
+                        None
+                    }
+                }
+            }
+            BrickData::Solid(voxel) => {
+                self.upload_targets
+                    .brick_ownership
+                    .remove_by_right(&mip_ownership);
+                self.render_data.node_mips[node_index] = 0x80000000 | voxel;
+                None
+            }
+            BrickData::Empty => {
+                self.upload_targets
+                    .brick_ownership
+                    .remove_by_right(&mip_ownership);
+                self.render_data.node_mips[node_index] = empty_marker();
+                None
+            }
+        };
+        CacheUpdatePackage {
+            allocation_failed: false,
+            brick_update,
+            modified_nodes: vec![node_index],
+        }
+    }
 }
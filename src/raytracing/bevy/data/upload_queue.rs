@@ -1,15 +1,16 @@
 use crate::{
     boxtree::{
         iterate::execute_for_relevant_sectants,
-        types::{BrickData, NodeContent},
+        types::{BoxTreeChanges, BrickData, NodeContent},
+        update::region::{BoxRegion, RegionOverlap, UpdateRegion},
         BoxTree, VoxelData, V3c, V3cf32, BOX_NODE_CHILDREN_COUNT, BOX_NODE_DIMENSION,
     },
     object_pool::empty_marker,
     raytracing::bevy::{
         data::{boxtree_properties, re_evaluate_view_size, write_range_to_buffer},
         types::{
-            BoxTreeGPUHost, BrickOwnedBy, BrickUploadRequest, NodeUploadRequest,
-            UploadQueueTargets, VhxRenderPipeline, VhxViewSet,
+            BoxTreeGPUHost, BrickOwnedBy, BrickUploadRequest, CacheUpdatePackage,
+            NodeUploadRequest, UploadQueueTargets, VhxRenderPipeline, VhxViewSet,
         },
     },
     spatial::Cube,
@@ -18,6 +19,7 @@ use bevy::{
     ecs::system::{Res, ResMut},
     render::render_resource::encase::UniformBuffer,
 };
+use std::collections::HashSet;
 
 impl UploadQueueTargets {
     pub(crate) fn reset(&mut self) {
@@ -271,6 +273,100 @@ fn add_children_to_upload_queue<T: VoxelData>(
     }
 }
 
+/// Adds the changed nodes and bricks not uploaded yet into the upload queue,
+/// in case they are inside a modified region and their parent is part of the view
+/// Uploaded nodes and bricks are kept, as they are updated in place.
+pub(crate) fn queue_changes<T: VoxelData>(
+    tree: &BoxTree<T>,
+    changes: &BoxTreeChanges,
+    upload_targets: &mut UploadQueueTargets,
+) {
+    let changed_nodes = changes.nodes.iter().copied().collect::<HashSet<_>>();
+    let changed_bricks = changes.bricks.iter().copied().collect::<HashSet<_>>();
+    let regions = changes
+        .regions
+        .iter()
+        .map(|(min_position, max_position)| BoxRegion {
+            min_position: *min_position,
+            max_position: *max_position,
+        })
+        .collect::<Vec<_>>();
+    let in_modified_region = |bounds: &Cube| {
+        regions
+            .iter()
+            .any(|region| region.overlap(bounds) != RegionOverlap::Outside)
+    };
+
+    let root_key = BoxTree::<T>::ROOT_NODE_KEY as usize;
+    if !upload_targets.nodes_to_see.contains(&root_key) {
+        return; // Nothing is in view yet, the upload targets are still to be built
+    }
+    let mut node_stack = vec![(root_key, Cube::root_bounds(tree.boxtree_size as f32))];
+    while let Some((node_key, node_bounds)) = node_stack.pop() {
+        match tree.nodes.get(node_key) {
+            NodeContent::Nothing => {}
+            NodeContent::Internal(_) => {
+                for sectant in 0..BOX_NODE_CHILDREN_COUNT as u8 {
+                    let child_bounds = node_bounds.child_bounds_for(sectant);
+                    let Some(child_key) = tree.valid_child_for(node_key, sectant) else {
+                        continue;
+                    };
+                    if !in_modified_region(&child_bounds) {
+                        continue;
+                    }
+
+                    // Children already in view are searched for changes,
+                    // changed children are brought into view
+                    if !upload_targets.nodes_to_see.contains(&child_key) {
+                        if !changed_nodes.contains(&child_key) {
+                            continue;
+                        }
+                        upload_targets.nodes_to_see.insert(child_key);
+                        upload_targets.node_upload_queue.push(NodeUploadRequest {
+                            node_key: child_key,
+                            parent_key: node_key,
+                            sectant,
+                        });
+                    }
+                    node_stack.push((child_key, child_bounds));
+                }
+            }
+            NodeContent::Leaf(bricks) => {
+                for (sectant, brick) in bricks.iter().enumerate() {
+                    let brick_ownership = BrickOwnedBy::NodeAsChild(node_key as u32, sectant as u8);
+                    if matches!(brick, BrickData::Parted(_))
+                        && changed_bricks.contains(&(node_key, sectant as u8))
+                        && upload_targets
+                            .brick_ownership
+                            .get_by_right(&brick_ownership)
+                            .is_none()
+                    {
+                        upload_targets.brick_upload_queue.push(BrickUploadRequest {
+                            ownership: brick_ownership,
+                            min_position: node_bounds.child_bounds_for(sectant as u8).min_position,
+                        });
+                    }
+                }
+            }
+            NodeContent::UniformLeaf(brick) => {
+                let brick_ownership = BrickOwnedBy::NodeAsChild(node_key as u32, 0);
+                if matches!(brick, BrickData::Parted(_))
+                    && changed_bricks.contains(&(node_key, 0))
+                    && upload_targets
+                        .brick_ownership
+                        .get_by_right(&brick_ownership)
+                        .is_none()
+                {
+                    upload_targets.brick_upload_queue.push(BrickUploadRequest {
+                        ownership: brick_ownership,
+                        min_position: node_bounds.min_position,
+                    });
+                }
+            }
+        }
+    }
+}
+
 pub(crate) fn handle_changes<T: VoxelData>(
     tree_gpu_host: Option<Res<BoxTreeGPUHost<T>>>,
     mut vhx_pipeline: Option<ResMut<VhxRenderPipeline>>,
@@ -295,6 +391,47 @@ pub(crate) fn handle_changes<T: VoxelData>(
     let mut ocbits_updated = usize::MAX..0;
 
     'uploading_buffers: {
+        // Node keys were renumbered in the tree, so every uploaded node is outdated
+        if view.data_handler.upload_state.uploaded_node_layout_revision
+            != tree_host.tree.node_layout_revision
+        {
+            view.reload();
+            view.data_handler.upload_state.uploaded_node_layout_revision =
+                tree_host.tree.node_layout_revision;
+        }
+
+        // Uploaded nodes, bricks or MIPs were modified in the tree, so they need to be uploaded again
+        if view.data_handler.upload_state.uploaded_generation != tree_host.tree.generation() {
+            let tree = &tree_host.tree;
+            let changes = tree.changes_since(view.data_handler.upload_state.uploaded_generation);
+            if changes.layout_changed {
+                view.reload();
+            } else {
+                let data_handler = &mut view.data_handler;
+                for node_key in changes.nodes.iter() {
+                    if let Some(node_index) = data_handler.update_node(tree, *node_key) {
+                        ocbits_updated.start = ocbits_updated.start.min(node_index * 2);
+                        ocbits_updated.end = ocbits_updated.end.max(node_index * 2 + 2);
+                        updates.push(CacheUpdatePackage {
+                            allocation_failed: false,
+                            brick_update: None,
+                            modified_nodes: vec![node_index],
+                        });
+                    }
+                }
+                for brick in changes.bricks.iter() {
+                    updates.push(data_handler.update_brick(tree, *brick));
+                }
+                for node_key in changes.mips.iter() {
+                    updates.push(data_handler.update_mip(tree, *node_key));
+                }
+
+                // Nodes and bricks not yet uploaded are added to the upload queue, uploaded ones are kept
+                queue_changes(tree, &changes, &mut data_handler.upload_targets);
+            }
+            view.data_handler.upload_state.uploaded_generation = changes.generation;
+        }
+
         // Decide upload targets
         if view.reload {
             rebuild::<T>(
@@ -523,7 +660,21 @@ pub(crate) fn handle_changes<T: VoxelData>(
     }
 
     // Data updates for color palette
-    let host_color_count = tree.map_to_color_index_in_palette.keys().len();
+    let host_color_count = tree.voxel_color_palette.len();
+    if view.data_handler.upload_state.uploaded_palette_revision != tree.palette_revision {
+        // Palette entries were changed in place, so the whole palette is uploaded again
+        for i in 0..host_color_count {
+            view.data_handler.render_data.color_palette[i] = tree.voxel_color_palette[i].into();
+        }
+        write_range_to_buffer(
+            &view.data_handler.render_data.color_palette,
+            0..host_color_count,
+            &view.resources.as_ref().unwrap().color_palette_buffer,
+            render_queue,
+        );
+        view.data_handler.upload_state.uploaded_color_palette_size = host_color_count;
+        view.data_handler.upload_state.uploaded_palette_revision = tree.palette_revision;
+    }
     let color_palette_size_diff =
         host_color_count - view.data_handler.upload_state.uploaded_color_palette_size;
 
@@ -547,8 +698,7 @@ pub(crate) fn handle_changes<T: VoxelData>(
             render_queue,
         );
     }
-    view.data_handler.upload_state.uploaded_color_palette_size =
-        tree.map_to_color_index_in_palette.keys().len();
+    view.data_handler.upload_state.uploaded_color_palette_size = host_color_count;
 
     // compile cache updates into write batches
     #[allow(clippy::reversed_empty_ranges)]
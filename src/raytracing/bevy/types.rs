@@ -213,6 +213,15 @@ pub(crate) struct UploadQueueStatus {
 
     /// The number of colors uploaded to the GPU
     pub(crate) uploaded_color_palette_size: usize,
+
+    /// The palette revision of the tree the uploaded colors belong to
+    pub(crate) uploaded_palette_revision: u64,
+
+    /// The node layout revision of the tree the uploaded nodes belong to
+    pub(crate) uploaded_node_layout_revision: u64,
+
+    /// The generation of the tree the uploaded nodes belong to, see @BoxTree::changes_since
+    pub(crate) uploaded_generation: u64,
 }
 
 #[derive(Debug, Resource, Clone)]
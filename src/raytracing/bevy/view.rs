@@ -100,6 +100,9 @@ impl<T: VoxelData> BoxTreeGPUHost<T> {
                 node_upload_progress: 0,
                 brick_upload_progress: 0,
                 uploaded_color_palette_size: 0,
+                uploaded_palette_revision: 0,
+                uploaded_node_layout_revision: 0,
+                uploaded_generation: self.tree.generation(),
             },
             nodes_in_view,
             bricks_in_view,
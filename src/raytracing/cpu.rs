@@ -1,6 +1,7 @@
 use crate::{
     boxtree::{
         BOX_NODE_DIMENSION, BoxTree, BoxTreeEntry, OOB_SECTANT, V3c, VoxelData,
+        distance::empty_sectants_box,
         types::{BrickData, NodeChildren, NodeContent, PaletteIndexValues},
     },
     spatial::{
@@ -135,6 +136,76 @@ impl<T: VoxelData> BoxTree<T> {
         )
     }
 
+    /// Steps the ray iteration over the given box of empty children of a node at once, see @dda_step_to_next_sibling
+    /// * `ray` - The ray to base the step on
+    /// * `ray_current_point` - The point on the ray iteration is currently at, inside the box
+    /// * `node_bounds` - The bounds of the node containing the box
+    /// * `(box_min, box_max)` - The first and last child of the box along each axis
+    /// * `ray_scale_factors` - Pre-computed dda values for the ray
+    /// * Returns with the sectant and bounds of the child inside the box the ray leaves it from, and the step out of it
+    pub(crate) fn dda_step_over_empty_box(
+        ray: &Ray,
+        ray_current_point: &mut V3c<f32>,
+        node_bounds: &Cube,
+        (box_min, box_max): (V3c<u32>, V3c<u32>),
+        ray_scale_factors: &V3c<f32>,
+    ) -> (u8, Cube, V3c<f32>) {
+        let child_size = node_bounds.size / BOX_NODE_DIMENSION as f32;
+        let box_min_position = node_bounds.min_position + V3c::<f32>::from(box_min) * child_size;
+        let box_size = V3c::<f32>::from(box_max - box_min + V3c::unit(1)) * child_size;
+        let signum_vec = V3c::new(
+            ray.direction.x.signum(),
+            ray.direction.y.signum(),
+            ray.direction.z.signum(),
+        );
+        let diff_from_min = *ray_current_point - box_min_position;
+        let steps_needed = V3c::new(
+            box_size.x * signum_vec.x.max(0.) - signum_vec.x * diff_from_min.x,
+            box_size.y * signum_vec.y.max(0.) - signum_vec.y * diff_from_min.y,
+            box_size.z * signum_vec.z.max(0.) - signum_vec.z * diff_from_min.z,
+        );
+        let d_x = (steps_needed.x * ray_scale_factors.x).abs();
+        let d_y = (steps_needed.y * ray_scale_factors.y).abs();
+        let d_z = (steps_needed.z * ray_scale_factors.z).abs();
+        let min_step = d_x.min(d_y).min(d_z);
+        *ray_current_point += ray.direction * min_step;
+
+        // The child the ray leaves from is on the side of the box the ray steps out of
+        let exit_component = |step: f32, min: u32, max: u32, position: f32| {
+            if 0. < step {
+                max
+            } else if step < 0. {
+                min
+            } else {
+                ((position / child_size).floor().max(0.) as u32).clamp(min, max)
+            }
+        };
+        let step_vec = V3c::new(
+            if min_step == d_x { signum_vec.x } else { 0. },
+            if min_step == d_y { signum_vec.y } else { 0. },
+            if min_step == d_z { signum_vec.z } else { 0. },
+        );
+        let position_in_node = *ray_current_point - node_bounds.min_position;
+        let exit_child = V3c::new(
+            exit_component(step_vec.x, box_min.x, box_max.x, position_in_node.x),
+            exit_component(step_vec.y, box_min.y, box_max.y, position_in_node.y),
+            exit_component(step_vec.z, box_min.z, box_max.z, position_in_node.z),
+        );
+        (
+            flat_projection(
+                exit_child.x as usize,
+                exit_child.y as usize,
+                exit_child.z as usize,
+                BOX_NODE_DIMENSION,
+            ) as u8,
+            Cube {
+                min_position: node_bounds.min_position + V3c::<f32>::from(exit_child) * child_size,
+                size: child_size,
+            },
+            step_vec,
+        )
+    }
+
     /// Iterates on the given ray and brick to find a potential intersection in 3D space
     /// Returns with the 3d and flat index values pointing to the voxel hit inside the brick in case there's a hit
     fn traverse_brick(
@@ -428,12 +499,28 @@ impl<T: VoxelData> BoxTree<T> {
                     // so advance iteration to the next sibling
                     loop {
                         // step the iteration to the next sibling cell!
-                        let step_vec = Self::dda_step_to_next_sibling(
-                            ray,
-                            &mut ray_current_point,
-                            &target_bounds,
-                            &ray_scale_factors,
-                        );
+                        // Empty targets are stepped over together with the empty siblings around them
+                        let step_vec = if let Some(empty_box) =
+                            empty_sectants_box(current_node_occupied_bits, target_sectant)
+                        {
+                            let step_vec;
+                            (target_sectant, target_bounds, step_vec) =
+                                Self::dda_step_over_empty_box(
+                                    ray,
+                                    &mut ray_current_point,
+                                    &current_bounds,
+                                    empty_box,
+                                    &ray_scale_factors,
+                                );
+                            step_vec
+                        } else {
+                            Self::dda_step_to_next_sibling(
+                                ray,
+                                &mut ray_current_point,
+                                &target_bounds,
+                                &ray_scale_factors,
+                            )
+                        };
                         target_sectant = step_sectant(target_sectant, step_vec);
                         if OOB_SECTANT != target_sectant {
                             target_bounds.min_position += step_vec * target_bounds.size;
@@ -623,6 +623,45 @@ mod boxtree_raytracing_tests {
         }));
     }
 
+    #[test]
+    fn test_get_by_ray_through_sparse_tree() {
+        // Rays step over the empty children around them at once, but still need to hit the first voxel along them
+        let mut tree: BoxTree = BoxTree::new(64, 1).ok().unwrap();
+        let targets = [
+            (V3c::new(50, 44, 45), Albedo::from(0xFF0000FF)),
+            (V3c::new(60, 3, 20), Albedo::from(0x00FF00FF)),
+            (V3c::new(5, 58, 33), Albedo::from(0x0000FFFF)),
+            (V3c::new(20, 12, 62), Albedo::from(0xFFFF00FF)),
+            (V3c::new(25, 20, 22), Albedo::from(0x00FFFFFF)),
+        ];
+        for (position, color) in targets.iter() {
+            tree.insert(position, color).ok().unwrap();
+        }
+
+        let origin = V3c::new(1.25, 2.5, 0.75);
+        for (position, color) in targets.iter() {
+            let ray = Ray {
+                direction: (V3c::<f32>::from(*position) + V3c::unit(0.5) - origin).normalized(),
+                origin,
+            };
+            assert!(tree
+                .get_by_ray(&ray)
+                .is_some_and(|hit| hit.0 == BoxTreeEntry::Visual(color)));
+        }
+
+        // The voxel in front shadows the one behind it
+        tree.insert(&V3c::new(49, 38, 44), &Albedo::from(0xFFFFFFFF))
+            .ok()
+            .unwrap();
+        let ray = Ray {
+            direction: (V3c::new(49.5, 38.5, 44.5) - origin).normalized(),
+            origin,
+        };
+        assert!(tree
+            .get_by_ray(&ray)
+            .is_some_and(|hit| hit.0 == BoxTreeEntry::Visual(&targets[4].1)));
+    }
+
     #[test]
     fn test_edge_case_deep_stack() {
         let tree_size = 1024;
@@ -906,3 +945,132 @@ mod node_stack_tests {
         );
     }
 }
+
+#[cfg(all(test, feature = "bevy_wgpu"))]
+mod gpu_cache_tests {
+    use crate::{
+        boxtree::{types::BrickData, Albedo, BoxTree, BoxTreeEntry, V3c, BOX_NODE_CHILDREN_COUNT},
+        object_pool::empty_marker,
+        raytracing::bevy::types::{
+            BoxTreeGPUDataHandler, BoxTreeMetaData, BoxTreeRenderData, BrickOwnedBy,
+            NodeUploadRequest, UploadQueueStatus, UploadQueueTargets,
+        },
+        spatial::Cube,
+    };
+    use bevy::prelude::Vec4;
+    use bimap::BiHashMap;
+    use std::collections::{HashMap, HashSet};
+
+    /// Creates a data handler for the given tree, with space for the given number of nodes and bricks
+    fn data_handler(
+        tree: &BoxTree,
+        nodes_in_view: usize,
+        bricks_in_view: usize,
+    ) -> BoxTreeGPUDataHandler {
+        BoxTreeGPUDataHandler {
+            upload_range: Cube::root_bounds(tree.get_size() as f32),
+            render_data: BoxTreeRenderData {
+                mips_enabled: tree.mip_map_strategy.is_enabled(),
+                boxtree_meta: BoxTreeMetaData {
+                    boxtree_size: tree.get_size(),
+                    tree_properties: 0,
+                    ambient_light_color: V3c::new(1., 1., 1.),
+                    ambient_light_position: V3c::unit(tree.get_size() as f32),
+                },
+                node_metadata: vec![0; (nodes_in_view as f32 / 8.).ceil() as usize],
+                node_ocbits: vec![0; nodes_in_view * 2],
+                node_children: vec![empty_marker(); nodes_in_view * BOX_NODE_CHILDREN_COUNT],
+                node_mips: vec![empty_marker(); nodes_in_view],
+                color_palette: vec![Vec4::ZERO; u16::MAX as usize],
+            },
+            upload_targets: UploadQueueTargets {
+                node_upload_queue: vec![],
+                brick_upload_queue: vec![],
+                brick_ownership: BiHashMap::new(),
+                brick_positions: vec![V3c::unit(0.); bricks_in_view],
+                node_key_vs_meta_index: BiHashMap::new(),
+                node_index_vs_parent: HashMap::new(),
+                nodes_to_see: HashSet::new(),
+            },
+            upload_state: UploadQueueStatus {
+                victim_node: 0,
+                victim_brick: 0,
+                node_upload_progress: 0,
+                brick_upload_progress: 0,
+                uploaded_color_palette_size: 0,
+                uploaded_palette_revision: 0,
+                uploaded_node_layout_revision: 0,
+                uploaded_generation: tree.generation(),
+            },
+            nodes_in_view,
+            bricks_in_view,
+            node_uploads_per_frame: 4,
+            brick_uploads_per_frame: 4,
+            brick_unload_search_perimeter: 8,
+        }
+    }
+
+    #[test]
+    fn test_uploaded_node_mip_becoming_parted_is_uploaded() {
+        let red: Albedo = 0xFF0000FF.into();
+        let green: Albedo = 0x00FF00FF.into();
+        let root_key = BoxTree::<u32>::ROOT_NODE_KEY as usize;
+        let mut tree: BoxTree = BoxTree::new(32, 2).ok().unwrap();
+        tree.albedo_mip_map_resampling_strategy()
+            .switch_albedo_mip_maps(true);
+        tree.insert_box(&V3c::new(0, 0, 0), &V3c::new(16, 16, 16), &red)
+            .expect("insert_box to work");
+
+        // Upload the root node with a solid MIP
+        let red_voxel = tree
+            .add_to_palette(&BoxTreeEntry::Visual(&red))
+            .expect("palette to have space");
+        tree.node_mips[root_key] = BrickData::Solid(red_voxel);
+        let mut data_handler = data_handler(&tree, 8, 8);
+        let (root_index, _) = data_handler.add_node(
+            &tree,
+            &NodeUploadRequest {
+                node_key: root_key,
+                parent_key: root_key,
+                sectant: 0,
+            },
+        );
+        assert_eq!(
+            data_handler.render_data.node_mips[root_index],
+            0x80000000 | red_voxel
+        );
+
+        // The MIP of the uploaded root becomes parted
+        let uploaded_generation = tree.generation();
+        tree.insert(&V3c::new(20, 20, 20), &green)
+            .expect("insert to work");
+        assert!(matches!(tree.node_mips[root_key], BrickData::Parted(_)));
+        let changes = tree.changes_since(uploaded_generation);
+        assert!(changes.mips.contains(&root_key));
+        let mip_update = data_handler.update_mip(&tree, root_key);
+        assert!(mip_update.brick_update.is_none());
+        assert_eq!(
+            data_handler.render_data.node_mips[root_index],
+            empty_marker::<u32>()
+        );
+
+        // The MIP is queued for upload, as the root node is not uploaded again
+        let mip_request = data_handler
+            .upload_targets
+            .brick_upload_queue
+            .iter()
+            .find(|request| request.ownership == BrickOwnedBy::NodeAsMIP(root_key as u32))
+            .cloned()
+            .expect("MIP to be queued for upload");
+        let mip_upload = data_handler.add_brick(&tree, mip_request);
+        assert!(!mip_upload.allocation_failed);
+        let brick_index = mip_upload
+            .brick_update
+            .expect("MIP brick to be written")
+            .brick_index;
+        assert_eq!(
+            data_handler.render_data.node_mips[root_index],
+            brick_index as u32
+        );
+    }
+}
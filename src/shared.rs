@@ -0,0 +1,365 @@
+use std::{
+    fmt::{Debug, Formatter},
+    ops::{Deref, DerefMut, Index, IndexMut},
+    sync::Arc,
+};
+
+#[cfg(feature = "bytecode")]
+use bendy::{
+    decoding::{FromBencode, Object},
+    encoding::{Error as BencodeError, SingleItemEncoder, ToBencode},
+};
+
+/// The number of items stored together in one page of a @SharedVec, must be a power of 2
+const PAGE_SIZE: usize = 64;
+
+/// Reference counted value, which is copied on the first modification while it is shared
+/// Cloning it only increments the reference count, so clones of the owning structure are cheap.
+pub(crate) struct Shared<T>(Arc<T>);
+
+#[cfg(test)]
+impl<T> Shared<T> {
+    /// True if both values refer to the same stored data
+    pub(crate) fn ptr_eq(a: &Self, b: &Self) -> bool {
+        Arc::ptr_eq(&a.0, &b.0)
+    }
+}
+
+impl<T> Clone for Shared<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<T: Default> Default for Shared<T> {
+    fn default() -> Self {
+        Self(Arc::new(T::default()))
+    }
+}
+
+impl<T> From<T> for Shared<T> {
+    fn from(value: T) -> Self {
+        Self(Arc::new(value))
+    }
+}
+
+impl<T> Deref for Shared<T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: Clone> DerefMut for Shared<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        Arc::make_mut(&mut self.0)
+    }
+}
+
+impl<T: PartialEq> PartialEq for Shared<T> {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0) || *self.0 == *other.0
+    }
+}
+
+impl<T: Eq> Eq for Shared<T> {}
+
+impl<T: Debug> Debug for Shared<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// A vector storing its items in reference counted pages
+/// Cloning it only copies the reference of the page list, while modifying an item
+/// copies only the page containing it, if the page is shared with another clone.
+pub(crate) struct SharedVec<T> {
+    pages: Arc<Vec<Arc<Vec<T>>>>,
+    len: usize,
+}
+
+impl<T> SharedVec<T> {
+    pub(crate) fn new() -> Self {
+        Self {
+            pages: Arc::new(Vec::new()),
+            len: 0,
+        }
+    }
+
+    pub(crate) fn with_capacity(capacity: usize) -> Self {
+        Self {
+            pages: Arc::new(Vec::with_capacity(capacity.div_ceil(PAGE_SIZE))),
+            len: 0,
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    /// The number of items the allocated pages can hold
+    pub(crate) fn capacity(&self) -> usize {
+        self.pages.iter().map(|page| page.capacity()).sum()
+    }
+
+    pub(crate) fn get(&self, index: usize) -> Option<&T> {
+        if index < self.len {
+            Some(&self.pages[index / PAGE_SIZE][index % PAGE_SIZE])
+        } else {
+            None
+        }
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &T> + '_ {
+        self.pages.iter().flat_map(|page| page.iter())
+    }
+
+    /// True if both vectors refer to the same stored pages
+    pub(crate) fn shares_storage(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.pages, &other.pages)
+    }
+
+    /// The number of pages stored by both vectors at the same place
+    #[cfg(test)]
+    pub(crate) fn shared_page_count(&self, other: &Self) -> usize {
+        self.pages
+            .iter()
+            .zip(other.pages.iter())
+            .filter(|(page, other_page)| Arc::ptr_eq(page, other_page))
+            .count()
+    }
+}
+
+impl<T: Clone> SharedVec<T> {
+    /// Provides the page of the given index for modification, copying it if it is shared
+    fn page_mut(&mut self, page_index: usize) -> &mut Vec<T> {
+        Arc::make_mut(&mut Arc::make_mut(&mut self.pages)[page_index])
+    }
+
+    pub(crate) fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        if index < self.len {
+            Some(&mut self.page_mut(index / PAGE_SIZE)[index % PAGE_SIZE])
+        } else {
+            None
+        }
+    }
+
+    pub(crate) fn push(&mut self, item: T) {
+        if self.len.is_multiple_of(PAGE_SIZE) {
+            Arc::make_mut(&mut self.pages).push(Arc::new(Vec::with_capacity(PAGE_SIZE)));
+        }
+        self.page_mut(self.len / PAGE_SIZE).push(item);
+        self.len += 1;
+    }
+
+    pub(crate) fn reserve(&mut self, additional: usize) {
+        let page_count = (self.len + additional).div_ceil(PAGE_SIZE);
+        let pages = Arc::make_mut(&mut self.pages);
+        pages.reserve(page_count.saturating_sub(pages.len()));
+    }
+
+    pub(crate) fn truncate(&mut self, len: usize) {
+        if len >= self.len {
+            return;
+        }
+        Arc::make_mut(&mut self.pages).truncate(len.div_ceil(PAGE_SIZE));
+        if !len.is_multiple_of(PAGE_SIZE) {
+            self.page_mut(len / PAGE_SIZE).truncate(len % PAGE_SIZE);
+        }
+        self.len = len;
+    }
+
+    pub(crate) fn resize(&mut self, len: usize, value: T) {
+        if len <= self.len {
+            self.truncate(len);
+        } else {
+            self.reserve(len - self.len);
+            while self.len < len {
+                self.push(value.clone());
+            }
+        }
+    }
+
+    pub(crate) fn swap(&mut self, a: usize, b: usize) {
+        if a != b {
+            let item_a = self[a].clone();
+            let item_b = std::mem::replace(&mut self[b], item_a);
+            self[a] = item_b;
+        }
+    }
+
+    pub(crate) fn shrink_to_fit(&mut self) {
+        if self.pages.len() < self.pages.capacity() {
+            Arc::make_mut(&mut self.pages).shrink_to_fit();
+        }
+        if self
+            .pages
+            .last()
+            .is_some_and(|last_page| last_page.len() < last_page.capacity())
+        {
+            self.page_mut(self.pages.len() - 1).shrink_to_fit();
+        }
+    }
+}
+
+impl<T> Default for SharedVec<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Clone for SharedVec<T> {
+    fn clone(&self) -> Self {
+        Self {
+            pages: self.pages.clone(),
+            len: self.len,
+        }
+    }
+}
+
+impl<T> Index<usize> for SharedVec<T> {
+    type Output = T;
+    fn index(&self, index: usize) -> &T {
+        assert!(
+            index < self.len,
+            "index out of bounds: the len is {} but the index is {}",
+            self.len,
+            index
+        );
+        &self.pages[index / PAGE_SIZE][index % PAGE_SIZE]
+    }
+}
+
+impl<T: Clone> IndexMut<usize> for SharedVec<T> {
+    fn index_mut(&mut self, index: usize) -> &mut T {
+        assert!(
+            index < self.len,
+            "index out of bounds: the len is {} but the index is {}",
+            self.len,
+            index
+        );
+        &mut self.page_mut(index / PAGE_SIZE)[index % PAGE_SIZE]
+    }
+}
+
+impl<T> FromIterator<T> for SharedVec<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut pages = Vec::new();
+        let mut page = Vec::with_capacity(PAGE_SIZE);
+        let mut len = 0;
+        for item in iter {
+            page.push(item);
+            len += 1;
+            if PAGE_SIZE == page.len() {
+                pages.push(Arc::new(std::mem::replace(
+                    &mut page,
+                    Vec::with_capacity(PAGE_SIZE),
+                )));
+            }
+        }
+        if !page.is_empty() {
+            pages.push(Arc::new(page));
+        }
+        Self {
+            pages: Arc::new(pages),
+            len,
+        }
+    }
+}
+
+impl<T> From<Vec<T>> for SharedVec<T> {
+    fn from(items: Vec<T>) -> Self {
+        items.into_iter().collect()
+    }
+}
+
+impl<T: PartialEq> PartialEq for SharedVec<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len
+            && (self.shares_storage(other) || self.iter().zip(other.iter()).all(|(a, b)| a == b))
+    }
+}
+
+impl<T: Debug> Debug for SharedVec<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+#[cfg(feature = "bytecode")]
+impl<T> ToBencode for SharedVec<T>
+where
+    T: ToBencode,
+{
+    const MAX_DEPTH: usize = T::MAX_DEPTH + 1;
+    fn encode(&self, encoder: SingleItemEncoder) -> Result<(), BencodeError> {
+        encoder.emit_list(|e| {
+            for item in self.iter() {
+                e.emit(item)?;
+            }
+            Ok(())
+        })
+    }
+}
+
+#[cfg(feature = "bytecode")]
+impl<T> FromBencode for SharedVec<T>
+where
+    T: FromBencode,
+{
+    fn decode_bencode_object(data: Object) -> Result<Self, bendy::decoding::Error> {
+        Ok(Vec::<T>::decode_bencode_object(data)?.into())
+    }
+}
+
+#[cfg(test)]
+mod shared_vec_tests {
+    use super::{Shared, SharedVec, PAGE_SIZE};
+
+    #[test]
+    fn test_push_resize_modify() {
+        let mut items = SharedVec::<u32>::new();
+        for i in 0..(PAGE_SIZE as u32 * 2 + 5) {
+            items.push(i);
+        }
+        assert_eq!(items.len(), PAGE_SIZE * 2 + 5);
+        assert_eq!(items[PAGE_SIZE + 3], PAGE_SIZE as u32 + 3);
+
+        items[PAGE_SIZE + 3] = 1000;
+        assert_eq!(items[PAGE_SIZE + 3], 1000);
+
+        items.swap(0, PAGE_SIZE * 2);
+        assert_eq!(items[0], PAGE_SIZE as u32 * 2);
+        assert_eq!(items[PAGE_SIZE * 2], 0);
+
+        items.resize(PAGE_SIZE + 1, 7);
+        assert_eq!(items.len(), PAGE_SIZE + 1);
+        assert!(items.get(PAGE_SIZE + 1).is_none());
+
+        items.resize(PAGE_SIZE * 3, 7);
+        assert_eq!(items.len(), PAGE_SIZE * 3);
+        assert_eq!(items[PAGE_SIZE * 3 - 1], 7);
+        assert_eq!(items.iter().count(), PAGE_SIZE * 3);
+    }
+
+    #[test]
+    fn test_clones_copy_only_modified_pages() {
+        let original: SharedVec<u32> = (0..(PAGE_SIZE as u32 * 4)).collect();
+        let mut copy = original.clone();
+        assert!(copy.shares_storage(&original));
+
+        copy[PAGE_SIZE * 2 + 1] = 1000;
+        assert!(!copy.shares_storage(&original));
+        assert_eq!(copy.shared_page_count(&original), 3);
+        assert_eq!(original[PAGE_SIZE * 2 + 1], PAGE_SIZE as u32 * 2 + 1);
+        assert_eq!(copy[PAGE_SIZE * 2 + 1], 1000);
+        assert!(copy != original);
+
+        let brick = Shared::from(vec![1, 2, 3]);
+        let mut brick_copy = brick.clone();
+        assert!(Shared::ptr_eq(&brick, &brick_copy));
+        brick_copy[0] = 5;
+        assert!(!Shared::ptr_eq(&brick, &brick_copy));
+        assert_eq!(brick[0], 1);
+    }
+}
@@ -59,7 +59,7 @@ pub(crate) fn hash_direction(direction: &V3c<f32>) -> u8 {
 /// Types are not u8 only because this utility is mainly used to index inside bricks
 pub(crate) fn octant_in_sectants(sectant: usize) -> usize {
     let offset = SECTANT_OFFSET_LUT[sectant] * 2.;
-    (offset.x >= 1.) as usize + (offset.z >= 1.) as usize * 2 + (offset.y >= 1.) as usize * 4
+    (offset.x >= 1.) as usize + (offset.y >= 1.) as usize * 2 + (offset.z >= 1.) as usize * 4
 }
 
 /// Provides an index value inside the brick contained in the given bounds
@@ -16,7 +16,7 @@ mod vector_tests {
 
 #[cfg(test)]
 mod sectant_tests {
-    use crate::spatial::math::offset_sectant;
+    use crate::spatial::math::{octant_in_sectants, offset_sectant};
     use crate::spatial::V3c;
 
     #[test]
@@ -27,6 +27,16 @@ mod sectant_tests {
         assert_eq!(offset_sectant(&V3c::new(0.0, 0.0, 3.0), 12.0), 16);
         assert_eq!(offset_sectant(&V3c::new(10.0, 10.0, 10.0), 12.0), 63);
     }
+
+    #[test]
+    fn test_octant_in_sectants() {
+        // Octants are ordered the same way as sectants: x, then y, then z
+        assert_eq!(octant_in_sectants(0), 0);
+        assert_eq!(octant_in_sectants(2), 1);
+        assert_eq!(octant_in_sectants(8), 2);
+        assert_eq!(octant_in_sectants(32), 4);
+        assert_eq!(octant_in_sectants(63), 7);
+    }
 }
 
 #[cfg(test)]